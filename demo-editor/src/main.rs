@@ -4,10 +4,124 @@ use demo_plugins::{
     BlenderMaterialAssetPlugin, BlenderMeshAssetPlugin, GlslAssetPlugin, GltfAssetPlugin,
     GpuBufferAssetPlugin, GpuImageAssetPlugin, MeshAdvAssetPlugin, SimpleDataAssetPlugin,
 };
-use hydrate::pipeline::HydrateProjectConfiguration;
-use std::path::PathBuf;
+use hydrate::model::HeadlessPipeline;
+use hydrate::pipeline::{
+    delete_build_output_garbage, find_build_output_garbage, inspect_built_artifact,
+    write_build_patch, ArtifactDecoderRegistry, AssetId, AssetPluginRegistryBuilders, BuildRoots,
+    BuildVerification, BuildVerificationMode, HydrateProjectConfiguration, JobWorkerCommand,
+    JobWorkerConfig, ManifestContents, ManifestDiff, PipelineResult, JOB_WORKER_TOKEN_ENV_VAR,
+};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug, Default)]
+pub struct DemoEditorArgs {
+    // If no command is provided, the editor window is opened
+    #[structopt(subcommand)]
+    pub command: Option<DemoEditorCommand>,
+}
+
+#[derive(StructOpt, Debug)]
+pub enum DemoEditorCommand {
+    // Import any source files that need it without opening the editor window
+    #[structopt(name = "import")]
+    Import,
+    // Import and then build without opening the editor window. Exits with a non-zero code if any
+    // errors occurred.
+    #[structopt(name = "build")]
+    Build {
+        // Asset IDs to build, along with anything they depend on. Everything is built if none
+        // are given.
+        #[structopt(name = "root", long, parse(try_from_str = AssetId::parse_str))]
+        roots: Vec<AssetId>,
+        // Name of a build profile in the project file. The first profile is used if not given.
+        #[structopt(name = "profile", long)]
+        profile: Option<String>,
+        // Run every job twice and report artifacts that differ between the runs
+        #[structopt(name = "verify-determinism", long)]
+        verify_determinism: bool,
+        // Run jobs even if they are cached and report artifacts that differ from the previous
+        // build of the same inputs
+        #[structopt(name = "verify-against-previous", long)]
+        verify_against_previous: bool,
+        // Only verify jobs whose processor type name contains one of these strings
+        #[structopt(name = "verify-job-type", long)]
+        verify_job_types: Vec<String>,
+        // Run jobs in this many worker processes instead of in the editor process
+        #[structopt(name = "worker-processes", long)]
+        worker_processes: Option<usize>,
+    },
+    // Run builders' validation over every asset without importing or building anything. Exits with
+    // a non-zero code if any errors were found.
+    #[structopt(name = "validate")]
+    Validate {
+        // Name of a build profile in the project file. The first profile is used if not given.
+        #[structopt(name = "profile", long)]
+        profile: Option<String>,
+    },
+    // Delete build output that isn't used by the newest builds or a running game
+    #[structopt(name = "collect-build-garbage")]
+    CollectBuildGarbage {
+        // Number of builds to keep for each build profile
+        #[structopt(name = "keep", long, default_value = "3")]
+        keep: usize,
+        // Only report what would be deleted and how much space it uses
+        #[structopt(name = "dry-run", long)]
+        dry_run: bool,
+    },
+    // List artifacts that were added, removed or changed between two builds. Each build is either a
+    // manifest file or the manifest build hash stored in a TOC file.
+    #[structopt(name = "diff-manifests")]
+    DiffManifests {
+        // Name of the build profile to find manifest build hashes in. The first profile is used if
+        // not given.
+        #[structopt(name = "profile", long)]
+        profile: Option<String>,
+        old: String,
+        new: String,
+    },
+    // Print a built artifact's header, sizes, build hashes and dependency tree, and its contents if
+    // the type is known. The artifact can be given by ID, symbol name or debug name.
+    #[structopt(name = "inspect-artifact")]
+    InspectArtifact {
+        // Name of the build profile to look in. The first profile is used if not given.
+        #[structopt(name = "profile", long)]
+        profile: Option<String>,
+        // Manifest build hash of the build to look in. The newest build is used if not given.
+        #[structopt(name = "build", long)]
+        build: Option<String>,
+        artifact: String,
+    },
+    // Write a patch with the artifacts and manifest that a game with the base build needs to load
+    // the new build. Builds are identified by the manifest build hash stored in their TOC file.
+    #[structopt(name = "create-build-patch")]
+    CreateBuildPatch {
+        // Name of the build profile the builds are in. The first profile is used if not given.
+        #[structopt(name = "profile", long)]
+        profile: Option<String>,
+        base: String,
+        new: String,
+        // Directory to write the patch to, it must be empty or not exist
+        #[structopt(parse(from_os_str))]
+        output: PathBuf,
+    },
+    // Run jobs for a build at the given address. Builds started with --worker-processes run
+    // this, and it can be run on other machines to share the work. The build's token must be in
+    // the HYDRATE_JOB_WORKER_TOKEN environment variable.
+    #[structopt(name = "job-worker")]
+    JobWorker { address: String },
+    // Delete import data, generated assets and meta file entries that are no longer needed
+    #[structopt(name = "collect-garbage")]
+    CollectGarbage {
+        // Only report what would be deleted
+        #[structopt(name = "dry-run", long)]
+        dry_run: bool,
+    },
+}
 
 fn main() -> eframe::Result<()> {
+    let args = DemoEditorArgs::from_args();
+
     profiling::tracy_client::Client::start();
     profiling::register_thread!("main");
 
@@ -36,6 +150,11 @@ fn main() -> eframe::Result<()> {
         .register_plugin::<GltfAssetPlugin>()
         .register_plugin::<SimpleDataAssetPlugin>();
 
+    // Headless commands exit when they are done, otherwise open the editor
+    if let Some(command) = &args.command {
+        run_headless_command(command, &project_configuration, asset_plugin_registry);
+    }
+
     let mut editor = hydrate::editor::Editor::new(project_configuration, asset_plugin_registry);
 
     let schema_set = editor.schema_set().clone();
@@ -43,3 +162,358 @@ fn main() -> eframe::Result<()> {
 
     editor.run()
 }
+
+fn run_headless_command(
+    command: &DemoEditorCommand,
+    project_configuration: &HydrateProjectConfiguration,
+    asset_plugin_registry: AssetPluginRegistryBuilders,
+) -> ! {
+    match command {
+        DemoEditorCommand::Import => run_headless(
+            project_configuration,
+            asset_plugin_registry,
+            None,
+            None,
+            None,
+            None,
+        ),
+        DemoEditorCommand::Build {
+            roots,
+            profile,
+            verify_determinism,
+            verify_against_previous,
+            verify_job_types,
+            worker_processes,
+        } => {
+            let build_roots = if roots.is_empty() {
+                BuildRoots::All
+            } else {
+                BuildRoots::Assets(roots.clone())
+            };
+            let verification_mode = if *verify_determinism {
+                Some(BuildVerificationMode::RunTwice)
+            } else if *verify_against_previous {
+                Some(BuildVerificationMode::CompareWithPreviousBuild)
+            } else {
+                None
+            };
+            let build_verification = verification_mode.map(|mode| BuildVerification {
+                mode,
+                job_type_filter: verify_job_types.clone(),
+            });
+            run_headless(
+                project_configuration,
+                asset_plugin_registry,
+                Some(build_roots),
+                profile.as_deref(),
+                build_verification,
+                *worker_processes,
+            )
+        }
+        DemoEditorCommand::Validate { profile } => validate(
+            project_configuration,
+            asset_plugin_registry,
+            profile.as_deref(),
+        ),
+        DemoEditorCommand::CollectBuildGarbage { keep, dry_run } => {
+            collect_build_garbage(project_configuration, *keep, *dry_run)
+        }
+        DemoEditorCommand::DiffManifests { profile, old, new } => {
+            diff_manifests(project_configuration, profile.as_deref(), old, new)
+        }
+        DemoEditorCommand::InspectArtifact {
+            profile,
+            build,
+            artifact,
+        } => inspect_artifact(
+            project_configuration,
+            profile.as_deref(),
+            build.as_deref(),
+            artifact,
+        ),
+        DemoEditorCommand::CreateBuildPatch {
+            profile,
+            base,
+            new,
+            output,
+        } => create_build_patch(project_configuration, profile.as_deref(), base, new, output),
+        DemoEditorCommand::JobWorker { address } => {
+            let result = match std::env::var(JOB_WORKER_TOKEN_ENV_VAR) {
+                Ok(token) => HeadlessPipeline::run_job_worker(
+                    project_configuration,
+                    asset_plugin_registry,
+                    address,
+                    &token,
+                ),
+                Err(_) => Err(format!("{} is not set", JOB_WORKER_TOKEN_ENV_VAR).into()),
+            };
+            if let Err(e) = &result {
+                log::error!("Job worker failed: {}", e.to_string());
+            }
+            std::process::exit(if result.is_ok() { 0 } else { 1 })
+        }
+        DemoEditorCommand::CollectGarbage { dry_run } => {
+            collect_garbage(project_configuration, asset_plugin_registry, *dry_run)
+        }
+    }
+}
+
+fn collect_garbage(
+    project_configuration: &HydrateProjectConfiguration,
+    asset_plugin_registry: AssetPluginRegistryBuilders,
+    dry_run: bool,
+) -> ! {
+    let mut editor =
+        hydrate::editor::Editor::new(project_configuration.clone(), asset_plugin_registry);
+    let result = editor.collect_garbage(dry_run);
+    if let Err(e) = &result {
+        log::error!("Garbage collection failed: {}", e.to_string());
+    }
+    std::process::exit(if result.is_ok() { 0 } else { 1 })
+}
+
+fn run_headless(
+    project_configuration: &HydrateProjectConfiguration,
+    asset_plugin_registry: AssetPluginRegistryBuilders,
+    build_roots: Option<BuildRoots>,
+    build_profile: Option<&str>,
+    build_verification: Option<BuildVerification>,
+    worker_processes: Option<usize>,
+) -> ! {
+    let result = HeadlessPipeline::new(project_configuration, asset_plugin_registry).and_then(
+        |mut pipeline| {
+            if let Some(build_profile) = build_profile {
+                pipeline
+                    .asset_engine_mut()
+                    .set_active_build_profile(build_profile)?;
+            }
+
+            pipeline
+                .asset_engine_mut()
+                .set_build_verification(build_verification);
+
+            if let Some(worker_processes) = worker_processes {
+                let job_worker_command = JobWorkerCommand::current_exe(vec!["job-worker".into()])?;
+                pipeline
+                    .asset_engine_mut()
+                    .start_job_workers(JobWorkerConfig::local(
+                        job_worker_command,
+                        worker_processes,
+                    ))?;
+            }
+
+            if let Some(build_roots) = build_roots {
+                pipeline.import_and_build_roots(build_roots)
+            } else {
+                pipeline.import()
+            }
+        },
+    );
+
+    match result {
+        Ok(result) => {
+            result.log_summary();
+            std::process::exit(if result.has_errors() { 1 } else { 0 })
+        }
+        Err(e) => {
+            log::error!("Headless pipeline failed: {}", e.to_string());
+            std::process::exit(1)
+        }
+    }
+}
+
+fn validate(
+    project_configuration: &HydrateProjectConfiguration,
+    asset_plugin_registry: AssetPluginRegistryBuilders,
+    build_profile: Option<&str>,
+) -> ! {
+    let result = HeadlessPipeline::new(project_configuration, asset_plugin_registry).and_then(
+        |mut pipeline| {
+            if let Some(build_profile) = build_profile {
+                pipeline
+                    .asset_engine_mut()
+                    .set_active_build_profile(build_profile)?;
+            }
+
+            pipeline.validate()
+        },
+    );
+
+    match result {
+        Ok(report) => std::process::exit(if report.has_errors() { 1 } else { 0 }),
+        Err(e) => {
+            log::error!("Validation failed: {}", e.to_string());
+            std::process::exit(1)
+        }
+    }
+}
+
+fn collect_build_garbage(
+    project_configuration: &HydrateProjectConfiguration,
+    keep_toc_count: usize,
+    dry_run: bool,
+) -> ! {
+    let result =
+        find_build_output_garbage(project_configuration, keep_toc_count).and_then(|garbage| {
+            log::info!("{}", garbage.to_text());
+            if garbage.is_empty() {
+                log::info!("No build garbage found");
+            } else if dry_run {
+                log::info!("Dry run, nothing was deleted");
+            } else {
+                delete_build_output_garbage(&garbage)?;
+                log::info!("Build garbage deleted");
+            }
+            Ok(())
+        });
+
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            log::error!("Build garbage collection failed: {}", e.to_string());
+            std::process::exit(1)
+        }
+    }
+}
+
+// The build output of the named build profile, or the first profile if no name is given
+fn profile_build_data_path(
+    project_configuration: &HydrateProjectConfiguration,
+    profile: Option<&str>,
+) -> PipelineResult<PathBuf> {
+    let build_profile = match profile {
+        Some(name) => project_configuration
+            .build_profiles
+            .iter()
+            .find(|x| x.name == name)
+            .ok_or_else(|| format!("Build profile {:?} is not defined in the project", name))?,
+        None => project_configuration
+            .build_profiles
+            .first()
+            .ok_or("The project has no build profiles")?,
+    };
+
+    Ok(build_profile.build_data_path(&project_configuration.build_data_path))
+}
+
+fn diff_manifests(
+    project_configuration: &HydrateProjectConfiguration,
+    profile: Option<&str>,
+    old: &str,
+    new: &str,
+) -> ! {
+    let load_manifest = |build: &str| -> PipelineResult<ManifestContents> {
+        let path = PathBuf::from(build);
+        if path.exists() {
+            return ManifestContents::load_file(&path);
+        }
+
+        let manifest_build_hash = u64::from_str_radix(build, 16)
+            .map_err(|_| format!("{:?} is not a manifest file or build hash", build))?;
+        ManifestContents::load_build(
+            &profile_build_data_path(project_configuration, profile)?,
+            manifest_build_hash,
+        )
+    };
+
+    let result = load_manifest(old).and_then(|old| {
+        let diff = ManifestDiff::new(&old, &load_manifest(new)?);
+        log::info!("{}", diff.to_text());
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            log::error!("Manifest diff failed: {}", e.to_string());
+            std::process::exit(1)
+        }
+    }
+}
+
+fn create_build_patch(
+    project_configuration: &HydrateProjectConfiguration,
+    profile: Option<&str>,
+    base: &str,
+    new: &str,
+    output: &Path,
+) -> ! {
+    let parse_build_hash = |build: &str| {
+        u64::from_str_radix(build, 16).map_err(|_| format!("{:?} is not a build hash", build))
+    };
+
+    let result =
+        profile_build_data_path(project_configuration, profile).and_then(|build_data_path| {
+            let report = write_build_patch(
+                &build_data_path,
+                parse_build_hash(base)?,
+                parse_build_hash(new)?,
+                output,
+            )?;
+            log::info!("{}", report.to_text());
+            log::info!("Build patch written to {:?}", output);
+            Ok(())
+        });
+
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            log::error!("Creating build patch failed: {}", e.to_string());
+            std::process::exit(1)
+        }
+    }
+}
+
+fn inspect_artifact(
+    project_configuration: &HydrateProjectConfiguration,
+    profile: Option<&str>,
+    build: Option<&str>,
+    artifact: &str,
+) -> ! {
+    let mut decoders = ArtifactDecoderRegistry::default();
+    decoders.register_artifact_type::<demo_types::glsl::GlslBuildTargetBuiltData>();
+    decoders.register_artifact_type::<demo_types::gpu_buffer::GpuBufferBuiltData>();
+    decoders.register_artifact_type::<demo_types::image::GpuImageAssetData>();
+    decoders.register_artifact_type::<demo_types::mesh_adv::SamplerAssetData>();
+    decoders.register_artifact_type::<demo_types::mesh_adv::MaterialAssetData>();
+    decoders.register_artifact_type::<demo_types::mesh_adv::MeshAdvMaterialData>();
+    decoders.register_artifact_type::<demo_types::mesh_adv::MeshAdvMaterialAssetData>();
+    decoders.register_artifact_type::<demo_types::mesh_adv::MeshAdvBufferAssetData>();
+    decoders.register_artifact_type::<demo_types::mesh_adv::MeshAdvMeshAssetData>();
+    decoders.register_artifact_type::<demo_types::simple_data::TransformRef>();
+    decoders.register_artifact_type::<demo_types::simple_data::Transform>();
+    decoders.register_artifact_type::<demo_types::simple_data::AllFields>();
+
+    let result =
+        profile_build_data_path(project_configuration, profile).and_then(|build_data_path| {
+            let manifest_build_hash = match build {
+                Some(build) => u64::from_str_radix(build, 16)
+                    .map_err(|_| format!("{:?} is not a build hash", build))?,
+                None => ManifestContents::find_latest_manifest_build_hash(&build_data_path)?,
+            };
+
+            let manifest = ManifestContents::load_build(&build_data_path, manifest_build_hash)?;
+            let artifact_id = manifest
+                .find_artifact(artifact)
+                .ok_or_else(|| {
+                    format!(
+                        "No artifact with ID or name {:?} in build {:0>16x}",
+                        artifact, manifest_build_hash
+                    )
+                })?
+                .artifact_id;
+            log::info!(
+                "{}",
+                inspect_built_artifact(&build_data_path, &manifest, artifact_id, &decoders)?
+            );
+            Ok(())
+        });
+
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            log::error!("Inspecting artifact failed: {}", e.to_string());
+            std::process::exit(1)
+        }
+    }
+}
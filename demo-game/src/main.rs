@@ -30,8 +30,16 @@ fn main() {
     //
     // Set up storage for loaded assets
     //
-    let mut artifact_manager =
-        hydrate::loader::ArtifactManager::new(build_data_source_path()).unwrap();
+    // A build patch made by the editor's create-build-patch command can be passed on the command
+    // line to load it over the base build
+    let mut artifact_manager = match std::env::args().nth(1) {
+        Some(patch_path) => hydrate::loader::ArtifactManager::new_with_patch(
+            build_data_source_path(),
+            PathBuf::from(patch_path),
+        ),
+        None => hydrate::loader::ArtifactManager::new(build_data_source_path()),
+    }
+    .unwrap();
     artifact_manager.add_storage_with_loader::<GpuImageAssetData, GpuImageAsset, GpuImageLoader>(
         Box::new(GpuImageLoader),
     );
@@ -446,8 +446,7 @@ pub(crate) fn include_impl<'a>(
 
     if let Some(referenced_asset_id) = referenced_asset {
         let import_info = context
-            .data_set
-            .import_info(*referenced_asset_id)
+            .import_info(*referenced_asset_id)?
             .ok_or("Imported GLSL source file had no import info")?;
         let resolved_path = import_info.source_file().clone();
 
@@ -564,9 +563,7 @@ impl JobProcessor for GlslBuildTargetJobProcessor {
 
         // Follow references to find all included source files without re-visiting the same file twice
         while let Some(next_reference) = visit_queue.pop_front() {
-            let references = context
-                .data_set
-                .resolve_all_path_reference_overrides(next_reference)?;
+            let references = context.resolve_all_path_reference_overrides(next_reference)?;
 
             for (_, &v) in &references {
                 if !dependencies.contains(&v) {
@@ -581,17 +578,14 @@ impl JobProcessor for GlslBuildTargetJobProcessor {
         //
         let mut dependency_lookup = HashMap::default();
         for dependency_asset_id in dependencies {
-            let all_hashed_references = context
-                .data_set
-                .resolve_all_hashed_path_references(dependency_asset_id)?;
+            let all_hashed_references =
+                context.resolve_all_hashed_path_references(dependency_asset_id)?;
 
-            let all_references = context
-                .data_set
-                .resolve_all_path_reference_overrides(dependency_asset_id)?;
+            let all_references =
+                context.resolve_all_path_reference_overrides(dependency_asset_id)?;
 
             let import_info = context
-                .data_set
-                .import_info(dependency_asset_id)
+                .import_info(dependency_asset_id)?
                 .ok_or("Imported GLSL source file had no import info")?;
             let this_path = import_info.source_file().clone();
             for (path_hash, canonical_path) in all_hashed_references {
@@ -608,8 +602,7 @@ impl JobProcessor for GlslBuildTargetJobProcessor {
         //TODO: Return error if source file not found
         if !source_file.is_null() {
             let source_file_import_info = context
-                .data_set
-                .import_info(source_file)
+                .import_info(source_file)?
                 .ok_or("Imported GLSL source file had no import info")?;
             let source_file_import_data =
                 &context.imported_data::<GlslSourceFileImportedDataRecord>(source_file)?;
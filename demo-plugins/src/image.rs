@@ -117,8 +117,18 @@ impl JobProcessor for GpuImageJobProcessor {
         //
         let image_bytes = if compressed {
             profiling::scope!("Compressing Image");
+            // Profiles can trade quality for size, i.e. ETC1S for mobile
+            let basis_format = match context.build_profile.setting("image_basis_format") {
+                None | Some("uastc") => basis_universal::BasisTextureFormat::UASTC4x4,
+                Some("etc1s") => basis_universal::BasisTextureFormat::ETC1S,
+                Some(other) => Err(format!(
+                    "Unknown image_basis_format {:?} in build profile {:?}",
+                    other, context.build_profile.name
+                ))?,
+            };
+
             let mut compressor_params = basis_universal::CompressorParams::new();
-            compressor_params.set_basis_format(basis_universal::BasisTextureFormat::UASTC4x4);
+            compressor_params.set_basis_format(basis_format);
             compressor_params.set_generate_mipmaps(true);
             compressor_params.set_color_space(basis_universal::ColorSpace::Srgb);
             compressor_params.set_uastc_quality_level(basis_universal::UASTC_QUALITY_DEFAULT);
@@ -1,7 +1,6 @@
 use hydrate_model::pipeline::Builder;
 use hydrate_pipeline::{
-    AssetId, BuilderContext, DataContainerRef, JobInput, JobOutput, JobProcessor, PipelineResult,
-    RunContext,
+    AssetId, BuilderContext, JobInput, JobOutput, JobProcessor, PipelineResult, RunContext,
 };
 use serde::{Deserialize, Serialize};
 use std::marker::PhantomData;
@@ -50,11 +49,7 @@ impl<T: SimpleData + Sized + Serialize + for<'a> Deserialize<'a> + TypeUuid> Job
         &self,
         context: &RunContext<Self::InputT>,
     ) -> PipelineResult<SimpleBincodeDataJobOutput> {
-        let data_set_view = DataContainerRef::from_dataset(
-            &context.data_set,
-            context.schema_set,
-            context.input.asset_id,
-        );
+        let data_set_view = context.asset_data_container(context.input.asset_id)?;
 
         //
         // Serialize and return
@@ -73,20 +73,20 @@ impl<'a> B3FWriter<'a> {
     pub fn write<W: std::io::Write>(
         &self,
         mut writer: W,
-    ) {
+    ) -> std::io::Result<()> {
         //
         // 16 byte header
         //
-        writer.write(&0xBB33FF00u32.to_ne_bytes()).unwrap();
-        writer.write(&self.file_tag.to_ne_bytes()).unwrap();
-        writer.write(&self.version.to_ne_bytes()).unwrap();
+        writer.write_all(&0xBB33FF00u32.to_ne_bytes())?;
+        writer.write_all(&self.file_tag.to_ne_bytes())?;
+        writer.write_all(&self.version.to_ne_bytes())?;
         let block_count = self.blocks.len() as u32;
-        writer.write(&block_count.to_ne_bytes()).unwrap();
+        writer.write_all(&block_count.to_ne_bytes())?;
 
         //
         // A single u64 zero + N u64 block end positions
         //
-        writer.write(&0u64.to_ne_bytes()).unwrap();
+        writer.write_all(&0u64.to_ne_bytes())?;
 
         let mut block_begin = 0;
         for block in &self.blocks {
@@ -94,7 +94,7 @@ impl<'a> B3FWriter<'a> {
             let block_end = block_begin + block.len();
 
             // Write the ending of the previous block (or 0 for first block)
-            writer.write(&(block_end as u64).to_ne_bytes()).unwrap();
+            writer.write_all(&(block_end as u64).to_ne_bytes())?;
 
             // Realign to 16 bytes, this is where the next block begins
             block_begin = ((block_end + BLOCK_ALIGNMENT_IN_BYTES - 1) / BLOCK_ALIGNMENT_IN_BYTES)
@@ -107,7 +107,7 @@ impl<'a> B3FWriter<'a> {
         let data_offset =
             HEADER_SIZE_IN_BYTES + ((self.blocks.len() + 1) * BLOCK_LENGTH_SIZE_IN_BYTES);
         if data_offset % 16 == 8 {
-            writer.write(&0u64.to_ne_bytes()).unwrap();
+            writer.write_all(&0u64.to_ne_bytes())?;
         } else {
             assert!(data_offset % 16 == 0);
         }
@@ -116,14 +116,16 @@ impl<'a> B3FWriter<'a> {
         // Write the blocks
         //
         for block in &self.blocks {
-            writer.write(*block).unwrap();
+            writer.write_all(*block)?;
             if block.len() % 16 != 0 {
                 let required_padding = 16 - block.len() % 16;
                 for _ in 0..required_padding {
-                    writer.write(&0u8.to_ne_bytes()).unwrap();
+                    writer.write_all(&0u8.to_ne_bytes())?;
                 }
             }
         }
+
+        Ok(())
     }
 }
 
@@ -0,0 +1,24 @@
+//! Build patches
+//!
+//! A patch holds the output of a new build that is missing from a base build: the artifacts that
+//! were added or changed and the new build's manifest and TOC. It uses the same layout as a build
+//! data path, plus a patch file that records which builds it goes between. The loader reads the
+//! TOC and manifest from the patch and loads each artifact from the patch if it's there, and from
+//! the base build otherwise.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const BUILD_PATCH_FILE_NAME: &str = "patch.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BuildPatchJson {
+    // stored as strings so we can encode as hex, like the debug manifest
+    pub base_manifest_build_hash: String,
+    pub manifest_build_hash: String,
+    pub artifact_count: usize,
+}
+
+pub fn build_patch_file_path(patch_path: &Path) -> PathBuf {
+    patch_path.join(BUILD_PATCH_FILE_NAME)
+}
@@ -0,0 +1,47 @@
+//! Build pins
+//!
+//! A running game pins the manifest it has loaded so that build output garbage collection doesn't
+//! delete artifacts it may still load. A pin is a small file in the "pins" directory of the build
+//! data path, named [manifest build hash].[process ID].pin. The game rewrites the pin every
+//! BUILD_PIN_REFRESH_INTERVAL and deletes it when it switches manifests or shuts down. Pins that
+//! haven't been written within BUILD_PIN_TIMEOUT were left behind by a game that didn't exit
+//! cleanly.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub const BUILD_PIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+pub const BUILD_PIN_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub fn build_pin_dir_path(build_data_path: &Path) -> PathBuf {
+    build_data_path.join("pins")
+}
+
+pub fn build_pin_path(
+    build_data_path: &Path,
+    manifest_build_hash: u64,
+    process_id: u32,
+) -> PathBuf {
+    build_pin_dir_path(build_data_path)
+        .join(format!("{:0>16x}.{}.pin", manifest_build_hash, process_id))
+}
+
+// Returns the manifest build hash of a pin file, or None if the file is not a pin
+pub fn parse_build_pin_path(path: &Path) -> Option<u64> {
+    let file_name = path.file_name()?.to_str()?;
+    let (manifest_build_hash, process_id) = file_name.strip_suffix(".pin")?.split_once('.')?;
+    process_id.parse::<u32>().ok()?;
+    u64::from_str_radix(manifest_build_hash, 16).ok()
+}
+
+// True if the pin was written recently enough that the game that wrote it is probably running
+pub fn is_build_pin_live(path: &Path) -> bool {
+    match path.metadata().and_then(|x| x.modified()) {
+        // A modified time in the future is treated as just written
+        Ok(modified) => modified
+            .elapsed()
+            .map(|x| x < BUILD_PIN_TIMEOUT)
+            .unwrap_or(true),
+        Err(_) => false,
+    }
+}
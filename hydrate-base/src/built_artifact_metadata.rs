@@ -55,7 +55,7 @@ const MAX_HEADER_SIZE: usize = 1024 * 1024;
 
 //TODO: Could use B3F here, but this is working fine for now.
 //TODO: Probably don't strictly need bincode either
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuiltArtifactHeaderData {
     pub dependencies: Vec<ArtifactId>,
     // Should be called artifact_type but this would be an unnecessary schema break
@@ -96,14 +96,22 @@ impl BuiltArtifactHeaderData {
         reader: &mut T
     ) -> std::io::Result<BuiltArtifactHeaderData> {
         let mut length_bytes = [0u8; 8];
-        reader.read(&mut length_bytes)?;
+        reader.read_exact(&mut length_bytes)?;
         let length = usize::from_le_bytes(length_bytes);
-        assert!(length <= MAX_HEADER_SIZE);
+        if length > MAX_HEADER_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Built artifact header is {} bytes, the limit is {}",
+                    length, MAX_HEADER_SIZE
+                ),
+            ));
+        }
 
         let mut read_buffer = vec![0u8; length];
-        reader.read_exact(&mut read_buffer).unwrap();
+        reader.read_exact(&mut read_buffer)?;
 
-        let metadata = bincode::deserialize(&read_buffer).unwrap();
-        Ok(metadata)
+        bincode::deserialize(&read_buffer)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
     }
 }
@@ -23,4 +23,10 @@ pub use string_hash::StringHash;
 
 pub mod b3f;
 
+pub mod release_manifest;
+
+pub mod build_pin;
+
+pub mod build_patch;
+
 pub mod lru_cache;
@@ -0,0 +1,349 @@
+//! Binary release manifest
+//!
+//! The release manifest is loaded by the game at startup, so it is stored in a form that can be
+//! used directly from a memory-mapped file without parsing. It is a B3F file with two blocks:
+//!
+//! Block 0: Artifact entries, sorted by artifact ID so they can be binary searched. Each entry is
+//! ENTRY_SIZE_IN_BYTES bytes:
+//! [16] artifact ID (u128)
+//! [8] build hash (u64)
+//! [8] combined build hash (u64)
+//! [16] artifact type (u128)
+//! [16] symbol hash (u128, 0 if the artifact has no symbol)
+//!
+//! Block 1: Symbol table, sorted by symbol hash so it can be binary searched. Each entry is
+//! SYMBOL_ENTRY_SIZE_IN_BYTES bytes:
+//! [16] symbol hash (u128)
+//! [8] index of the artifact entry in block 0 (u64)
+//! [8] padding
+//!
+//! All values are little-endian.
+
+use crate::b3f::{B3FReader, B3FWriter};
+use crate::{ArtifactId, ArtifactManifestData, StringHash};
+use std::convert::TryInto;
+use std::io::Cursor;
+use std::ops::Range;
+use uuid::Uuid;
+
+pub const RELEASE_MANIFEST_FILE_TAG: &[u8; 4] = b"HYRM";
+pub const RELEASE_MANIFEST_VERSION: u32 = 1;
+
+const ENTRY_SIZE_IN_BYTES: usize = 64;
+const SYMBOL_ENTRY_SIZE_IN_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReleaseManifestEntry {
+    pub artifact_id: ArtifactId,
+    pub build_hash: u64,
+    pub combined_build_hash: u64,
+    pub artifact_type: Uuid,
+    // 0 if the artifact can't be looked up by symbol
+    pub symbol_hash: u128,
+}
+
+impl ReleaseManifestEntry {
+    fn write_to(
+        &self,
+        bytes: &mut Vec<u8>,
+    ) {
+        bytes.extend_from_slice(&self.artifact_id.as_u128().to_le_bytes());
+        bytes.extend_from_slice(&self.build_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.combined_build_hash.to_le_bytes());
+        bytes.extend_from_slice(&self.artifact_type.as_u128().to_le_bytes());
+        bytes.extend_from_slice(&self.symbol_hash.to_le_bytes());
+    }
+
+    fn read_from(bytes: &[u8]) -> Self {
+        ReleaseManifestEntry {
+            artifact_id: ArtifactId::from_u128(read_u128(bytes, 0)),
+            build_hash: read_u64(bytes, 16),
+            combined_build_hash: read_u64(bytes, 24),
+            artifact_type: Uuid::from_u128(read_u128(bytes, 32)),
+            symbol_hash: read_u128(bytes, 48),
+        }
+    }
+
+    // Debug names and symbol strings are not in the release manifest
+    pub fn to_manifest_data(&self) -> ArtifactManifestData {
+        ArtifactManifestData {
+            artifact_id: self.artifact_id,
+            simple_build_hash: self.build_hash,
+            combined_build_hash: self.combined_build_hash,
+            symbol_hash: if self.symbol_hash != 0 {
+                Some(StringHash::from_hash(self.symbol_hash))
+            } else {
+                None
+            },
+            artifact_type: self.artifact_type,
+            debug_name: None,
+        }
+    }
+}
+
+fn read_u64(
+    bytes: &[u8],
+    offset: usize,
+) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_u128(
+    bytes: &[u8],
+    offset: usize,
+) -> u128 {
+    u128::from_le_bytes(bytes[offset..offset + 16].try_into().unwrap())
+}
+
+// Entries may be in any order. Symbol hashes must be unique (other than 0).
+pub fn write_release_manifest<W: std::io::Write>(
+    writer: W,
+    entries: &mut [ReleaseManifestEntry],
+) -> Result<(), String> {
+    entries.sort_by_key(|x| x.artifact_id.as_u128());
+
+    let mut entry_bytes = Vec::with_capacity(entries.len() * ENTRY_SIZE_IN_BYTES);
+    let mut symbols = Vec::default();
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 && entries[i - 1].artifact_id == entry.artifact_id {
+            return Err(format!(
+                "Artifact {:?} is in the manifest more than once",
+                entry.artifact_id
+            ));
+        }
+
+        entry.write_to(&mut entry_bytes);
+        if entry.symbol_hash != 0 {
+            symbols.push((entry.symbol_hash, i as u64));
+        }
+    }
+
+    symbols.sort();
+    let mut symbol_bytes = Vec::with_capacity(symbols.len() * SYMBOL_ENTRY_SIZE_IN_BYTES);
+    for (i, (symbol_hash, entry_index)) in symbols.iter().enumerate() {
+        if i > 0 && symbols[i - 1].0 == *symbol_hash {
+            return Err(format!(
+                "Symbol hash {:0>32x} is in the manifest more than once",
+                symbol_hash
+            ));
+        }
+
+        symbol_bytes.extend_from_slice(&symbol_hash.to_le_bytes());
+        symbol_bytes.extend_from_slice(&entry_index.to_le_bytes());
+        symbol_bytes.extend_from_slice(&0u64.to_le_bytes());
+    }
+
+    let mut b3f_writer =
+        B3FWriter::new_from_u8_tag(*RELEASE_MANIFEST_FILE_TAG, RELEASE_MANIFEST_VERSION);
+    b3f_writer.add_block(&entry_bytes);
+    b3f_writer.add_block(&symbol_bytes);
+    b3f_writer
+        .write(writer)
+        .map_err(|e| format!("Could not write the manifest: {}", e))
+}
+
+/// Read-only view of a release manifest. The data can be anything that derefs to bytes, like a
+/// Vec<u8> or a memory-mapped file. Lookups binary search the data in place.
+pub struct ReleaseManifest<T: AsRef<[u8]>> {
+    data: T,
+    entries: Range<usize>,
+    symbols: Range<usize>,
+}
+
+impl<T: AsRef<[u8]>> ReleaseManifest<T> {
+    pub fn new(data: T) -> Result<Self, String> {
+        let bytes = data.as_ref();
+        let reader = B3FReader::new(&mut Cursor::new(bytes))
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "Release manifest is not a B3F file".to_string())?;
+
+        if reader.file_tag_as_u8() != RELEASE_MANIFEST_FILE_TAG {
+            return Err("Release manifest has an unexpected file tag".to_string());
+        }
+
+        if reader.version() != RELEASE_MANIFEST_VERSION {
+            return Err(format!(
+                "Release manifest is version {} but version {} is expected",
+                reader.version(),
+                RELEASE_MANIFEST_VERSION
+            ));
+        }
+
+        if reader.block_count() != 2 {
+            return Err(format!(
+                "Release manifest has {} blocks but 2 are expected",
+                reader.block_count()
+            ));
+        }
+
+        let mut cursor = Cursor::new(bytes);
+        let entries = reader
+            .get_block_location(&mut cursor, 0)
+            .map_err(|e| e.to_string())?;
+        let symbols = reader
+            .get_block_location(&mut cursor, 1)
+            .map_err(|e| e.to_string())?;
+
+        if entries.start > entries.end
+            || symbols.start > symbols.end
+            || entries.end > bytes.len()
+            || symbols.end > bytes.len()
+            || entries.len() % ENTRY_SIZE_IN_BYTES != 0
+            || symbols.len() % SYMBOL_ENTRY_SIZE_IN_BYTES != 0
+        {
+            return Err("Release manifest is truncated or corrupt".to_string());
+        }
+
+        Ok(ReleaseManifest {
+            data,
+            entries,
+            symbols,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() / ENTRY_SIZE_IN_BYTES
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry_bytes(
+        &self,
+        index: usize,
+    ) -> &[u8] {
+        let begin = self.entries.start + index * ENTRY_SIZE_IN_BYTES;
+        &self.data.as_ref()[begin..begin + ENTRY_SIZE_IN_BYTES]
+    }
+
+    pub fn entry(
+        &self,
+        index: usize,
+    ) -> ReleaseManifestEntry {
+        ReleaseManifestEntry::read_from(self.entry_bytes(index))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ReleaseManifestEntry> + '_ {
+        (0..self.len()).map(move |i| self.entry(i))
+    }
+
+    pub fn find(
+        &self,
+        artifact_id: ArtifactId,
+    ) -> Option<ReleaseManifestEntry> {
+        let artifact_id = artifact_id.as_u128();
+        let index = binary_search(self.len(), |i| {
+            read_u128(self.entry_bytes(i), 0).cmp(&artifact_id)
+        })?;
+        Some(self.entry(index))
+    }
+
+    pub fn find_by_symbol(
+        &self,
+        symbol_hash: u128,
+    ) -> Option<ReleaseManifestEntry> {
+        let symbol_bytes = &self.data.as_ref()[self.symbols.clone()];
+        let symbol_count = symbol_bytes.len() / SYMBOL_ENTRY_SIZE_IN_BYTES;
+        let index = binary_search(symbol_count, |i| {
+            read_u128(symbol_bytes, i * SYMBOL_ENTRY_SIZE_IN_BYTES).cmp(&symbol_hash)
+        })?;
+
+        let entry_index = read_u64(symbol_bytes, index * SYMBOL_ENTRY_SIZE_IN_BYTES + 16) as usize;
+        if entry_index < self.len() {
+            Some(self.entry(entry_index))
+        } else {
+            None
+        }
+    }
+}
+
+fn binary_search<F: Fn(usize) -> std::cmp::Ordering>(
+    len: usize,
+    compare: F,
+) -> Option<usize> {
+    let mut begin = 0;
+    let mut end = len;
+    while begin < end {
+        let mid = begin + (end - begin) / 2;
+        match compare(mid) {
+            std::cmp::Ordering::Less => begin = mid + 1,
+            std::cmp::Ordering::Greater => end = mid,
+            std::cmp::Ordering::Equal => return Some(mid),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn release_manifest_lookup() {
+        let mut entries: Vec<_> = (0..100u128)
+            .rev()
+            .map(|i| ReleaseManifestEntry {
+                artifact_id: ArtifactId::from_u128(i * 7 + 1),
+                build_hash: i as u64,
+                combined_build_hash: i as u64 * 2,
+                artifact_type: Uuid::from_u128(5),
+                symbol_hash: if i % 3 == 0 { 1000 - i } else { 0 },
+            })
+            .collect();
+
+        let mut data = Vec::default();
+        write_release_manifest(&mut data, &mut entries).unwrap();
+        let manifest = ReleaseManifest::new(data).unwrap();
+        assert_eq!(manifest.len(), 100);
+
+        for i in 0..100u128 {
+            let entry = manifest.find(ArtifactId::from_u128(i * 7 + 1)).unwrap();
+            assert_eq!(entry.build_hash, i as u64);
+            assert_eq!(entry.combined_build_hash, i as u64 * 2);
+            assert!(manifest.find(ArtifactId::from_u128(i * 7 + 2)).is_none());
+
+            if i % 3 == 0 {
+                let entry = manifest.find_by_symbol(1000 - i).unwrap();
+                assert_eq!(entry.artifact_id, ArtifactId::from_u128(i * 7 + 1));
+            } else {
+                assert!(manifest.find_by_symbol(1000 - i).is_none());
+            }
+        }
+
+        assert!(manifest.find_by_symbol(0).is_none());
+    }
+
+    #[test]
+    fn release_manifest_rejects_corrupt_data() {
+        let mut entries = vec![ReleaseManifestEntry {
+            artifact_id: ArtifactId::from_u128(1),
+            build_hash: 2,
+            combined_build_hash: 3,
+            artifact_type: Uuid::from_u128(4),
+            symbol_hash: 5,
+        }];
+        let mut data = Vec::default();
+        write_release_manifest(&mut data, &mut entries).unwrap();
+
+        for len in 0..data.len() {
+            assert!(ReleaseManifest::new(&data[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn release_manifest_write_error() {
+        let mut entries = vec![ReleaseManifestEntry {
+            artifact_id: ArtifactId::from_u128(1),
+            build_hash: 2,
+            combined_build_hash: 3,
+            artifact_type: Uuid::from_u128(4),
+            symbol_hash: 5,
+        }];
+
+        // A slice fails writes once it is full, like a full disk
+        let mut data = [0u8; 64];
+        assert!(write_release_manifest(&mut data[..], &mut entries).is_err());
+    }
+}
@@ -1,15 +1,17 @@
 use crate::path_reference::CanonicalPathReference;
 use crate::{
-    AssetId, HashMap, HashSet, OrderedSet, PathReference, PathReferenceHash, Schema,
-    SchemaFingerprint, SchemaRecord, SingleObject, Value,
+    AssetId, HashMap, HashSet, OrderedSet, PathReference, PathReferenceHash,
+    PathReferenceNamespaceResolver, Schema, SchemaFingerprint, SchemaRecord, SingleObject, Value,
 };
 pub use crate::{DataSetError, DataSetResult};
 use crate::{NullOverride, SchemaSet};
 use siphasher::sip::SipHasher;
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
+use std::path::Path;
 use std::str::FromStr;
 use std::string::ToString;
+use std::sync::Arc;
 use uuid::Uuid;
 
 #[derive(Copy, Clone, PartialEq)]
@@ -209,6 +211,41 @@ impl ImportInfo {
     pub fn import_data_contents_hash(&self) -> u64 {
         self.import_data_contents_hash
     }
+
+    // Returns a copy with any references to old_path pointed at new_path instead, or None if
+    // nothing refers to old_path
+    pub fn relocate_path_references(
+        &self,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+        old_path: &Path,
+        new_path: &Path,
+    ) -> Option<ImportInfo> {
+        let mut relocated = self.clone();
+        let mut any_relocated = false;
+
+        if let Some(source_file) = self
+            .source_file
+            .relocate(namespace_resolver, old_path, new_path)
+        {
+            relocated.source_file = source_file;
+            any_relocated = true;
+        }
+
+        for (_, path_reference) in &mut relocated.path_references {
+            if let Some(new_path_reference) =
+                path_reference.relocate(namespace_resolver, old_path, new_path)
+            {
+                *path_reference = new_path_reference;
+                any_relocated = true;
+            }
+        }
+
+        if any_relocated {
+            Some(relocated)
+        } else {
+            None
+        }
+    }
 }
 
 impl Hash for ImportInfo {
@@ -399,7 +436,7 @@ impl PropertiesBundle {
 /// The full state of a single asset in a dataset
 #[derive(Clone, Debug)]
 pub struct DataSetAssetInfo {
-    schema: SchemaRecord,
+    pub(super) schema: SchemaRecord,
 
     pub(super) asset_name: AssetName,
     pub(super) asset_location: AssetLocation,
@@ -461,23 +498,33 @@ impl DataSetAssetInfo {
 /// etc. This includes being aware of schema and prototypes.
 #[derive(Default, Clone)]
 pub struct DataSet {
-    assets: HashMap<AssetId, DataSetAssetInfo>,
+    // Assets are shared between clones of the data set and copied when one of them modifies the
+    // asset. This keeps cloning cheap, a build can snapshot the data set while editing continues.
+    assets: HashMap<AssetId, Arc<DataSetAssetInfo>>,
 }
 
 impl DataSet {
-    pub fn assets(&self) -> &HashMap<AssetId, DataSetAssetInfo> {
+    pub fn assets(&self) -> &HashMap<AssetId, Arc<DataSetAssetInfo>> {
         &self.assets
     }
 
     // Exposed to allow diffs to apply changes
-    pub(super) fn assets_mut(&mut self) -> &mut HashMap<AssetId, DataSetAssetInfo> {
+    pub(super) fn assets_mut(&mut self) -> &mut HashMap<AssetId, Arc<DataSetAssetInfo>> {
         &mut self.assets
     }
 
-    pub fn take_assets(self) -> HashMap<AssetId, DataSetAssetInfo> {
+    pub fn take_assets(self) -> HashMap<AssetId, Arc<DataSetAssetInfo>> {
         self.assets
     }
 
+    // Copies the asset first if a clone of the data set still refers to it
+    fn asset_mut(
+        &mut self,
+        asset_id: AssetId,
+    ) -> Option<&mut DataSetAssetInfo> {
+        self.assets.get_mut(&asset_id).map(Arc::make_mut)
+    }
+
     // Inserts the asset but only if the ID is not already in use
     fn insert_asset(
         &mut self,
@@ -487,7 +534,7 @@ impl DataSet {
         if self.assets.contains_key(&id) {
             Err(DataSetError::DuplicateAssetId)?
         } else {
-            let old = self.assets.insert(id, obj_info);
+            let old = self.assets.insert(id, Arc::new(obj_info));
             assert!(old.is_none());
             Ok(())
         }
@@ -527,7 +574,7 @@ impl DataSet {
             dynamic_collection_entries,
         };
 
-        self.assets.insert(asset_id, obj);
+        self.assets.insert(asset_id, Arc::new(obj));
         Ok(())
     }
 
@@ -590,8 +637,7 @@ impl DataSet {
             asset_location,
             &prototype_schema.schema().clone(),
         );
-        self.assets
-            .get_mut(&id)
+        self.asset_mut(id)
             .expect("Newly created asset was not found")
             .prototype = Some(prototype_asset_id);
         Ok(id)
@@ -605,8 +651,7 @@ impl DataSet {
         single_object: &SingleObject,
     ) -> DataSetResult<()> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
 
         if asset.schema.fingerprint() != single_object.schema().fingerprint() {
@@ -728,8 +773,7 @@ impl DataSet {
         }
 
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
 
         asset.asset_location = new_location;
@@ -743,8 +787,7 @@ impl DataSet {
         import_info: ImportInfo,
     ) -> DataSetResult<()> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
 
         asset.import_info = Some(import_info);
@@ -787,8 +830,7 @@ impl DataSet {
         asset_name: AssetName,
     ) -> DataSetResult<()> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
 
         asset.asset_name = asset_name;
@@ -1032,8 +1074,7 @@ impl DataSet {
         referenced_asset_id: AssetId,
     ) -> DataSetResult<()> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
 
         asset
@@ -1043,6 +1084,18 @@ impl DataSet {
         Ok(())
     }
 
+    pub fn remove_path_reference_override(
+        &mut self,
+        asset_id: AssetId,
+        path: &CanonicalPathReference,
+    ) -> DataSetResult<Option<AssetId>> {
+        let asset = self
+            .asset_mut(asset_id)
+            .ok_or(DataSetError::AssetNotFound)?;
+
+        Ok(asset.build_info.path_reference_overrides.remove(path))
+    }
+
     pub fn asset_prototype(
         &self,
         asset_id: AssetId,
@@ -1227,8 +1280,7 @@ impl DataSet {
         null_override: NullOverride,
     ) -> DataSetResult<()> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         let property_schema = asset
             .schema
@@ -1392,8 +1444,7 @@ impl DataSet {
         let _ = self.validate_parent_paths(schema_set, asset_id, path.as_ref())?;
 
         let obj = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         let old_value = if let Some(value) = value {
             obj.properties.insert(path.as_ref().to_string(), value)
@@ -1535,8 +1586,7 @@ impl DataSet {
         path: impl AsRef<str>,
     ) -> DataSetResult<Uuid> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         let property_schema = asset
             .schema
@@ -1557,8 +1607,7 @@ impl DataSet {
         path: impl AsRef<str>,
     ) -> DataSetResult<Uuid> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         let property_schema = asset
             .schema
@@ -1581,8 +1630,7 @@ impl DataSet {
         entry_uuid: Uuid,
     ) -> DataSetResult<()> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         let property_schema = asset
             .schema
@@ -1627,8 +1675,7 @@ impl DataSet {
         element_id: Uuid,
     ) -> DataSetResult<bool> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         let property_schema = asset
             .schema
@@ -1650,8 +1697,7 @@ impl DataSet {
         element_id: Uuid,
     ) -> DataSetResult<bool> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         let property_schema = asset
             .schema
@@ -1769,8 +1815,7 @@ impl DataSet {
         behavior: OverrideBehavior,
     ) -> DataSetResult<()> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         let property_schema = asset
             .schema
@@ -1814,8 +1859,7 @@ impl DataSet {
         properties_bundle: &PropertiesBundle,
     ) -> DataSetResult<()> {
         let asset = self
-            .assets
-            .get_mut(&asset_id)
+            .asset_mut(asset_id)
             .ok_or(DataSetError::AssetNotFound)?;
         properties_bundle.write(asset, path, schema_set)
     }
@@ -4,29 +4,30 @@ use crate::{
     AssetId, AssetLocation, AssetName, DataSet, DataSetAssetInfo, DataSetResult, HashSet,
     NullOverride, OrderedSet, SchemaSet,
 };
+use std::sync::Arc;
 use uuid::Uuid;
 
 #[derive(Debug)]
 pub struct DynamicArrayEntryDelta {
-    key: String,
+    pub(crate) key: String,
     // was previous add/remove, but order is important and this didn't maintain order
-    entries: OrderedSet<Uuid>,
+    pub(crate) entries: OrderedSet<Uuid>,
 }
 
 #[derive(Default, Debug)]
 pub struct AssetDiff {
-    set_name: Option<AssetName>,
-    set_location: Option<AssetLocation>,
-    set_prototype: Option<Option<AssetId>>,
-    set_properties: Vec<(String, PropertyValue)>,
-    remove_properties: Vec<String>,
-    set_null_overrides: Vec<(String, NullOverride)>,
-    remove_null_overrides: Vec<String>,
-    add_properties_in_replace_mode: Vec<String>,
-    remove_properties_in_replace_mode: Vec<String>,
-    dynamic_array_entry_deltas: Vec<DynamicArrayEntryDelta>,
-    set_canonical_path_references: Vec<(CanonicalPathReference, AssetId)>,
-    remove_canonical_path_references: Vec<CanonicalPathReference>,
+    pub(crate) set_name: Option<AssetName>,
+    pub(crate) set_location: Option<AssetLocation>,
+    pub(crate) set_prototype: Option<Option<AssetId>>,
+    pub(crate) set_properties: Vec<(String, PropertyValue)>,
+    pub(crate) remove_properties: Vec<String>,
+    pub(crate) set_null_overrides: Vec<(String, NullOverride)>,
+    pub(crate) remove_null_overrides: Vec<String>,
+    pub(crate) add_properties_in_replace_mode: Vec<String>,
+    pub(crate) remove_properties_in_replace_mode: Vec<String>,
+    pub(crate) dynamic_array_entry_deltas: Vec<DynamicArrayEntryDelta>,
+    pub(crate) set_canonical_path_references: Vec<(CanonicalPathReference, AssetId)>,
+    pub(crate) remove_canonical_path_references: Vec<CanonicalPathReference>,
 }
 
 impl AssetDiff {
@@ -45,6 +46,46 @@ impl AssetDiff {
             || !self.remove_canonical_path_references.is_empty()
     }
 
+    pub fn changes_name(&self) -> bool {
+        self.set_name.is_some()
+    }
+
+    pub fn changes_location(&self) -> bool {
+        self.set_location.is_some()
+    }
+
+    pub fn changes_prototype(&self) -> bool {
+        self.set_prototype.is_some()
+    }
+
+    // Paths of any properties whose value, null override, override behavior, or collection
+    // entries are changed by this diff. Sorted and deduplicated.
+    pub fn modified_property_paths(&self) -> Vec<&str> {
+        let mut paths: Vec<&str> = Vec::default();
+        paths.extend(self.set_properties.iter().map(|(k, _)| k.as_str()));
+        paths.extend(self.remove_properties.iter().map(|k| k.as_str()));
+        paths.extend(self.set_null_overrides.iter().map(|(k, _)| k.as_str()));
+        paths.extend(self.remove_null_overrides.iter().map(|k| k.as_str()));
+        paths.extend(
+            self.add_properties_in_replace_mode
+                .iter()
+                .map(|k| k.as_str()),
+        );
+        paths.extend(
+            self.remove_properties_in_replace_mode
+                .iter()
+                .map(|k| k.as_str()),
+        );
+        paths.extend(
+            self.dynamic_array_entry_deltas
+                .iter()
+                .map(|x| x.key.as_str()),
+        );
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
     pub fn apply(
         &self,
         asset: &mut DataSetAssetInfo,
@@ -364,11 +405,18 @@ impl AssetDiffSet {
     }
 }
 
+// How a DataSetDiff affects a single asset
+pub enum DataSetAssetDiff<'a> {
+    Create(&'a DataSetAssetInfo),
+    Delete,
+    Change(&'a AssetDiff),
+}
+
 #[derive(Default, Debug)]
 pub struct DataSetDiff {
-    creates: Vec<(AssetId, DataSetAssetInfo)>,
-    deletes: Vec<AssetId>,
-    changes: Vec<(AssetId, AssetDiff)>,
+    pub(crate) creates: Vec<(AssetId, Arc<DataSetAssetInfo>)>,
+    pub(crate) deletes: Vec<AssetId>,
+    pub(crate) changes: Vec<(AssetId, AssetDiff)>,
 }
 
 impl DataSetDiff {
@@ -404,7 +452,67 @@ impl DataSetDiff {
 
         for (asset_id, v) in &self.changes {
             if let Some(asset) = data_set.assets_mut().get_mut(asset_id) {
-                v.apply(asset);
+                v.apply(Arc::make_mut(asset));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn asset_diff(
+        &self,
+        asset_id: AssetId,
+    ) -> Option<DataSetAssetDiff<'_>> {
+        // Same order that apply() uses, a create after a delete is the final state
+        if let Some((_, create)) = self.creates.iter().find(|(id, _)| *id == asset_id) {
+            return Some(DataSetAssetDiff::Create(create));
+        }
+
+        if self.deletes.contains(&asset_id) {
+            return Some(DataSetAssetDiff::Delete);
+        }
+
+        self.changes
+            .iter()
+            .find(|(id, _)| *id == asset_id)
+            .map(|(_, change)| DataSetAssetDiff::Change(change))
+    }
+
+    // Like apply(), but ignores everything in the diff that doesn't affect the given asset
+    pub fn apply_to_asset(
+        &self,
+        data_set: &mut DataSet,
+        schema_set: &SchemaSet,
+        asset_id: AssetId,
+    ) -> DataSetResult<()> {
+        if self.deletes.contains(&asset_id) && data_set.assets().contains_key(&asset_id) {
+            data_set.delete_asset(asset_id)?;
+        }
+
+        for (id, create) in &self.creates {
+            if *id == asset_id {
+                data_set.restore_asset(
+                    *id,
+                    create.asset_name.clone(),
+                    create.asset_location.clone(),
+                    create.import_info.clone(),
+                    create.build_info.clone(),
+                    schema_set,
+                    create.prototype,
+                    create.schema().fingerprint(),
+                    create.properties.clone(),
+                    create.property_null_overrides.clone(),
+                    create.properties_in_replace_mode.clone(),
+                    create.dynamic_collection_entries.clone(),
+                )?;
+            }
+        }
+
+        for (id, v) in &self.changes {
+            if *id == asset_id {
+                if let Some(asset) = data_set.assets_mut().get_mut(id) {
+                    v.apply(Arc::make_mut(asset));
+                }
             }
         }
 
@@ -489,6 +597,7 @@ impl DataSetDiffSet {
             } else if existed_after {
                 // Asset was created
                 let after_asset_info = after.assets().get(&asset_id).unwrap();
+                modified_assets.insert(asset_id);
                 if !modified_locations.contains(&after_asset_info.asset_location) {
                     modified_locations.insert(after_asset_info.asset_location.clone());
                 }
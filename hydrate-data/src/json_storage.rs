@@ -1,10 +1,14 @@
-use crate::value::ValueEnum;
+use crate::diff::{AssetDiff, DynamicArrayEntryDelta};
+use crate::value::{PropertyValue, ValueEnum};
 use crate::{
-    AssetId, BuildInfo, DataSetAssetInfo, HashMap, HashSet, ImportInfo, ImporterId, NullOverride,
-    PathReference, PathReferenceHash, PathReferenceNamespaceResolver, Schema, SchemaFingerprint,
-    SchemaNamedType, SchemaSet, SingleObject, Value,
+    AssetId, BuildInfo, DataSet, DataSetAssetInfo, HashMap, HashSet, ImportInfo, ImporterId,
+    NullOverride, PathReference, PathReferenceHash, PathReferenceNamespaceResolver, Schema,
+    SchemaFingerprint, SchemaNamedType, SchemaSet, SingleObject, Value,
+};
+use crate::{
+    AssetLocation, AssetName, DataSetDiff, DataSetDiffSet, DataSetResult, ImportableName,
+    OrderedSet,
 };
-use crate::{AssetLocation, AssetName, DataSetResult, ImportableName, OrderedSet};
 use hydrate_schema::{CachedSchemaNamedType, DataSetError, SchemaRecord};
 use serde::{Deserialize, Serialize};
 use std::hash::Hash;
@@ -787,7 +791,7 @@ impl AssetJson {
     #[profiling::function]
     pub fn save_asset_to_string(
         schema_set: &SchemaSet,
-        assets: &HashMap<AssetId, DataSetAssetInfo>,
+        assets: &HashMap<AssetId, Arc<DataSetAssetInfo>>,
         asset_id: AssetId,
         // We only save the ID in the file if using path-based file system storage. Otherwise the
         // id is the file path/name
@@ -1083,3 +1087,455 @@ impl MetaFileJson {
         serde_json::to_string_pretty(&json_object).unwrap()
     }
 }
+
+//
+// Diffs. These are used to persist undo history. Unlike asset files, property values in a diff are
+// stored with their type since the asset (and its schema) may not exist when the diff is loaded
+//
+#[derive(Debug, Serialize, Deserialize)]
+pub enum PropertyValueJson {
+    Boolean(bool),
+    I32(i32),
+    I64(i64),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Bytes(String),
+    String(String),
+    AssetRef(Uuid),
+    Enum(String),
+}
+
+impl PropertyValueJson {
+    pub fn new(value: &PropertyValue) -> Self {
+        match value {
+            PropertyValue::Boolean(x) => PropertyValueJson::Boolean(*x),
+            PropertyValue::I32(x) => PropertyValueJson::I32(*x),
+            PropertyValue::I64(x) => PropertyValueJson::I64(*x),
+            PropertyValue::U32(x) => PropertyValueJson::U32(*x),
+            PropertyValue::U64(x) => PropertyValueJson::U64(*x),
+            PropertyValue::F32(x) => PropertyValueJson::F32(*x),
+            PropertyValue::F64(x) => PropertyValueJson::F64(*x),
+            PropertyValue::Bytes(x) => PropertyValueJson::Bytes(base64::encode(&**x)),
+            PropertyValue::String(x) => PropertyValueJson::String(x.to_string()),
+            PropertyValue::AssetRef(x) => PropertyValueJson::AssetRef(x.as_uuid()),
+            PropertyValue::Enum(x) => PropertyValueJson::Enum(x.symbol_name().to_string()),
+        }
+    }
+
+    pub fn to_property_value(&self) -> DataSetResult<PropertyValue> {
+        Ok(match self {
+            PropertyValueJson::Boolean(x) => PropertyValue::Boolean(*x),
+            PropertyValueJson::I32(x) => PropertyValue::I32(*x),
+            PropertyValueJson::I64(x) => PropertyValue::I64(*x),
+            PropertyValueJson::U32(x) => PropertyValue::U32(*x),
+            PropertyValueJson::U64(x) => PropertyValue::U64(*x),
+            PropertyValueJson::F32(x) => PropertyValue::F32(*x),
+            PropertyValueJson::F64(x) => PropertyValue::F64(*x),
+            PropertyValueJson::Bytes(x) => PropertyValue::Bytes(Arc::new(
+                base64::decode(x).map_err(|_| DataSetError::StorageFormatError)?,
+            )),
+            PropertyValueJson::String(x) => PropertyValue::String(Arc::new(x.clone())),
+            PropertyValueJson::AssetRef(x) => PropertyValue::AssetRef(AssetId::from_uuid(*x)),
+            PropertyValueJson::Enum(x) => PropertyValue::Enum(ValueEnum::new(x.clone())),
+        })
+    }
+}
+
+// Wrapped so that "set the prototype to none" and "don't change the prototype" can be told apart
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetPrototypeJson {
+    prototype: Option<Uuid>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AssetDiffJson {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    set_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    set_location: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    set_prototype: Option<SetPrototypeJson>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    set_properties: Vec<(String, PropertyValueJson)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    remove_properties: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    set_null_overrides: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    remove_null_overrides: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    add_properties_in_replace_mode: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    remove_properties_in_replace_mode: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dynamic_array_entry_deltas: Vec<(String, Vec<Uuid>)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    set_canonical_path_references: Vec<(String, Uuid)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    remove_canonical_path_references: Vec<String>,
+}
+
+impl AssetDiffJson {
+    pub fn new(diff: &AssetDiff) -> Self {
+        AssetDiffJson {
+            set_name: diff
+                .set_name
+                .as_ref()
+                .map(|x| x.as_string().cloned().unwrap_or_default()),
+            set_location: diff.set_location.map(|x| x.path_node_id().as_uuid()),
+            set_prototype: diff.set_prototype.map(|x| SetPrototypeJson {
+                prototype: x.map(|x| x.as_uuid()),
+            }),
+            set_properties: diff
+                .set_properties
+                .iter()
+                .map(|(k, v)| (k.clone(), PropertyValueJson::new(v)))
+                .collect(),
+            remove_properties: diff.remove_properties.clone(),
+            set_null_overrides: diff
+                .set_null_overrides
+                .iter()
+                .map(|(k, v)| (k.clone(), null_override_to_string_value(*v).to_string()))
+                .collect(),
+            remove_null_overrides: diff.remove_null_overrides.clone(),
+            add_properties_in_replace_mode: diff.add_properties_in_replace_mode.clone(),
+            remove_properties_in_replace_mode: diff.remove_properties_in_replace_mode.clone(),
+            dynamic_array_entry_deltas: diff
+                .dynamic_array_entry_deltas
+                .iter()
+                .map(|x| (x.key.clone(), x.entries.iter().copied().collect()))
+                .collect(),
+            set_canonical_path_references: diff
+                .set_canonical_path_references
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.as_uuid()))
+                .collect(),
+            remove_canonical_path_references: diff
+                .remove_canonical_path_references
+                .iter()
+                .map(|x| x.to_string())
+                .collect(),
+        }
+    }
+
+    pub fn to_asset_diff(
+        &self,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+    ) -> DataSetResult<AssetDiff> {
+        let mut set_properties = Vec::with_capacity(self.set_properties.len());
+        for (k, v) in &self.set_properties {
+            set_properties.push((k.clone(), v.to_property_value()?));
+        }
+
+        let mut set_null_overrides = Vec::with_capacity(self.set_null_overrides.len());
+        for (k, v) in &self.set_null_overrides {
+            let null_override =
+                string_to_null_override_value(v).ok_or(DataSetError::StorageFormatError)?;
+            set_null_overrides.push((k.clone(), null_override));
+        }
+
+        let mut dynamic_array_entry_deltas =
+            Vec::with_capacity(self.dynamic_array_entry_deltas.len());
+        for (key, elements) in &self.dynamic_array_entry_deltas {
+            let mut entries = OrderedSet::default();
+            for element in elements {
+                entries.try_insert_at_end(*element);
+            }
+
+            dynamic_array_entry_deltas.push(DynamicArrayEntryDelta {
+                key: key.clone(),
+                entries,
+            });
+        }
+
+        Ok(AssetDiff {
+            set_name: self.set_name.as_ref().map(|x| {
+                if x.is_empty() {
+                    AssetName::empty()
+                } else {
+                    AssetName::new(x.clone())
+                }
+            }),
+            set_location: self
+                .set_location
+                .map(|x| AssetLocation::new(AssetId::from_uuid(x))),
+            set_prototype: self
+                .set_prototype
+                .as_ref()
+                .map(|x| x.prototype.map(|x| AssetId::from_uuid(x))),
+            set_properties,
+            remove_properties: self.remove_properties.clone(),
+            set_null_overrides,
+            remove_null_overrides: self.remove_null_overrides.clone(),
+            add_properties_in_replace_mode: self.add_properties_in_replace_mode.clone(),
+            remove_properties_in_replace_mode: self.remove_properties_in_replace_mode.clone(),
+            dynamic_array_entry_deltas,
+            set_canonical_path_references: self
+                .set_canonical_path_references
+                .iter()
+                .map(|(k, v)| {
+                    let path_reference: PathReference = k.into();
+                    (
+                        path_reference.simplify(namespace_resolver),
+                        AssetId::from_uuid(*v),
+                    )
+                })
+                .collect(),
+            remove_canonical_path_references: self
+                .remove_canonical_path_references
+                .iter()
+                .map(|x| {
+                    let path_reference: PathReference = x.into();
+                    path_reference.simplify(namespace_resolver)
+                })
+                .collect(),
+        })
+    }
+}
+
+// A full copy of an asset, used when a diff creates an asset. This is similar to AssetJson but
+// does not carry a schema cache, so the schema must still exist when loading
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataSetAssetInfoJson {
+    id: Uuid,
+    name: String,
+    location: Uuid,
+    root_schema: Uuid,
+    import_info: Option<AssetImportInfoJson>,
+    build_info: AssetBuildInfoJson,
+    prototype: Option<Uuid>,
+    #[serde(serialize_with = "ordered_map_json_value")]
+    properties: HashMap<String, serde_json::Value>,
+}
+
+impl DataSetAssetInfoJson {
+    pub fn new(
+        asset_id: AssetId,
+        obj: &DataSetAssetInfo,
+    ) -> Self {
+        let mut buffers = None;
+        let properties = store_json_properties(
+            obj.properties(),
+            obj.property_null_overrides(),
+            Some(obj.properties_in_replace_mode()),
+            obj.dynamic_collection_entries(),
+            &mut buffers,
+        );
+
+        DataSetAssetInfoJson {
+            id: asset_id.as_uuid(),
+            name: obj.asset_name().as_string().cloned().unwrap_or_default(),
+            location: obj.asset_location().path_node_id().as_uuid(),
+            root_schema: obj.schema().fingerprint().as_uuid(),
+            import_info: obj
+                .import_info()
+                .as_ref()
+                .map(|x| AssetImportInfoJson::new(x)),
+            build_info: AssetBuildInfoJson::new(obj.build_info()),
+            prototype: obj.prototype().map(|x| x.as_uuid()),
+            properties,
+        }
+    }
+
+    pub fn to_asset_info(
+        &self,
+        schema_set: &SchemaSet,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+    ) -> DataSetResult<(AssetId, DataSetAssetInfo)> {
+        let root_schema_fingerprint = SchemaFingerprint::from_uuid(self.root_schema);
+        let named_type = schema_set
+            .find_named_type_by_fingerprint(root_schema_fingerprint)
+            .ok_or(DataSetError::SchemaNotFound)?;
+
+        let mut properties: HashMap<String, Value> = Default::default();
+        let mut property_null_overrides: HashMap<String, NullOverride> = Default::default();
+        let mut properties_in_replace_mode: HashSet<String> = Default::default();
+        let mut dynamic_collection_entries: HashMap<String, OrderedSet<Uuid>> = Default::default();
+        let mut buffers = None;
+
+        load_json_properties(
+            named_type,
+            schema_set.schemas(),
+            schema_set.schemas_by_type_uuid(),
+            root_schema_fingerprint,
+            None,
+            &self.properties,
+            &mut properties,
+            &mut property_null_overrides,
+            Some(&mut properties_in_replace_mode),
+            &mut dynamic_collection_entries,
+            &mut buffers,
+        );
+
+        let import_info = if let Some(import_info) = &self.import_info {
+            Some(import_info.to_import_info(schema_set, namespace_resolver)?)
+        } else {
+            None
+        };
+
+        let asset_info = DataSetAssetInfo {
+            schema: named_type.as_record()?.clone(),
+            asset_name: if self.name.is_empty() {
+                AssetName::empty()
+            } else {
+                AssetName::new(self.name.clone())
+            },
+            asset_location: AssetLocation::new(AssetId::from_uuid(self.location)),
+            import_info,
+            build_info: self
+                .build_info
+                .to_build_info(schema_set, namespace_resolver),
+            prototype: self.prototype.map(|x| AssetId::from_uuid(x)),
+            properties,
+            property_null_overrides,
+            properties_in_replace_mode,
+            dynamic_collection_entries,
+        };
+
+        Ok((AssetId::from_uuid(self.id), asset_info))
+    }
+}
+
+// A full copy of every asset in a data set, used to send the data set to another process. The
+// schemas must already be loaded by the receiver.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DataSetJson {
+    assets: Vec<DataSetAssetInfoJson>,
+}
+
+impl DataSetJson {
+    pub fn new(data_set: &DataSet) -> Self {
+        DataSetJson {
+            assets: data_set
+                .assets()
+                .iter()
+                .map(|(asset_id, asset_info)| DataSetAssetInfoJson::new(*asset_id, asset_info))
+                .collect(),
+        }
+    }
+
+    pub fn to_data_set(
+        &self,
+        schema_set: &SchemaSet,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+    ) -> DataSetResult<DataSet> {
+        let mut data_set = DataSet::default();
+        for asset in &self.assets {
+            let (asset_id, asset_info) = asset.to_asset_info(schema_set, namespace_resolver)?;
+            data_set.assets_mut().insert(asset_id, Arc::new(asset_info));
+        }
+
+        Ok(data_set)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DataSetDiffJson {
+    creates: Vec<DataSetAssetInfoJson>,
+    deletes: Vec<Uuid>,
+    changes: Vec<(Uuid, AssetDiffJson)>,
+}
+
+impl DataSetDiffJson {
+    pub fn new(diff: &DataSetDiff) -> Self {
+        DataSetDiffJson {
+            creates: diff
+                .creates
+                .iter()
+                .map(|(asset_id, obj)| DataSetAssetInfoJson::new(*asset_id, obj))
+                .collect(),
+            deletes: diff.deletes.iter().map(|x| x.as_uuid()).collect(),
+            changes: diff
+                .changes
+                .iter()
+                .map(|(asset_id, diff)| (asset_id.as_uuid(), AssetDiffJson::new(diff)))
+                .collect(),
+        }
+    }
+
+    pub fn to_diff(
+        &self,
+        schema_set: &SchemaSet,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+    ) -> DataSetResult<DataSetDiff> {
+        let mut creates = Vec::with_capacity(self.creates.len());
+        for create in &self.creates {
+            let (asset_id, asset_info) = create.to_asset_info(schema_set, namespace_resolver)?;
+            creates.push((asset_id, Arc::new(asset_info)));
+        }
+
+        let mut changes = Vec::with_capacity(self.changes.len());
+        for (asset_id, diff) in &self.changes {
+            changes.push((
+                AssetId::from_uuid(*asset_id),
+                diff.to_asset_diff(namespace_resolver)?,
+            ));
+        }
+
+        Ok(DataSetDiff {
+            creates,
+            deletes: self
+                .deletes
+                .iter()
+                .map(|x| AssetId::from_uuid(*x))
+                .collect(),
+            changes,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataSetDiffSetJson {
+    apply_diff: DataSetDiffJson,
+    revert_diff: DataSetDiffJson,
+    modified_assets: Vec<Uuid>,
+    modified_locations: Vec<Uuid>,
+}
+
+impl DataSetDiffSetJson {
+    pub fn new(diff_set: &DataSetDiffSet) -> Self {
+        let mut modified_assets: Vec<_> = diff_set
+            .modified_assets
+            .iter()
+            .map(|x| x.as_uuid())
+            .collect();
+        modified_assets.sort();
+
+        let mut modified_locations: Vec<_> = diff_set
+            .modified_locations
+            .iter()
+            .map(|x| x.path_node_id().as_uuid())
+            .collect();
+        modified_locations.sort();
+
+        DataSetDiffSetJson {
+            apply_diff: DataSetDiffJson::new(&diff_set.apply_diff),
+            revert_diff: DataSetDiffJson::new(&diff_set.revert_diff),
+            modified_assets,
+            modified_locations,
+        }
+    }
+
+    pub fn to_diff_set(
+        &self,
+        schema_set: &SchemaSet,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+    ) -> DataSetResult<DataSetDiffSet> {
+        Ok(DataSetDiffSet {
+            apply_diff: self.apply_diff.to_diff(schema_set, namespace_resolver)?,
+            revert_diff: self.revert_diff.to_diff(schema_set, namespace_resolver)?,
+            modified_assets: self
+                .modified_assets
+                .iter()
+                .map(|x| AssetId::from_uuid(*x))
+                .collect(),
+            modified_locations: self
+                .modified_locations
+                .iter()
+                .map(|x| AssetLocation::new(AssetId::from_uuid(*x)))
+                .collect(),
+        })
+    }
+}
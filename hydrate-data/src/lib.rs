@@ -29,6 +29,8 @@ mod single_object;
 pub use single_object::SingleObject;
 
 mod diff;
+pub use diff::AssetDiff;
+pub use diff::DataSetAssetDiff;
 pub use diff::DataSetDiff;
 pub use diff::DataSetDiffSet;
 
@@ -126,6 +126,50 @@ impl CanonicalPathReference {
             source_file_path,
         )
     }
+
+    // If this reference points at old_path, returns a reference to new_path with the same
+    // importable name. Both paths must be canonical and absolute. The new path does not need to
+    // exist yet. The namespace is kept if the new path is still within it.
+    pub fn relocate(
+        &self,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+        old_path: &Path,
+        new_path: &Path,
+    ) -> Option<CanonicalPathReference> {
+        let namespace_root = if self.namespace.is_empty() {
+            None
+        } else {
+            Some(namespace_resolver.namespace_root(&self.namespace)?)
+        };
+
+        let absolute_path = match &namespace_root {
+            Some(namespace_root) => namespace_root.join(&self.path),
+            None => PathBuf::from(&self.path),
+        };
+
+        if absolute_path != old_path {
+            return None;
+        }
+
+        if let Some(namespace_root) = namespace_root {
+            if let Ok(relative_path) = new_path.strip_prefix(namespace_root) {
+                return Some(CanonicalPathReference {
+                    namespace: self.namespace.clone(),
+                    path: relative_path.to_string_lossy().to_string(),
+                    importable_name: self.importable_name.clone(),
+                });
+            }
+        }
+
+        Some(
+            PathReference {
+                namespace: "".to_string(),
+                path: new_path.to_string_lossy().to_string(),
+                importable_name: self.importable_name.clone(),
+            }
+            .simplify_without_canonicalize(namespace_resolver),
+        )
+    }
 }
 
 // A hash of a potentially non-canonical path. Be very careful how these are used to check equality. It should really
@@ -209,6 +253,31 @@ impl PathReference {
         )
     }
 
+    // Same as simplify(), but for absolute paths that are already canonical or do not exist on
+    // disk yet
+    fn simplify_without_canonicalize(
+        self,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+    ) -> CanonicalPathReference {
+        if self.namespace.is_empty() && Path::new(&self.path).is_absolute() {
+            if let Some((namespace, prefix)) =
+                namespace_resolver.simplify_path(Path::new(&self.path))
+            {
+                return CanonicalPathReference {
+                    namespace,
+                    path: prefix.to_string_lossy().to_string(),
+                    importable_name: self.importable_name,
+                };
+            }
+        }
+
+        CanonicalPathReference {
+            namespace: self.namespace,
+            path: self.path,
+            importable_name: self.importable_name,
+        }
+    }
+
     pub fn simplify(
         self,
         namespace_resolver: &dyn PathReferenceNamespaceResolver,
@@ -10,7 +10,7 @@ use hydrate_model::pipeline::{AssetEngine, HydrateProjectConfiguration, ImportJo
 use hydrate_model::{
     AssetId, AssetLocation, AssetName, DataSetError, DataSetErrorWithBacktrace, DataSetResult,
     EditorModel, EndContextBehavior, NullOverride, OverrideBehavior, PropertyPath, Schema,
-    SchemaFingerprint, SchemaRecord, Value,
+    SchemaFingerprint, SchemaRecord, UndoStepId, Value,
 };
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -39,6 +39,7 @@ pub enum UIAction {
     NewAsset(AssetName, AssetLocation, SchemaRecord, Option<AssetId>),
     DuplicateAssets(Vec<AssetId>),
     DeleteAssets(Vec<AssetId>),
+    RestoreAssetFromHistory(AssetId, Option<UndoStepId>),
     SetProperty(
         Vec<AssetId>,
         PropertyPath,
@@ -273,24 +274,9 @@ impl UIActionQueueReceiver {
                     asset_engine.queue_build_all();
                 }
                 UIAction::MoveOrRename(asset_ids, new_name, new_location) => {
-                    editor_model.root_edit_context_mut().with_undo_context(
-                        "MoveOrRename",
-                        |edit_context| {
-                            for &asset_id in &asset_ids {
-                                if let Some(new_name) = &new_name {
-                                    edit_context
-                                        .set_asset_name(asset_id, new_name.clone())
-                                        .unwrap();
-                                }
-
-                                edit_context
-                                    .set_asset_location(asset_id, new_location)
-                                    .unwrap();
-                            }
-
-                            EndContextBehavior::Finish
-                        },
-                    );
+                    if let Err(e) = editor_model.move_assets(&asset_ids, new_name, new_location) {
+                        log::error!("Could not move or rename assets: {:?}", e);
+                    }
                 }
                 UIAction::ReimportAndRebuild(asset_ids) => {
                     // - Only update assets that were requested
@@ -371,31 +357,21 @@ impl UIActionQueueReceiver {
                     }
                 }
                 UIAction::MoveAssets(moving_assets, new_location) => {
-                    editor_model.root_edit_context_mut().with_undo_context(
-                        "move asset",
-                        |edit_context| {
-                            for &moving_asset in &moving_assets {
-                                let result =
-                                    edit_context.set_asset_location(moving_asset, new_location);
-                                match result {
-                                    Ok(_) => {
-                                        // do nothing
-                                    }
-                                    Err(DataSetErrorWithBacktrace {
-                                        error: DataSetError::NewLocationIsChildOfCurrentAsset,
-                                        ..
-                                    }) => {
-                                        // do nothing
-                                    }
-                                    _ => {
-                                        unimplemented!()
-                                    }
-                                }
-                            }
-
-                            EndContextBehavior::Finish
-                        },
-                    );
+                    let result = editor_model.move_assets(&moving_assets, None, new_location);
+                    match result {
+                        Ok(_) => {
+                            // do nothing
+                        }
+                        Err(DataSetErrorWithBacktrace {
+                            error: DataSetError::NewLocationIsChildOfCurrentAsset,
+                            ..
+                        }) => {
+                            // do nothing
+                        }
+                        Err(e) => {
+                            log::error!("Could not move assets: {:?}", e);
+                        }
+                    }
                 }
                 UIAction::NewAsset(asset_name, asset_location, schema_record, prototype) => {
                     editor_model.root_edit_context_mut().with_undo_context(
@@ -446,6 +422,12 @@ impl UIActionQueueReceiver {
                         },
                     );
                 }
+                UIAction::RestoreAssetFromHistory(asset_id, undo_step_id) => {
+                    if let Err(e) = editor_model.restore_asset_from_history(asset_id, undo_step_id)
+                    {
+                        log::error!("Could not restore asset from history: {:?}", e);
+                    }
+                }
                 UIAction::SetProperty(asset_ids, property_path, value, end_context_behavior) => {
                     editor_model.root_edit_context_mut().with_undo_context(
                         "set property",
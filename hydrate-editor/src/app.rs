@@ -12,7 +12,7 @@ use crate::ui::modals::ImportFilesModal;
 use crate::ui_state::EditorModelUiState;
 use egui::{Ui, ViewportCommand, WidgetText};
 use egui_tiles::{SimplificationOptions, TileId};
-use hydrate_model::pipeline::{AssetEngine, AssetEngineState, LogData};
+use hydrate_model::pipeline::{AssetEngine, AssetEngineState, JobId, LogData, PipelineEvent};
 use hydrate_model::EditorModelWithCache;
 use std::sync::Arc;
 
@@ -126,6 +126,7 @@ fn draw_property_inspector(
             .primary_selected_asset(),
         ui_context.inspector_registry,
         ui_context.thumbnail_image_loader,
+        ui_context.asset_engine,
     );
 }
 
@@ -152,6 +153,9 @@ pub struct HydrateEditorApp {
     inspector_registry: InspectorRegistry,
     thumbnail_image_loader: Arc<ThumbnailImageLoader>,
     dock_state: egui_tiles::Tree<DockingPanelKind>,
+    pipeline_event_rx: crossbeam_channel::Receiver<PipelineEvent>,
+    // Jobs that are currently running in start order, with the most recently reported progress
+    running_jobs: Vec<(JobId, Arc<String>, Option<f32>)>,
 }
 
 impl HydrateEditorApp {
@@ -206,6 +210,7 @@ impl HydrateEditorApp {
         let root = tiles.insert_container(root_container);
 
         let dock_state = egui_tiles::Tree::new("tree", root, tiles);
+        let pipeline_event_rx = asset_engine.subscribe_pipeline_events();
 
         HydrateEditorApp {
             db_state,
@@ -217,6 +222,8 @@ impl HydrateEditorApp {
             inspector_registry,
             thumbnail_image_loader: image_loader.clone(),
             dock_state,
+            pipeline_event_rx,
+            running_jobs: Default::default(),
         }
     }
 }
@@ -241,6 +248,7 @@ impl eframe::App for HydrateEditorApp {
         //
         // Main work here is caching information the rest of the UI is going to want to have (AssetId -> string path)
         //
+        self.db_state.editor_model.update_undo_history();
         self.ui_state
             .editor_model_ui_state
             .update(&self.db_state.editor_model);
@@ -279,6 +287,10 @@ impl eframe::App for HydrateEditorApp {
         // Handle any import, build, and thumbnail generation operations
         //
         let asset_engine_state = {
+            // Build what the user is looking at first
+            self.asset_engine
+                .set_prioritized_assets(self.ui_state.asset_gallery_ui_state.selected_assets());
+
             let mut editor_model_with_cache = EditorModelWithCache {
                 editor_model: &mut self.db_state.editor_model,
                 asset_path_cache: &self.ui_state.editor_model_ui_state.asset_path_cache,
@@ -291,6 +303,31 @@ impl eframe::App for HydrateEditorApp {
 
         self.thumbnail_image_loader.check_for_stale_thumbnails(ctx);
 
+        //
+        // Track which jobs are running so the status bar can show what the build is waiting on
+        //
+        for pipeline_event in self.pipeline_event_rx.try_iter() {
+            match pipeline_event {
+                PipelineEvent::JobStarted { job_id, debug_name } => {
+                    self.running_jobs.push((job_id, debug_name, None))
+                }
+                PipelineEvent::JobProgress { job_id, progress } => {
+                    if let Some(running_job) = self.running_jobs.iter_mut().find(|x| x.0 == job_id)
+                    {
+                        running_job.2 = Some(progress);
+                    }
+                }
+                PipelineEvent::JobFinished { job_id, .. } => {
+                    self.running_jobs.retain(|x| x.0 != job_id)
+                }
+                _ => {}
+            }
+        }
+
+        if !matches!(asset_engine_state, AssetEngineState::Building(_)) {
+            self.running_jobs.clear();
+        }
+
         //
         // If we have any completed logs, store them
         //
@@ -323,30 +360,51 @@ impl eframe::App for HydrateEditorApp {
         egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
             match asset_engine_state {
                 AssetEngineState::Importing(import_state) => {
-                    let text = format!(
-                        "Importing {}/{} assets",
-                        import_state.completed_job_count, import_state.total_job_count
-                    );
-                    ui.add(
-                        egui::ProgressBar::new(
-                            import_state.completed_job_count as f32
-                                / import_state.total_job_count as f32,
-                        )
-                        .text(text),
-                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Cancel").clicked() {
+                            self.asset_engine.cancel_import();
+                        }
+
+                        let text = format!(
+                            "Importing {}/{} assets",
+                            import_state.completed_job_count, import_state.total_job_count
+                        );
+                        ui.add(
+                            egui::ProgressBar::new(
+                                import_state.completed_job_count as f32
+                                    / import_state.total_job_count as f32,
+                            )
+                            .text(text),
+                        );
+                    });
                 }
                 AssetEngineState::Building(build_state) => {
-                    let text = format!(
-                        "Building {}/{} assets",
-                        build_state.completed_job_count, build_state.total_job_count
-                    );
-                    ui.add(
-                        egui::ProgressBar::new(
-                            build_state.completed_job_count as f32
-                                / build_state.total_job_count as f32,
-                        )
-                        .text(text),
-                    );
+                    ui.horizontal(|ui| {
+                        if ui.button("Cancel").clicked() {
+                            self.asset_engine.cancel_build();
+                        }
+
+                        let mut text = format!(
+                            "Building {}/{} assets",
+                            build_state.completed_job_count, build_state.total_job_count
+                        );
+
+                        // The oldest running job is most likely the one holding up the build
+                        if let Some((_, debug_name, progress)) = self.running_jobs.first() {
+                            text += &format!(" - {}", debug_name);
+                            if let Some(progress) = progress {
+                                text += &format!(" ({:.0}%)", progress * 100.0);
+                            }
+                        }
+
+                        ui.add(
+                            egui::ProgressBar::new(
+                                build_state.completed_job_count as f32
+                                    / build_state.total_job_count as f32,
+                            )
+                            .text(text),
+                        );
+                    });
                 }
                 _ => {
                     ui.horizontal(|ui| {
@@ -364,6 +422,15 @@ impl eframe::App for HydrateEditorApp {
                                     *x == egui_tiles::Tile::Pane(DockingPanelKind::LogEventView)
                                 });
                             }
+
+                            // Rebuilds changed assets shortly after edits, a running game picks
+                            // them up when it sees the new TOC
+                            let mut auto_build = self.asset_engine.auto_build_debounce().is_some();
+                            if ui.checkbox(&mut auto_build, "Auto Build").changed() {
+                                self.asset_engine.set_auto_build_debounce(
+                                    auto_build.then(|| std::time::Duration::from_millis(500)),
+                                );
+                            }
                         });
                     });
                 }
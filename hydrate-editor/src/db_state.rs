@@ -1,7 +1,5 @@
 use hydrate_model::pipeline::{HydrateProjectConfiguration, ImportJobToQueue, ImporterRegistry};
-use hydrate_model::{
-    EditorModel, PathNode, PathNodeRoot, SchemaLinker, SchemaSet, SchemaSetBuilder,
-};
+use hydrate_model::{EditorModel, SchemaSet};
 
 pub struct DbState {
     pub project_configuration: HydrateProjectConfiguration,
@@ -16,40 +14,20 @@ impl DbState {
         import_job_to_queue: &mut ImportJobToQueue,
     ) -> EditorModel {
         let mut editor_model = EditorModel::new(project_configuration.clone(), schema_set);
-        for pair in &project_configuration.id_based_asset_sources {
-            editor_model.add_file_system_id_based_asset_source(
-                project_configuration,
-                &pair.name,
-                &pair.path,
-                import_job_to_queue,
-            );
-        }
-        for pair in &project_configuration.path_based_asset_sources {
-            editor_model.add_file_system_path_based_data_source(
-                project_configuration,
-                &pair.name,
-                &pair.path,
-                importer_registry,
-                import_job_to_queue,
-            );
-        }
+        editor_model.add_project_data_sources(
+            project_configuration,
+            importer_registry,
+            import_job_to_queue,
+        );
+
+        editor_model.load_undo_history();
 
         editor_model
     }
 
     #[profiling::function]
     pub fn load_schema(hydrate_project_configuration: &HydrateProjectConfiguration) -> SchemaSet {
-        let mut linker = SchemaLinker::default();
-        let mut schema_set = SchemaSetBuilder::default();
-
-        PathNode::register_schema(&mut linker);
-        PathNodeRoot::register_schema(&mut linker);
-        for path in &hydrate_project_configuration.schema_def_paths {
-            linker.add_source_dir(path, "**.json").unwrap();
-        }
-        schema_set.add_linked_types(linker).unwrap();
-
-        schema_set.build()
+        EditorModel::load_schema_set(hydrate_project_configuration)
     }
 
     #[profiling::function]
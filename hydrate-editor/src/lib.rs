@@ -16,13 +16,14 @@ mod ui_state;
 
 pub use egui;
 pub use egui_extras;
-use hydrate_model::{AssetPathCache, EditorModelWithCache, SchemaSet};
+use hydrate_model::{AssetPathCache, EditorModelWithCache, GarbageReport, SchemaSet};
 
 use crate::app::HydrateEditorApp;
 use crate::inspector_system::InspectorRegistry;
 pub use crate::ui::components::inspector_system;
 use hydrate_model::pipeline::{
     AssetEngine, AssetPluginRegistryBuilders, HydrateProjectConfiguration, ImportJobToQueue,
+    PipelineResult,
 };
 
 pub struct Editor {
@@ -92,6 +93,71 @@ impl Editor {
         }
     }
 
+    // Finds data that is no longer needed and deletes it unless dry_run is set. This does not open
+    // the editor window. Imports that were queued when the project was loaded are finished first.
+    // Everything that is found is logged.
+    pub fn collect_garbage(
+        &mut self,
+        dry_run: bool,
+    ) -> PipelineResult<GarbageReport> {
+        while self.asset_engine.has_pending_imports() {
+            let asset_path_cache = AssetPathCache::build(&self.db_state.editor_model)?;
+            let mut editor_model_with_cache = EditorModelWithCache {
+                editor_model: &mut self.db_state.editor_model,
+                asset_path_cache: &asset_path_cache,
+            };
+            self.asset_engine.update(&mut editor_model_with_cache)?;
+            // Work happens on other threads, avoid spinning while waiting for it
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let editor_model = &mut self.db_state.editor_model;
+        let garbage = editor_model.find_garbage(&self.asset_engine)?;
+
+        for import_data_file in &garbage.orphaned_import_data {
+            log::info!("Orphaned import data {:?}", import_data_file);
+        }
+        for (asset_id, source_file_path) in &garbage.orphaned_generated_assets {
+            log::info!(
+                "Generated asset {:?} has missing source file {:?}",
+                asset_id,
+                source_file_path
+            );
+        }
+        for meta_file in &garbage.orphaned_meta_files {
+            log::info!("Orphaned meta file {:?}", meta_file);
+        }
+        for (meta_file, stale_importables) in &garbage.stale_meta_file_entries {
+            log::info!(
+                "Meta file {:?} lists importables that no longer exist: {:?}",
+                meta_file,
+                stale_importables
+            );
+        }
+
+        if garbage.is_empty() {
+            log::info!("No garbage found");
+        } else if dry_run {
+            log::info!("Dry run, nothing was deleted");
+        } else {
+            let not_deleted = editor_model.delete_garbage(&garbage);
+            for (path, error) in &not_deleted.files {
+                log::error!("Could not delete {:?}: {}", path, error);
+            }
+            for (asset_id, error) in &not_deleted.assets {
+                log::error!("Could not delete asset {:?}: {}", asset_id, error);
+            }
+
+            if not_deleted.is_empty() {
+                log::info!("Garbage deleted");
+            } else {
+                log::warn!("Some garbage could not be deleted");
+            }
+        }
+
+        Ok(garbage)
+    }
+
     pub fn run(self) -> eframe::Result<()> {
         let native_options = eframe::NativeOptions {
             viewport: egui::ViewportBuilder::default().with_inner_size([450.0, 300.0]),
@@ -8,6 +8,7 @@ use crate::DbState;
 use egui::text::LayoutJob;
 use egui::{Layout, Ui, Widget};
 use hydrate_model::{AssetId, AssetLocation, DataSetAssetInfo, EditorModel, HashSet};
+use std::sync::Arc;
 
 #[derive(Default, PartialEq, Copy, Clone)]
 pub enum AssetGalleryViewMode {
@@ -337,7 +338,7 @@ fn draw_asset_gallery_list(
     ui_state: &EditorModelUiState,
     asset_gallery_ui_state: &mut AssetGalleryUiState,
     action_queue: &UIActionQueueSender,
-    all_assets: &Vec<(&AssetId, &DataSetAssetInfo)>,
+    all_assets: &Vec<(&AssetId, &Arc<DataSetAssetInfo>)>,
     thumbnail_image_loader: &ThumbnailImageLoader,
 ) {
     ui.style_mut().spacing.item_spacing = egui::vec2(8.0, 2.0);
@@ -480,7 +481,7 @@ fn draw_asset_gallery_tile_grid(
     ui_state: &EditorModelUiState,
     asset_gallery_ui_state: &mut AssetGalleryUiState,
     action_queue: &UIActionQueueSender,
-    all_assets: &Vec<(&AssetId, &DataSetAssetInfo)>,
+    all_assets: &Vec<(&AssetId, &Arc<DataSetAssetInfo>)>,
     thumbnail_image_loader: &ThumbnailImageLoader,
 ) {
     ui.style_mut().spacing.item_spacing = egui::vec2(12.0, 12.0);
@@ -512,7 +513,7 @@ fn draw_asset_gallery_tile(
     asset_id: AssetId,
     asset_info: &DataSetAssetInfo,
     action_queue: &UIActionQueueSender,
-    all_assets: &Vec<(&AssetId, &DataSetAssetInfo)>,
+    all_assets: &Vec<(&AssetId, &Arc<DataSetAssetInfo>)>,
     thumbnail_image_loader: &ThumbnailImageLoader,
 ) {
     let short_name = db_state
@@ -785,7 +786,7 @@ fn handle_asset_selection(
     asset_id: AssetId,
     ui: &mut Ui,
     is_selected: bool,
-    all_assets: &Vec<(&AssetId, &DataSetAssetInfo)>,
+    all_assets: &Vec<(&AssetId, &Arc<DataSetAssetInfo>)>,
 ) {
     let mut _primary_index = None;
     let mut selected_index = None;
@@ -2,16 +2,23 @@ use crate::action_queue::{UIAction, UIActionQueueSender};
 use crate::image_loader::ThumbnailImageLoader;
 use crate::ui::modals::{MoveAssetsModal, NewAssetModal};
 use crate::ui_state::EditorModelUiState;
+use hydrate_model::pipeline::{AssetEngine, AssetValidationReport, LogEventLevel};
 use hydrate_model::{
-    AssetId, EditorModel, HashSet, PropertyPath, Schema, SchemaDefRecordFieldMarkup,
+    AssetId, DataSetAssetDiff, EditorModel, HashObjectMode, HashSet, PropertyPath, Schema,
+    SchemaDefRecordFieldMarkup, UndoStepId,
 };
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use super::inspector_system::*;
 
+// The selected assets with a hash of each one's properties, and the build profile validated with
+type ValidationReportKey = (Vec<(AssetId, u64)>, String);
+
 #[derive(Default)]
 pub struct InspectorUiState {
     pinned_selection: Option<(AssetId, Arc<HashSet<AssetId>>)>,
+    validation_report: Option<(ValidationReportKey, AssetValidationReport)>,
 }
 
 pub fn draw_inspector(
@@ -24,6 +31,7 @@ pub fn draw_inspector(
     primary_asset_id_unpinned: Option<AssetId>,
     inspector_registry: &InspectorRegistry,
     thumbnail_image_loader: &ThumbnailImageLoader,
+    asset_engine: &AssetEngine,
 ) {
     egui::ScrollArea::vertical()
         .max_width(f32::INFINITY)
@@ -252,8 +260,79 @@ pub fn draw_inspector(
                         ui.label(format!("Prototype: {}", prototype_display_name));
                     });
                 }
+
+                //
+                // Edit history
+                //
+                let history = editor_model.asset_edit_history(primary_asset_id);
+                if !history.is_empty() {
+                    ui.collapsing(format!("History ({})", history.len()), |ui| {
+                        let now = std::time::SystemTime::now();
+                        for (index, entry) in history.iter().enumerate().rev() {
+                            ui.horizontal(|ui| {
+                                // Restoring the most recent entry would not change anything
+                                let is_latest = index == history.len() - 1;
+                                let restore_button = ui.add_enabled(!is_latest, egui::Button::new("Restore"));
+                                if restore_button.clicked() {
+                                    action_sender.queue_action(UIAction::RestoreAssetFromHistory(primary_asset_id, Some(entry.undo_step_id)));
+                                }
+
+                                ui.label(format!("{} ({})", entry.context_name, format_time_since(now, entry.timestamp)))
+                                    .on_hover_ui(|ui| draw_history_preview(ui, editor_model, primary_asset_id, Some(entry.undo_step_id)));
+                            });
+                            ui.indent("changes", |ui| {
+                                ui.label(describe_asset_diff(&entry.diff));
+                            });
+                        }
+
+                        ui.horizontal(|ui| {
+                            if ui.button("Restore").clicked() {
+                                action_sender.queue_action(UIAction::RestoreAssetFromHistory(primary_asset_id, None));
+                            }
+
+                            ui.label("Before first edit")
+                                .on_hover_ui(|ui| draw_history_preview(ui, editor_model, primary_asset_id, None));
+                        });
+                    });
+                }
+            }
+
+            //
+            // Validation issues reported by the builders of the selected assets. This is drawn every
+            // frame, so the assets are only validated again when the selection or their data changes
+            //
+            let mut selected_asset_hashes: Vec<_> = selected_assets.iter().map(|&asset_id| {
+                let hash = edit_context.data_set().hash_object(asset_id, HashObjectMode::PropertiesOnly).unwrap_or_default();
+                (asset_id, hash)
+            }).collect();
+            selected_asset_hashes.sort();
+            let validation_report_key = (selected_asset_hashes, asset_engine.active_build_profile().name.clone());
+            if inspector_ui_state.validation_report.as_ref().map(|(key, _)| key) != Some(&validation_report_key) {
+                let validation_report = asset_engine.validate_assets(edit_context.data_set(), editor_model.schema_set(), selected_assets.iter().copied());
+                inspector_ui_state.validation_report = Some((validation_report_key, validation_report));
             }
 
+            let validation_report = &inspector_ui_state.validation_report.as_ref().unwrap().1;
+            if !validation_report.issues.is_empty() {
+                let header = format!("Validation ({} errors, {} warnings)", validation_report.error_count(), validation_report.warning_count());
+                ui.collapsing(header, |ui| {
+                    for issue in &validation_report.issues {
+                        let color = match issue.level {
+                            LogEventLevel::Warning => ui.visuals().warn_fg_color,
+                            LogEventLevel::Error | LogEventLevel::FatalError => ui.visuals().error_fg_color,
+                        };
+
+                        let text = if selected_assets.len() > 1 {
+                            let display_name = editor_model.asset_display_name_long(issue.asset_id, &editor_model_ui_state.asset_path_cache);
+                            format!("{}: {}", display_name, issue.to_text())
+                        } else {
+                            issue.to_text()
+                        };
+
+                        ui.colored_label(color, text);
+                    }
+                });
+            }
 
             //
             // Explain that generated assets are not editable (TODO: Make this prettier)
@@ -312,3 +391,78 @@ pub fn draw_inspector(
                 });
         });
 }
+
+fn format_time_since(
+    now: SystemTime,
+    timestamp: SystemTime,
+) -> String {
+    let seconds = now.duration_since(timestamp).unwrap_or_default().as_secs();
+    if seconds < 60 {
+        format!("{}s ago", seconds)
+    } else if seconds < 60 * 60 {
+        format!("{}m ago", seconds / 60)
+    } else if seconds < 60 * 60 * 24 {
+        format!("{}h ago", seconds / (60 * 60))
+    } else {
+        format!("{}d ago", seconds / (60 * 60 * 24))
+    }
+}
+
+fn describe_asset_diff(diff: &DataSetAssetDiff) -> String {
+    match diff {
+        DataSetAssetDiff::Create(_) => "Created".to_string(),
+        DataSetAssetDiff::Delete => "Deleted".to_string(),
+        DataSetAssetDiff::Change(asset_diff) => {
+            let mut changes = Vec::default();
+            if asset_diff.changes_name() {
+                changes.push("(name)");
+            }
+            if asset_diff.changes_location() {
+                changes.push("(location)");
+            }
+            if asset_diff.changes_prototype() {
+                changes.push("(prototype)");
+            }
+            changes.extend(asset_diff.modified_property_paths());
+
+            if changes.is_empty() {
+                "No changes".to_string()
+            } else {
+                changes.join(", ")
+            }
+        }
+    }
+}
+
+// Shows the property overrides the asset had at the given point in its history
+fn draw_history_preview(
+    ui: &mut egui::Ui,
+    editor_model: &EditorModel,
+    asset_id: AssetId,
+    undo_step_id: Option<UndoStepId>,
+) {
+    let preview = match editor_model.preview_asset_history(asset_id, undo_step_id) {
+        Ok(preview) => preview,
+        Err(e) => {
+            ui.label(format!("Preview unavailable: {:?}", e));
+            return;
+        }
+    };
+
+    let Some(asset_info) = preview.assets().get(&asset_id) else {
+        ui.label("Asset did not exist");
+        return;
+    };
+
+    ui.label(format!("Name: {}", asset_info.asset_name().as_string().cloned().unwrap_or_default()));
+
+    let mut properties: Vec<_> = asset_info.properties().iter().collect();
+    properties.sort_by(|lhs, rhs| lhs.0.cmp(rhs.0));
+    if properties.is_empty() {
+        ui.label("No property overrides");
+    }
+
+    for (path, value) in properties {
+        ui.label(format!("{}: {:?}", path, value));
+    }
+}
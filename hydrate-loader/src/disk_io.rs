@@ -6,15 +6,17 @@ use crate::loader::{
 use crate::storage::IndirectIdentifier;
 use crate::ArtifactTypeId;
 use crossbeam_channel::{Receiver, Sender};
+use hydrate_base::build_patch::{self, BuildPatchJson};
+use hydrate_base::build_pin;
 use hydrate_base::hashing::HashMap;
+use hydrate_base::release_manifest::{ReleaseManifest, ReleaseManifestEntry};
 use hydrate_base::{ArtifactId, ArtifactManifestData, DebugManifestFileJson};
 use hydrate_base::{LoadHandle, StringHash};
-use std::io::{BufRead, SeekFrom};
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
-use uuid::Uuid;
 
 struct DiskArtifactIORequestMetadata {
     artifact_id: ArtifactId,
@@ -43,6 +45,76 @@ enum DiskArtifactIORequest {
     CheckNewToc(DiskArtifactIORequestCheckNewToc),
 }
 
+// Where build output is read from. If a patch is layered over the base build, the TOC and
+// manifests come from the patch and artifacts come from the patch if it has them.
+struct BuildDataPaths {
+    base_path: PathBuf,
+    patch_path: Option<PathBuf>,
+}
+
+impl BuildDataPaths {
+    fn toc_and_manifest_path(&self) -> &Path {
+        self.patch_path.as_deref().unwrap_or(&self.base_path)
+    }
+
+    fn artifact_path(
+        &self,
+        artifact_id: ArtifactId,
+        hash: u64,
+    ) -> PathBuf {
+        if let Some(patch_path) = &self.patch_path {
+            let path = hydrate_base::uuid_path::uuid_and_hash_to_path(
+                patch_path,
+                artifact_id.as_uuid(),
+                hash,
+                "bf",
+            );
+            if path.exists() {
+                return path;
+            }
+        }
+
+        hydrate_base::uuid_path::uuid_and_hash_to_path(
+            &self.base_path,
+            artifact_id.as_uuid(),
+            hash,
+            "bf",
+        )
+    }
+}
+
+// Reads the patch file and checks that the base build the patch was made from is present
+fn read_build_patch(
+    build_data_root_path: &Path,
+    patch_path: &Path,
+) -> Result<ManifestBuildHash, String> {
+    let patch_file_path = build_patch::build_patch_file_path(patch_path);
+    let patch_json = std::fs::read_to_string(&patch_file_path)
+        .map_err(|e| format!("Could not read build patch {:?}: {}", patch_file_path, e))?;
+    let build_patch: BuildPatchJson = serde_json::from_str(&patch_json)
+        .map_err(|e| format!("Could not read build patch {:?}: {}", patch_file_path, e))?;
+    let base_manifest_build_hash = u64::from_str_radix(&build_patch.base_manifest_build_hash, 16)
+        .map_err(|_| {
+        format!(
+            "Build patch {:?} has an invalid base build hash",
+            patch_file_path
+        )
+    })?;
+
+    let base_manifest_path = build_data_root_path.join("manifests").join(format!(
+        "{:0>16x}.manifest_release",
+        base_manifest_build_hash
+    ));
+    if !base_manifest_path.exists() {
+        return Err(format!(
+            "Build patch {:?} was made for build {:0>16x} which is not in {:?}",
+            patch_path, base_manifest_build_hash, build_data_root_path
+        ));
+    }
+
+    Ok(ManifestBuildHash(base_manifest_build_hash))
+}
+
 // Thread that tries to take jobs out of the request channel and ends when the finish channel is signalled
 struct DiskArtifactIOWorkerThread {
     finish_tx: Sender<()>,
@@ -65,13 +137,13 @@ fn find_and_load_latest_toc_if_changed(
     }
 
     let manifest =
-        BuildManifest::load_from_file(&build_data_root_path.join("manifests"), build_hash);
+        BuildManifest::load_from_file(&build_data_root_path.join("manifests"), build_hash)?;
     Ok(Some((build_hash, manifest)))
 }
 
 impl DiskArtifactIOWorkerThread {
     fn new(
-        root_path: Arc<PathBuf>,
+        build_data_paths: Arc<BuildDataPaths>,
         request_rx: Receiver<DiskArtifactIORequest>,
         load_event_tx: Sender<LoaderEvent>,
         toc_event_tx: Sender<DiskArtifactIOResponseNewToc>,
@@ -86,14 +158,19 @@ impl DiskArtifactIOWorkerThread {
                     recv(request_rx) -> msg => {
                         match msg.unwrap() {
                             DiskArtifactIORequest::CheckNewToc(msg) => {
-                                match find_and_load_latest_toc_if_changed(&*root_path, Some(msg.current_manifest_build_hash)) {
+                                match find_and_load_latest_toc_if_changed(build_data_paths.toc_and_manifest_path(), Some(msg.current_manifest_build_hash)) {
                                     Ok(Some(new_build_manifest)) => {
                                         toc_event_tx.send(DiskArtifactIOResponseNewToc {
                                             new_build_manifest: Some(new_build_manifest),
 
                                         }).unwrap();
                                     },
-                                    _ => {
+                                    result => {
+                                        // The current build stays loaded
+                                        if let Err(e) = result {
+                                            log::error!("Failed to load new build: {}", e);
+                                        }
+
                                         toc_event_tx.send(DiskArtifactIOResponseNewToc {
                                             new_build_manifest: None,
                                         }).unwrap();
@@ -104,7 +181,7 @@ impl DiskArtifactIOWorkerThread {
                             DiskArtifactIORequest::Metadata(msg) => {
                                 profiling::scope!("DiskartifactIORequest::Metadata");
                                 log::trace!("Start metadata read {:?}", msg.artifact_id);
-                                let path = hydrate_base::uuid_path::uuid_and_hash_to_path(&*root_path, msg.artifact_id.as_uuid(), msg.hash, "bf");
+                                let path = build_data_paths.artifact_path(msg.artifact_id, msg.hash);
                                 let mut reader = std::fs::File::open(path).unwrap();
                                 let header_data = hydrate_base::BuiltArtifactHeaderData::read_header(&mut reader).unwrap();
 
@@ -128,7 +205,7 @@ impl DiskArtifactIOWorkerThread {
                                 log::trace!("Start read {:?}", msg.artifact_id);
                                 //log::trace!("Start read {:?} {:?}", msg.artifact_id, msg.subresource);
 
-                                let path = hydrate_base::uuid_path::uuid_and_hash_to_path(&*root_path, msg.artifact_id.as_uuid(), msg.hash, "bf");
+                                let path = build_data_paths.artifact_path(msg.artifact_id, msg.hash);
                                 let mut reader = std::fs::File::open(&path).unwrap();
                                 let _header_data = hydrate_base::BuiltArtifactHeaderData::read_header(&mut reader).unwrap();
 
@@ -182,7 +259,7 @@ struct DiskArtifactIOThreadPool {
 
 impl DiskArtifactIOThreadPool {
     fn new(
-        root_path: Arc<PathBuf>,
+        build_data_paths: Arc<BuildDataPaths>,
         max_requests_in_flight: usize,
         load_event_tx: Sender<LoaderEvent>,
         new_toc_tx: Sender<DiskArtifactIOResponseNewToc>,
@@ -193,7 +270,7 @@ impl DiskArtifactIOThreadPool {
         let mut worker_threads = Vec::with_capacity(max_requests_in_flight);
         for thread_index in 0..max_requests_in_flight {
             let worker = DiskArtifactIOWorkerThread::new(
-                root_path.clone(),
+                build_data_paths.clone(),
                 request_rx.clone(),
                 load_event_tx.clone(),
                 new_toc_tx.clone(),
@@ -229,82 +306,48 @@ impl DiskArtifactIOThreadPool {
     }
 }
 
+// Debug info that isn't in the release manifest, only available if the debug manifest exists
+struct DebugManifestEntry {
+    symbol_hash: Option<StringHash>,
+    debug_name: Arc<String>,
+}
+
 pub struct BuildManifest {
-    pub artifact_lookup: HashMap<ArtifactId, ArtifactManifestData>,
-    pub symbol_lookup: HashMap<u128, ArtifactId>,
+    // The release manifest is memory-mapped and searched in place rather than parsed at startup
+    release_manifest: ReleaseManifest<memmap2::Mmap>,
+    debug_entries: HashMap<ArtifactId, DebugManifestEntry>,
 }
 
 impl BuildManifest {
     fn load_from_file(
         manifest_dir_path: &Path,
         build_hash: ManifestBuildHash,
-    ) -> BuildManifest {
+    ) -> Result<BuildManifest, String> {
         //
         // Load release manifest data, this must exist and load correctly
         //
-        let mut build_manifest = {
+        let release_manifest = {
             profiling::scope!("Load release manifest data");
 
-            let mut artifact_build_hashes = HashMap::default();
-
             let file_name = format!("{:0>16x}.manifest_release", build_hash.0);
             let file_path = manifest_dir_path.join(file_name);
-            let file = std::fs::File::open(file_path).unwrap();
-            let buf_reader = std::io::BufReader::new(file);
-
-            let mut artifact_lookup = HashMap::default();
-            let mut symbol_lookup = HashMap::default();
-
-            for line in buf_reader.lines() {
-                let line_str = line.unwrap().to_string();
-                if line_str.is_empty() {
-                    continue;
-                }
-
-                let fragments: Vec<_> = line_str.split(",").into_iter().collect();
-
-                let artifact_id =
-                    ArtifactId::from_u128(u128::from_str_radix(fragments[0], 16).unwrap());
-                let build_hash = u64::from_str_radix(fragments[1], 16).unwrap();
-                let combined_build_hash = u64::from_str_radix(fragments[2], 16).unwrap();
-                let artifact_type =
-                    Uuid::from_u128(u128::from_str_radix(fragments[3], 16).unwrap());
-                let symbol_hash_u128 = u128::from_str_radix(fragments[4], 16).unwrap();
-
-                let symbol_hash = if symbol_hash_u128 != 0 {
-                    let old = symbol_lookup.insert(symbol_hash_u128, artifact_id);
-                    assert!(old.is_none());
-                    Some(StringHash::from_hash(symbol_hash_u128))
-                } else {
-                    None
-                };
-
-                let old = artifact_lookup.insert(
-                    artifact_id,
-                    ArtifactManifestData {
-                        artifact_id,
-                        simple_build_hash: build_hash,
-                        combined_build_hash,
-                        symbol_hash,
-                        artifact_type,
-                        debug_name: Default::default(),
-                    },
-                );
-                assert!(old.is_none());
-
-                artifact_build_hashes.insert(artifact_id, build_hash);
-            }
-
-            BuildManifest {
-                artifact_lookup,
-                symbol_lookup,
-            }
+            let file = std::fs::File::open(&file_path)
+                .map_err(|e| format!("Failed to open manifest {:?}: {}", file_path, e))?;
+
+            // This is unsafe because the file could be modified while it's mapped. Manifests are
+            // never modified after they are written, rebuilding the same build hash replaces the
+            // file instead.
+            let mmap = unsafe { memmap2::Mmap::map(&file) }
+                .map_err(|e| format!("Failed to map manifest {:?}: {}", file_path, e))?;
+            ReleaseManifest::new(mmap)
+                .map_err(|e| format!("Failed to read manifest {:?}: {}", file_path, e))?
         };
 
         //
         // Load manifest debug data, it's ok if these files don't exist. This is just additive to
         // the critical data provided by the release manifest
         //
+        let mut debug_entries = HashMap::default();
         {
             let file_name = format!("{:0>16x}.manifest_debug", build_hash.0);
             let file_path = manifest_dir_path.join(file_name);
@@ -318,9 +361,8 @@ impl BuildManifest {
                 };
 
                 for debug_manifest_entry in manifest_file.artifacts {
-                    let manifest_entry = build_manifest
-                        .artifact_lookup
-                        .get_mut(&debug_manifest_entry.artifact_id)
+                    let manifest_entry = release_manifest
+                        .find(debug_manifest_entry.artifact_id)
                         .unwrap();
 
                     assert_eq!(manifest_entry.artifact_id, debug_manifest_entry.artifact_id);
@@ -330,7 +372,7 @@ impl BuildManifest {
                     );
                     let debug_manifest_build_hash =
                         u64::from_str_radix(&debug_manifest_entry.build_hash, 16).unwrap();
-                    assert_eq!(manifest_entry.simple_build_hash, debug_manifest_build_hash);
+                    assert_eq!(manifest_entry.build_hash, debug_manifest_build_hash);
 
                     let debug_manifest_build_hash =
                         u64::from_str_radix(&debug_manifest_entry.combined_build_hash, 16).unwrap();
@@ -339,19 +381,26 @@ impl BuildManifest {
                         debug_manifest_build_hash
                     );
 
-                    if debug_manifest_entry.symbol_name.is_empty() {
-                        assert_eq!(manifest_entry.symbol_hash, None);
+                    let symbol_hash = if debug_manifest_entry.symbol_name.is_empty() {
+                        assert_eq!(manifest_entry.symbol_hash, 0);
+                        None
                     } else {
                         let debug_manifest_symbol_hash =
                             StringHash::from_runtime_str(&debug_manifest_entry.symbol_name);
                         assert_eq!(
-                            manifest_entry.symbol_hash.as_ref().unwrap().hash(),
+                            manifest_entry.symbol_hash,
                             debug_manifest_symbol_hash.hash()
                         );
-                        manifest_entry.symbol_hash = Some(debug_manifest_symbol_hash);
-                    }
-
-                    manifest_entry.debug_name = Some(Arc::new(debug_manifest_entry.debug_name));
+                        Some(debug_manifest_symbol_hash)
+                    };
+
+                    debug_entries.insert(
+                        debug_manifest_entry.artifact_id,
+                        DebugManifestEntry {
+                            symbol_hash,
+                            debug_name: Arc::new(debug_manifest_entry.debug_name),
+                        },
+                    );
                 }
             } else {
                 log::info!(
@@ -360,7 +409,39 @@ impl BuildManifest {
             }
         }
 
-        build_manifest
+        Ok(BuildManifest {
+            release_manifest,
+            debug_entries,
+        })
+    }
+
+    fn to_manifest_data(
+        &self,
+        entry: ReleaseManifestEntry,
+    ) -> ArtifactManifestData {
+        let mut manifest_data = entry.to_manifest_data();
+        if let Some(debug_entry) = self.debug_entries.get(&entry.artifact_id) {
+            // The debug symbol hash includes the string, which is helpful for debugging
+            manifest_data.symbol_hash = debug_entry.symbol_hash.clone();
+            manifest_data.debug_name = Some(debug_entry.debug_name.clone());
+        }
+        manifest_data
+    }
+
+    pub fn find(
+        &self,
+        artifact_id: ArtifactId,
+    ) -> Option<ArtifactManifestData> {
+        let entry = self.release_manifest.find(artifact_id)?;
+        Some(self.to_manifest_data(entry))
+    }
+
+    pub fn find_by_symbol(
+        &self,
+        symbol_hash: u128,
+    ) -> Option<ArtifactManifestData> {
+        let entry = self.release_manifest.find_by_symbol(symbol_hash)?;
+        Some(self.to_manifest_data(entry))
     }
 }
 
@@ -401,6 +482,7 @@ fn read_toc(path: &Path) -> BuildToc {
 
 pub struct DiskArtifactIO {
     thread_pool: Option<DiskArtifactIOThreadPool>,
+    build_data_root_path: PathBuf,
     manifest: BuildManifest,
     build_hash: ManifestBuildHash,
     load_event_tx: Sender<LoaderEvent>,
@@ -408,38 +490,60 @@ pub struct DiskArtifactIO {
     last_toc_check: std::time::Instant,
     toc_check_queued: bool,
     pending_new_build_manifest: Option<(ManifestBuildHash, BuildManifest)>,
+    // If a patch is loaded, the base build it was made from. The patch's artifacts are not in the
+    // base build data path so the base build is pinned instead.
+    patch_base_build_hash: Option<ManifestBuildHash>,
+    // Pin on the manifest that is loaded, see hydrate_base::build_pin
+    build_pin_path: Option<PathBuf>,
+    last_build_pin_write: std::time::Instant,
 }
 
 impl Drop for DiskArtifactIO {
     fn drop(&mut self) {
         self.thread_pool.take().unwrap().finish();
+        self.remove_build_pin();
     }
 }
 
 impl DiskArtifactIO {
+    // If patch_path is set, the patch is layered over the build in build_data_root_path. See
+    // hydrate_base::build_patch.
     pub fn new(
         build_data_root_path: PathBuf,
+        patch_path: Option<PathBuf>,
         load_event_tx: Sender<LoaderEvent>,
     ) -> Result<Self, String> {
         let (new_toc_tx, new_toc_rx) =
             crossbeam_channel::unbounded::<DiskArtifactIOResponseNewToc>();
 
-        let max_toc_path = find_latest_toc(&build_data_root_path.join("toc"));
+        let patch_base_build_hash = match &patch_path {
+            Some(patch_path) => Some(read_build_patch(&build_data_root_path, patch_path)?),
+            None => None,
+        };
+
+        let build_data_paths = BuildDataPaths {
+            base_path: build_data_root_path.clone(),
+            patch_path,
+        };
+
+        let toc_and_manifest_path = build_data_paths.toc_and_manifest_path();
+        let max_toc_path = find_latest_toc(&toc_and_manifest_path.join("toc"));
         let max_toc_path = max_toc_path.ok_or_else(|| "Could not find TOC file".to_string())?;
         let build_toc = read_toc(&max_toc_path);
         let build_hash = build_toc.build_hash;
 
         let manifest =
-            BuildManifest::load_from_file(&build_data_root_path.join("manifests"), build_hash);
+            BuildManifest::load_from_file(&toc_and_manifest_path.join("manifests"), build_hash)?;
         let thread_pool = Some(DiskArtifactIOThreadPool::new(
-            Arc::new(build_data_root_path),
+            Arc::new(build_data_paths),
             4,
             load_event_tx.clone(),
             new_toc_tx,
         ));
 
-        Ok(DiskArtifactIO {
+        let mut disk_artifact_io = DiskArtifactIO {
             thread_pool,
+            build_data_root_path,
             manifest,
             build_hash,
             load_event_tx,
@@ -447,7 +551,44 @@ impl DiskArtifactIO {
             last_toc_check: std::time::Instant::now(),
             toc_check_queued: false,
             pending_new_build_manifest: None,
-        })
+            patch_base_build_hash,
+            build_pin_path: None,
+            last_build_pin_write: std::time::Instant::now(),
+        };
+
+        disk_artifact_io.write_build_pin();
+        Ok(disk_artifact_io)
+    }
+
+    // Pins the current manifest so that build output garbage collection doesn't delete artifacts
+    // while the game is running. Failing to pin is not fatal, the game still works unless garbage
+    // is collected.
+    fn write_build_pin(&mut self) {
+        let pinned_build_hash = self.patch_base_build_hash.unwrap_or(self.build_hash);
+        let build_pin_path = build_pin::build_pin_path(
+            &self.build_data_root_path,
+            pinned_build_hash.0,
+            std::process::id(),
+        );
+        if self.build_pin_path.as_ref() != Some(&build_pin_path) {
+            self.remove_build_pin();
+        }
+
+        let result =
+            std::fs::create_dir_all(build_pin::build_pin_dir_path(&self.build_data_root_path))
+                .and_then(|_| std::fs::write(&build_pin_path, std::process::id().to_string()));
+        if let Err(e) = result {
+            log::warn!("Failed to write build pin {:?}: {}", build_pin_path, e);
+        }
+
+        self.build_pin_path = Some(build_pin_path);
+        self.last_build_pin_write = std::time::Instant::now();
+    }
+
+    fn remove_build_pin(&mut self) {
+        if let Some(build_pin_path) = self.build_pin_path.take() {
+            let _ = std::fs::remove_file(build_pin_path);
+        }
     }
 
     fn request_check_for_new_toc(&self) {
@@ -487,6 +628,10 @@ impl LoaderIO for DiskArtifactIO {
 
             self.request_check_for_new_toc();
         }
+
+        if self.last_build_pin_write.elapsed() > build_pin::BUILD_PIN_REFRESH_INTERVAL {
+            self.write_build_pin();
+        }
     }
 
     fn pending_build_hash(&self) -> Option<ManifestBuildHash> {
@@ -504,6 +649,7 @@ impl LoaderIO for DiskArtifactIO {
             } else {
                 self.manifest = build_manifest;
                 self.build_hash = manifest_build_hash;
+                self.write_build_pin();
             }
         } else {
             panic!("Tried to switch to new build manifest but the new manifest is not pending")
@@ -517,25 +663,24 @@ impl LoaderIO for DiskArtifactIO {
     fn manifest_entry(
         &self,
         artifact_id: ArtifactId,
-    ) -> Option<&ArtifactManifestData> {
-        self.manifest.artifact_lookup.get(&artifact_id)
+    ) -> Option<ArtifactManifestData> {
+        self.manifest.find(artifact_id)
     }
 
     fn resolve_indirect(
         &self,
         indirect_identifier: &IndirectIdentifier,
-    ) -> Option<&ArtifactManifestData> {
-        let (artifact_id, artifact_type) = match indirect_identifier {
+    ) -> Option<ArtifactManifestData> {
+        let (metadata, artifact_type) = match indirect_identifier {
             IndirectIdentifier::ArtifactId(artifact_id, artifact_type) => {
-                (*artifact_id, *artifact_type)
-            }
-            IndirectIdentifier::SymbolWithType(symbol_name, artifact_type) => {
-                let artifact_id = self.manifest.symbol_lookup.get(&symbol_name.hash())?;
-                (*artifact_id, *artifact_type)
+                (self.manifest.find(*artifact_id)?, *artifact_type)
             }
+            IndirectIdentifier::SymbolWithType(symbol_name, artifact_type) => (
+                self.manifest.find_by_symbol(symbol_name.hash())?,
+                *artifact_type,
+            ),
         };
 
-        let metadata = self.manifest.artifact_lookup.get(&artifact_id)?;
         if metadata.artifact_type == artifact_type.0 {
             Some(metadata)
         } else {
@@ -558,9 +703,9 @@ impl LoaderIO for DiskArtifactIO {
 
         let hash = self
             .manifest
-            .artifact_lookup
-            .get(&artifact_id)
-            .map(|x| x.simple_build_hash);
+            .release_manifest
+            .find(artifact_id)
+            .map(|x| x.build_hash);
         if let Some(hash) = hash {
             // Queue up the work
             self.thread_pool
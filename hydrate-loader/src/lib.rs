@@ -41,10 +41,27 @@ pub struct ArtifactManager {
 
 impl ArtifactManager {
     pub fn new(build_data_root_path: PathBuf) -> Result<Self, String> {
+        Self::create(build_data_root_path, None)
+    }
+
+    // Loads the build in build_data_root_path with a patch made by the pipeline layered over it.
+    // Artifacts the patch doesn't have are loaded from the base build.
+    pub fn new_with_patch(
+        build_data_root_path: PathBuf,
+        patch_path: PathBuf,
+    ) -> Result<Self, String> {
+        Self::create(build_data_root_path, Some(patch_path))
+    }
+
+    fn create(
+        build_data_root_path: PathBuf,
+        patch_path: Option<PathBuf>,
+    ) -> Result<Self, String> {
         let (ref_op_tx, ref_op_rx) = crossbeam_channel::unbounded();
         let (loader_events_tx, loader_events_rx) = crossbeam_channel::unbounded();
 
-        let artifact_io = DiskArtifactIO::new(build_data_root_path, loader_events_tx.clone())?;
+        let artifact_io =
+            DiskArtifactIO::new(build_data_root_path, patch_path, loader_events_tx.clone())?;
         let loader = Loader::new(Box::new(artifact_io), loader_events_tx, loader_events_rx);
         let artifact_storage = ArtifactStorageSet::new(ref_op_tx.clone());
 
@@ -105,14 +105,14 @@ pub trait LoaderIO: Sync + Send {
     fn manifest_entry(
         &self,
         artifact_id: ArtifactId,
-    ) -> Option<&ArtifactManifestData>;
+    ) -> Option<ArtifactManifestData>;
 
     // Provide manifest data for an artifact, determined by indirect identifier (for example a
     // symbol name)
     fn resolve_indirect(
         &self,
         indirect_identifier: &IndirectIdentifier,
-    ) -> Option<&ArtifactManifestData>;
+    ) -> Option<ArtifactManifestData>;
 
     // Load the metadata for an artifact.
     // This results in a RequestMetadataResult being sent to the loader
@@ -286,7 +286,7 @@ impl LoaderInner {
             match load_handle_info.load_state {
                 LoadState::Unloaded | LoadState::Loaded => {
                     // do nothing
-                },
+                }
                 _ => {
                     loading_artifact_count += 1;
                 }
@@ -449,7 +449,7 @@ impl LoaderInner {
             for (_, indirect_load) in &self.indirect_states {
                 // Resolve the indirect handle under the new manifest
                 let new_manifest_entry = self.loader_io.resolve_indirect(&indirect_load.id);
-                let new_id_and_hash = new_manifest_entry.map(|x| ArtifactIdAndHash {
+                let new_id_and_hash = new_manifest_entry.as_ref().map(|x| ArtifactIdAndHash {
                     id: x.artifact_id,
                     hash: x.combined_build_hash,
                 });
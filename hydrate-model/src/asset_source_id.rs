@@ -1,3 +1,5 @@
+use siphasher::sip128::Hasher128;
+use std::hash::Hash;
 use uuid::Uuid;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
@@ -8,6 +10,14 @@ impl AssetSourceId {
         AssetSourceId(Uuid::new_v4())
     }
 
+    // Data sources are recreated every time the editor starts. Using an ID derived from the data
+    // source's name keeps the IDs of root assets stable between sessions
+    pub fn from_data_source_name(data_source_name: &str) -> Self {
+        let mut hasher = siphasher::sip128::SipHasher::default();
+        data_source_name.hash(&mut hasher);
+        AssetSourceId(Uuid::from_u128(hasher.finish128().as_u128()))
+    }
+
     pub fn null() -> Self {
         AssetSourceId(Uuid::nil())
     }
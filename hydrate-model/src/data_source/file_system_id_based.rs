@@ -1,8 +1,11 @@
 use crate::edit_context::EditContext;
-use crate::{AssetId, AssetSourceId, DataSource, PathNodeRoot, PendingFileOperations};
+use crate::{
+    AssetId, AssetSourceId, DataSource, GarbageNotDeleted, GarbageReport, PathNodeRoot,
+    PendingFileOperations,
+};
 use hydrate_base::hashing::HashMap;
 use hydrate_base::uuid_path::{path_to_uuid, uuid_to_path};
-use hydrate_data::{AssetLocation, HashObjectMode};
+use hydrate_data::{AssetLocation, DataSetResult, HashObjectMode};
 use hydrate_pipeline::{HydrateProjectConfiguration, ImportJobToQueue};
 use hydrate_schema::SchemaNamedType;
 use std::path::PathBuf;
@@ -350,6 +353,55 @@ impl DataSource for FileSystemIdBasedDataSource {
         return false;
     }
 
+    fn assets_sharing_source_file(
+        &self,
+        _asset_id: AssetId,
+    ) -> Vec<AssetId> {
+        // this data source does not contain source files
+        Vec::default()
+    }
+
+    fn check_files_writable(
+        &self,
+        _edit_context: &EditContext,
+    ) -> DataSetResult<()> {
+        // Assets are always written to new files or files that belong to this data source
+        Ok(())
+    }
+
+    fn source_file_relocations(
+        &self,
+        _edit_context: &EditContext,
+    ) -> Vec<(PathBuf, PathBuf)> {
+        // this data source does not contain source files
+        Vec::default()
+    }
+
+    fn relocate_source_files(
+        &mut self,
+        _edit_context: &EditContext,
+    ) -> DataSetResult<()> {
+        // this data source does not contain source files
+        Ok(())
+    }
+
+    fn append_garbage(
+        &self,
+        _edit_context: &EditContext,
+        _garbage: &mut GarbageReport,
+    ) {
+        // this data source does not contain source files or meta files
+    }
+
+    fn delete_garbage(
+        &mut self,
+        _edit_context: &mut EditContext,
+        _garbage: &GarbageReport,
+        _not_deleted: &mut GarbageNotDeleted,
+    ) {
+        // this data source does not contain source files or meta files
+    }
+
     fn append_pending_file_operations(
         &self,
         edit_context: &EditContext,
@@ -1,11 +1,11 @@
 use crate::edit_context::EditContext;
-use crate::{AssetSourceId, DataSource, PendingFileOperations};
+use crate::{AssetSourceId, DataSource, GarbageNotDeleted, GarbageReport, PendingFileOperations};
 use crate::{PathNode, PathNodeRoot};
 use hydrate_base::hashing::HashSet;
 use hydrate_data::json_storage::{MetaFile, MetaFileJson};
 use hydrate_data::{
-    AssetId, AssetLocation, AssetName, CanonicalPathReference, DataSetAssetInfo, HashObjectMode,
-    ImportableName, ImporterId, PathReference,
+    AssetId, AssetLocation, AssetName, CanonicalPathReference, DataSetAssetInfo, DataSetError,
+    DataSetResult, HashObjectMode, ImportableName, ImporterId, PathReference,
 };
 use hydrate_pipeline::{
     HydrateProjectConfiguration, ImportJobSourceFile, ImportJobToQueue, ImportLogEvent, ImportType,
@@ -13,7 +13,9 @@ use hydrate_pipeline::{
     ScannedImportable,
 };
 use hydrate_schema::{HashMap, SchemaNamedType};
+use siphasher::sip128::Hasher128;
 use std::ffi::OsStr;
+use std::hash::Hash;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use uuid::Uuid;
@@ -71,9 +73,11 @@ struct SourceFileDiskState {
     // may be generated or persisted
     generated_assets: HashSet<AssetId>,
     persisted_assets: HashSet<AssetId>,
+    // Path node for the directory the source file is in
+    directory_path_node_id: AssetId,
     //source_file_metadata: FileMetadata,
     _importer_id: ImporterId,
-    _importables: HashMap<ImportableName, AssetId>,
+    importables: HashMap<ImportableName, AssetId>,
 }
 
 // Key: AssetId
@@ -332,6 +336,23 @@ impl FileSystemPathBasedDataSource {
         all_paths
     }
 
+    // Path nodes are recreated every time the data source is loaded. Deriving the ID from the path
+    // keeps the ID stable between sessions so that saved undo history still refers to the same node
+    fn path_node_id_for_path(
+        &self,
+        path: &Path,
+    ) -> AssetId {
+        let mut hasher = siphasher::sip128::SipHasher::default();
+        self.asset_source_id.uuid().hash(&mut hasher);
+        if let Ok(relative_path) = path.strip_prefix(&self.file_system_root_path) {
+            for component in relative_path.components() {
+                component.as_os_str().to_string_lossy().hash(&mut hasher);
+            }
+        }
+
+        AssetId::from_u128(hasher.finish128().as_u128())
+    }
+
     fn ensure_asset_location_exists(
         &self,
         ancestor_path: &Path,
@@ -372,11 +393,15 @@ impl FileSystemPathBasedDataSource {
             } else {
                 // The path node doesn't exist, we need to create it
                 let file_name = ancestor_path.file_name().unwrap().to_string_lossy();
-                let new_path_node_id = edit_context.new_asset(
-                    &AssetName::new(file_name),
-                    &AssetLocation::new(previous_asset_id),
-                    self.path_node_schema.as_record().unwrap(),
-                );
+                let new_path_node_id = self.path_node_id_for_path(ancestor_path);
+                edit_context
+                    .new_asset_with_id(
+                        new_path_node_id,
+                        &AssetName::new(file_name),
+                        &AssetLocation::new(previous_asset_id),
+                        self.path_node_schema.as_record().unwrap(),
+                    )
+                    .unwrap();
 
                 // add this path node to our canonical list of paths/IDs
                 path_to_path_node_id.insert(ancestor_path.to_path_buf(), new_path_node_id);
@@ -387,6 +412,113 @@ impl FileSystemPathBasedDataSource {
         AssetLocation::new(previous_asset_id)
     }
 
+    // The directory on disk the path node represents, or None if the path node is not in this data
+    // source
+    fn directory_for_path_node(
+        &self,
+        edit_context: &EditContext,
+        path_node_id: AssetId,
+    ) -> Option<PathBuf> {
+        if path_node_id.as_uuid() == *self.asset_source_id.uuid() {
+            return Some(self.file_system_root_path.clone());
+        }
+
+        let asset_info = edit_context.assets().get(&path_node_id)?;
+        if !self.is_asset_owned_by_this_data_source(edit_context, path_node_id) {
+            return None;
+        }
+
+        let containing_file_path = self.containing_file_path_for_asset(edit_context, path_node_id);
+        Some(self.path_for_asset(&containing_file_path, path_node_id, asset_info))
+    }
+
+    // Generated assets are always located next to their source file, so if they have been moved,
+    // the source file must follow them. Otherwise the source file stays in its directory, which
+    // may itself have been moved or renamed.
+    fn source_file_path_node(
+        &self,
+        edit_context: &EditContext,
+        source_file_disk_state: &SourceFileDiskState,
+    ) -> AssetId {
+        source_file_disk_state
+            .generated_assets
+            .iter()
+            .filter(|&&asset_id| {
+                edit_context.has_asset(asset_id)
+                    && self.is_asset_owned_by_this_data_source(edit_context, asset_id)
+            })
+            .find_map(|&asset_id| edit_context.asset_location(asset_id))
+            .map(|x| x.path_node_id())
+            .unwrap_or(source_file_disk_state.directory_path_node_id)
+    }
+
+    // Generated assets that were moved into another data source, along with their source file.
+    // The other data source stores them as regular assets, so the meta file needs to record them
+    // as persisted to keep them from being generated here again.
+    fn generated_assets_moved_out(
+        &self,
+        edit_context: &EditContext,
+    ) -> Vec<(AssetId, PathBuf)> {
+        let mut moved_out = Vec::default();
+        for (&asset_id, asset_disk_state) in &self.assets_disk_state {
+            if let AssetDiskState::Generated(generated_asset_disk_state) = asset_disk_state {
+                if edit_context.has_asset(asset_id)
+                    && !self.is_asset_owned_by_this_data_source(edit_context, asset_id)
+                {
+                    moved_out.push((
+                        asset_id,
+                        generated_asset_disk_state.source_file_path.clone(),
+                    ));
+                }
+            }
+        }
+
+        moved_out
+    }
+
+    // Moves a source file and its meta file. If the meta file can't be moved, the source file is
+    // moved back so that they stay together.
+    fn relocate_source_file(
+        old_path: &Path,
+        new_path: &Path,
+    ) -> std::io::Result<()> {
+        let mut old_meta_file_path = old_path.to_path_buf().into_os_string();
+        old_meta_file_path.push(".meta");
+        let mut new_meta_file_path = new_path.to_path_buf().into_os_string();
+        new_meta_file_path.push(".meta");
+
+        // rename() replaces existing files on some platforms, never overwrite anything
+        if new_path.exists() || Path::new(&new_meta_file_path).exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                "destination already exists",
+            ));
+        }
+
+        if let Some(parent) = new_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::rename(old_path, new_path)?;
+
+        if Path::new(&old_meta_file_path).exists() {
+            if let Err(e) = std::fs::rename(&old_meta_file_path, &new_meta_file_path) {
+                if let Err(e) = std::fs::rename(new_path, old_path) {
+                    log::error!(
+                        "Could not move source file {:?} back to {:?}: {}",
+                        new_path,
+                        old_path,
+                        e
+                    );
+                }
+
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
     fn find_canonical_path_references(
         project_config: &HydrateProjectConfiguration,
         source_file_path: &PathBuf,
@@ -810,14 +942,19 @@ impl DataSource for FileSystemPathBasedDataSource {
                         );
                     }
 
+                    let directory_path_node_id = *path_to_path_node_id
+                        .get(source_file.parent().unwrap())
+                        .unwrap();
+
                     source_files_disk_state.insert(
                         source_file.clone(),
                         SourceFileDiskState {
                             generated_assets: Default::default(),
                             persisted_assets: Default::default(),
+                            directory_path_node_id,
                             //source_file_metadata,
                             _importer_id: importer.importer_id(),
-                            _importables: importables,
+                            importables,
                         },
                     );
 
@@ -1000,6 +1137,52 @@ impl DataSource for FileSystemPathBasedDataSource {
             self.file_system_root_path
         ));
 
+        //
+        // Hand off generated assets that were moved to another data source
+        //
+        // The meta files were checked by check_files_writable() before the move was committed. If
+        // one can't be updated anyway, the failure is logged and the asset will be generated again
+        // the next time the data source is loaded.
+        for (asset_id, source_file_path) in self.generated_assets_moved_out(edit_context) {
+            let mut meta_file_path = source_file_path.clone().into_os_string();
+            meta_file_path.push(".meta");
+
+            let result = std::fs::read_to_string(&meta_file_path).and_then(|contents| {
+                let mut meta_file_contents = MetaFileJson::load_from_string(&contents);
+                meta_file_contents.persisted_assets.insert(asset_id);
+                std::fs::write(
+                    &meta_file_path,
+                    MetaFileJson::store_to_string(&meta_file_contents),
+                )
+            });
+
+            if let Err(e) = result {
+                log::error!(
+                    "Could not record asset {:?} as moved out of meta file {:?}: {}",
+                    asset_id,
+                    meta_file_path,
+                    e
+                );
+            }
+
+            self.assets_disk_state.remove(&asset_id);
+            if let Some(source_file_disk_state) =
+                self.source_files_disk_state.get_mut(&source_file_path)
+            {
+                source_file_disk_state.generated_assets.remove(&asset_id);
+            }
+        }
+
+        //
+        // Move source files (and their meta files) to wherever their assets were moved
+        //
+        // The editor model moves source files when the move is made, undone or redone. This only
+        // catches anything left over. A source file that can't be moved stays where it is and its
+        // assets are generated next to it again the next time the data source is loaded.
+        if let Err(e) = self.relocate_source_files(edit_context) {
+            log::error!("Could not move source files: {:?}", e);
+        }
+
         let mut pending_writes = Vec::<AssetId>::default();
         let mut pending_deletes = Vec::<AssetId>::default();
 
@@ -1067,6 +1250,13 @@ impl DataSource for FileSystemPathBasedDataSource {
 
         //let modified_assets = self.find_all_modified_assets(edit_context);
 
+        let mut deferred_directory_deletes = Vec::default();
+
+        // Files left behind by assets that were moved or renamed. Removed after all writes in case
+        // another asset was moved to the same path.
+        let mut stale_asset_files = Vec::default();
+        let mut written_asset_files = HashSet::default();
+
         // We will write out any files that were modified or moved
         for asset_id in &pending_writes {
             if let Some(asset_info) = edit_context.assets().get(asset_id) {
@@ -1091,9 +1281,41 @@ impl DataSource for FileSystemPathBasedDataSource {
                     let asset_file_path =
                         self.path_for_asset(&containing_file_path, *asset_id, asset_info);
 
+                    if let Some(AssetDiskState::Persisted(old_disk_state)) =
+                        self.assets_disk_state.get(asset_id)
+                    {
+                        if old_disk_state.asset_file_path != asset_file_path {
+                            if is_directory {
+                                deferred_directory_deletes
+                                    .push((*asset_id, old_disk_state.asset_file_path.clone()));
+                            } else {
+                                stale_asset_files.push(old_disk_state.asset_file_path.clone());
+                            }
+                        }
+                    }
+
                     if is_directory {
                         // It's a path node, ensure the dir exists
                         std::fs::create_dir_all(&asset_file_path).unwrap();
+
+                        let object_hash = edit_context
+                            .data_set()
+                            .hash_object(
+                                *asset_id,
+                                HashObjectMode::FullObjectWithLocationChainNames,
+                            )
+                            .unwrap();
+
+                        let asset_file_metadata =
+                            FileMetadata::new(&std::fs::metadata(&asset_file_path).unwrap());
+                        self.assets_disk_state.insert(
+                            *asset_id,
+                            AssetDiskState::Persisted(PersistedAssetDiskState {
+                                _asset_file_metadata: asset_file_metadata,
+                                asset_file_path: asset_file_path.clone(),
+                                object_hash,
+                            }),
+                        );
                     } else {
                         // It's a asset, create an asset file
                         let data = crate::json_storage::AssetJson::save_asset_to_string(
@@ -1106,6 +1328,7 @@ impl DataSource for FileSystemPathBasedDataSource {
 
                         std::fs::create_dir_all(&containing_file_path).unwrap();
                         std::fs::write(&asset_file_path, data).unwrap();
+                        written_asset_files.insert(asset_file_path.clone());
 
                         let object_hash = edit_context
                             .data_set()
@@ -1132,7 +1355,18 @@ impl DataSource for FileSystemPathBasedDataSource {
             }
         }
 
-        let mut deferred_directory_deletes = Vec::default();
+        for stale_asset_file in stale_asset_files {
+            if !written_asset_files.contains(&stale_asset_file) && stale_asset_file.exists() {
+                // The asset was saved at its new path, the old file is only left over
+                if let Err(e) = std::fs::remove_file(&stale_asset_file) {
+                    log::error!(
+                        "Could not remove asset file {:?} left behind by a move: {}",
+                        stale_asset_file,
+                        e
+                    );
+                }
+            }
+        }
 
         // First pass to delete files
         for &asset_id in &pending_deletes {
@@ -1169,6 +1403,10 @@ impl DataSource for FileSystemPathBasedDataSource {
 
         // Second pass to delete directories if they are empty and path node does not exist
         for (_, directory) in deferred_directory_deletes {
+            if !directory.exists() {
+                continue;
+            }
+
             let is_empty = directory.read_dir().unwrap().next().is_none();
             if is_empty {
                 std::fs::remove_dir(&directory).unwrap();
@@ -1180,6 +1418,16 @@ impl DataSource for FileSystemPathBasedDataSource {
         &self,
         edit_context: &EditContext,
     ) -> bool {
+        if !self.source_file_relocations(edit_context).is_empty() {
+            // Generated assets were moved and the source file needs to follow them
+            return true;
+        }
+
+        if !self.generated_assets_moved_out(edit_context).is_empty() {
+            // Generated assets were moved to another data source and the meta file needs to be updated
+            return true;
+        }
+
         for (&asset_id, asset_info) in edit_context.assets() {
             if asset_id.as_uuid() == *self.asset_source_id.uuid() {
                 // ignore the root asset
@@ -1236,11 +1484,298 @@ impl DataSource for FileSystemPathBasedDataSource {
         return false;
     }
 
+    fn assets_sharing_source_file(
+        &self,
+        asset_id: AssetId,
+    ) -> Vec<AssetId> {
+        if let Some(AssetDiskState::Generated(generated_asset_disk_state)) =
+            self.assets_disk_state.get(&asset_id)
+        {
+            if let Some(source_file_disk_state) = self
+                .source_files_disk_state
+                .get(&generated_asset_disk_state.source_file_path)
+            {
+                return source_file_disk_state
+                    .generated_assets
+                    .iter()
+                    .copied()
+                    .collect();
+            }
+        }
+
+        Vec::default()
+    }
+
+    fn source_file_relocations(
+        &self,
+        edit_context: &EditContext,
+    ) -> Vec<(PathBuf, PathBuf)> {
+        let mut relocations = Vec::default();
+        for (source_file_path, source_file_disk_state) in &self.source_files_disk_state {
+            let path_node_id = self.source_file_path_node(edit_context, source_file_disk_state);
+            // If the directory is gone or no longer in this data source, there's nowhere to move to
+            if let Some(directory) = self.directory_for_path_node(edit_context, path_node_id) {
+                let Some(file_name) = source_file_path.file_name() else {
+                    continue;
+                };
+
+                let new_source_file_path = directory.join(file_name);
+                if new_source_file_path != *source_file_path {
+                    relocations.push((source_file_path.clone(), new_source_file_path));
+                }
+            }
+        }
+
+        relocations
+    }
+
+    fn relocate_source_files(
+        &mut self,
+        edit_context: &EditContext,
+    ) -> DataSetResult<()> {
+        let relocations = self.source_file_relocations(edit_context);
+        for (i, (old_path, new_path)) in relocations.iter().enumerate() {
+            if let Err(e) = Self::relocate_source_file(old_path, new_path) {
+                log::error!(
+                    "Could not move source file {:?} to {:?}: {}",
+                    old_path,
+                    new_path,
+                    e
+                );
+
+                // Nothing is moved unless everything can be
+                for (old_path, new_path) in relocations[..i].iter().rev() {
+                    if let Err(e) = Self::relocate_source_file(new_path, old_path) {
+                        log::error!(
+                            "Could not move source file {:?} back to {:?}: {}",
+                            new_path,
+                            old_path,
+                            e
+                        );
+                    }
+                }
+
+                return Err(DataSetError::FileNotWritable)?;
+            }
+        }
+
+        for (old_path, new_path) in relocations {
+            let mut source_file_disk_state =
+                self.source_files_disk_state.remove(&old_path).unwrap();
+            source_file_disk_state.directory_path_node_id =
+                self.source_file_path_node(edit_context, &source_file_disk_state);
+            for asset_id in &source_file_disk_state.generated_assets {
+                self.assets_disk_state.insert(
+                    *asset_id,
+                    AssetDiskState::Generated(GeneratedAssetDiskState {
+                        source_file_path: new_path.clone(),
+                    }),
+                );
+            }
+            self.source_files_disk_state
+                .insert(new_path, source_file_disk_state);
+        }
+
+        Ok(())
+    }
+
+    fn check_files_writable(
+        &self,
+        edit_context: &EditContext,
+    ) -> DataSetResult<()> {
+        // Meta files of generated assets that were moved to another data source are rewritten
+        for (_, source_file_path) in self.generated_assets_moved_out(edit_context) {
+            let mut meta_file_path = source_file_path.into_os_string();
+            meta_file_path.push(".meta");
+
+            // Opening for writing does not truncate the file, so nothing is changed yet
+            let is_writable = std::fs::read_to_string(&meta_file_path).is_ok()
+                && std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(&meta_file_path)
+                    .is_ok();
+            if !is_writable {
+                log::error!("Meta file {:?} can't be read and written", meta_file_path);
+                return Err(DataSetError::FileNotWritable)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn append_garbage(
+        &self,
+        edit_context: &EditContext,
+        garbage: &mut GarbageReport,
+    ) {
+        for (source_file_path, source_file_disk_state) in &self.source_files_disk_state {
+            if !source_file_path.exists() {
+                for &asset_id in &source_file_disk_state.generated_assets {
+                    if edit_context.has_asset(asset_id) {
+                        garbage
+                            .orphaned_generated_assets
+                            .push((asset_id, source_file_path.clone()));
+                    }
+                }
+
+                // The meta file will be picked up below
+                continue;
+            }
+
+            let mut meta_file_path = source_file_path.clone().into_os_string();
+            meta_file_path.push(".meta");
+            if let Ok(contents) = std::fs::read_to_string(&meta_file_path) {
+                let meta_file_contents = MetaFileJson::load_from_string(&contents);
+                let stale_importables: Vec<_> = meta_file_contents
+                    .past_id_assignments
+                    .keys()
+                    .filter(|x| !source_file_disk_state.importables.contains_key(x))
+                    .cloned()
+                    .collect();
+
+                if !stale_importables.is_empty() {
+                    garbage
+                        .stale_meta_file_entries
+                        .push((PathBuf::from(meta_file_path), stale_importables));
+                }
+            }
+        }
+
+        // Source files may have been deleted while the editor was closed, so look at everything on disk
+        let walker =
+            globwalk::GlobWalkerBuilder::from_patterns(&self.file_system_root_path, &["**.meta"])
+                .file_type(globwalk::FileType::FILE)
+                .build();
+        let walker = match walker {
+            Ok(walker) => walker,
+            Err(e) => {
+                log::error!(
+                    "Could not search {:?} for orphaned meta files: {}",
+                    self.file_system_root_path,
+                    e
+                );
+                return;
+            }
+        };
+
+        for file in walker {
+            if let Ok(file) = file {
+                // The file may have been removed since it was found
+                let meta_file = match dunce::canonicalize(&file.path()) {
+                    Ok(meta_file) => meta_file,
+                    Err(e) => {
+                        log::warn!("Skipping meta file {:?}: {}", file.path(), e);
+                        continue;
+                    }
+                };
+                if !meta_file.with_extension("").exists() {
+                    garbage.orphaned_meta_files.push(meta_file);
+                }
+            }
+        }
+    }
+
+    fn delete_garbage(
+        &mut self,
+        edit_context: &mut EditContext,
+        garbage: &GarbageReport,
+        not_deleted: &mut GarbageNotDeleted,
+    ) {
+        for (asset_id, source_file_path) in &garbage.orphaned_generated_assets {
+            if !self
+                .assets_disk_state
+                .get(asset_id)
+                .map(|x| x.is_generated())
+                .unwrap_or(false)
+            {
+                continue;
+            }
+
+            if edit_context.has_asset(*asset_id) {
+                if let Err(e) = edit_context.delete_asset(*asset_id) {
+                    not_deleted.assets.push((*asset_id, format!("{:?}", e)));
+                    continue;
+                }
+            }
+
+            self.assets_disk_state.remove(asset_id);
+
+            // Nothing else can be imported from a source file that doesn't exist
+            if let Some(source_file_disk_state) =
+                self.source_files_disk_state.get_mut(source_file_path)
+            {
+                source_file_disk_state.generated_assets.remove(asset_id);
+                if source_file_disk_state.generated_assets.is_empty() {
+                    self.source_files_disk_state.remove(source_file_path);
+                }
+            }
+        }
+
+        let root_path = dunce::canonicalize(&self.file_system_root_path)
+            .unwrap_or_else(|_| self.file_system_root_path.clone());
+
+        for meta_file in &garbage.orphaned_meta_files {
+            if meta_file.starts_with(&root_path) && meta_file.exists() {
+                if let Err(e) = std::fs::remove_file(meta_file) {
+                    not_deleted.files.push((meta_file.clone(), e.to_string()));
+                }
+            }
+        }
+
+        for (meta_file, stale_importables) in &garbage.stale_meta_file_entries {
+            if !meta_file.starts_with(&root_path) {
+                continue;
+            }
+
+            let contents = match std::fs::read_to_string(meta_file) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    not_deleted.files.push((meta_file.clone(), e.to_string()));
+                    continue;
+                }
+            };
+
+            let mut meta_file_contents = MetaFileJson::load_from_string(&contents);
+            for stale_importable in stale_importables {
+                if let Some(asset_id) = meta_file_contents
+                    .past_id_assignments
+                    .remove(stale_importable)
+                {
+                    meta_file_contents.persisted_assets.remove(&asset_id);
+                }
+            }
+
+            if let Err(e) = std::fs::write(
+                meta_file,
+                MetaFileJson::store_to_string(&meta_file_contents),
+            ) {
+                not_deleted.files.push((meta_file.clone(), e.to_string()));
+            }
+        }
+    }
+
     fn append_pending_file_operations(
         &self,
         edit_context: &EditContext,
         pending_file_operations: &mut PendingFileOperations,
     ) {
+        for (old_path, new_path) in self.source_file_relocations(edit_context) {
+            let source_file_disk_state = self.source_files_disk_state.get(&old_path).unwrap();
+            for &asset_id in &source_file_disk_state.generated_assets {
+                pending_file_operations
+                    .modify_operations
+                    .push((asset_id, new_path.clone()));
+            }
+        }
+
+        for (asset_id, source_file_path) in self.generated_assets_moved_out(edit_context) {
+            let mut meta_file_path = source_file_path.into_os_string();
+            meta_file_path.push(".meta");
+            pending_file_operations
+                .modify_operations
+                .push((asset_id, PathBuf::from(meta_file_path)));
+        }
+
         for (&asset_id, asset_info) in edit_context.assets() {
             if asset_id.as_uuid() == *self.asset_source_id.uuid() {
                 // ignore the root asset
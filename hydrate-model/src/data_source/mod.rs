@@ -4,7 +4,8 @@ pub use file_system_id_based::*;
 use std::path::PathBuf;
 
 use crate::edit_context::EditContext;
-use crate::AssetId;
+use crate::{AssetId, ImportableName};
+use hydrate_data::DataSetResult;
 
 mod file_system_path_based;
 pub use file_system_path_based::*;
@@ -17,6 +18,42 @@ pub struct PendingFileOperations {
     pub delete_operations: Vec<(AssetId, PathBuf)>,
 }
 
+// Files and assets that are no longer needed. Produced by EditorModel::find_garbage() so that it
+// can be reviewed before passing it to EditorModel::delete_garbage()
+#[derive(Default, Debug)]
+pub struct GarbageReport {
+    // Import data files (.if) for assets that no longer exist
+    pub orphaned_import_data: Vec<PathBuf>,
+    // Generated assets whose source file no longer exists, along with the missing source file path
+    pub orphaned_generated_assets: Vec<(AssetId, PathBuf)>,
+    // Meta files with no source file next to them
+    pub orphaned_meta_files: Vec<PathBuf>,
+    // Meta files that list importables the source file no longer produces
+    pub stale_meta_file_entries: Vec<(PathBuf, Vec<ImportableName>)>,
+}
+
+// Garbage that EditorModel::delete_garbage() could not remove, along with the reason
+#[derive(Default, Debug)]
+pub struct GarbageNotDeleted {
+    pub files: Vec<(PathBuf, String)>,
+    pub assets: Vec<(AssetId, String)>,
+}
+
+impl GarbageNotDeleted {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.assets.is_empty()
+    }
+}
+
+impl GarbageReport {
+    pub fn is_empty(&self) -> bool {
+        self.orphaned_import_data.is_empty()
+            && self.orphaned_generated_assets.is_empty()
+            && self.orphaned_meta_files.is_empty()
+            && self.stale_meta_file_entries.is_empty()
+    }
+}
+
 pub trait DataSource {
     // Replace memory with storage state
     // Reset memory to storage
@@ -56,4 +93,50 @@ pub trait DataSource {
         edit_context: &EditContext,
         pending_file_operations: &mut PendingFileOperations,
     );
+
+    // Assets that are generated from the same source file as the given asset (including the asset
+    // itself). These are stored next to the source file, so they can only be moved as a group.
+    fn assets_sharing_source_file(
+        &self,
+        asset_id: AssetId,
+    ) -> Vec<AssetId>;
+
+    // Source files that must be moved on disk to match the locations of assets in the edit
+    // context, as (path on disk, new path). They are moved by relocate_source_files().
+    fn source_file_relocations(
+        &self,
+        edit_context: &EditContext,
+    ) -> Vec<(PathBuf, PathBuf)>;
+
+    // Moves source files on disk to match the locations of their assets in the edit context. If
+    // any can't be moved, the ones already moved are moved back and an error is returned.
+    fn relocate_source_files(
+        &mut self,
+        edit_context: &EditContext,
+    ) -> DataSetResult<()>;
+
+    // Checks that existing files that will be rewritten when flushing to storage can be read and
+    // written. Called before committing a change that requires it, so that the change is rejected
+    // instead of failing part way through saving.
+    fn check_files_writable(
+        &self,
+        edit_context: &EditContext,
+    ) -> DataSetResult<()>;
+
+    // Adds anything this data source stores that is no longer needed to the report. Import data is
+    // not stored by data sources and is handled by the editor model.
+    fn append_garbage(
+        &self,
+        edit_context: &EditContext,
+        garbage: &mut GarbageReport,
+    );
+
+    // Deletes garbage that was found by append_garbage(). Anything in the report that was not found
+    // by this data source is ignored. Anything that can't be deleted is added to not_deleted.
+    fn delete_garbage(
+        &mut self,
+        edit_context: &mut EditContext,
+        garbage: &GarbageReport,
+        not_deleted: &mut GarbageNotDeleted,
+    );
 }
@@ -5,6 +5,7 @@ use hydrate_data::{
 };
 use hydrate_pipeline::{DynEditContext, HydrateProjectConfiguration};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::editor::undo::{UndoContext, UndoStack};
@@ -190,6 +191,11 @@ impl EditContext {
         self.undo_context.cancel_context(&mut self.data_set)
     }
 
+    // True if an undo context was left open to be resumed and hasn't been committed yet
+    pub fn has_pending_undo_context(&self) -> bool {
+        self.undo_context.has_open_context()
+    }
+
     // pub fn apply_diff(&mut self, diff: &DataSetDiff) {
     //     diff.apply(&mut self.data_set);
     // }
@@ -216,7 +222,7 @@ impl EditContext {
     //     &mut self.data_set
     // }
 
-    pub fn assets(&self) -> &HashMap<AssetId, DataSetAssetInfo> {
+    pub fn assets(&self) -> &HashMap<AssetId, Arc<DataSetAssetInfo>> {
         self.data_set.assets()
     }
 
@@ -313,6 +319,21 @@ impl EditContext {
         Ok(())
     }
 
+    // Overwrites (or creates) the asset with the state it has in the other data set
+    pub fn copy_asset_from(
+        &mut self,
+        other: &DataSet,
+        asset_id: AssetId,
+    ) -> DataSetResult<()> {
+        if self.data_set.assets().contains_key(&asset_id) {
+            self.track_existing_asset(asset_id)?;
+        } else {
+            self.track_new_asset(asset_id);
+        }
+
+        self.data_set.copy_from(other, asset_id)
+    }
+
     pub(crate) fn restore_asset(
         &mut self,
         asset_id: AssetId,
@@ -474,6 +495,15 @@ impl EditContext {
             .set_path_reference_override(asset_id, path, referenced_asset_id)
     }
 
+    pub fn remove_path_reference_override(
+        &mut self,
+        asset_id: AssetId,
+        path: &CanonicalPathReference,
+    ) -> DataSetResult<Option<AssetId>> {
+        self.track_existing_asset(asset_id)?;
+        self.data_set.remove_path_reference_override(asset_id, path)
+    }
+
     pub fn asset_prototype(
         &self,
         asset_id: AssetId,
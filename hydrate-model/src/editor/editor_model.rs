@@ -1,19 +1,23 @@
 use crate::edit_context::EditContext;
-use crate::editor::undo::UndoStack;
+use crate::editor::undo::{AssetHistoryEntry, EndContextBehavior, UndoStack, UndoStepId};
 use crate::{
     AssetId, AssetPath, AssetPathCache, AssetSourceId, DataSet, DataSource,
-    FileSystemIdBasedDataSource, FileSystemPathBasedDataSource, HashMap, PathNode, PathNodeRoot,
-    PendingFileOperations, SchemaNamedType, SchemaSet,
+    FileSystemIdBasedDataSource, FileSystemPathBasedDataSource, GarbageNotDeleted, GarbageReport,
+    HashMap, HashSet, PathNode, PathNodeRoot, PendingFileOperations, SchemaLinker, SchemaNamedType,
+    SchemaSet, SchemaSetBuilder,
 };
+use hydrate_base::uuid_path::path_to_uuid;
 use hydrate_data::{
-    AssetLocation, AssetName, CanonicalPathReference, DataSetError, DataSetResult, ImportInfo,
-    PathReferenceHash, SingleObject,
+    AssetLocation, AssetName, CanonicalPathReference, DataSetError, DataSetResult, HashObjectMode,
+    ImportInfo, PathReferenceHash, SingleObject,
 };
 use hydrate_pipeline::{
-    DynEditorModel, HydrateProjectConfiguration, ImportJobToQueue, ImporterRegistry,
+    AssetEngine, DynEditorModel, HydrateProjectConfiguration, ImportJobToQueue, ImporterRegistry,
+    PipelineError, PipelineResult,
 };
 use hydrate_schema::{SchemaFingerprint, SchemaRecord};
 use slotmap::DenseSlotMap;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 slotmap::new_key_type! { pub struct EditContextKey; }
 
@@ -102,6 +106,12 @@ impl<'a> DynEditorModel for EditorModelWithCache<'a> {
         self.editor_model
             .asset_display_name_long(asset_id, &self.asset_path_cache)
     }
+
+    fn has_pending_edits(&self) -> bool {
+        self.editor_model
+            .root_edit_context()
+            .has_pending_undo_context()
+    }
 }
 
 impl EditorModel {
@@ -140,6 +150,48 @@ impl EditorModel {
         }
     }
 
+    // Loads the schema for a project, including the schema types the editor model relies on
+    #[profiling::function]
+    pub fn load_schema_set(project_configuration: &HydrateProjectConfiguration) -> SchemaSet {
+        let mut linker = SchemaLinker::default();
+        let mut schema_set = SchemaSetBuilder::default();
+
+        PathNode::register_schema(&mut linker);
+        PathNodeRoot::register_schema(&mut linker);
+        for path in &project_configuration.schema_def_paths {
+            linker.add_source_dir(path, "**.json").unwrap();
+        }
+        schema_set.add_linked_types(linker).unwrap();
+
+        schema_set.build()
+    }
+
+    // Adds all the data sources listed in the project configuration
+    pub fn add_project_data_sources(
+        &mut self,
+        project_configuration: &HydrateProjectConfiguration,
+        importer_registry: &ImporterRegistry,
+        import_job_to_queue: &mut ImportJobToQueue,
+    ) {
+        for pair in &project_configuration.id_based_asset_sources {
+            self.add_file_system_id_based_asset_source(
+                project_configuration,
+                &pair.name,
+                &pair.path,
+                import_job_to_queue,
+            );
+        }
+        for pair in &project_configuration.path_based_asset_sources {
+            self.add_file_system_path_based_data_source(
+                project_configuration,
+                &pair.name,
+                &pair.path,
+                importer_registry,
+                import_job_to_queue,
+            );
+        }
+    }
+
     pub fn path_node_schema(&self) -> &SchemaNamedType {
         &self.path_node_schema
     }
@@ -312,7 +364,7 @@ impl EditorModel {
         //
         // Create the PathNodeRoot asset that acts as the root location for all assets in this DS
         //
-        let asset_source_id = AssetSourceId::new();
+        let asset_source_id = AssetSourceId::from_data_source_name(data_source_name);
         let root_asset_id = AssetId::from_uuid(*asset_source_id.uuid());
         root_edit_context
             .new_asset_with_id(
@@ -356,7 +408,7 @@ impl EditorModel {
         //
         // Create the PathNodeRoot asset that acts as the root location for all assets in this DS
         //
-        let asset_source_id = AssetSourceId::new();
+        let asset_source_id = AssetSourceId::from_data_source_name(data_source_name);
         let root_asset_id = AssetId::from_uuid(*asset_source_id.uuid());
         root_edit_context
             .new_asset_with_id(
@@ -396,6 +448,9 @@ impl EditorModel {
         for (_id, data_source) in &mut self.data_sources {
             data_source.flush_to_storage(root_edit_context);
         }
+
+        // Disk now matches what's in memory, so this is the point the undo history can be resumed from
+        self.save_undo_history();
     }
 
     pub fn revert_root_edit_context(
@@ -419,6 +474,10 @@ impl EditorModel {
             data_source.load_from_storage(project_config, root_edit_context, import_job_to_queue);
         }
 
+        // The in-memory history no longer applies to the reloaded data, go back to what was saved
+        // with it
+        self.load_undo_history();
+
         //
         // Clear modified assets list since we reloaded everything from disk.
         //
@@ -515,11 +574,554 @@ impl EditorModel {
         self.edit_contexts.remove(edit_context);
     }
 
+    // Steps that move assets with source files are not undone or redone if the source files can't
+    // be moved back, as path references would point at where the files aren't
     pub fn undo(&mut self) -> DataSetResult<()> {
-        self.undo_stack.undo(&mut self.edit_contexts)
+        self.undo_stack.undo(&mut self.edit_contexts)?;
+        self.relocate_source_files_or_revert(|editor_model| {
+            editor_model
+                .undo_stack
+                .redo(&mut editor_model.edit_contexts)
+        })
     }
 
     pub fn redo(&mut self) -> DataSetResult<()> {
-        self.undo_stack.redo(&mut self.edit_contexts)
+        self.undo_stack.redo(&mut self.edit_contexts)?;
+        self.relocate_source_files_or_revert(|editor_model| {
+            editor_model
+                .undo_stack
+                .undo(&mut editor_model.edit_contexts)
+        })
+    }
+
+    pub fn undo_stack(&self) -> &UndoStack {
+        &self.undo_stack
+    }
+
+    // Moves to any step in the undo tree, including steps on branches that were abandoned by undoing
+    // and then making a different edit. None undoes everything.
+    pub fn jump_to_undo_step(
+        &mut self,
+        step: Option<UndoStepId>,
+    ) -> DataSetResult<()> {
+        let previous_step = self.undo_stack.current_step();
+        self.undo_stack
+            .jump_to_step(&mut self.edit_contexts, step)?;
+        self.relocate_source_files_or_revert(|editor_model| {
+            editor_model
+                .undo_stack
+                .jump_to_step(&mut editor_model.edit_contexts, previous_step)
+        })
+    }
+
+    // Moves source files to match the locations of their assets in the root edit context
+    fn relocate_source_files(
+        data_sources: &mut HashMap<AssetSourceId, Box<dyn DataSource>>,
+        root_edit_context: &EditContext,
+    ) -> DataSetResult<()> {
+        for data_source in data_sources.values_mut() {
+            data_source.relocate_source_files(root_edit_context)?;
+        }
+
+        Ok(())
+    }
+
+    // Reverts the change that was just applied if the source files can't be moved to match it
+    fn relocate_source_files_or_revert(
+        &mut self,
+        revert: impl FnOnce(&mut Self) -> DataSetResult<()>,
+    ) -> DataSetResult<()> {
+        let root_edit_context = &self.edit_contexts[self.root_edit_context_key];
+        let Err(e) = Self::relocate_source_files(&mut self.data_sources, root_edit_context) else {
+            return Ok(());
+        };
+
+        revert(self)?;
+
+        // Other data sources may have moved their files before one failed
+        let root_edit_context = &self.edit_contexts[self.root_edit_context_key];
+        if let Err(e) = Self::relocate_source_files(&mut self.data_sources, root_edit_context) {
+            log::error!("Could not move source files back: {:?}", e);
+        }
+
+        Err(e)
+    }
+
+    // Moves any steps that have been committed into the undo tree. Call this periodically so that
+    // history queries include recent edits. Undo contexts that are still open are left alone.
+    pub fn update_undo_history(&mut self) {
+        self.undo_stack.drain_rx();
+    }
+
+    // Changes made to the asset in the root edit context by each step that led to the current
+    // state, oldest first. Only includes steps picked up by the last update_undo_history().
+    pub fn asset_edit_history(
+        &self,
+        asset_id: AssetId,
+    ) -> Vec<AssetHistoryEntry<'_>> {
+        self.undo_stack
+            .asset_journal(asset_id, self.root_edit_context_key)
+    }
+
+    // The state of the asset immediately after the given step. None is the state before the
+    // first step in the current history. The data set is empty if the asset did not exist.
+    pub fn preview_asset_history(
+        &self,
+        asset_id: AssetId,
+        step: Option<UndoStepId>,
+    ) -> DataSetResult<DataSet> {
+        self.undo_stack.preview_asset_at_step(
+            self.root_edit_context().data_set(),
+            &self.schema_set,
+            self.root_edit_context_key,
+            asset_id,
+            step,
+        )
+    }
+
+    // Puts the asset back to the state it had immediately after the given step. This is a new
+    // edit that can be undone. Other assets are not affected.
+    pub fn restore_asset_from_history(
+        &mut self,
+        asset_id: AssetId,
+        step: Option<UndoStepId>,
+    ) -> DataSetResult<()> {
+        self.commit_all_pending_undo_contexts();
+        self.update_undo_history();
+        let preview = self.preview_asset_history(asset_id, step)?;
+
+        let mut result = Ok(());
+        self.root_edit_context_mut().with_undo_context(
+            "restore asset from history",
+            |edit_context| {
+                result = if preview.assets().contains_key(&asset_id) {
+                    edit_context.copy_asset_from(&preview, asset_id)
+                } else if edit_context.has_asset(asset_id) {
+                    edit_context.delete_asset(asset_id)
+                } else {
+                    Ok(())
+                };
+                EndContextBehavior::Finish
+            },
+        );
+
+        result
+    }
+
+    // The root path node of the data source that contains the location
+    fn data_source_root_for_location(
+        edit_context: &EditContext,
+        location: AssetLocation,
+    ) -> DataSetResult<AssetId> {
+        let location_chain = edit_context.asset_location_chain(location.path_node_id())?;
+        Ok(location_chain
+            .last()
+            .map(|x| x.path_node_id())
+            .unwrap_or(location.path_node_id()))
+    }
+
+    // Moves and optionally renames assets as a single undo step. Generated assets are stored next to
+    // their source file, so all assets generated from the same source file are moved together and
+    // the source file is moved with them. Any path references to a source file that will be
+    // moved are updated to point at the new path. Nothing is changed if the move would produce
+    // conflicting asset names or file paths, or if a file that must be rewritten to save it can't
+    // be written.
+    //
+    // Assets and whole subtrees can also be moved to a different data source. They are removed from
+    // the old data source and written by the new one when saving. Import data is stored by asset
+    // ID so it is unaffected. Generated assets moved out of a path-based data source become
+    // regular assets in the new data source, and their source file stays where it is.
+    pub fn move_assets(
+        &mut self,
+        asset_ids: &[AssetId],
+        new_name: Option<AssetName>,
+        new_location: AssetLocation,
+    ) -> DataSetResult<()> {
+        self.commit_all_pending_undo_contexts();
+
+        let root_edit_context = self.root_edit_context();
+        let destination_root =
+            Self::data_source_root_for_location(root_edit_context, new_location)?;
+        if !self.is_a_root_asset(destination_root) {
+            // Nothing would save the assets
+            return Err(DataSetError::LocationNotInDataSource)?;
+        }
+
+        let mut moving_assets = Vec::default();
+        let mut moving_assets_set = HashSet::default();
+        for &asset_id in asset_ids {
+            if new_name.is_some() && self.is_generated_asset(asset_id) {
+                // Generated assets are named after the importable, rename the source file instead
+                return Err(DataSetError::GeneratedAssetCannotBeRenamed)?;
+            }
+
+            if moving_assets_set.insert(asset_id) {
+                moving_assets.push(asset_id);
+            }
+
+            // Within a data source, the source file moves with its generated assets, so they all
+            // have to move together. If they are moved to another data source, the source file stays
+            // where it is and the moved assets are persisted by the new data source.
+            let asset_location = root_edit_context
+                .asset_location(asset_id)
+                .ok_or(DataSetError::AssetNotFound)?;
+            let source_root =
+                Self::data_source_root_for_location(root_edit_context, asset_location)?;
+            if source_root == destination_root {
+                for data_source in self.data_sources.values() {
+                    for sibling_asset_id in data_source.assets_sharing_source_file(asset_id) {
+                        if moving_assets_set.insert(sibling_asset_id) {
+                            moving_assets.push(sibling_asset_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        //
+        // Validate the move before touching anything
+        //
+        let mut names_at_new_location = HashSet::default();
+        for (asset_id, asset_info) in root_edit_context.assets() {
+            if !moving_assets_set.contains(asset_id) && asset_info.asset_location() == new_location
+            {
+                if let Some(name) = asset_info.asset_name().as_string() {
+                    names_at_new_location.insert(name.clone());
+                }
+            }
+        }
+
+        for &asset_id in &moving_assets {
+            let asset_name = match &new_name {
+                Some(new_name) => new_name,
+                None => root_edit_context.asset_name(asset_id)?,
+            };
+
+            if let Some(name) = asset_name.as_string() {
+                if !names_at_new_location.insert(name.clone()) {
+                    return Err(DataSetError::AssetNameCollision)?;
+                }
+            }
+        }
+
+        //
+        // Apply the move, then fix up any path references to source files that will move with it
+        //
+        let root_edit_context = self
+            .edit_contexts
+            .get_mut(self.root_edit_context_key)
+            .unwrap();
+        let data_sources = &self.data_sources;
+
+        let mut result = Ok(());
+        root_edit_context.with_undo_context("move assets", |edit_context| {
+            result = Self::do_move_assets(
+                data_sources,
+                edit_context,
+                &moving_assets,
+                new_name.as_ref(),
+                new_location,
+            );
+            // Keep the context open so that it can be cancelled if the move failed part way
+            EndContextBehavior::AllowResume
+        });
+
+        // Source files are moved before the step is committed, so that path references are only
+        // rewritten if the files they point at are actually moved
+        if result.is_ok() {
+            result = Self::relocate_source_files(&mut self.data_sources, root_edit_context);
+        }
+
+        if result.is_ok() {
+            root_edit_context.commit_pending_undo_context();
+        } else {
+            root_edit_context.cancel_pending_undo_context()?;
+
+            // Other data sources may have moved their files before one failed
+            if let Err(e) = Self::relocate_source_files(&mut self.data_sources, root_edit_context) {
+                log::error!("Could not move source files back: {:?}", e);
+            }
+        }
+
+        result
+    }
+
+    fn do_move_assets(
+        data_sources: &HashMap<AssetSourceId, Box<dyn DataSource>>,
+        edit_context: &mut EditContext,
+        moving_assets: &[AssetId],
+        new_name: Option<&AssetName>,
+        new_location: AssetLocation,
+    ) -> DataSetResult<()> {
+        for &asset_id in moving_assets {
+            if let Some(new_name) = new_name {
+                edit_context.set_asset_name(asset_id, new_name.clone())?;
+            }
+
+            edit_context.set_asset_location(asset_id, new_location)?;
+        }
+
+        let mut source_file_relocations = Vec::default();
+        let mut new_source_file_paths = HashSet::default();
+        for data_source in data_sources.values() {
+            for (old_path, new_path) in data_source.source_file_relocations(edit_context) {
+                if new_path.exists() || !new_source_file_paths.insert(new_path.clone()) {
+                    return Err(DataSetError::FilePathCollision)?;
+                }
+
+                source_file_relocations.push((old_path, new_path));
+            }
+
+            data_source.check_files_writable(edit_context)?;
+        }
+
+        for (old_path, new_path) in &source_file_relocations {
+            let mut path_reference_updates = Vec::default();
+            let mut import_info_updates = Vec::default();
+            for (&asset_id, asset_info) in edit_context.assets() {
+                for (path_reference, &referenced_asset_id) in
+                    &asset_info.build_info().path_reference_overrides
+                {
+                    if let Some(new_path_reference) =
+                        path_reference.relocate(edit_context, old_path, new_path)
+                    {
+                        path_reference_updates.push((
+                            asset_id,
+                            path_reference.clone(),
+                            new_path_reference,
+                            referenced_asset_id,
+                        ));
+                    }
+                }
+
+                if let Some(import_info) = asset_info.import_info() {
+                    if let Some(new_import_info) =
+                        import_info.relocate_path_references(edit_context, old_path, new_path)
+                    {
+                        import_info_updates.push((asset_id, new_import_info));
+                    }
+                }
+            }
+
+            for (asset_id, old_path_reference, new_path_reference, referenced_asset_id) in
+                path_reference_updates
+            {
+                edit_context.remove_path_reference_override(asset_id, &old_path_reference)?;
+                edit_context.set_path_reference_override(
+                    asset_id,
+                    new_path_reference,
+                    referenced_asset_id,
+                )?;
+            }
+
+            for (asset_id, new_import_info) in import_info_updates {
+                edit_context.set_import_info(asset_id, new_import_info)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn assets_in_undo_history(&self) -> HashSet<AssetId> {
+        self.undo_stack
+            .steps()
+            .iter()
+            .flat_map(|step| step.diff_set().modified_assets.iter().copied())
+            .collect()
+    }
+
+    // Finds import data, generated assets and meta file entries that are no longer needed. Nothing is
+    // changed, pass the result to delete_garbage() to remove them. Fails if the asset engine has
+    // imports running or queued, as import data is written before its asset is created. Import data
+    // of assets in the undo history picked up by the last update_undo_history() is kept.
+    pub fn find_garbage(
+        &self,
+        asset_engine: &AssetEngine,
+    ) -> PipelineResult<GarbageReport> {
+        profiling::scope!("find_garbage");
+        if asset_engine.has_pending_imports() {
+            return Err(PipelineError::StringError(
+                "Garbage can't be collected while imports are pending".to_string(),
+            ))?;
+        }
+
+        let mut garbage = GarbageReport::default();
+        let root_edit_context = self.root_edit_context();
+        for data_source in self.data_sources.values() {
+            data_source.append_garbage(root_edit_context, &mut garbage);
+        }
+
+        // Import data for the generated assets that are about to be deleted is garbage too
+        let orphaned_generated_assets: HashSet<AssetId> = garbage
+            .orphaned_generated_assets
+            .iter()
+            .map(|(asset_id, _)| *asset_id)
+            .collect();
+
+        let import_data_root_path = &self.project_config.import_data_path;
+        if import_data_root_path.exists() {
+            let walker =
+                globwalk::GlobWalkerBuilder::from_patterns(import_data_root_path, &["**.if"])
+                    .file_type(globwalk::FileType::FILE)
+                    .build();
+            let walker = match walker {
+                Ok(walker) => walker,
+                Err(e) => {
+                    log::error!(
+                        "Could not search {:?} for orphaned import data: {}",
+                        import_data_root_path,
+                        e
+                    );
+                    return Ok(garbage);
+                }
+            };
+
+            // Undoing or redoing a step can bring back an asset that is not in the root edit
+            // context, so keep the import data of any asset the undo history touches
+            let assets_in_undo_history = self.assets_in_undo_history();
+
+            for file in walker {
+                if let Ok(file) = file {
+                    let Some(uuid) = path_to_uuid(import_data_root_path, file.path()) else {
+                        continue;
+                    };
+
+                    let asset_id = AssetId::from_uuid(uuid);
+                    if (!root_edit_context.has_asset(asset_id)
+                        && !assets_in_undo_history.contains(&asset_id))
+                        || orphaned_generated_assets.contains(&asset_id)
+                    {
+                        garbage.orphaned_import_data.push(file.path().to_path_buf());
+                    }
+                }
+            }
+        }
+
+        Ok(garbage)
+    }
+
+    // Deletes everything in a report produced by find_garbage(). Generated assets are removed
+    // from the root edit context without creating an undo step, as they could not be restored
+    // without their source file. Returns anything that could not be deleted.
+    pub fn delete_garbage(
+        &mut self,
+        garbage: &GarbageReport,
+    ) -> GarbageNotDeleted {
+        profiling::scope!("delete_garbage");
+        self.commit_all_pending_undo_contexts();
+
+        let root_edit_context = self
+            .edit_contexts
+            .get_mut(self.root_edit_context_key)
+            .unwrap();
+        let mut not_deleted = GarbageNotDeleted::default();
+        for data_source in self.data_sources.values_mut() {
+            data_source.delete_garbage(root_edit_context, garbage, &mut not_deleted);
+        }
+
+        // Edits committed since find_garbage() ran may have deleted an asset that still has import data
+        self.undo_stack.drain_rx();
+        let assets_in_undo_history = self.assets_in_undo_history();
+        let import_data_root_path = &self.project_config.import_data_path;
+        for import_data_file in &garbage.orphaned_import_data {
+            if let Some(uuid) = path_to_uuid(import_data_root_path, import_data_file) {
+                if assets_in_undo_history.contains(&AssetId::from_uuid(uuid)) {
+                    continue;
+                }
+            }
+
+            if import_data_file.starts_with(import_data_root_path) && import_data_file.exists() {
+                if let Err(e) = std::fs::remove_file(import_data_file) {
+                    not_deleted
+                        .files
+                        .push((import_data_file.clone(), e.to_string()));
+                }
+            }
+        }
+
+        not_deleted
+    }
+
+    // Used to detect that the data on disk no longer matches the saved undo history (i.e. files
+    // were changed outside the editor, or the editor closed without saving)
+    fn root_data_set_hash(&self) -> u64 {
+        profiling::scope!("root_data_set_hash");
+        let data_set = self.root_edit_context().data_set();
+        let mut combined_hash = 0;
+        for &asset_id in data_set.assets().keys() {
+            let mut hasher = siphasher::sip::SipHasher::default();
+            asset_id.hash(&mut hasher);
+            data_set
+                .hash_object(asset_id, HashObjectMode::FullObjectWithLocationId)
+                .unwrap()
+                .hash(&mut hasher);
+            combined_hash ^= hasher.finish();
+        }
+
+        combined_hash
+    }
+
+    #[profiling::function]
+    pub fn save_undo_history(&mut self) {
+        self.commit_all_pending_undo_contexts();
+        let data_set_hash = self.root_data_set_hash();
+        let json = self
+            .undo_stack
+            .save_to_string(self.root_edit_context_key, data_set_hash);
+
+        let undo_history_path = &self.project_config.undo_history_path;
+        if let Some(parent) = undo_history_path.parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                log::error!(
+                    "Failed to save undo history to {:?}: {:?}",
+                    undo_history_path,
+                    e
+                );
+                return;
+            }
+        }
+
+        if let Err(e) = std::fs::write(undo_history_path, json) {
+            log::error!(
+                "Failed to save undo history to {:?}: {:?}",
+                undo_history_path,
+                e
+            );
+        }
+    }
+
+    // Should be called after data sources are loaded. The history is discarded if it does not match
+    // what was loaded.
+    #[profiling::function]
+    pub fn load_undo_history(&mut self) {
+        let undo_history_path = self.project_config.undo_history_path.clone();
+        let json = match std::fs::read_to_string(&undo_history_path) {
+            Ok(json) => json,
+            // No saved history, this is normal for a new project
+            Err(_) => return,
+        };
+
+        self.commit_all_pending_undo_contexts();
+        let data_set_hash = self.root_data_set_hash();
+        let result = self.undo_stack.load_from_string(
+            &json,
+            self.root_edit_context_key,
+            data_set_hash,
+            &self.schema_set,
+            &self.project_config,
+        );
+
+        match result {
+            Ok(true) => log::info!("Loaded undo history from {:?}", undo_history_path),
+            Ok(false) => log::info!(
+                "Undo history at {:?} does not match the loaded assets and was discarded",
+                undo_history_path
+            ),
+            Err(e) => log::warn!(
+                "Failed to load undo history from {:?}: {:?}",
+                undo_history_path,
+                e
+            ),
+        }
     }
 }
@@ -4,8 +4,11 @@ mod editor_model;
 pub use editor_model::{EditContextKey, EditorModel, EditorModelWithCache};
 
 mod undo;
+pub use undo::AssetHistoryEntry;
 pub use undo::EndContextBehavior;
 pub use undo::UndoStack;
+pub use undo::UndoStep;
+pub use undo::UndoStepId;
 
 mod location_tree;
 pub use location_tree::*;
@@ -1,7 +1,12 @@
-use hydrate_data::DataSetResult;
+use hydrate_data::json_storage::DataSetDiffSetJson;
+use hydrate_data::{
+    DataSetAssetDiff, DataSetError, DataSetResult, PathReferenceNamespaceResolver, SchemaSet,
+};
+use serde::{Deserialize, Serialize};
 use slotmap::DenseSlotMap;
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
+use std::time::SystemTime;
 
 use crate::edit_context::EditContext;
 use crate::{AssetId, DataSet, DataSetDiffSet, EditContextKey, HashSet};
@@ -22,15 +27,98 @@ pub enum EndContextBehavior {
 
 pub struct CompletedUndoContextMessage {
     edit_context_key: EditContextKey,
+    context_name: &'static str,
+    timestamp: SystemTime,
     diff_set: DataSetDiffSet,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct UndoStepId(usize);
+
+pub struct UndoStep {
+    // None means this step was applied on top of the initial state
+    parent: Option<UndoStepId>,
+    // The child that redo will follow. This is the most recently created or visited child, other
+    // children are branches that were abandoned by undoing and then making a different edit
+    active_child: Option<UndoStepId>,
+    edit_context_key: EditContextKey,
+    context_name: String,
+    timestamp: SystemTime,
+    diff_set: DataSetDiffSet,
+}
+
+impl UndoStep {
+    pub fn parent(&self) -> Option<UndoStepId> {
+        self.parent
+    }
+
+    pub fn active_child(&self) -> Option<UndoStepId> {
+        self.active_child
+    }
+
+    pub fn edit_context_key(&self) -> EditContextKey {
+        self.edit_context_key
+    }
+
+    pub fn context_name(&self) -> &str {
+        &self.context_name
+    }
+
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    pub fn diff_set(&self) -> &DataSetDiffSet {
+        &self.diff_set
+    }
+
+    pub fn modifies_asset(
+        &self,
+        asset_id: AssetId,
+    ) -> bool {
+        self.diff_set.modified_assets.contains(&asset_id)
+    }
+}
+
+// A single step in the edit history of one asset
+pub struct AssetHistoryEntry<'a> {
+    pub undo_step_id: UndoStepId,
+    pub context_name: &'a str,
+    pub timestamp: SystemTime,
+    pub diff: DataSetAssetDiff<'a>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UndoStepJson {
+    parent: Option<usize>,
+    active_child: Option<usize>,
+    context_name: String,
+    timestamp: SystemTime,
+    diff_set: DataSetDiffSetJson,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UndoHistoryJson {
+    // Hash of the data set the history was saved with. The history is only usable if the data
+    // loaded from disk still matches it. Encoded as hex to avoid json/u64 weirdness
+    data_set_hash: String,
+    root_active_child: Option<usize>,
+    current_step: Option<usize>,
+    steps: Vec<UndoStepJson>,
+}
+
+// Undo history is stored as a tree. Undoing and then making a new edit starts a new branch rather
+// than discarding the steps that were undone, so any previous state can still be reached with
+// jump_to_step().
 pub struct UndoStack {
-    undo_chain: Vec<CompletedUndoContextMessage>,
-    // Undo/Redo will decrease/increase this value, using apply/revert diffs to move backward and
-    // forward. Appending new diffs will truncate the chain at current position and push a new
-    // step on the chain. Zero means we have undone everything or there are no steps to undo.
-    current_undo_index: usize,
+    // Steps are only ever appended, so a step's parent always has a lower index than the step
+    undo_steps: Vec<UndoStep>,
+    // Same as UndoStep::active_child, but for steps that have no parent
+    root_active_child: Option<UndoStepId>,
+    // The most recent step whose apply diff has been applied. Undo/Redo move this to the parent
+    // or active child, using revert/apply diffs. None means we have undone everything or there
+    // are no steps to undo.
+    current_step: Option<UndoStepId>,
     completed_undo_context_tx: Sender<CompletedUndoContextMessage>,
     completed_undo_context_rx: Receiver<CompletedUndoContextMessage>,
 }
@@ -39,8 +127,9 @@ impl Default for UndoStack {
     fn default() -> Self {
         let (tx, rx) = mpsc::channel();
         UndoStack {
-            undo_chain: Default::default(),
-            current_undo_index: 0,
+            undo_steps: Default::default(),
+            root_active_child: None,
+            current_step: None,
             completed_undo_context_tx: tx,
             completed_undo_context_rx: rx,
         }
@@ -49,14 +138,212 @@ impl Default for UndoStack {
 
 impl UndoStack {
     // This pulls incoming steps off the receive queue. These diffs have already been applied, so
-    // we mainly just use this to drop undone steps that can no longer be used, and to place them
-    // on the end of the chain
-    fn drain_rx(&mut self) {
+    // we just need to add them to the tree as a child of the current step
+    pub(crate) fn drain_rx(&mut self) {
         while let Ok(diff) = self.completed_undo_context_rx.try_recv() {
-            self.undo_chain.truncate(self.current_undo_index);
-            self.undo_chain.push(diff);
-            self.current_undo_index += 1;
+            let step_id = UndoStepId(self.undo_steps.len());
+            self.undo_steps.push(UndoStep {
+                parent: self.current_step,
+                active_child: None,
+                edit_context_key: diff.edit_context_key,
+                context_name: diff.context_name.to_string(),
+                timestamp: diff.timestamp,
+                diff_set: diff.diff_set,
+            });
+            self.set_active_child(self.current_step, step_id);
+            self.current_step = Some(step_id);
+        }
+    }
+
+    fn set_active_child(
+        &mut self,
+        parent: Option<UndoStepId>,
+        child: UndoStepId,
+    ) {
+        if let Some(parent) = parent {
+            self.undo_steps[parent.0].active_child = Some(child);
+        } else {
+            self.root_active_child = Some(child);
+        }
+    }
+
+    fn clear(&mut self) {
+        // Anything still in flight was applied to a state we are discarding
+        while let Ok(_) = self.completed_undo_context_rx.try_recv() {}
+        self.undo_steps.clear();
+        self.root_active_child = None;
+        self.current_step = None;
+    }
+
+    pub fn steps(&self) -> &[UndoStep] {
+        &self.undo_steps
+    }
+
+    pub fn step(
+        &self,
+        step_id: UndoStepId,
+    ) -> Option<&UndoStep> {
+        self.undo_steps.get(step_id.0)
+    }
+
+    pub fn current_step(&self) -> Option<UndoStepId> {
+        self.current_step
+    }
+
+    pub fn children(
+        &self,
+        parent: Option<UndoStepId>,
+    ) -> Vec<UndoStepId> {
+        self.undo_steps
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| step.parent == parent)
+            .map(|(index, _)| UndoStepId(index))
+            .collect()
+    }
+
+    // Returns the given step and all its ancestors, starting with the given step
+    fn step_chain(
+        &self,
+        mut step_id: Option<UndoStepId>,
+    ) -> Vec<UndoStepId> {
+        let mut chain = Vec::default();
+        while let Some(id) = step_id {
+            chain.push(id);
+            step_id = self.undo_steps[id.0].parent;
+        }
+
+        chain
+    }
+
+    // Steps that led to the current state and modified the given asset, oldest first
+    pub fn asset_history(
+        &self,
+        asset_id: AssetId,
+    ) -> Vec<UndoStepId> {
+        let mut history: Vec<_> = self
+            .step_chain(self.current_step)
+            .into_iter()
+            .filter(|x| self.undo_steps[x.0].modifies_asset(asset_id))
+            .collect();
+        history.reverse();
+        history
+    }
+
+    // All steps in the tree that modified the given asset, including abandoned branches and steps
+    // that have been undone, in the order they were created
+    pub fn asset_history_all_branches(
+        &self,
+        asset_id: AssetId,
+    ) -> Vec<UndoStepId> {
+        self.undo_steps
+            .iter()
+            .enumerate()
+            .filter(|(_, step)| step.modifies_asset(asset_id))
+            .map(|(index, _)| UndoStepId(index))
+            .collect()
+    }
+
+    // The changes made to the given asset by each step that led to the current state, oldest
+    // first. Only steps made in the given edit context are included.
+    pub fn asset_journal(
+        &self,
+        asset_id: AssetId,
+        edit_context_key: EditContextKey,
+    ) -> Vec<AssetHistoryEntry<'_>> {
+        let mut journal = Vec::default();
+        for step_id in self.asset_history(asset_id) {
+            let step = &self.undo_steps[step_id.0];
+            if step.edit_context_key != edit_context_key {
+                continue;
+            }
+
+            if let Some(diff) = step.diff_set.apply_diff.asset_diff(asset_id) {
+                journal.push(AssetHistoryEntry {
+                    undo_step_id: step_id,
+                    context_name: &step.context_name,
+                    timestamp: step.timestamp,
+                    diff,
+                });
+            }
         }
+
+        journal
+    }
+
+    // Produces a data set containing only the given asset as it was immediately after the given
+    // step was applied. None produces the asset as it was before any step on the current branch
+    // was applied. The returned data set will be empty if the asset did not exist at that point.
+    // The step must be the current step or one of its ancestors.
+    pub fn preview_asset_at_step(
+        &self,
+        data_set: &DataSet,
+        schema_set: &SchemaSet,
+        edit_context_key: EditContextKey,
+        asset_id: AssetId,
+        target_step: Option<UndoStepId>,
+    ) -> DataSetResult<DataSet> {
+        let chain = self.step_chain(self.current_step);
+        if let Some(target_step) = target_step {
+            if !chain.contains(&target_step) {
+                return Err(DataSetError::UndoStepNotFound)?;
+            }
+        }
+
+        let mut preview = DataSet::default();
+        if data_set.assets().contains_key(&asset_id) {
+            preview.copy_from(data_set, asset_id)?;
+        }
+
+        // Walk backwards from the current state, reverting only the changes to this asset
+        for step_id in chain {
+            if Some(step_id) == target_step {
+                break;
+            }
+
+            let step = &self.undo_steps[step_id.0];
+            if step.edit_context_key == edit_context_key && step.modifies_asset(asset_id) {
+                step.diff_set
+                    .revert_diff
+                    .apply_to_asset(&mut preview, schema_set, asset_id)?;
+            }
+        }
+
+        Ok(preview)
+    }
+
+    fn undo_current_step(
+        &mut self,
+        edit_contexts: &mut DenseSlotMap<EditContextKey, EditContext>,
+    ) -> DataSetResult<()> {
+        if let Some(current_step_id) = self.current_step {
+            let current_step = &self.undo_steps[current_step_id.0];
+            let edit_context = edit_contexts
+                .get_mut(current_step.edit_context_key)
+                .unwrap();
+
+            let result = edit_context.apply_diff(&current_step.diff_set.revert_diff);
+            self.current_step = current_step.parent;
+            return result;
+        }
+
+        Ok(())
+    }
+
+    fn redo_step(
+        &mut self,
+        edit_contexts: &mut DenseSlotMap<EditContextKey, EditContext>,
+        step_id: UndoStepId,
+    ) -> DataSetResult<()> {
+        let step = &self.undo_steps[step_id.0];
+        debug_assert_eq!(step.parent, self.current_step);
+        let edit_context = edit_contexts.get_mut(step.edit_context_key).unwrap();
+        // We don't want anything being written to the undo context at this point, since we're using it
+        edit_context.cancel_pending_undo_context()?;
+        let result = edit_context.apply_diff(&step.diff_set.apply_diff);
+        self.set_active_child(self.current_step, step_id);
+        self.current_step = Some(step_id);
+        result
     }
 
     pub fn undo(
@@ -72,46 +359,175 @@ impl UndoStack {
         self.drain_rx();
 
         // Now undo one step, if there is a step to undo
-        // if we undo the first step in the chain (i.e. our undo index is currently 1), we want to
-        // use the revert diff in the 0th index of the chain
-        if self.current_undo_index > 0 {
-            if let Some(current_step) = self.undo_chain.get(self.current_undo_index - 1) {
-                let edit_context = edit_contexts
-                    .get_mut(current_step.edit_context_key)
-                    .unwrap();
+        self.undo_current_step(edit_contexts)
+    }
 
-                let result = edit_context.apply_diff(&current_step.diff_set.revert_diff);
-                self.current_undo_index -= 1;
-                return result;
-            }
+    pub fn redo(
+        &mut self,
+        edit_contexts: &mut DenseSlotMap<EditContextKey, EditContext>,
+    ) -> DataSetResult<()> {
+        // If we have any incoming steps, consume them now
+        self.drain_rx();
+
+        // Follow the most recently used branch. If the current step has no children, there is
+        // nothing to redo
+        let next_step = if let Some(current_step) = self.current_step {
+            self.undo_steps[current_step.0].active_child
+        } else {
+            self.root_active_child
+        };
+
+        if let Some(next_step) = next_step {
+            return self.redo_step(edit_contexts, next_step);
         }
 
         Ok(())
     }
 
-    pub fn redo(
+    // Undoes back to the closest common ancestor of the current step and the target step, then
+    // redoes along the target's branch. Passing None will undo everything.
+    pub fn jump_to_step(
         &mut self,
         edit_contexts: &mut DenseSlotMap<EditContextKey, EditContext>,
+        target_step: Option<UndoStepId>,
     ) -> DataSetResult<()> {
-        // If we have any incoming steps, consume them now
+        for (_, edit_context) in edit_contexts.iter_mut() {
+            edit_context.commit_pending_undo_context();
+        }
+
         self.drain_rx();
 
-        // if we redo the first step in the chain (i.e. our undo index is currently 0), we want to
-        // use the apply diff in the 0th index of the chain. If our current step is == length of
-        // chain, we have no more steps available to redo
-        if let Some(current_step) = self.undo_chain.get(self.current_undo_index) {
-            let edit_context = edit_contexts
-                .get_mut(current_step.edit_context_key)
-                .unwrap();
-            // We don't want anything being written to the undo context at this point, since we're using it
-            edit_context.cancel_pending_undo_context()?;
-            let result = edit_context.apply_diff(&current_step.diff_set.apply_diff);
-            self.current_undo_index += 1;
-            return result;
+        if let Some(target_step) = target_step {
+            if target_step.0 >= self.undo_steps.len() {
+                return Err(DataSetError::UndoStepNotFound)?;
+            }
+        }
+
+        let target_chain = self.step_chain(target_step);
+        let common_ancestor = self
+            .step_chain(self.current_step)
+            .into_iter()
+            .find(|x| target_chain.contains(x));
+
+        while self.current_step != common_ancestor {
+            self.undo_current_step(edit_contexts)?;
+        }
+
+        let steps_to_redo: Vec<_> = target_chain
+            .into_iter()
+            .take_while(|x| Some(*x) != common_ancestor)
+            .collect();
+        for step_id in steps_to_redo.into_iter().rev() {
+            self.redo_step(edit_contexts, step_id)?;
         }
 
         Ok(())
     }
+
+    // Only steps made in the given (root) edit context are saved, other edit contexts do not
+    // outlive the editor session. Steps made in another context, and anything branching from
+    // them, are dropped.
+    pub(crate) fn save_to_string(
+        &mut self,
+        root_edit_context_key: EditContextKey,
+        data_set_hash: u64,
+    ) -> String {
+        self.drain_rx();
+
+        let mut new_indices: Vec<Option<usize>> = Vec::with_capacity(self.undo_steps.len());
+        let mut steps = Vec::default();
+        for step in &self.undo_steps {
+            let parent_is_saved = match step.parent {
+                Some(parent) => new_indices[parent.0].is_some(),
+                None => true,
+            };
+
+            if parent_is_saved && step.edit_context_key == root_edit_context_key {
+                new_indices.push(Some(steps.len()));
+                steps.push(UndoStepJson {
+                    parent: step.parent.and_then(|x| new_indices[x.0]),
+                    active_child: None,
+                    context_name: step.context_name.clone(),
+                    timestamp: step.timestamp,
+                    diff_set: DataSetDiffSetJson::new(&step.diff_set),
+                });
+            } else {
+                new_indices.push(None);
+            }
+        }
+
+        // Children always come after their parent, so fix up active children now that all the
+        // indices are known
+        for (old_index, step) in self.undo_steps.iter().enumerate() {
+            if let Some(new_index) = new_indices[old_index] {
+                steps[new_index].active_child = step.active_child.and_then(|x| new_indices[x.0]);
+            }
+        }
+
+        let current_step = self
+            .step_chain(self.current_step)
+            .into_iter()
+            .find_map(|x| new_indices[x.0]);
+
+        let history = UndoHistoryJson {
+            data_set_hash: format!("{:0>16x}", data_set_hash),
+            root_active_child: self.root_active_child.and_then(|x| new_indices[x.0]),
+            current_step,
+            steps,
+        };
+
+        profiling::scope!("serde_json::to_string_pretty");
+        serde_json::to_string_pretty(&history).unwrap()
+    }
+
+    // Replaces the current history with the saved history. Returns false (leaving the history
+    // empty) if the saved history was made against different data than what is loaded now.
+    pub(crate) fn load_from_string(
+        &mut self,
+        json: &str,
+        root_edit_context_key: EditContextKey,
+        data_set_hash: u64,
+        schema_set: &SchemaSet,
+        namespace_resolver: &dyn PathReferenceNamespaceResolver,
+    ) -> DataSetResult<bool> {
+        self.clear();
+
+        let history: UndoHistoryJson = {
+            profiling::scope!("serde_json::from_str");
+            serde_json::from_str(json).map_err(|_| DataSetError::StorageFormatError)?
+        };
+
+        let saved_data_set_hash = u64::from_str_radix(&history.data_set_hash, 16)
+            .map_err(|_| DataSetError::StorageFormatError)?;
+        if saved_data_set_hash != data_set_hash {
+            return Ok(false);
+        }
+
+        let step_count = history.steps.len();
+        let to_step_id = |index: Option<usize>| -> DataSetResult<Option<UndoStepId>> {
+            match index {
+                Some(index) if index >= step_count => Err(DataSetError::StorageFormatError)?,
+                _ => Ok(index.map(UndoStepId)),
+            }
+        };
+
+        let mut undo_steps = Vec::with_capacity(step_count);
+        for step in history.steps {
+            undo_steps.push(UndoStep {
+                parent: to_step_id(step.parent)?,
+                active_child: to_step_id(step.active_child)?,
+                edit_context_key: root_edit_context_key,
+                context_name: step.context_name,
+                timestamp: step.timestamp,
+                diff_set: step.diff_set.to_diff_set(schema_set, namespace_resolver)?,
+            });
+        }
+
+        self.undo_steps = undo_steps;
+        self.root_active_child = to_step_id(history.root_active_child)?;
+        self.current_step = to_step_id(history.current_step)?;
+        Ok(true)
+    }
 }
 
 // Transaction that holds exclusive access for the data and will directly commit changes. It can
@@ -261,6 +677,8 @@ impl UndoContext {
                 self.completed_undo_context_tx
                     .send(CompletedUndoContextMessage {
                         edit_context_key: self.edit_context_key,
+                        context_name: self.context_name.unwrap_or_default(),
+                        timestamp: SystemTime::now(),
                         diff_set,
                     })
                     .unwrap();
@@ -0,0 +1,359 @@
+use crate::{AssetPathCache, EditorModel, EditorModelWithCache};
+use hydrate_pipeline::{
+    run_job_worker, AssetEngine, AssetEngineState, AssetPluginRegistryBuilders,
+    AssetValidationReport, BuildLogData, BuildRoots, HydrateProjectConfiguration, ImportJobToQueue,
+    ImportLogData, LogEventLevel, PipelineEvent, PipelineResult,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+// The outcome of a headless import and build. Log events are kept so that the caller can report
+// them however it likes.
+#[derive(Default)]
+pub struct HeadlessPipelineResult {
+    pub import_logs: Vec<Arc<ImportLogData>>,
+    pub build_logs: Vec<Arc<BuildLogData>>,
+}
+
+impl HeadlessPipelineResult {
+    pub fn warning_count(&self) -> usize {
+        self.count_log_events(|level| matches!(level, LogEventLevel::Warning))
+    }
+
+    // Counts Error and FatalError events
+    pub fn error_count(&self) -> usize {
+        self.count_log_events(|level| {
+            matches!(level, LogEventLevel::Error | LogEventLevel::FatalError)
+        })
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    // Writes every warning and error to the log, followed by totals
+    pub fn log_summary(&self) {
+        for import_log in &self.import_logs {
+            for log_event in import_log.log_events() {
+                let level = Self::log_level(log_event.level);
+                log::log!(
+                    level,
+                    "Import {:?} {:?}: {}",
+                    log_event.path,
+                    log_event.asset_id,
+                    log_event.message
+                );
+            }
+        }
+
+        for build_log in &self.build_logs {
+            for log_event in build_log.log_events() {
+                let level = Self::log_level(log_event.level);
+                log::log!(
+                    level,
+                    "Build {:?} {:?}: {}",
+                    log_event.asset_id,
+                    log_event.job_id,
+                    log_event.message
+                );
+            }
+        }
+
+        for build_log in &self.build_logs {
+            let failed_assets = build_log.failed_assets();
+            if !failed_assets.is_empty() {
+                log::error!("Assets that failed to build:");
+                for asset_id in failed_assets {
+                    log::error!("  {:?}", asset_id);
+                }
+            }
+
+            let determinism_mismatches = build_log.determinism_mismatches();
+            if !determinism_mismatches.is_empty() {
+                log::error!("Job processors that are not deterministic:");
+                let mut job_type_names: Vec<_> = determinism_mismatches
+                    .iter()
+                    .map(|x| x.job_type_name)
+                    .collect();
+                job_type_names.sort();
+                job_type_names.dedup();
+                for job_type_name in job_type_names {
+                    log::error!("  {}", job_type_name);
+                    for determinism_mismatch in determinism_mismatches
+                        .iter()
+                        .filter(|x| x.job_type_name == job_type_name)
+                    {
+                        log::error!(
+                            "    {}: {}",
+                            determinism_mismatch.debug_name,
+                            determinism_mismatch.message
+                        );
+                    }
+                }
+            }
+        }
+
+        log::info!(
+            "{} errors, {} warnings",
+            self.error_count(),
+            self.warning_count()
+        );
+    }
+
+    fn log_level(level: LogEventLevel) -> log::Level {
+        match level {
+            LogEventLevel::Warning => log::Level::Warn,
+            LogEventLevel::Error | LogEventLevel::FatalError => log::Level::Error,
+        }
+    }
+
+    fn count_log_events<F: Fn(LogEventLevel) -> bool>(
+        &self,
+        f: F,
+    ) -> usize {
+        let import_count: usize = self
+            .import_logs
+            .iter()
+            .map(|x| x.log_events().iter().filter(|x| f(x.level)).count())
+            .sum();
+        let build_count: usize = self
+            .build_logs
+            .iter()
+            .map(|x| x.log_events().iter().filter(|x| f(x.level)).count())
+            .sum();
+        import_count + build_count
+    }
+}
+
+// Loads a project and runs import and build without the editor UI. This is intended for build
+// machines that don't have a display.
+pub struct HeadlessPipeline {
+    editor_model: EditorModel,
+    asset_engine: AssetEngine,
+}
+
+impl HeadlessPipeline {
+    pub fn new(
+        project_configuration: &HydrateProjectConfiguration,
+        asset_plugin_registry: AssetPluginRegistryBuilders,
+    ) -> PipelineResult<Self> {
+        profiling::scope!("HeadlessPipeline::new");
+        let schema_set = EditorModel::load_schema_set(project_configuration);
+        let registries = asset_plugin_registry.finish(&schema_set);
+
+        let mut import_job_to_queue = ImportJobToQueue::default();
+        let mut editor_model = EditorModel::new(project_configuration.clone(), schema_set.clone());
+        editor_model.add_project_data_sources(
+            project_configuration,
+            &registries.importer_registry,
+            &mut import_job_to_queue,
+        );
+
+        let asset_path_cache = AssetPathCache::build(&editor_model)?;
+        let mut asset_engine = AssetEngine::new(
+            &schema_set,
+            registries,
+            &mut EditorModelWithCache {
+                editor_model: &mut editor_model,
+                asset_path_cache: &asset_path_cache,
+            },
+            project_configuration,
+        );
+
+        if !import_job_to_queue.is_empty() {
+            asset_engine.queue_import_operation(import_job_to_queue);
+        }
+
+        Ok(HeadlessPipeline {
+            editor_model,
+            asset_engine,
+        })
+    }
+
+    // Runs jobs for a build in another process until that build disconnects. The address is the
+    // one returned by AssetEngine::start_job_workers and the token is the one in its
+    // JobWorkerConfig. Only the schemas and job processors are loaded, the build sends its data
+    // set to the worker.
+    pub fn run_job_worker(
+        project_configuration: &HydrateProjectConfiguration,
+        asset_plugin_registry: AssetPluginRegistryBuilders,
+        address: &str,
+        token: &str,
+    ) -> PipelineResult<()> {
+        let schema_set = EditorModel::load_schema_set(project_configuration);
+        let registries = asset_plugin_registry.finish(&schema_set);
+        run_job_worker(
+            address,
+            token,
+            &schema_set,
+            &registries.job_processor_registry,
+            project_configuration,
+        )
+    }
+
+    pub fn editor_model(&self) -> &EditorModel {
+        &self.editor_model
+    }
+
+    pub fn editor_model_mut(&mut self) -> &mut EditorModel {
+        &mut self.editor_model
+    }
+
+    pub fn asset_engine(&self) -> &AssetEngine {
+        &self.asset_engine
+    }
+
+    pub fn asset_engine_mut(&mut self) -> &mut AssetEngine {
+        &mut self.asset_engine
+    }
+
+    // Runs any queued imports to completion. Imports are queued when data sources are loaded.
+    pub fn import(&mut self) -> PipelineResult<HeadlessPipelineResult> {
+        self.run_until_idle(false)
+    }
+
+    // Runs any queued imports and then builds everything
+    pub fn import_and_build(&mut self) -> PipelineResult<HeadlessPipelineResult> {
+        self.import_and_build_roots(BuildRoots::All)
+    }
+
+    // Validates every asset as it is on disk and logs the issues. Nothing is imported or built.
+    pub fn validate(&self) -> PipelineResult<AssetValidationReport> {
+        let asset_path_cache = AssetPathCache::build(&self.editor_model)?;
+        let data_set = self.editor_model.root_edit_context().data_set();
+        let report = self.asset_engine.validate_assets(
+            data_set,
+            self.editor_model.schema_set(),
+            data_set.assets().keys().copied(),
+        );
+
+        for issue in &report.issues {
+            let level = HeadlessPipelineResult::log_level(issue.level);
+            log::log!(
+                level,
+                "{}: {}",
+                self.editor_model
+                    .asset_display_name_long(issue.asset_id, &asset_path_cache),
+                issue.to_text()
+            );
+        }
+
+        log::info!(
+            "Validated {} assets: {} errors, {} warnings",
+            report.validated_asset_count,
+            report.error_count(),
+            report.warning_count()
+        );
+        Ok(report)
+    }
+
+    // Runs any queued imports and then builds the given assets and anything they depend on
+    pub fn import_and_build_roots(
+        &mut self,
+        build_roots: BuildRoots,
+    ) -> PipelineResult<HeadlessPipelineResult> {
+        self.asset_engine.queue_build_roots(build_roots);
+        self.run_until_idle(true)
+    }
+
+    // Runs a single update of the asset engine. This is for callers that drive the pipeline
+    // themselves, for example to cancel a build partway through. The path cache must be rebuilt
+    // after an import completes.
+    pub fn update(
+        &mut self,
+        asset_path_cache: &AssetPathCache,
+    ) -> PipelineResult<AssetEngineState> {
+        self.asset_engine.update(&mut EditorModelWithCache {
+            editor_model: &mut self.editor_model,
+            asset_path_cache,
+        })
+    }
+
+    fn run_until_idle(
+        &mut self,
+        wait_for_build: bool,
+    ) -> PipelineResult<HeadlessPipelineResult> {
+        let mut result = HeadlessPipelineResult::default();
+        let mut asset_path_cache = AssetPathCache::build(&self.editor_model)?;
+        let mut build_completed = false;
+        let pipeline_event_rx = self.asset_engine.subscribe_pipeline_events();
+
+        loop {
+            for pipeline_event in pipeline_event_rx.try_iter() {
+                Self::log_pipeline_event(pipeline_event);
+            }
+
+            let asset_engine_state = self.update(&asset_path_cache)?;
+
+            match asset_engine_state {
+                AssetEngineState::Idle => {
+                    if build_completed || !wait_for_build {
+                        return Ok(result);
+                    }
+                }
+                AssetEngineState::Importing(import_state) => {
+                    log::debug!(
+                        "Importing {}/{} assets",
+                        import_state.completed_job_count,
+                        import_state.total_job_count
+                    );
+                }
+                AssetEngineState::Building(build_state) => {
+                    log::debug!(
+                        "Building {}/{} assets",
+                        build_state.completed_job_count,
+                        build_state.total_job_count
+                    );
+                }
+                AssetEngineState::ImportCompleted(import_log_data) => {
+                    log::info!("Import completed");
+                    result.import_logs.push(import_log_data);
+
+                    // Imports may have added assets, and the build uses paths in the manifest
+                    asset_path_cache = AssetPathCache::build(&self.editor_model)?;
+                }
+                AssetEngineState::BuildCompleted(build_log_data) => {
+                    log::info!("Build completed");
+                    result.build_logs.push(build_log_data);
+                    build_completed = true;
+                }
+            }
+
+            // Work happens on other threads, avoid spinning while waiting for it
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn log_pipeline_event(pipeline_event: PipelineEvent) {
+        match pipeline_event {
+            PipelineEvent::ImportFinished {
+                path,
+                duration,
+                succeeded,
+            } => {
+                log::debug!(
+                    "Imported {:?} in {:?}{}",
+                    path,
+                    duration,
+                    if succeeded { "" } else { " (failed)" }
+                );
+            }
+            PipelineEvent::JobFinished {
+                debug_name,
+                duration,
+                used_cached_result,
+                succeeded,
+                ..
+            } => {
+                log::debug!(
+                    "Job {} finished in {:?}{}{}",
+                    debug_name,
+                    duration,
+                    if used_cached_result { " (cached)" } else { "" },
+                    if succeeded { "" } else { " (failed)" }
+                );
+            }
+            _ => {}
+        }
+    }
+}
@@ -14,7 +14,13 @@ pub use data_source::*;
 mod asset_source_id;
 pub use asset_source_id::AssetSourceId;
 
+mod headless;
+pub use headless::*;
+
 pub use hydrate_pipeline as pipeline;
 
 #[cfg(test)]
 mod tests;
+
+#[cfg(test)]
+mod project_tests;
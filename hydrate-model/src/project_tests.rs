@@ -0,0 +1,1706 @@
+// Tests that load a project from disk and run it through the asset engine
+use crate::{
+    AssetPathCache, AssetSourceId, EditorModel, EndContextBehavior, HeadlessPipeline,
+    HeadlessPipelineResult, PathNode, Value,
+};
+use hydrate_base::uuid_path::{uuid_and_hash_to_path, uuid_to_path};
+use hydrate_base::{ArtifactId, AssetId, Handle};
+use hydrate_data::json_storage::{MetaFile, MetaFileJson};
+use hydrate_data::{
+    AssetLocation, AssetName, CanonicalPathReference, ImportableName, SingleObject,
+};
+use hydrate_pipeline::{
+    build_chrome_trace_json, inspect_built_artifact, ArtifactDecoderRegistry, AssetEngineState,
+    AssetPlugin, AssetPluginRegistryBuilders, AssetPluginSetupContext, BuildLogData, BuildProfile,
+    BuildRoots, BuildTimingReport, BuildVerification, BuildVerificationMode, Builder,
+    BuilderContext, EnumerateDependenciesContext, HydrateProjectConfiguration, ImportContext,
+    Importer, JobEnumeratedDependencies, JobId, JobInput, JobOutput, JobProcessor, JobTiming,
+    JobWorkerConfig, LogEventLevel, ManifestContents, NamePathPair, PipelineResult, RunContext,
+    ScanContext, ValidationContext,
+};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use type_uuid::TypeUuid;
+use uuid::Uuid;
+
+const TEST_SCHEMA: &str = r#"[
+  {
+    "type": "record",
+    "name": "TestAsset",
+    "uuid": "1b6bb2a7-8d5c-4f1c-9a0e-5d4b7f2a9c01",
+    "fields": [
+      {
+        "name": "value",
+        "type": "f32",
+        "uuid": "5a0f6a43-41d1-4b4a-a4f5-0c2f1b6e7d02"
+      },
+      {
+        "name": "reads",
+        "type": { "name": "asset_ref", "inner_type": "TestAsset" },
+        "uuid": "2f4c8e1a-7b3d-4a6f-9c2e-1d5b8a7f3e04"
+      },
+      {
+        "name": "fail",
+        "type": "bool",
+        "uuid": "6e1d3b5a-9f2c-4e8b-a7d4-3c6f0b2e9a05"
+      },
+      {
+        "name": "downstream",
+        "type": "bool",
+        "uuid": "9b7a2c4e-1d6f-4b3a-8e5c-7f0a2d4b6c06"
+      },
+      {
+        "name": "enqueues",
+        "type": "bool",
+        "uuid": "3d8f6a1c-5e2b-4c9d-b0a7-4e1f3c5a7b07"
+      },
+      {
+        "name": "nondeterministic",
+        "type": "bool",
+        "uuid": "7c2e9f4b-3a1d-4f6e-8b5c-0d7a9e1f3c08"
+      },
+      {
+        "name": "validation_error",
+        "type": "bool",
+        "uuid": "4a6b1d3f-8c5e-4a2b-9d7f-6e3c0a8b5d09"
+      },
+      {
+        "name": "validation_warning",
+        "type": "bool",
+        "uuid": "8e3f5a7c-2b4d-4e1f-a6c8-9b1d3f5e7a0a"
+      }
+    ]
+  }
+]"#;
+
+const TEST_ASSET_TYPE: &str = "TestAsset";
+
+// Source files with this extension produce a TestAsset. Each line of the file is either
+// "importable <name>" to produce an additional named asset or "ref <path>" to reference another
+// source file.
+#[derive(TypeUuid, Default)]
+#[uuid = "0c3d0a4e-6f5b-4b8e-9d2c-7a1e3f4b5c03"]
+struct TestImporter;
+
+impl Importer for TestImporter {
+    fn supported_file_extensions(&self) -> &[&'static str] {
+        &["test_source"]
+    }
+
+    fn scan_file(
+        &self,
+        context: ScanContext,
+    ) -> PipelineResult<()> {
+        let asset_type = context
+            .schema_set
+            .find_named_type(TEST_ASSET_TYPE)?
+            .as_record()?
+            .clone();
+        let contents = std::fs::read_to_string(context.path)?;
+        let importable =
+            context.add_importable_with_record(ImportableName::default(), asset_type.clone())?;
+        for line in contents.lines() {
+            if let Some(name) = line.strip_prefix("importable ") {
+                context.add_importable_with_record(
+                    ImportableName::new(name.to_string()),
+                    asset_type.clone(),
+                )?;
+            } else if let Some(path) = line.strip_prefix("ref ") {
+                importable.add_path_reference(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn import_file(
+        &self,
+        context: ImportContext,
+    ) -> PipelineResult<()> {
+        let asset_type = context
+            .schema_set
+            .find_named_type(TEST_ASSET_TYPE)?
+            .as_record()?
+            .clone();
+        let contents = std::fs::read_to_string(context.path)?;
+        let mut importable_names = vec![ImportableName::default()];
+        for line in contents.lines() {
+            if let Some(name) = line.strip_prefix("importable ") {
+                importable_names.push(ImportableName::new(name.to_string()));
+            }
+        }
+
+        for importable_name in importable_names {
+            if context.should_import(&importable_name) {
+                context.add_importable(importable_name, SingleObject::new(&asset_type), None);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, TypeUuid)]
+#[uuid = "5d2b7e9a-4c1f-4a3e-8b6d-2f9c1e7a4b0b"]
+struct TestArtifact {
+    value: f32,
+    read_value: Option<f32>,
+    noise: u128,
+}
+
+#[derive(Serialize, Deserialize, TypeUuid)]
+#[uuid = "1e8c4a6f-7d3b-4f2e-9a5c-6b0d8f2e4a0c"]
+struct TestDownstreamArtifact {
+    test_artifact: Handle<TestArtifact>,
+}
+
+#[derive(Hash, Serialize, Deserialize)]
+struct TestJobInput {
+    asset_id: AssetId,
+}
+impl JobInput for TestJobInput {}
+
+#[derive(Serialize, Deserialize)]
+struct TestJobOutput {}
+impl JobOutput for TestJobOutput {}
+
+// Produces a TestArtifact from a TestAsset's properties. Fields of the asset make it fail, read
+// another asset, enqueue a TestDownstreamJob, or produce different output every time it runs.
+#[derive(TypeUuid, Default)]
+#[uuid = "a4c6e8f0-2b4d-4f6a-8c0e-3d5f7b9a1c0d"]
+struct TestJob;
+
+impl JobProcessor for TestJob {
+    type InputT = TestJobInput;
+    type OutputT = TestJobOutput;
+
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn run<'a>(
+        &'a self,
+        context: &'a RunContext<'a, Self::InputT>,
+    ) -> PipelineResult<Self::OutputT> {
+        let asset_id = context.input.asset_id;
+        let asset = context.asset_data_container(asset_id)?;
+        if asset.resolve_property("fail")?.as_boolean()? {
+            Err(format!("Asset {:?} is set to fail", asset_id))?;
+        }
+
+        let reads = asset.resolve_property("reads")?.as_asset_ref()?;
+        let read_value = if reads.is_null() {
+            None
+        } else {
+            Some(
+                context
+                    .asset_data_container(reads)?
+                    .resolve_property("value")?
+                    .as_f32()?,
+            )
+        };
+
+        let noise = if asset.resolve_property("nondeterministic")?.as_boolean()? {
+            Uuid::new_v4().as_u128()
+        } else {
+            0
+        };
+
+        context.produce_default_artifact(
+            asset_id,
+            TestArtifact {
+                value: asset.resolve_property("value")?.as_f32()?,
+                read_value,
+                noise,
+            },
+        )?;
+
+        if asset.resolve_property("enqueues")?.as_boolean()? {
+            context.enqueue_job::<TestDownstreamJob>(TestDownstreamJobInput {
+                asset_id,
+                upstream_job: None,
+            })?;
+        }
+
+        Ok(TestJobOutput {})
+    }
+}
+
+#[derive(Hash, Serialize, Deserialize)]
+struct TestDownstreamJobInput {
+    asset_id: AssetId,
+    upstream_job: Option<JobId>,
+}
+impl JobInput for TestDownstreamJobInput {}
+
+// Produces an artifact that references the TestArtifact of the asset's reads field, or of the asset
+// itself if it is not set
+#[derive(TypeUuid, Default)]
+#[uuid = "b5d7f9a1-3c5e-4a7b-9d1f-4e6a8c0b2d0e"]
+struct TestDownstreamJob;
+
+impl JobProcessor for TestDownstreamJob {
+    type InputT = TestDownstreamJobInput;
+    type OutputT = TestJobOutput;
+
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn enumerate_dependencies(
+        &self,
+        context: EnumerateDependenciesContext<Self::InputT>,
+    ) -> PipelineResult<JobEnumeratedDependencies> {
+        Ok(JobEnumeratedDependencies {
+            upstream_jobs: context.input.upstream_job.into_iter().collect(),
+        })
+    }
+
+    fn run<'a>(
+        &'a self,
+        context: &'a RunContext<'a, Self::InputT>,
+    ) -> PipelineResult<Self::OutputT> {
+        let asset_id = context.input.asset_id;
+        let reads = context
+            .asset_data_container(asset_id)?
+            .resolve_property("reads")?
+            .as_asset_ref()?;
+        let referenced_asset_id = if reads.is_null() { asset_id } else { reads };
+        context.produce_artifact_with_handles(asset_id, Some("downstream"), |handle_factory| {
+            Ok(TestDownstreamArtifact {
+                test_artifact: handle_factory.make_handle_to_default_artifact(referenced_asset_id),
+            })
+        })?;
+
+        Ok(TestJobOutput {})
+    }
+}
+
+// Runs a TestJob for every TestAsset, followed by a TestDownstreamJob if the asset's downstream
+// field is set
+#[derive(TypeUuid, Default)]
+#[uuid = "c6e8a0b2-4d6f-4b8c-a0e2-5f7b9d1c3e0f"]
+struct TestAssetBuilder;
+
+impl Builder for TestAssetBuilder {
+    fn asset_type(&self) -> &'static str {
+        TEST_ASSET_TYPE
+    }
+
+    fn start_jobs(
+        &self,
+        context: BuilderContext,
+    ) -> PipelineResult<()> {
+        let test_job_id = context.enqueue_job::<TestJob>(
+            context.data_set,
+            context.schema_set,
+            context.job_api,
+            TestJobInput {
+                asset_id: context.asset_id,
+            },
+        )?;
+
+        let downstream = context
+            .data_set
+            .resolve_property(context.schema_set, context.asset_id, "downstream")?
+            .as_boolean()?;
+        if downstream {
+            context.enqueue_job::<TestDownstreamJob>(
+                context.data_set,
+                context.schema_set,
+                context.job_api,
+                TestDownstreamJobInput {
+                    asset_id: context.asset_id,
+                    upstream_job: Some(test_job_id),
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn validate(
+        &self,
+        context: ValidationContext,
+    ) -> PipelineResult<()> {
+        let asset = context.asset_data_container();
+        if asset.resolve_property("validation_error")?.as_boolean()? {
+            context.error_property("validation_error", "validation_error is set");
+        }
+
+        if asset.resolve_property("validation_warning")?.as_boolean()? {
+            context.warn_property("validation_warning", "validation_warning is set");
+        }
+
+        Ok(())
+    }
+}
+
+struct TestAssetPlugin;
+
+impl AssetPlugin for TestAssetPlugin {
+    fn setup(context: AssetPluginSetupContext) {
+        context.importer_registry.register_handler::<TestImporter>();
+        context
+            .builder_registry
+            .register_handler::<TestAssetBuilder>();
+        context
+            .job_processor_registry
+            .register_job_processor::<TestJob>();
+        context
+            .job_processor_registry
+            .register_job_processor::<TestDownstreamJob>();
+    }
+}
+
+// A project in a new temporary directory with one path-based and one ID-based data source
+struct TestProject {
+    root_path: PathBuf,
+    project_configuration: HydrateProjectConfiguration,
+}
+
+impl TestProject {
+    fn new() -> Self {
+        let root_path = std::env::temp_dir().join(format!("hydrate-test-{}", Uuid::new_v4()));
+        let schema_path = root_path.join("schema");
+        let path_based_path = root_path.join("path_based");
+        let id_based_path = root_path.join("id_based");
+        std::fs::create_dir_all(&schema_path).unwrap();
+        std::fs::create_dir_all(&path_based_path).unwrap();
+        std::fs::create_dir_all(&id_based_path).unwrap();
+        std::fs::write(schema_path.join("test.json"), TEST_SCHEMA).unwrap();
+
+        // Data source roots are canonicalized when they are loaded, paths in the configuration
+        // need to match
+        let root_path = dunce::canonicalize(root_path).unwrap();
+        let project_configuration = HydrateProjectConfiguration {
+            schema_def_paths: vec![root_path.join("schema")],
+            import_data_path: root_path.join("import_data"),
+            build_data_path: root_path.join("build_data"),
+            job_data_path: root_path.join("job_data"),
+            undo_history_path: root_path.join("undo_history.json"),
+            id_based_asset_sources: vec![NamePathPair {
+                name: "id_based".to_string(),
+                path: root_path.join("id_based"),
+            }],
+            path_based_asset_sources: vec![NamePathPair {
+                name: "path_based".to_string(),
+                path: root_path.join("path_based"),
+            }],
+            source_file_locations: vec![],
+            schema_codegen_jobs: vec![],
+            build_profiles: vec![Default::default()],
+        };
+
+        TestProject {
+            root_path,
+            project_configuration,
+        }
+    }
+
+    fn path_based_path(&self) -> PathBuf {
+        self.root_path.join("path_based")
+    }
+
+    fn write_file(
+        &self,
+        relative_path: impl AsRef<Path>,
+        contents: &str,
+    ) {
+        let path = self.path_based_path().join(relative_path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    // Loads the project. Imports are queued for new or modified source files but not run.
+    fn load(&self) -> HeadlessPipeline {
+        let asset_plugin_registry =
+            AssetPluginRegistryBuilders::new().register_plugin::<TestAssetPlugin>();
+        HeadlessPipeline::new(&self.project_configuration, asset_plugin_registry).unwrap()
+    }
+
+    // Loads the project and runs the imports that loading it queued
+    fn open(&self) -> (HeadlessPipeline, HeadlessPipelineResult) {
+        let mut pipeline = self.load();
+        let result = pipeline.import().unwrap();
+        (pipeline, result)
+    }
+
+    fn read_meta_file(
+        &self,
+        relative_path: impl AsRef<Path>,
+    ) -> MetaFile {
+        let path = self.path_based_path().join(relative_path);
+        MetaFileJson::load_from_string(&std::fs::read_to_string(path).unwrap())
+    }
+
+    fn import_data_path(
+        &self,
+        asset_id: AssetId,
+    ) -> PathBuf {
+        uuid_to_path(
+            &self.project_configuration.import_data_path,
+            asset_id.as_uuid(),
+            "if",
+        )
+    }
+}
+
+impl TestProject {
+    fn build_data_path(&self) -> PathBuf {
+        self.project_configuration.build_data_path.clone()
+    }
+
+    // The manifest of the build that the newest TOC points at
+    fn latest_manifest(&self) -> ManifestContents {
+        let manifest_build_hash =
+            ManifestContents::find_latest_manifest_build_hash(&self.build_data_path()).unwrap();
+        ManifestContents::load_build(&self.build_data_path(), manifest_build_hash).unwrap()
+    }
+
+    fn artifact_path(
+        &self,
+        artifact_id: ArtifactId,
+        build_hash: u64,
+    ) -> PathBuf {
+        uuid_and_hash_to_path(
+            &self.build_data_path(),
+            artifact_id.as_uuid(),
+            build_hash,
+            "bf",
+        )
+    }
+}
+
+impl Drop for TestProject {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root_path);
+    }
+}
+
+fn data_source_root(data_source_name: &str) -> AssetLocation {
+    AssetLocation::new(AssetId::from_uuid(
+        *AssetSourceId::from_data_source_name(data_source_name).uuid(),
+    ))
+}
+
+fn find_asset_by_name(
+    editor_model: &EditorModel,
+    name: &str,
+) -> AssetId {
+    editor_model
+        .root_edit_context()
+        .assets()
+        .iter()
+        .find(|(_, asset_info)| asset_info.asset_name().as_string() == Some(&name.to_string()))
+        .map(|(asset_id, _)| *asset_id)
+        .unwrap()
+}
+
+fn new_test_asset(
+    editor_model: &mut EditorModel,
+    name: &str,
+    location: AssetLocation,
+) -> AssetId {
+    let test_asset_schema = editor_model
+        .schema_set()
+        .find_named_type(TEST_ASSET_TYPE)
+        .unwrap()
+        .as_record()
+        .unwrap()
+        .clone();
+    editor_model.root_edit_context_mut().new_asset(
+        &AssetName::new(name),
+        &location,
+        &test_asset_schema,
+    )
+}
+
+fn set_property(
+    editor_model: &mut EditorModel,
+    asset_id: AssetId,
+    path: &str,
+    value: Value,
+) {
+    editor_model
+        .root_edit_context_mut()
+        .set_property_override(asset_id, path, Some(value))
+        .unwrap();
+}
+
+// Imports anything that is queued and builds the given roots
+fn build(
+    pipeline: &mut HeadlessPipeline,
+    build_roots: BuildRoots,
+) -> Arc<BuildLogData> {
+    // TOC files are named after the time in milliseconds, keep builds from sharing one
+    std::thread::sleep(Duration::from_millis(2));
+    pipeline
+        .import_and_build_roots(build_roots)
+        .unwrap()
+        .build_logs
+        .pop()
+        .unwrap()
+}
+
+// Timings of the jobs of the given type that ran for the asset, or used a cached result
+fn jobs_for_asset<T: JobProcessor>(
+    log: &BuildLogData,
+    asset_id: AssetId,
+) -> Vec<JobTiming> {
+    log.job_timings()
+        .values()
+        .filter(|x| x.job_type_name == std::any::type_name::<T>())
+        .filter(|x| log.assets_relying_on_job(x.job_id).contains(&asset_id))
+        .cloned()
+        .collect()
+}
+
+fn new_directory(
+    editor_model: &mut EditorModel,
+    name: &str,
+    location: AssetLocation,
+) -> AssetId {
+    let path_node_schema = editor_model
+        .schema_set()
+        .find_named_type(PathNode::schema_name())
+        .unwrap()
+        .as_record()
+        .unwrap()
+        .clone();
+    editor_model.root_edit_context_mut().new_asset(
+        &AssetName::new(name),
+        &location,
+        &path_node_schema,
+    )
+}
+
+// Moving a generated asset moves its source file, and path references to the source file are
+// updated as part of the same undo step
+#[test]
+fn move_generated_asset_relocates_source_file() {
+    let project = TestProject::new();
+    project.write_file("a.test_source", "ref path_based://b.test_source");
+    project.write_file("b.test_source", "");
+
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let a = find_asset_by_name(editor_model, "a.test_source");
+    let b = find_asset_by_name(editor_model, "b.test_source");
+    let sub = new_directory(editor_model, "sub", data_source_root("path_based"));
+    editor_model.commit_all_pending_undo_contexts();
+
+    let referenced_paths = |editor_model: &EditorModel| -> Vec<String> {
+        editor_model
+            .root_edit_context()
+            .resolve_all_path_reference_overrides(a)
+            .unwrap()
+            .keys()
+            .map(|x| x.path().to_string())
+            .collect()
+    };
+    assert_eq!(referenced_paths(editor_model), vec!["b.test_source"]);
+
+    editor_model
+        .move_assets(&[b], None, AssetLocation::new(sub))
+        .unwrap();
+    assert_eq!(
+        editor_model.root_edit_context().asset_location(b),
+        Some(AssetLocation::new(sub))
+    );
+    assert_eq!(referenced_paths(editor_model), vec!["sub/b.test_source"]);
+
+    // The move and the path reference update are undone together
+    editor_model.undo().unwrap();
+    assert_eq!(
+        editor_model.root_edit_context().asset_location(b),
+        Some(data_source_root("path_based"))
+    );
+    assert_eq!(referenced_paths(editor_model), vec!["b.test_source"]);
+
+    editor_model.redo().unwrap();
+    editor_model.save_root_edit_context();
+    assert!(!project.path_based_path().join("b.test_source").exists());
+    assert!(!project
+        .path_based_path()
+        .join("b.test_source.meta")
+        .exists());
+    assert!(project.path_based_path().join("sub/b.test_source").exists());
+    assert!(project
+        .path_based_path()
+        .join("sub/b.test_source.meta")
+        .exists());
+
+    // The meta file moved with the source file, so the asset keeps its ID
+    drop(pipeline);
+    let (pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model();
+    assert_eq!(find_asset_by_name(editor_model, "b.test_source"), b);
+    assert_eq!(
+        editor_model
+            .root_edit_context()
+            .asset_location(b)
+            .map(|x| x.path_node_id()),
+        Some(find_asset_by_name(editor_model, "sub"))
+    );
+}
+
+// Moving, undoing or redoing fails without changing anything if a source file can't be moved, so
+// path references never point at a file that wasn't moved
+#[test]
+fn move_generated_asset_onto_existing_file() {
+    let project = TestProject::new();
+    project.write_file("a.test_source", "ref path_based://b.test_source");
+    project.write_file("b.test_source", "");
+
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let a = find_asset_by_name(editor_model, "a.test_source");
+    let b = find_asset_by_name(editor_model, "b.test_source");
+    let sub = new_directory(editor_model, "sub", data_source_root("path_based"));
+    editor_model.commit_all_pending_undo_contexts();
+    let referenced_paths = |editor_model: &EditorModel| -> Vec<String> {
+        editor_model
+            .root_edit_context()
+            .resolve_all_path_reference_overrides(a)
+            .unwrap()
+            .keys()
+            .map(|x| x.path().to_string())
+            .collect()
+    };
+
+    // Only the source file's path is checked for collisions before moving, not its meta file's
+    project.write_file("sub/b.test_source.meta", "");
+    let undo_step_count = editor_model.undo_stack().steps().len();
+    assert!(editor_model
+        .move_assets(&[b], None, AssetLocation::new(sub))
+        .is_err());
+    assert_eq!(
+        editor_model.root_edit_context().asset_location(b),
+        Some(data_source_root("path_based"))
+    );
+    assert_eq!(referenced_paths(editor_model), vec!["b.test_source"]);
+    assert_eq!(editor_model.undo_stack().steps().len(), undo_step_count);
+    assert!(project.path_based_path().join("b.test_source").exists());
+    assert!(project
+        .path_based_path()
+        .join("b.test_source.meta")
+        .exists());
+    assert!(!project.path_based_path().join("sub/b.test_source").exists());
+
+    // The source file is moved as soon as the move is made
+    std::fs::remove_file(project.path_based_path().join("sub/b.test_source.meta")).unwrap();
+    editor_model
+        .move_assets(&[b], None, AssetLocation::new(sub))
+        .unwrap();
+    assert!(project.path_based_path().join("sub/b.test_source").exists());
+    assert!(!project.path_based_path().join("b.test_source").exists());
+    assert_eq!(referenced_paths(editor_model), vec!["sub/b.test_source"]);
+
+    // Undoing has to move it back
+    project.write_file("b.test_source.meta", "");
+    assert!(editor_model.undo().is_err());
+    assert_eq!(
+        editor_model.root_edit_context().asset_location(b),
+        Some(AssetLocation::new(sub))
+    );
+    assert_eq!(referenced_paths(editor_model), vec!["sub/b.test_source"]);
+    assert!(project.path_based_path().join("sub/b.test_source").exists());
+
+    std::fs::remove_file(project.path_based_path().join("b.test_source.meta")).unwrap();
+    editor_model.undo().unwrap();
+    assert_eq!(referenced_paths(editor_model), vec!["b.test_source"]);
+    assert!(project.path_based_path().join("b.test_source").exists());
+    assert!(project
+        .path_based_path()
+        .join("b.test_source.meta")
+        .exists());
+    assert!(!project.path_based_path().join("sub/b.test_source").exists());
+}
+
+// Assets and whole subtrees can be moved to another data source. Moved generated assets become
+// regular assets there and their source file stays where it is.
+#[test]
+fn move_assets_to_another_data_source() {
+    let project = TestProject::new();
+    project.write_file("a.test_source", "");
+
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let a = find_asset_by_name(editor_model, "a.test_source");
+    let dir = new_directory(editor_model, "dir", data_source_root("path_based"));
+    let c = new_test_asset(editor_model, "c", AssetLocation::new(dir));
+    editor_model.commit_all_pending_undo_contexts();
+    editor_model.save_root_edit_context();
+    assert!(project.path_based_path().join("dir/c.af").exists());
+
+    editor_model
+        .move_assets(&[a], None, data_source_root("id_based"))
+        .unwrap();
+    editor_model
+        .move_assets(&[dir], None, data_source_root("id_based"))
+        .unwrap();
+    editor_model.save_root_edit_context();
+
+    // The source file stays and its meta file records that the asset is now stored elsewhere
+    let id_based_path = project.root_path.join("id_based");
+    assert!(project.path_based_path().join("a.test_source").exists());
+    let meta_file = project.read_meta_file("a.test_source.meta");
+    assert!(meta_file.persisted_assets.contains(&a));
+    assert!(!project.path_based_path().join("dir").exists());
+    for asset_id in [a, dir, c] {
+        assert!(uuid_to_path(&id_based_path, asset_id.as_uuid(), "af").exists());
+    }
+
+    // Everything keeps its ID, and the source file doesn't generate the asset again
+    drop(pipeline);
+    let (pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model();
+    let root_edit_context = editor_model.root_edit_context();
+    assert_eq!(root_edit_context.assets().len(), 5);
+    assert!(!editor_model.is_generated_asset(a));
+    assert_eq!(
+        root_edit_context.asset_location(a),
+        Some(data_source_root("id_based"))
+    );
+    assert_eq!(
+        root_edit_context.asset_location(dir),
+        Some(data_source_root("id_based"))
+    );
+    assert_eq!(
+        root_edit_context.asset_location(c),
+        Some(AssetLocation::new(dir))
+    );
+}
+
+// A move that could not be saved is rejected without changing anything
+#[test]
+fn move_assets_to_another_data_source_without_meta_file() {
+    let project = TestProject::new();
+    project.write_file("a.test_source", "");
+
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let a = find_asset_by_name(editor_model, "a.test_source");
+    editor_model.save_root_edit_context();
+    std::fs::remove_file(project.path_based_path().join("a.test_source.meta")).unwrap();
+
+    let undo_step_count = editor_model.undo_stack().steps().len();
+    assert!(editor_model
+        .move_assets(&[a], None, data_source_root("id_based"))
+        .is_err());
+    assert_eq!(
+        editor_model.root_edit_context().asset_location(a),
+        Some(data_source_root("path_based"))
+    );
+    assert_eq!(editor_model.undo_stack().steps().len(), undo_step_count);
+    assert!(!editor_model.any_edit_context_has_unsaved_changes());
+}
+
+// Garbage is only looked for once imports are done, and only garbage is deleted
+#[test]
+fn collect_garbage() {
+    let project = TestProject::new();
+    project.write_file("a.test_source", "importable x\nimportable y");
+    project.write_file("b.test_source", "");
+    project.write_file("c.test_source", "");
+    let (mut pipeline, _) = project.open();
+    pipeline.editor_model_mut().save_root_edit_context();
+    drop(pipeline);
+
+    let a_meta_file = project.read_meta_file("a.test_source.meta");
+    let a = a_meta_file.past_id_assignments[&ImportableName::default()];
+    let x = a_meta_file.past_id_assignments[&ImportableName::new("x".to_string())];
+    let y = a_meta_file.past_id_assignments[&ImportableName::new("y".to_string())];
+    let b = project
+        .read_meta_file("b.test_source.meta")
+        .past_id_assignments[&ImportableName::default()];
+    let c = project
+        .read_meta_file("c.test_source.meta")
+        .past_id_assignments[&ImportableName::default()];
+    for asset_id in [a, x, y, b, c] {
+        assert!(project.import_data_path(asset_id).exists());
+    }
+
+    // y is no longer produced by a and c was deleted while the project was closed
+    project.write_file("a.test_source", "importable x");
+    std::fs::remove_file(project.path_based_path().join("c.test_source")).unwrap();
+
+    // Until the import of a is done, its import data may not match its assets
+    let mut pipeline = project.load();
+    assert!(pipeline.asset_engine().has_pending_imports());
+    assert!(pipeline
+        .editor_model()
+        .find_garbage(pipeline.asset_engine())
+        .is_err());
+    pipeline.import().unwrap();
+
+    // b is deleted while the project is open
+    std::fs::remove_file(project.path_based_path().join("b.test_source")).unwrap();
+
+    let garbage = pipeline
+        .editor_model()
+        .find_garbage(pipeline.asset_engine())
+        .unwrap();
+    let path_based_path = project.path_based_path();
+    assert_eq!(
+        garbage.orphaned_generated_assets,
+        vec![(b, path_based_path.join("b.test_source"))]
+    );
+    let mut orphaned_meta_files = garbage.orphaned_meta_files.clone();
+    orphaned_meta_files.sort();
+    assert_eq!(
+        orphaned_meta_files,
+        vec![
+            path_based_path.join("b.test_source.meta"),
+            path_based_path.join("c.test_source.meta")
+        ]
+    );
+    assert_eq!(
+        garbage.stale_meta_file_entries,
+        vec![(
+            path_based_path.join("a.test_source.meta"),
+            vec![ImportableName::new("y".to_string())]
+        )]
+    );
+    let mut orphaned_import_data = garbage.orphaned_import_data.clone();
+    orphaned_import_data.sort();
+    let mut expected_orphaned_import_data: Vec<_> = [y, b, c]
+        .iter()
+        .map(|&asset_id| project.import_data_path(asset_id))
+        .collect();
+    expected_orphaned_import_data.sort();
+    assert_eq!(orphaned_import_data, expected_orphaned_import_data);
+
+    let not_deleted = pipeline.editor_model_mut().delete_garbage(&garbage);
+    assert!(not_deleted.is_empty());
+
+    // The garbage is gone
+    let editor_model = pipeline.editor_model();
+    assert!(!editor_model.root_edit_context().has_asset(b));
+    assert!(!path_based_path.join("b.test_source.meta").exists());
+    assert!(!path_based_path.join("c.test_source.meta").exists());
+    for asset_id in [y, b, c] {
+        assert!(!project.import_data_path(asset_id).exists());
+    }
+
+    // Everything else is kept
+    assert!(editor_model.root_edit_context().has_asset(a));
+    assert!(editor_model.root_edit_context().has_asset(x));
+    let a_meta_file = project.read_meta_file("a.test_source.meta");
+    assert_eq!(a_meta_file.past_id_assignments.len(), 2);
+    assert_eq!(
+        a_meta_file.past_id_assignments[&ImportableName::new("x".to_string())],
+        x
+    );
+    for asset_id in [a, x] {
+        assert!(project.import_data_path(asset_id).exists());
+    }
+
+    assert!(editor_model
+        .find_garbage(pipeline.asset_engine())
+        .unwrap()
+        .is_empty());
+}
+
+// Import data of an asset that undo could bring back is not garbage
+#[test]
+fn collect_garbage_keeps_import_data_in_undo_history() {
+    let project = TestProject::new();
+    project.write_file("a.test_source", "importable x");
+    let (mut pipeline, _) = project.open();
+    let x = project
+        .read_meta_file("a.test_source.meta")
+        .past_id_assignments[&ImportableName::new("x".to_string())];
+
+    let editor_model = pipeline.editor_model_mut();
+    editor_model.persist_generated_asset(x);
+    editor_model.save_root_edit_context();
+    editor_model
+        .root_edit_context_mut()
+        .with_undo_context("delete", |edit_context| {
+            edit_context.delete_asset(x).unwrap();
+            EndContextBehavior::Finish
+        });
+    editor_model.save_root_edit_context();
+    assert!(!editor_model.root_edit_context().has_asset(x));
+
+    let garbage = pipeline
+        .editor_model()
+        .find_garbage(pipeline.asset_engine())
+        .unwrap();
+    assert!(garbage.is_empty());
+
+    let editor_model = pipeline.editor_model_mut();
+    editor_model.undo().unwrap();
+    assert!(editor_model.root_edit_context().has_asset(x));
+    assert!(project.import_data_path(x).exists());
+}
+
+// A job whose inputs haven't changed reuses its cached result, and the artifacts it wrote and jobs
+// it enqueued are replayed. The cached result is not used if one of its artifacts is missing.
+#[test]
+fn unchanged_jobs_use_cached_results() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let a = new_test_asset(editor_model, "a", data_source_root("path_based"));
+    set_property(editor_model, a, "value", Value::F32(1.0));
+    set_property(editor_model, a, "enqueues", Value::Boolean(true));
+    editor_model.save_root_edit_context();
+
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(!jobs_for_asset::<TestJob>(&log, a)[0].used_cached_result);
+    assert!(!jobs_for_asset::<TestDownstreamJob>(&log, a)[0].used_cached_result);
+    let manifest = project.latest_manifest();
+    assert_eq!(manifest.artifacts.len(), 2);
+
+    // Everything is cached, and the downstream job is still enqueued by the cached job
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(jobs_for_asset::<TestJob>(&log, a)[0].used_cached_result);
+    assert!(jobs_for_asset::<TestDownstreamJob>(&log, a)[0].used_cached_result);
+    assert!(log.log_events().is_empty());
+    assert_eq!(project.latest_manifest().artifacts, manifest.artifacts);
+
+    // The cache also survives restarting the editor
+    drop(pipeline);
+    let (mut pipeline, _) = project.open();
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(jobs_for_asset::<TestJob>(&log, a)[0].used_cached_result);
+
+    // Without the artifact file, the job has to run again to write it
+    let artifact = &manifest.artifacts[&ArtifactId::from_uuid(a.as_uuid())];
+    let artifact_path = project.artifact_path(artifact.artifact_id, artifact.build_hash);
+    std::fs::remove_file(&artifact_path).unwrap();
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(!jobs_for_asset::<TestJob>(&log, a)[0].used_cached_result);
+    assert!(jobs_for_asset::<TestDownstreamJob>(&log, a)[0].used_cached_result);
+    assert!(artifact_path.exists());
+    assert_eq!(project.latest_manifest().artifacts, manifest.artifacts);
+}
+
+// Reads are tracked per asset. Editing an asset that a job didn't read keeps its cached result,
+// and editing an asset it read, whether its own or one it followed a reference to, invalidates it.
+#[test]
+fn edits_invalidate_only_jobs_that_read_them() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let location = data_source_root("path_based");
+    let a = new_test_asset(editor_model, "a", location);
+    let b = new_test_asset(editor_model, "b", location);
+    let unrelated = new_test_asset(editor_model, "unrelated", location);
+    set_property(editor_model, a, "reads", Value::AssetRef(b));
+    editor_model.save_root_edit_context();
+
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(!jobs_for_asset::<TestJob>(&log, a)[0].used_cached_result);
+    let manifest = project.latest_manifest();
+
+    // Neither a property nor a path reference override of an asset the job didn't read matters
+    let editor_model = pipeline.editor_model_mut();
+    set_property(editor_model, unrelated, "value", Value::F32(1.0));
+    let root_edit_context = editor_model.root_edit_context_mut();
+    let path_reference = CanonicalPathReference::new(
+        root_edit_context,
+        "path_based".to_string(),
+        "b.test_source".to_string(),
+        ImportableName::default(),
+    );
+    root_edit_context
+        .set_path_reference_override(unrelated, path_reference, b)
+        .unwrap();
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(jobs_for_asset::<TestJob>(&log, a)[0].used_cached_result);
+    assert!(jobs_for_asset::<TestJob>(&log, b)[0].used_cached_result);
+    assert!(!jobs_for_asset::<TestJob>(&log, unrelated)[0].used_cached_result);
+
+    // Editing the referenced asset invalidates the job that read it
+    set_property(pipeline.editor_model_mut(), b, "value", Value::F32(2.0));
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(!jobs_for_asset::<TestJob>(&log, a)[0].used_cached_result);
+    assert!(!jobs_for_asset::<TestJob>(&log, b)[0].used_cached_result);
+    assert!(jobs_for_asset::<TestJob>(&log, unrelated)[0].used_cached_result);
+    let a_artifact_id = ArtifactId::from_uuid(a.as_uuid());
+    assert_ne!(
+        project.latest_manifest().artifacts[&a_artifact_id].build_hash,
+        manifest.artifacts[&a_artifact_id].build_hash
+    );
+
+    // So does editing the job's own asset
+    set_property(pipeline.editor_model_mut(), a, "value", Value::F32(3.0));
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(!jobs_for_asset::<TestJob>(&log, a)[0].used_cached_result);
+    assert!(jobs_for_asset::<TestJob>(&log, b)[0].used_cached_result);
+}
+
+// Building roots only builds those assets and the assets their artifacts depend on. The manifest
+// of such a build only has their artifacts, so it is not the manifest of a full build.
+#[test]
+fn build_roots_and_their_dependencies() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let location = data_source_root("path_based");
+    let a = new_test_asset(editor_model, "a", location);
+    let b = new_test_asset(editor_model, "b", location);
+    let c = new_test_asset(editor_model, "c", location);
+    set_property(editor_model, a, "reads", Value::AssetRef(b));
+    set_property(editor_model, a, "downstream", Value::Boolean(true));
+    editor_model.save_root_edit_context();
+
+    let log = build(&mut pipeline, BuildRoots::Assets(vec![a]));
+    assert_eq!(jobs_for_asset::<TestJob>(&log, a).len(), 1);
+    assert_eq!(jobs_for_asset::<TestJob>(&log, b).len(), 1);
+    assert!(jobs_for_asset::<TestJob>(&log, c).is_empty());
+    let partial_manifest_build_hash =
+        ManifestContents::find_latest_manifest_build_hash(&project.build_data_path()).unwrap();
+    let manifest = project.latest_manifest();
+    assert_eq!(manifest.artifacts.len(), 3);
+    assert!(manifest
+        .artifacts
+        .contains_key(&ArtifactId::from_uuid(b.as_uuid())));
+    assert!(!manifest
+        .artifacts
+        .contains_key(&ArtifactId::from_uuid(c.as_uuid())));
+
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert_eq!(jobs_for_asset::<TestJob>(&log, c).len(), 1);
+    let full_manifest_build_hash =
+        ManifestContents::find_latest_manifest_build_hash(&project.build_data_path()).unwrap();
+    assert_ne!(partial_manifest_build_hash, full_manifest_build_hash);
+    assert_eq!(project.latest_manifest().artifacts.len(), 4);
+}
+
+// A failed job cancels the jobs downstream of it instead of ending the build. The manifest has
+// everything that succeeded, but nothing that references an artifact of the failed job.
+#[test]
+fn failed_jobs_cancel_downstream_jobs() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let location = data_source_root("path_based");
+    let a = new_test_asset(editor_model, "a", location);
+    let b = new_test_asset(editor_model, "b", location);
+    let c = new_test_asset(editor_model, "c", location);
+    set_property(editor_model, a, "fail", Value::Boolean(true));
+    set_property(editor_model, a, "downstream", Value::Boolean(true));
+    set_property(editor_model, b, "reads", Value::AssetRef(a));
+    set_property(editor_model, b, "downstream", Value::Boolean(true));
+    editor_model.save_root_edit_context();
+
+    let log = build(&mut pipeline, BuildRoots::All);
+    let downstream_job = &jobs_for_asset::<TestDownstreamJob>(&log, a)[0];
+    assert!(downstream_job.started.is_none());
+    assert!(log
+        .log_events()
+        .iter()
+        .any(|x| { x.job_id == Some(downstream_job.job_id) && x.message.contains("cancelled") }));
+    assert_eq!(log.failed_assets(), {
+        let mut failed_assets = vec![a, b];
+        failed_assets.sort();
+        failed_assets
+    });
+
+    // b's default artifact doesn't depend on a, but its downstream artifact does
+    let manifest = project.latest_manifest();
+    assert_eq!(manifest.artifacts.len(), 2);
+    for asset_id in [b, c] {
+        assert!(manifest
+            .artifacts
+            .contains_key(&ArtifactId::from_uuid(asset_id.as_uuid())));
+    }
+}
+
+// Updates the pipeline until the state matches, and returns that state
+fn update_until(
+    pipeline: &mut HeadlessPipeline,
+    f: impl Fn(&AssetEngineState) -> bool,
+) -> AssetEngineState {
+    let asset_path_cache = AssetPathCache::build(pipeline.editor_model()).unwrap();
+    loop {
+        let asset_engine_state = pipeline.update(&asset_path_cache).unwrap();
+        if f(&asset_engine_state) {
+            return asset_engine_state;
+        }
+
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+// Cancelling an import drops the source files that haven't been imported, they are imported the
+// next time the project is loaded. Cancelling a build drops the jobs that haven't started and
+// doesn't write a manifest. Neither gets in the way of the next build.
+#[test]
+fn cancel_import_and_build() {
+    const ASSET_COUNT: usize = 500;
+    let project = TestProject::new();
+    for i in 0..ASSET_COUNT {
+        project.write_file(format!("{}.test_source", i), "");
+    }
+
+    let mut pipeline = project.load();
+    update_until(&mut pipeline, |x| {
+        matches!(x, AssetEngineState::Importing(_))
+    });
+    pipeline.asset_engine_mut().cancel_import();
+    let AssetEngineState::ImportCompleted(import_log) = update_until(&mut pipeline, |x| {
+        matches!(x, AssetEngineState::ImportCompleted(_))
+    }) else {
+        unreachable!()
+    };
+    let cancelled_import_count = import_log
+        .log_events()
+        .iter()
+        .filter(|x| x.message.contains("cancelled"))
+        .count();
+    assert!(cancelled_import_count > 0);
+    let test_asset_count = |pipeline: &HeadlessPipeline| {
+        let editor_model = pipeline.editor_model();
+        editor_model
+            .root_edit_context()
+            .assets()
+            .values()
+            .filter(|x| x.schema().name() == TEST_ASSET_TYPE)
+            .count()
+    };
+    assert_eq!(
+        test_asset_count(&pipeline) + cancelled_import_count,
+        ASSET_COUNT
+    );
+
+    drop(pipeline);
+    let (mut pipeline, _) = project.open();
+    assert_eq!(test_asset_count(&pipeline), ASSET_COUNT);
+
+    pipeline
+        .asset_engine_mut()
+        .queue_build_roots(BuildRoots::All);
+    update_until(&mut pipeline, |x| {
+        matches!(x, AssetEngineState::Building(_))
+    });
+    pipeline.asset_engine_mut().cancel_build();
+    let AssetEngineState::BuildCompleted(log) = update_until(&mut pipeline, |x| {
+        matches!(x, AssetEngineState::BuildCompleted(_))
+    }) else {
+        unreachable!()
+    };
+    let started_job_count = log
+        .job_timings()
+        .values()
+        .filter(|x| x.started.is_some())
+        .count();
+    assert!(started_job_count < ASSET_COUNT);
+    assert!(ManifestContents::find_latest_manifest_build_hash(&project.build_data_path()).is_err());
+
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert_eq!(log.job_timings().len(), ASSET_COUNT);
+    assert_eq!(project.latest_manifest().artifacts.len(), ASSET_COUNT);
+}
+
+// The timing report totals jobs by type, and the trace written next to the manifest has one
+// event for each job that ran
+#[test]
+fn build_timing_report_and_trace() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let location = data_source_root("path_based");
+    for name in ["a", "b", "c"] {
+        let asset_id = new_test_asset(editor_model, name, location);
+        if name != "c" {
+            set_property(editor_model, asset_id, "downstream", Value::Boolean(true));
+        }
+    }
+    editor_model.save_root_edit_context();
+
+    let job_type_counts = |log: &BuildLogData| {
+        let report = BuildTimingReport::new(log);
+        let mut job_type_counts: Vec<_> = report
+            .job_type_timings
+            .iter()
+            .map(|x| (x.job_type_name, x.job_count, x.cached_job_count))
+            .collect();
+        job_type_counts.sort();
+        job_type_counts
+    };
+
+    let log = build(&mut pipeline, BuildRoots::All);
+    let test_job_type_name = std::any::type_name::<TestJob>();
+    let downstream_job_type_name = std::any::type_name::<TestDownstreamJob>();
+    let mut expected_job_type_counts =
+        vec![(test_job_type_name, 3, 0), (downstream_job_type_name, 2, 0)];
+    expected_job_type_counts.sort();
+    assert_eq!(job_type_counts(&log), expected_job_type_counts);
+
+    let manifest_build_hash =
+        ManifestContents::find_latest_manifest_build_hash(&project.build_data_path()).unwrap();
+    let trace_path = project
+        .build_data_path()
+        .join("manifests")
+        .join(format!("{:0>16x}.build_trace.json", manifest_build_hash));
+    let trace: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(trace_path).unwrap()).unwrap();
+    assert_eq!(trace, build_chrome_trace_json(&log));
+    let trace_events = trace["traceEvents"].as_array().unwrap();
+    assert_eq!(trace_events.len(), 5);
+    let mut job_ids: Vec<_> = trace_events
+        .iter()
+        .map(|x| x["args"]["job_id"].as_str().unwrap().to_string())
+        .collect();
+    job_ids.sort();
+    job_ids.dedup();
+    assert_eq!(job_ids.len(), 5);
+    for &(job_type_name, job_count, _) in &expected_job_type_counts {
+        let trace_event_count = trace_events
+            .iter()
+            .filter(|x| x["cat"] == job_type_name)
+            .count();
+        assert_eq!(trace_event_count, job_count);
+    }
+
+    // Cached jobs are counted separately
+    let log = build(&mut pipeline, BuildRoots::All);
+    for x in &mut expected_job_type_counts {
+        x.2 = x.1;
+    }
+    assert_eq!(job_type_counts(&log), expected_job_type_counts);
+}
+
+// Building unchanged data again writes a manifest with the same name. A running game may have the
+// old one mapped into memory, so the file is replaced rather than truncated and rewritten.
+#[cfg(unix)]
+#[test]
+fn rebuild_replaces_manifest() {
+    use std::os::unix::fs::MetadataExt;
+
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    new_test_asset(
+        pipeline.editor_model_mut(),
+        "a",
+        data_source_root("path_based"),
+    );
+    pipeline.editor_model_mut().save_root_edit_context();
+
+    let manifest_path = |project: &TestProject| {
+        let manifest_build_hash =
+            ManifestContents::find_latest_manifest_build_hash(&project.build_data_path()).unwrap();
+        project
+            .build_data_path()
+            .join("manifests")
+            .join(format!("{:0>16x}.manifest_release", manifest_build_hash))
+    };
+
+    build(&mut pipeline, BuildRoots::All);
+    let old_manifest_path = manifest_path(&project);
+    let old_manifest_file = std::fs::File::open(&old_manifest_path).unwrap();
+    let old_manifest_len = old_manifest_file.metadata().unwrap().len();
+
+    build(&mut pipeline, BuildRoots::All);
+    assert_eq!(manifest_path(&project), old_manifest_path);
+    let new_metadata = std::fs::metadata(&old_manifest_path).unwrap();
+    assert_ne!(
+        new_metadata.ino(),
+        old_manifest_file.metadata().unwrap().ino()
+    );
+    assert_eq!(
+        old_manifest_file.metadata().unwrap().len(),
+        old_manifest_len
+    );
+}
+
+// Verification builds report job processors that produce different output for the same input,
+// either within one build or compared to the cached result of the previous build
+#[test]
+fn verification_reports_nondeterministic_jobs() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let location = data_source_root("path_based");
+    let a = new_test_asset(editor_model, "a", location);
+    let b = new_test_asset(editor_model, "b", location);
+    set_property(editor_model, a, "nondeterministic", Value::Boolean(true));
+    set_property(editor_model, b, "enqueues", Value::Boolean(true));
+    editor_model.save_root_edit_context();
+
+    // The previous build has to cache the result to compare against
+    build(&mut pipeline, BuildRoots::All);
+    for mode in [
+        BuildVerificationMode::CompareWithPreviousBuild,
+        BuildVerificationMode::RunTwice,
+    ] {
+        pipeline
+            .asset_engine_mut()
+            .set_build_verification(Some(BuildVerification::new(mode)));
+        let log = build(&mut pipeline, BuildRoots::All);
+        let determinism_mismatches = log.determinism_mismatches();
+        assert!(!determinism_mismatches.is_empty());
+        let a_job_id = jobs_for_asset::<TestJob>(&log, a)[0].job_id;
+        for determinism_mismatch in determinism_mismatches {
+            assert_eq!(determinism_mismatch.job_id, a_job_id);
+            assert_eq!(
+                determinism_mismatch.job_type_name,
+                std::any::type_name::<TestJob>()
+            );
+        }
+    }
+}
+
+// Connects a job worker to a listener and returns the hello message it sent, length prefix
+// included, so that it can be replayed
+fn capture_job_worker_hello(
+    project: &TestProject,
+    token: &str,
+) -> Vec<u8> {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let project_configuration = project.project_configuration.clone();
+    let token = token.to_string();
+    let worker_thread = std::thread::spawn(move || {
+        HeadlessPipeline::run_job_worker(
+            &project_configuration,
+            AssetPluginRegistryBuilders::new().register_plugin::<TestAssetPlugin>(),
+            &address,
+            &token,
+        )
+    });
+
+    let (mut stream, _) = listener.accept().unwrap();
+    let mut length_bytes = [0u8; 8];
+    stream.read_exact(&mut length_bytes).unwrap();
+    let mut hello = length_bytes.to_vec();
+    hello.resize(8 + u64::from_le_bytes(length_bytes) as usize, 0);
+    stream.read_exact(&mut hello[8..]).unwrap();
+    // The worker treats this like the build closing, which isn't an error
+    drop(stream);
+    worker_thread.join().unwrap().unwrap();
+    hello
+}
+
+// Workers must send the build's token. A worker that disconnects while idle is only noticed when
+// a job is sent to it, and the job goes to another worker instead of failing. A connection that
+// never says hello doesn't keep other workers from connecting.
+#[test]
+fn job_workers_require_token_and_replace_lost_workers() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let location = data_source_root("path_based");
+    let a = new_test_asset(editor_model, "a", location);
+    let b = new_test_asset(editor_model, "b", location);
+    set_property(editor_model, a, "reads", Value::AssetRef(b));
+    set_property(editor_model, b, "enqueues", Value::Boolean(true));
+    editor_model.save_root_edit_context();
+
+    let token = Uuid::new_v4().to_string();
+    let address = pipeline
+        .asset_engine_mut()
+        .start_job_workers(JobWorkerConfig {
+            listen_address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            local_worker_command: None,
+            local_worker_count: 0,
+            worker_wait_timeout: Duration::from_secs(10),
+            token: token.clone(),
+        })
+        .unwrap()
+        .to_string();
+    let start_worker = |token: &str| {
+        let project_configuration = project.project_configuration.clone();
+        let address = address.clone();
+        let token = token.to_string();
+        std::thread::spawn(move || {
+            HeadlessPipeline::run_job_worker(
+                &project_configuration,
+                AssetPluginRegistryBuilders::new().register_plugin::<TestAssetPlugin>(),
+                &address,
+                &token,
+            )
+        })
+    };
+
+    // The build turns the worker away, which closes its connection
+    assert!(start_worker("wrong token").join().unwrap().is_err());
+
+    // A worker that says hello and then goes away is the first idle worker a job gets
+    let hello = capture_job_worker_hello(&project, &token);
+    let mut lost_worker = TcpStream::connect(&address).unwrap();
+    lost_worker.write_all(&hello).unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    drop(lost_worker);
+
+    // Connections get 10 seconds to say hello
+    let silent_connection = TcpStream::connect(&address).unwrap();
+    let build_start_time = std::time::Instant::now();
+    let worker_thread = start_worker(&token);
+
+    let log = build(&mut pipeline, BuildRoots::All);
+    assert!(log.log_events().is_empty());
+    assert_eq!(project.latest_manifest().artifacts.len(), 3);
+    assert!(build_start_time.elapsed() < Duration::from_secs(5));
+    drop(silent_connection);
+
+    // The worker exits once the build stops using it
+    pipeline.asset_engine_mut().stop_job_workers();
+    worker_thread.join().unwrap().unwrap();
+}
+
+// Same UUID as TestArtifact but too large to be decoded from one
+#[derive(Serialize, Deserialize, TypeUuid)]
+#[uuid = "5d2b7e9a-4c1f-4a3e-8b6d-2f9c1e7a4b0b"]
+struct MisreadTestArtifact {
+    values: [u128; 4],
+}
+
+// Inspecting an artifact lists its dependencies and decodes it if it can. Problems with the build
+// output are reported in the text rather than failing the inspection.
+#[test]
+fn inspect_built_artifacts() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let a = new_test_asset(editor_model, "a", data_source_root("path_based"));
+    set_property(editor_model, a, "downstream", Value::Boolean(true));
+    editor_model.save_root_edit_context();
+    build(&mut pipeline, BuildRoots::All);
+
+    let mut manifest = project.latest_manifest();
+    let a_artifact_id = ArtifactId::from_uuid(a.as_uuid());
+    let downstream_artifact_id = manifest
+        .artifacts
+        .values()
+        .find(|x| x.artifact_type == Uuid::from_bytes(TestDownstreamArtifact::UUID))
+        .unwrap()
+        .artifact_id;
+
+    let mut decoders = ArtifactDecoderRegistry::default();
+    decoders.register_artifact_type::<TestDownstreamArtifact>();
+    let text = inspect_built_artifact(
+        &project.build_data_path(),
+        &manifest,
+        downstream_artifact_id,
+        &decoders,
+    )
+    .unwrap();
+    assert!(text.contains(std::any::type_name::<TestDownstreamArtifact>()));
+    assert!(text.contains(&format!("[{:?}] type:", a_artifact_id)));
+    assert!(text.contains("\nContents:\n"));
+
+    // A decoder that doesn't match the data
+    let mut decoders = ArtifactDecoderRegistry::default();
+    decoders.register_artifact_type::<MisreadTestArtifact>();
+    let text = inspect_built_artifact(
+        &project.build_data_path(),
+        &manifest,
+        a_artifact_id,
+        &decoders,
+    )
+    .unwrap();
+    assert!(text.contains("Could not decode artifact"));
+
+    // A .bf file that is truncated or has a corrupt header
+    let a_path =
+        project.artifact_path(a_artifact_id, manifest.artifacts[&a_artifact_id].build_hash);
+    let a_bytes = std::fs::read(&a_path).unwrap();
+    let header_length = usize::from_le_bytes(a_bytes[0..8].try_into().unwrap());
+    let mut huge_header = a_bytes.clone();
+    huge_header[0..8].copy_from_slice(&usize::MAX.to_le_bytes());
+    for corrupt_bytes in [
+        a_bytes[0..4].to_vec(),
+        a_bytes[0..8 + header_length / 2].to_vec(),
+        huge_header,
+    ] {
+        std::fs::write(&a_path, corrupt_bytes).unwrap();
+        let error = inspect_built_artifact(
+            &project.build_data_path(),
+            &manifest,
+            a_artifact_id,
+            &decoders,
+        )
+        .unwrap_err();
+        assert!(format!("{:?}", error).contains("Could not read the header"));
+    }
+    std::fs::write(&a_path, a_bytes).unwrap();
+
+    // A dependency that isn't in the manifest
+    manifest.artifacts.remove(&a_artifact_id);
+    let text = inspect_built_artifact(
+        &project.build_data_path(),
+        &manifest,
+        downstream_artifact_id,
+        &decoders,
+    )
+    .unwrap();
+    assert!(text.contains(&format!("[{:?}] not in manifest", a_artifact_id)));
+}
+
+// Building what changed rebuilds edited assets, the assets whose jobs read them and assets that
+// failed last time. Everything else is carried over from the previous build of all assets with the
+// same profile.
+#[test]
+fn changed_builds_rebuild_only_what_changed() {
+    let mut project = TestProject::new();
+    project
+        .project_configuration
+        .build_profiles
+        .push(BuildProfile {
+            name: "release".to_string(),
+            ..Default::default()
+        });
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let location = data_source_root("path_based");
+    let a = new_test_asset(editor_model, "a", location);
+    let b = new_test_asset(editor_model, "b", location);
+    let c = new_test_asset(editor_model, "c", location);
+    let d = new_test_asset(editor_model, "d", location);
+    set_property(editor_model, a, "reads", Value::AssetRef(b));
+    set_property(editor_model, d, "fail", Value::Boolean(true));
+    editor_model.save_root_edit_context();
+
+    let artifact_id = |asset_id: AssetId| ArtifactId::from_uuid(asset_id.as_uuid());
+    let rebuilt_assets = |log: &BuildLogData| {
+        let mut rebuilt_assets: Vec<_> = [a, b, c, d]
+            .into_iter()
+            .filter(|&x| !jobs_for_asset::<TestJob>(log, x).is_empty())
+            .collect();
+        rebuilt_assets.sort();
+        rebuilt_assets
+    };
+    let sorted = |mut asset_ids: Vec<AssetId>| {
+        asset_ids.sort();
+        asset_ids
+    };
+
+    build(&mut pipeline, BuildRoots::All);
+    let manifest = project.latest_manifest();
+    assert!(!manifest.artifacts.contains_key(&artifact_id(d)));
+
+    // Nothing changed, but the failed asset is tried again
+    let log = build(&mut pipeline, BuildRoots::Changed);
+    assert_eq!(rebuilt_assets(&log), vec![d]);
+    assert_eq!(project.latest_manifest().artifacts, manifest.artifacts);
+
+    // a read b, c didn't read anything that changed
+    let editor_model = pipeline.editor_model_mut();
+    set_property(editor_model, b, "value", Value::F32(2.0));
+    set_property(editor_model, d, "fail", Value::Boolean(false));
+    editor_model.save_root_edit_context();
+    let log = build(&mut pipeline, BuildRoots::Changed);
+    assert_eq!(rebuilt_assets(&log), sorted(vec![a, b, d]));
+    let changed_manifest = project.latest_manifest();
+    assert_eq!(changed_manifest.artifacts.len(), 4);
+    assert_ne!(
+        changed_manifest.artifacts[&artifact_id(a)].build_hash,
+        manifest.artifacts[&artifact_id(a)].build_hash
+    );
+    assert_eq!(
+        changed_manifest.artifacts[&artifact_id(c)],
+        manifest.artifacts[&artifact_id(c)]
+    );
+
+    let log = build(&mut pipeline, BuildRoots::Changed);
+    assert!(log.job_timings().is_empty());
+
+    // A deleted asset's artifacts are not carried over
+    let editor_model = pipeline.editor_model_mut();
+    editor_model
+        .root_edit_context_mut()
+        .delete_asset(c)
+        .unwrap();
+    editor_model.save_root_edit_context();
+    let log = build(&mut pipeline, BuildRoots::Changed);
+    assert!(log.job_timings().is_empty());
+    let manifest = project.latest_manifest();
+    assert_eq!(manifest.artifacts.len(), 3);
+    assert!(!manifest.artifacts.contains_key(&artifact_id(c)));
+
+    // The previous build was made with another profile, so everything is built
+    pipeline
+        .asset_engine_mut()
+        .set_active_build_profile("release")
+        .unwrap();
+    let log = build(&mut pipeline, BuildRoots::Changed);
+    assert_eq!(rebuilt_assets(&log), sorted(vec![a, b, d]));
+    let log = build(&mut pipeline, BuildRoots::Changed);
+    assert!(log.job_timings().is_empty());
+}
+
+// Assets with validation errors are reported as failed and not built, warnings are only reported
+#[test]
+fn validation_errors_skip_assets() {
+    let project = TestProject::new();
+    let (mut pipeline, _) = project.open();
+    let editor_model = pipeline.editor_model_mut();
+    let location = data_source_root("path_based");
+    let error = new_test_asset(editor_model, "error", location);
+    let warning = new_test_asset(editor_model, "warning", location);
+    let both = new_test_asset(editor_model, "both", location);
+    let valid = new_test_asset(editor_model, "valid", location);
+    new_directory(editor_model, "directory", location);
+    set_property(
+        editor_model,
+        error,
+        "validation_error",
+        Value::Boolean(true),
+    );
+    set_property(
+        editor_model,
+        warning,
+        "validation_warning",
+        Value::Boolean(true),
+    );
+    set_property(editor_model, both, "validation_error", Value::Boolean(true));
+    set_property(
+        editor_model,
+        both,
+        "validation_warning",
+        Value::Boolean(true),
+    );
+    editor_model.save_root_edit_context();
+
+    // Directories have no builder and aren't validated
+    let report = pipeline.validate().unwrap();
+    assert_eq!(report.validated_asset_count, 4);
+    assert_eq!(report.error_count(), 2);
+    assert_eq!(report.warning_count(), 2);
+    assert!(report.has_errors());
+    assert_eq!(report.issues_for_asset(both).count(), 2);
+    assert_eq!(report.issues_for_asset(valid).count(), 0);
+    let error_issues: Vec<_> = report.issues_for_asset(error).collect();
+    assert_eq!(error_issues.len(), 1);
+    assert!(matches!(error_issues[0].level, LogEventLevel::Error));
+    assert_eq!(
+        error_issues[0].to_text(),
+        "validation_error: validation_error is set"
+    );
+
+    let log = build(&mut pipeline, BuildRoots::All);
+    let mut failed_assets = log.failed_assets();
+    failed_assets.sort();
+    let mut expected_failed_assets = vec![error, both];
+    expected_failed_assets.sort();
+    assert_eq!(failed_assets, expected_failed_assets);
+    assert!(jobs_for_asset::<TestJob>(&log, error).is_empty());
+    assert!(jobs_for_asset::<TestJob>(&log, both).is_empty());
+    assert!(log.log_events().iter().any(|x| {
+        x.asset_id == Some(warning)
+            && matches!(x.level, LogEventLevel::Warning)
+            && x.message.starts_with("Validation: ")
+    }));
+
+    let manifest = project.latest_manifest();
+    let mut built_assets: Vec<_> = manifest
+        .artifacts
+        .keys()
+        .map(|x| AssetId::from_uuid(x.as_uuid()))
+        .collect();
+    built_assets.sort();
+    let mut expected_built_assets = vec![warning, valid];
+    expected_built_assets.sort();
+    assert_eq!(built_assets, expected_built_assets);
+}
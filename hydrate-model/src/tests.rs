@@ -1,12 +1,18 @@
 use crate::edit_context::EditContext;
 use crate::{
-    AssetLocation, AssetPath, AssetSourceId, EditContextKey, NullOverride, OverrideBehavior,
-    SchemaDefType, SchemaLinker, SchemaLinkerResult, SchemaSet, UndoStack, Value,
+    AssetLocation, AssetPath, AssetSourceId, EditContextKey, EndContextBehavior, NullOverride,
+    OverrideBehavior, SchemaDefType, SchemaLinker, SchemaLinkerResult, SchemaSet, UndoStack, Value,
 };
 use hydrate_base::AssetId;
-use hydrate_data::{AssetName, SchemaSetBuilder};
+use hydrate_data::json_storage::DataSetJson;
+use hydrate_data::{
+    AssetName, CanonicalPathReference, DataSetAssetDiff, HashObjectMode, ImportableName,
+    PathReferenceNamespaceResolver, SchemaSetBuilder,
+};
 use hydrate_pipeline::HydrateProjectConfiguration;
 use hydrate_schema::Schema::Nullable;
+use slotmap::DenseSlotMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use uuid::Uuid;
 
@@ -30,10 +36,12 @@ fn default_project_config() -> HydrateProjectConfiguration {
         import_data_path: Default::default(),
         build_data_path: Default::default(),
         job_data_path: Default::default(),
+        undo_history_path: Default::default(),
         id_based_asset_sources: vec![],
         path_based_asset_sources: vec![],
         source_file_locations: vec![],
         schema_codegen_jobs: vec![],
+        build_profiles: vec![Default::default()],
     }
 }
 
@@ -279,6 +287,665 @@ fn set_simple_property_override() {
     assert_eq!(db.get_property_override(obj2, "x").unwrap().is_none(), true);
 }
 
+#[test]
+fn undo_tree_branches_and_persistence() {
+    let mut linker = SchemaLinker::default();
+    create_vec3_schema(&mut linker).unwrap();
+
+    let mut schema_set_builder = SchemaSetBuilder::default();
+    schema_set_builder.add_linked_types(linker).unwrap();
+    let schema_set = schema_set_builder.build();
+
+    let mut undo_stack = UndoStack::default();
+    let project_config = default_project_config();
+    let mut edit_contexts: DenseSlotMap<EditContextKey, EditContext> = Default::default();
+    let key = edit_contexts.insert_with_key(|key| {
+        EditContext::new(&project_config, key, schema_set.clone(), &undo_stack)
+    });
+    let asset_location = asset_location();
+
+    let vec3_type = schema_set
+        .find_named_type("Vec3")
+        .unwrap()
+        .as_record()
+        .unwrap()
+        .clone();
+
+    let mut obj = AssetId::null();
+    let db = edit_contexts.get_mut(key).unwrap();
+    db.with_undo_context("create", |db| {
+        obj = db.new_asset(&AssetName::new("obj1"), &asset_location, &vec3_type);
+        EndContextBehavior::Finish
+    });
+    db.with_undo_context("set x", |db| {
+        db.set_property_override(obj, "x", Some(Value::F32(10.0)))
+            .unwrap();
+        EndContextBehavior::Finish
+    });
+
+    // Undo and make a different edit, the undone step should be kept as a separate branch
+    undo_stack.undo(&mut edit_contexts).unwrap();
+    let db = edit_contexts.get_mut(key).unwrap();
+    assert_eq!(
+        db.resolve_property(obj, "x").unwrap().as_f32().unwrap(),
+        0.0
+    );
+    db.with_undo_context("set x again", |db| {
+        db.set_property_override(obj, "x", Some(Value::F32(20.0)))
+            .unwrap();
+        EndContextBehavior::Finish
+    });
+    undo_stack.undo(&mut edit_contexts).unwrap();
+    undo_stack.redo(&mut edit_contexts).unwrap();
+
+    let create_step = undo_stack.asset_history(obj)[0];
+    let branches = undo_stack.children(Some(create_step));
+    assert_eq!(branches.len(), 2);
+    assert_eq!(undo_stack.current_step(), Some(branches[1]));
+    assert_eq!(undo_stack.asset_history_all_branches(obj).len(), 3);
+
+    // Jump back to the abandoned branch
+    undo_stack
+        .jump_to_step(&mut edit_contexts, Some(branches[0]))
+        .unwrap();
+    assert_eq!(
+        edit_contexts
+            .get(key)
+            .unwrap()
+            .resolve_property(obj, "x")
+            .unwrap()
+            .as_f32()
+            .unwrap(),
+        10.0
+    );
+    assert_eq!(
+        undo_stack.asset_history(obj),
+        vec![create_step, branches[0]]
+    );
+
+    // Round trip the history, it should only load against matching data
+    let json = undo_stack.save_to_string(key, 1234);
+    let mut loaded_undo_stack = UndoStack::default();
+    assert!(!loaded_undo_stack
+        .load_from_string(&json, key, 5678, &schema_set, &project_config)
+        .unwrap());
+    assert!(loaded_undo_stack
+        .load_from_string(&json, key, 1234, &schema_set, &project_config)
+        .unwrap());
+    assert_eq!(loaded_undo_stack.steps().len(), 3);
+    assert_eq!(loaded_undo_stack.current_step(), Some(branches[0]));
+    assert_eq!(
+        loaded_undo_stack.step(branches[1]).unwrap().context_name(),
+        "set x again"
+    );
+
+    // Undoing everything with the loaded history should delete the asset
+    loaded_undo_stack
+        .jump_to_step(&mut edit_contexts, None)
+        .unwrap();
+    assert!(!edit_contexts.get(key).unwrap().has_asset(obj));
+    loaded_undo_stack.redo(&mut edit_contexts).unwrap();
+    loaded_undo_stack.redo(&mut edit_contexts).unwrap();
+    assert_eq!(
+        edit_contexts
+            .get(key)
+            .unwrap()
+            .resolve_property(obj, "x")
+            .unwrap()
+            .as_f32()
+            .unwrap(),
+        10.0
+    );
+}
+
+#[test]
+fn asset_history_preview_and_restore() {
+    let mut linker = SchemaLinker::default();
+    create_vec3_schema(&mut linker).unwrap();
+
+    let mut schema_set_builder = SchemaSetBuilder::default();
+    schema_set_builder.add_linked_types(linker).unwrap();
+    let schema_set = schema_set_builder.build();
+
+    let mut undo_stack = UndoStack::default();
+    let project_config = default_project_config();
+    let mut edit_contexts: DenseSlotMap<EditContextKey, EditContext> = Default::default();
+    let key = edit_contexts.insert_with_key(|key| {
+        EditContext::new(&project_config, key, schema_set.clone(), &undo_stack)
+    });
+    let asset_location = asset_location();
+
+    let vec3_type = schema_set
+        .find_named_type("Vec3")
+        .unwrap()
+        .as_record()
+        .unwrap()
+        .clone();
+
+    let mut obj1 = AssetId::null();
+    let mut obj2 = AssetId::null();
+    let db = edit_contexts.get_mut(key).unwrap();
+    db.with_undo_context("create", |db| {
+        obj1 = db.new_asset(&AssetName::new("obj1"), &asset_location, &vec3_type);
+        obj2 = db.new_asset(&AssetName::new("obj2"), &asset_location, &vec3_type);
+        EndContextBehavior::Finish
+    });
+    db.with_undo_context("set obj1 x", |db| {
+        db.set_property_override(obj1, "x", Some(Value::F32(10.0)))
+            .unwrap();
+        EndContextBehavior::Finish
+    });
+    db.with_undo_context("set obj2 y", |db| {
+        db.set_property_override(obj2, "y", Some(Value::F32(5.0)))
+            .unwrap();
+        EndContextBehavior::Finish
+    });
+    db.with_undo_context("set obj1 x again", |db| {
+        db.set_property_override(obj1, "x", Some(Value::F32(20.0)))
+            .unwrap();
+        EndContextBehavior::Finish
+    });
+    undo_stack.drain_rx();
+
+    let journal = undo_stack.asset_journal(obj1, key);
+    assert_eq!(journal.len(), 3);
+    assert!(matches!(journal[0].diff, DataSetAssetDiff::Create(_)));
+    assert_eq!(journal[1].context_name, "set obj1 x");
+    match &journal[2].diff {
+        DataSetAssetDiff::Change(diff) => assert_eq!(diff.modified_property_paths(), vec!["x"]),
+        _ => panic!("expected a change"),
+    }
+    let set_x_step = journal[1].undo_step_id;
+
+    // Preview only contains the one asset, with changes after the step reverted
+    let db = edit_contexts.get(key).unwrap();
+    let preview = undo_stack
+        .preview_asset_at_step(db.data_set(), &schema_set, key, obj1, Some(set_x_step))
+        .unwrap();
+    assert_eq!(preview.assets().len(), 1);
+    assert_eq!(
+        preview
+            .resolve_property(&schema_set, obj1, "x")
+            .unwrap()
+            .as_f32()
+            .unwrap(),
+        10.0
+    );
+    let preview_before_creation = undo_stack
+        .preview_asset_at_step(db.data_set(), &schema_set, key, obj1, None)
+        .unwrap();
+    assert!(preview_before_creation.assets().is_empty());
+
+    // Restoring is a new undoable step and leaves other assets alone
+    let db = edit_contexts.get_mut(key).unwrap();
+    db.with_undo_context("restore", |db| {
+        db.copy_asset_from(&preview, obj1).unwrap();
+        EndContextBehavior::Finish
+    });
+    assert_eq!(
+        db.resolve_property(obj1, "x").unwrap().as_f32().unwrap(),
+        10.0
+    );
+    assert_eq!(
+        db.resolve_property(obj2, "y").unwrap().as_f32().unwrap(),
+        5.0
+    );
+
+    undo_stack.undo(&mut edit_contexts).unwrap();
+    assert_eq!(
+        edit_contexts
+            .get(key)
+            .unwrap()
+            .resolve_property(obj1, "x")
+            .unwrap()
+            .as_f32()
+            .unwrap(),
+        20.0
+    );
+}
+
+struct TestNamespaceResolver;
+
+impl PathReferenceNamespaceResolver for TestNamespaceResolver {
+    fn namespace_root(
+        &self,
+        namespace: &str,
+    ) -> Option<PathBuf> {
+        (namespace == "assets").then(|| PathBuf::from("/project/assets"))
+    }
+
+    fn simplify_path(
+        &self,
+        path: &Path,
+    ) -> Option<(String, PathBuf)> {
+        path.strip_prefix("/project/assets")
+            .ok()
+            .map(|x| ("assets".to_string(), x.to_path_buf()))
+    }
+}
+
+#[test]
+fn relocate_path_references() {
+    let resolver = TestNamespaceResolver;
+    let importable_name = ImportableName::new("mesh".to_string());
+    let path_reference = CanonicalPathReference::new(
+        &resolver,
+        "assets".to_string(),
+        "models/a.gltf".to_string(),
+        importable_name.clone(),
+    );
+
+    // References to other files are left alone
+    assert!(path_reference
+        .relocate(
+            &resolver,
+            Path::new("/project/assets/models/b.gltf"),
+            Path::new("/project/assets/moved/b.gltf"),
+        )
+        .is_none());
+
+    // Moving within the namespace keeps the namespace and importable name
+    let relocated = path_reference
+        .relocate(
+            &resolver,
+            Path::new("/project/assets/models/a.gltf"),
+            Path::new("/project/assets/moved/a.gltf"),
+        )
+        .unwrap();
+    assert_eq!(relocated.namespace(), "assets");
+    assert_eq!(Path::new(relocated.path()), Path::new("moved/a.gltf"));
+    assert_eq!(relocated.importable_name(), &importable_name);
+
+    // Moving out of the namespace falls back to an absolute path
+    let relocated = path_reference
+        .relocate(
+            &resolver,
+            Path::new("/project/assets/models/a.gltf"),
+            Path::new("/elsewhere/a.gltf"),
+        )
+        .unwrap();
+    assert_eq!(relocated.namespace(), "");
+    assert_eq!(Path::new(relocated.path()), Path::new("/elsewhere/a.gltf"));
+}
+
+// Job workers receive the data set as a DataSetJson, the copy must hash the same as the original
+// so that job cache entries written by workers are valid in the editor
+#[test]
+fn data_set_json_round_trip() {
+    let mut linker = SchemaLinker::default();
+    create_vec3_schema(&mut linker).unwrap();
+
+    let mut schema_set_builder = SchemaSetBuilder::default();
+    schema_set_builder.add_linked_types(linker).unwrap();
+    let schema_set = schema_set_builder.build();
+
+    let undo_stack = UndoStack::default();
+    let project_config = default_project_config();
+    let mut db = EditContext::new(
+        &project_config,
+        EditContextKey::default(),
+        schema_set.clone(),
+        &undo_stack,
+    );
+
+    let vec3_type = schema_set
+        .find_named_type("Vec3")
+        .unwrap()
+        .as_record()
+        .unwrap()
+        .clone();
+
+    let obj = db.new_asset(&AssetName::new("obj1"), &asset_location(), &vec3_type);
+    db.set_property_override(obj, "x", Some(Value::F32(10.0)))
+        .unwrap();
+
+    let data_set_json = serde_json::to_string(&DataSetJson::new(db.data_set())).unwrap();
+    let data_set = serde_json::from_str::<DataSetJson>(&data_set_json)
+        .unwrap()
+        .to_data_set(&schema_set, &TestNamespaceResolver)
+        .unwrap();
+
+    assert_eq!(
+        data_set
+            .hash_object(obj, HashObjectMode::PropertiesOnly)
+            .unwrap(),
+        db.data_set()
+            .hash_object(obj, HashObjectMode::PropertiesOnly)
+            .unwrap()
+    );
+    assert_eq!(
+        data_set
+            .resolve_property(&schema_set, obj, "x")
+            .unwrap()
+            .as_f32()
+            .unwrap(),
+        10.0
+    );
+}
+
+// Old builds are garbage unless they are among the newest builds kept or a running game pinned them
+#[test]
+fn build_output_garbage_collection() {
+    use hydrate_base::build_pin::build_pin_path;
+    use hydrate_base::release_manifest::{write_release_manifest, ReleaseManifestEntry};
+    use hydrate_base::uuid_path::uuid_and_hash_to_path;
+    use hydrate_base::ArtifactId;
+    use hydrate_pipeline::{delete_build_output_garbage, find_build_output_garbage};
+
+    let build_data_path = std::env::temp_dir().join(format!("hydrate-gc-test-{}", Uuid::new_v4()));
+    let mut project_config = default_project_config();
+    project_config.build_data_path = build_data_path.clone();
+
+    // Two builds, each with one artifact
+    let mut artifact_paths = Vec::default();
+    for build in 1..=2u64 {
+        let artifact_id = ArtifactId::from_u128(build as u128);
+        let artifact_path =
+            uuid_and_hash_to_path(&build_data_path, artifact_id.as_uuid(), build, "bf");
+        std::fs::create_dir_all(artifact_path.parent().unwrap()).unwrap();
+        std::fs::write(&artifact_path, [0u8; 100]).unwrap();
+        artifact_paths.push(artifact_path);
+
+        let manifest_dir_path = build_data_path.join("manifests");
+        std::fs::create_dir_all(&manifest_dir_path).unwrap();
+        let manifest_file = std::fs::File::create(
+            manifest_dir_path.join(format!("{:0>16x}.manifest_release", build)),
+        )
+        .unwrap();
+        write_release_manifest(
+            manifest_file,
+            &mut [ReleaseManifestEntry {
+                artifact_id,
+                build_hash: build,
+                combined_build_hash: build,
+                artifact_type: Uuid::from_u128(5),
+                symbol_hash: 0,
+            }],
+        )
+        .unwrap();
+
+        std::fs::create_dir_all(build_data_path.join("toc")).unwrap();
+        std::fs::write(
+            build_data_path
+                .join("toc")
+                .join(format!("{:0>16x}.toc", build)),
+            format!("{:0>16x}", build),
+        )
+        .unwrap();
+    }
+
+    let garbage = find_build_output_garbage(&project_config, 1).unwrap();
+    assert_eq!(garbage.tocs.len(), 1);
+    assert_eq!(garbage.manifests.len(), 1);
+    assert_eq!(garbage.artifacts, vec![artifact_paths[0].clone()]);
+    let garbage_size_in_bytes: u64 = garbage
+        .tocs
+        .iter()
+        .chain(&garbage.manifests)
+        .chain(&garbage.artifacts)
+        .map(|x| x.metadata().unwrap().len())
+        .sum();
+    assert_eq!(garbage.garbage_size_in_bytes, garbage_size_in_bytes);
+
+    // A game that has the first build loaded keeps it alive
+    let pin_path = build_pin_path(&build_data_path, 1, 1234);
+    std::fs::create_dir_all(pin_path.parent().unwrap()).unwrap();
+    std::fs::write(&pin_path, "1234").unwrap();
+    assert!(find_build_output_garbage(&project_config, 1)
+        .unwrap()
+        .is_empty());
+
+    std::fs::remove_file(&pin_path).unwrap();
+    delete_build_output_garbage(&garbage).unwrap();
+    assert!(!artifact_paths[0].exists());
+    assert!(artifact_paths[1].exists());
+    assert!(find_build_output_garbage(&project_config, 1)
+        .unwrap()
+        .is_empty());
+
+    std::fs::remove_dir_all(&build_data_path).unwrap();
+}
+
+#[test]
+fn manifest_diff() {
+    use hydrate_base::{ArtifactId, DebugArtifactManifestDataJson, DebugManifestFileJson};
+    use hydrate_pipeline::{ManifestArtifactInfo, ManifestContents, ManifestDiff};
+
+    let artifact =
+        |id: u128, build_hash: u64, combined_build_hash: u64| DebugArtifactManifestDataJson {
+            artifact_id: ArtifactId::from_u128(id),
+            build_hash: format!("{:0>16x}", build_hash),
+            combined_build_hash: format!("{:0>16x}", combined_build_hash),
+            symbol_name: String::default(),
+            symbol_hash: format!("{:0>32x}", 0),
+            artifact_type: Uuid::from_u128(5),
+            debug_name: format!("artifact {}", id),
+        };
+
+    let old = ManifestContents::from_debug_manifest(&DebugManifestFileJson {
+        artifacts: vec![
+            artifact(1, 1, 1),
+            artifact(2, 2, 2),
+            artifact(3, 3, 3),
+            artifact(4, 4, 4),
+        ],
+    })
+    .unwrap();
+    let new = ManifestContents::from_debug_manifest(&DebugManifestFileJson {
+        artifacts: vec![
+            artifact(2, 20, 20),
+            artifact(3, 3, 30),
+            artifact(4, 4, 4),
+            artifact(5, 5, 5),
+        ],
+    })
+    .unwrap();
+
+    let diff = ManifestDiff::new(&old, &new);
+    let id = |x: &ManifestArtifactInfo| x.artifact_id.as_u128();
+    assert_eq!(diff.added.iter().map(id).collect::<Vec<_>>(), vec![5]);
+    assert_eq!(diff.removed.iter().map(id).collect::<Vec<_>>(), vec![1]);
+    assert_eq!(
+        diff.content_changed
+            .iter()
+            .map(|x| id(&x.1))
+            .collect::<Vec<_>>(),
+        vec![2]
+    );
+    assert_eq!(
+        diff.dependency_changed
+            .iter()
+            .map(|x| id(&x.1))
+            .collect::<Vec<_>>(),
+        vec![3]
+    );
+    assert!(ManifestDiff::new(&new, &new).is_empty());
+}
+
+#[test]
+fn build_patch() {
+    use hydrate_base::build_patch::build_patch_file_path;
+    use hydrate_base::release_manifest::{write_release_manifest, ReleaseManifestEntry};
+    use hydrate_base::uuid_path::uuid_and_hash_to_path;
+    use hydrate_base::ArtifactId;
+    use hydrate_pipeline::write_build_patch;
+
+    let test_path = std::env::temp_dir().join(format!("hydrate-patch-test-{}", Uuid::new_v4()));
+    let build_data_path = test_path.join("build_data");
+    let patch_path = test_path.join("patch");
+    std::fs::create_dir_all(build_data_path.join("manifests")).unwrap();
+
+    // Artifact 1 is in both builds, artifact 2 changes and artifact 3 is only in the second build
+    let builds: [(u64, &[(u128, u64)]); 2] =
+        [(1, &[(1, 1), (2, 2)]), (2, &[(1, 1), (2, 3), (3, 3)])];
+    for (manifest_build_hash, artifacts) in builds {
+        let mut entries = Vec::default();
+        for &(artifact_id, build_hash) in artifacts {
+            let artifact_id = ArtifactId::from_u128(artifact_id);
+            let artifact_path =
+                uuid_and_hash_to_path(&build_data_path, artifact_id.as_uuid(), build_hash, "bf");
+            std::fs::create_dir_all(artifact_path.parent().unwrap()).unwrap();
+            std::fs::write(&artifact_path, [0u8; 100]).unwrap();
+            entries.push(ReleaseManifestEntry {
+                artifact_id,
+                build_hash,
+                combined_build_hash: build_hash,
+                artifact_type: Uuid::from_u128(5),
+                symbol_hash: 0,
+            });
+        }
+
+        let manifest_file = std::fs::File::create(
+            build_data_path
+                .join("manifests")
+                .join(format!("{:0>16x}.manifest_release", manifest_build_hash)),
+        )
+        .unwrap();
+        write_release_manifest(manifest_file, &mut entries).unwrap();
+    }
+
+    let report = write_build_patch(&build_data_path, 1, 2, &patch_path).unwrap();
+    assert_eq!(report.diff.added.len(), 1);
+    assert_eq!(report.diff.content_changed.len(), 1);
+
+    let in_patch = |artifact_id: u128, build_hash: u64| {
+        let artifact_id = ArtifactId::from_u128(artifact_id);
+        uuid_and_hash_to_path(&patch_path, artifact_id.as_uuid(), build_hash, "bf").exists()
+    };
+    assert!(!in_patch(1, 1));
+    assert!(in_patch(2, 3));
+    assert!(in_patch(3, 3));
+    assert!(patch_path
+        .join("manifests")
+        .join(format!("{:0>16x}.manifest_release", 2))
+        .exists());
+    assert!(build_patch_file_path(&patch_path).exists());
+
+    // A patch is never written over another one
+    assert!(write_build_patch(&build_data_path, 1, 2, &patch_path).is_err());
+
+    std::fs::remove_dir_all(&test_path).unwrap();
+}
+
+#[test]
+fn build_graph_for_asset() {
+    use hydrate_base::ArtifactId;
+    use hydrate_pipeline::{
+        BuildGraph, BuildGraphArtifact, BuildGraphAsset, BuildGraphJob, JobId, JobRequestor,
+    };
+
+    let job = |id: u128, requested_by: Vec<JobRequestor>, upstream_jobs: Vec<u128>| BuildGraphJob {
+        job_id: JobId::from_u128(id),
+        job_type_name: "TestJob",
+        debug_name: format!("job {}", id),
+        requested_by,
+        upstream_jobs: upstream_jobs.into_iter().map(JobId::from_u128).collect(),
+        produced_artifacts: vec![ArtifactId::from_u128(id)],
+        used_cached_result: false,
+    };
+
+    // Asset 1 requests job 1, which requests job 2, which waits on job 3. Asset 2 requests job 3
+    // and job 4.
+    let asset_1 = AssetId::from_u128(1);
+    let asset_2 = AssetId::from_u128(2);
+    let graph = BuildGraph {
+        assets: vec![
+            BuildGraphAsset {
+                asset_id: asset_1,
+                name: "asset 1".to_string(),
+            },
+            BuildGraphAsset {
+                asset_id: asset_2,
+                name: "asset 2".to_string(),
+            },
+        ],
+        jobs: vec![
+            job(1, vec![JobRequestor::Builder(asset_1)], vec![]),
+            job(2, vec![JobRequestor::Job(JobId::from_u128(1))], vec![3]),
+            job(3, vec![JobRequestor::Builder(asset_2)], vec![]),
+            job(4, vec![JobRequestor::Builder(asset_2)], vec![]),
+        ],
+        artifacts: (1..=4)
+            .map(|id| BuildGraphArtifact {
+                artifact_id: ArtifactId::from_u128(id),
+                asset_id: if id == 1 { asset_1 } else { asset_2 },
+                artifact_key_debug_name: None,
+                produced_by: JobId::from_u128(id),
+                dependencies: vec![],
+            })
+            .collect(),
+    };
+
+    let asset_graph = graph.for_asset(asset_1);
+    let job_ids: Vec<_> = asset_graph
+        .jobs
+        .iter()
+        .map(|x| x.job_id.as_u128())
+        .collect();
+    assert_eq!(job_ids, vec![1, 2, 3]);
+    assert_eq!(asset_graph.artifacts.len(), 3);
+    assert_eq!(asset_graph.assets.len(), 1);
+
+    let dot = asset_graph.to_dot();
+    assert!(dot.starts_with("digraph"));
+    assert!(dot.contains(&format!(
+        "\"{}\" -> \"{}\" [style=dashed];",
+        JobId::from_u128(3).as_uuid(),
+        JobId::from_u128(2).as_uuid()
+    )));
+}
+
+#[test]
+fn data_set_clone_shares_unmodified_assets() {
+    use hydrate_data::DataSet;
+
+    let mut linker = SchemaLinker::default();
+    create_vec3_schema(&mut linker).unwrap();
+
+    let mut schema_set_builder = SchemaSetBuilder::default();
+    schema_set_builder.add_linked_types(linker).unwrap();
+    let schema_set = schema_set_builder.build();
+
+    let vec3_type = schema_set
+        .find_named_type("Vec3")
+        .unwrap()
+        .as_record()
+        .unwrap()
+        .clone();
+
+    let mut data_set = DataSet::default();
+    let edited = data_set.new_asset(AssetName::new("edited"), asset_location(), &vec3_type);
+    let untouched = data_set.new_asset(AssetName::new("untouched"), asset_location(), &vec3_type);
+    data_set
+        .set_property_override(&schema_set, edited, "x", Some(Value::F32(1.0)))
+        .unwrap();
+
+    // Editing continues after the snapshot is taken, the snapshot keeps the old values
+    let snapshot = data_set.clone();
+    data_set
+        .set_property_override(&schema_set, edited, "x", Some(Value::F32(2.0)))
+        .unwrap();
+
+    let resolve_x = |data_set: &DataSet| {
+        data_set
+            .resolve_property(&schema_set, edited, "x")
+            .unwrap()
+            .as_f32()
+            .unwrap()
+    };
+    assert_eq!(resolve_x(&snapshot), 1.0);
+    assert_eq!(resolve_x(&data_set), 2.0);
+
+    assert!(!Arc::ptr_eq(
+        &snapshot.assets()[&edited],
+        &data_set.assets()[&edited]
+    ));
+    assert!(Arc::ptr_eq(
+        &snapshot.assets()[&untouched],
+        &data_set.assets()[&untouched]
+    ));
+}
+
 // Tests below this point rotted
 
 /*
@@ -0,0 +1,246 @@
+use crate::{ManifestArtifactInfo, ManifestContents, PipelineResult};
+use hydrate_base::handle::DummySerdeContextHandle;
+use hydrate_base::hashing::{HashMap, HashSet};
+use hydrate_base::uuid_path::uuid_and_hash_to_path;
+use hydrate_base::{ArtifactId, BuiltArtifactHeaderData};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use type_uuid::TypeUuid;
+use uuid::Uuid;
+
+struct ArtifactDecoder {
+    type_name: &'static str,
+    decode_fn: fn(&[u8]) -> PipelineResult<serde_json::Value>,
+}
+
+fn decode_artifact<T: DeserializeOwned + Serialize>(
+    data: &[u8]
+) -> PipelineResult<serde_json::Value> {
+    // Handles in the artifact need a serde context. The dummy context turns them back into the
+    // artifact IDs they were serialized from.
+    let ctx = DummySerdeContextHandle::default();
+    ctx.scope(|| {
+        let artifact: T = bincode::deserialize(data)?;
+        Ok(serde_json::to_value(&artifact)?)
+    })
+}
+
+// Artifact types that can be pretty-printed when inspecting built artifacts. Types that aren't
+// registered are shown without their contents.
+#[derive(Default)]
+pub struct ArtifactDecoderRegistry {
+    decoders: HashMap<Uuid, ArtifactDecoder>,
+}
+
+impl ArtifactDecoderRegistry {
+    pub fn register_artifact_type<T: TypeUuid + DeserializeOwned + Serialize>(&mut self) {
+        let old = self.decoders.insert(
+            Uuid::from_bytes(T::UUID),
+            ArtifactDecoder {
+                type_name: std::any::type_name::<T>(),
+                decode_fn: decode_artifact::<T>,
+            },
+        );
+        if old.is_some() {
+            panic!("Multiple artifact decoders registered with the same UUID");
+        }
+    }
+
+    pub fn type_name(
+        &self,
+        artifact_type: Uuid,
+    ) -> Option<&'static str> {
+        self.decoders.get(&artifact_type).map(|x| x.type_name)
+    }
+
+    // Returns None if no decoder is registered for the type
+    pub fn decode(
+        &self,
+        artifact_type: Uuid,
+        data: &[u8],
+    ) -> Option<PipelineResult<serde_json::Value>> {
+        self.decoders
+            .get(&artifact_type)
+            .map(|decoder| (decoder.decode_fn)(data))
+    }
+}
+
+// A .bf file read from the build output
+pub struct BuiltArtifactFile {
+    pub path: PathBuf,
+    pub header: BuiltArtifactHeaderData,
+    pub data: Vec<u8>,
+    pub file_size: u64,
+}
+
+impl BuiltArtifactFile {
+    pub fn read(
+        build_data_path: &Path,
+        artifact_id: ArtifactId,
+        build_hash: u64,
+    ) -> PipelineResult<Self> {
+        let path = uuid_and_hash_to_path(build_data_path, artifact_id.as_uuid(), build_hash, "bf");
+        let bytes =
+            std::fs::read(&path).map_err(|e| format!("Could not read {:?}: {}", path, e))?;
+
+        // The header is prefixed with its length, the artifact's data is the rest of the file
+        let mut reader = bytes.as_slice();
+        let header = BuiltArtifactHeaderData::read_header(&mut reader)
+            .map_err(|e| format!("Could not read the header of {:?}: {}", path, e))?;
+        let data = reader.to_vec();
+
+        Ok(BuiltArtifactFile {
+            path,
+            header,
+            data,
+            file_size: bytes.len() as u64,
+        })
+    }
+}
+
+impl ManifestContents {
+    // Finds an artifact by ID, symbol name or debug name. Names are only available if the build
+    // wrote a debug manifest.
+    pub fn find_artifact(
+        &self,
+        id_or_name: &str,
+    ) -> Option<&ManifestArtifactInfo> {
+        if let Ok(artifact_id) = ArtifactId::parse_str(id_or_name) {
+            if let Some(artifact) = self.artifacts.get(&artifact_id) {
+                return Some(artifact);
+            }
+        }
+
+        self.artifacts.values().find(|x| {
+            x.symbol_name.as_deref() == Some(id_or_name)
+                || x.debug_name.as_deref() == Some(id_or_name)
+        })
+    }
+}
+
+fn write_dependency_tree(
+    text: &mut String,
+    build_data_path: &Path,
+    manifest: &ManifestContents,
+    dependencies: &[ArtifactId],
+    depth: usize,
+    visited: &mut HashSet<ArtifactId>,
+) {
+    for &dependency in dependencies {
+        let indent = "  ".repeat(depth);
+        let Some(artifact) = manifest.artifacts.get(&dependency) else {
+            writeln!(text, "{}[{:?}] not in manifest", indent, dependency).unwrap();
+            continue;
+        };
+
+        write!(
+            text,
+            "{}{} [{:?}] type: {} build hash: {:0>16x}",
+            indent,
+            artifact.display_name(),
+            artifact.artifact_id,
+            artifact.artifact_type,
+            artifact.build_hash
+        )
+        .unwrap();
+
+        // Artifacts can be reached through more than one path, only expand them once
+        if !visited.insert(dependency) {
+            writeln!(text, " (listed above)").unwrap();
+            continue;
+        }
+
+        match BuiltArtifactFile::read(build_data_path, dependency, artifact.build_hash) {
+            Ok(file) => {
+                writeln!(text, " size: {} bytes", file.file_size).unwrap();
+                write_dependency_tree(
+                    text,
+                    build_data_path,
+                    manifest,
+                    &file.header.dependencies,
+                    depth + 1,
+                    visited,
+                );
+            }
+            Err(e) => writeln!(text, " {}", e).unwrap(),
+        }
+    }
+}
+
+// Describes a built artifact and everything it depends on. The artifact's contents are included
+// if a decoder is registered for its type.
+pub fn inspect_built_artifact(
+    build_data_path: &Path,
+    manifest: &ManifestContents,
+    artifact_id: ArtifactId,
+    decoders: &ArtifactDecoderRegistry,
+) -> PipelineResult<String> {
+    let artifact = manifest
+        .artifacts
+        .get(&artifact_id)
+        .ok_or_else(|| format!("Artifact {:?} is not in the manifest", artifact_id))?;
+    let file = BuiltArtifactFile::read(build_data_path, artifact_id, artifact.build_hash)?;
+
+    // Writing to a String can't fail
+    let mut text = String::default();
+    writeln!(text, "{} [{:?}]", artifact.display_name(), artifact_id).unwrap();
+    writeln!(
+        text,
+        "  type: {} {}",
+        artifact.artifact_type,
+        decoders
+            .type_name(artifact.artifact_type)
+            .unwrap_or("(no decoder registered)")
+    )
+    .unwrap();
+    if let Some(symbol_name) = &artifact.symbol_name {
+        writeln!(text, "  symbol: {}", symbol_name).unwrap();
+    }
+    writeln!(text, "  build hash: {:0>16x}", artifact.build_hash).unwrap();
+    writeln!(
+        text,
+        "  combined build hash: {:0>16x}",
+        artifact.combined_build_hash
+    )
+    .unwrap();
+    writeln!(text, "  file: {:?}", file.path).unwrap();
+    writeln!(
+        text,
+        "  size: {} bytes ({} bytes of data)",
+        file.file_size,
+        file.data.len()
+    )
+    .unwrap();
+    if file.header.asset_type != artifact.artifact_type {
+        writeln!(
+            text,
+            "  header type {} does not match the manifest",
+            file.header.asset_type
+        )
+        .unwrap();
+    }
+
+    writeln!(text, "\nDependencies:").unwrap();
+    let mut visited = HashSet::default();
+    visited.insert(artifact_id);
+    write_dependency_tree(
+        &mut text,
+        build_data_path,
+        manifest,
+        &file.header.dependencies,
+        1,
+        &mut visited,
+    );
+
+    if let Some(decoded) = decoders.decode(artifact.artifact_type, &file.data) {
+        writeln!(text, "\nContents:").unwrap();
+        match decoded {
+            Ok(value) => writeln!(text, "{}", serde_json::to_string_pretty(&value)?).unwrap(),
+            Err(e) => writeln!(text, "Could not decode artifact: {}", e).unwrap(),
+        }
+    }
+
+    Ok(text)
+}
@@ -0,0 +1,193 @@
+use super::{Builder, BuilderRegistry};
+use crate::{BuildProfile, LogEventLevel, PipelineResult};
+use hydrate_data::{AssetId, DataContainerRef, DataSet, SchemaSet};
+use hydrate_schema::PropertyPath;
+use std::cell::RefCell;
+
+// A problem with an asset found by Builder::validate()
+#[derive(Clone)]
+pub struct AssetValidationIssue {
+    pub asset_id: AssetId,
+    // The property the issue is about, if it is about a particular property
+    pub property_path: Option<PropertyPath>,
+    pub level: LogEventLevel,
+    pub message: String,
+}
+
+impl AssetValidationIssue {
+    pub fn to_text(&self) -> String {
+        match &self.property_path {
+            Some(property_path) => format!("{}: {}", property_path.path(), self.message),
+            None => self.message.clone(),
+        }
+    }
+}
+
+pub struct ValidationContext<'a> {
+    pub asset_id: AssetId,
+    pub data_set: &'a DataSet,
+    pub schema_set: &'a SchemaSet,
+    pub build_profile: &'a BuildProfile,
+    pub(crate) issues: &'a RefCell<Vec<AssetValidationIssue>>,
+}
+
+impl<'a> ValidationContext<'a> {
+    pub fn asset_data_container(&self) -> DataContainerRef<'a> {
+        DataContainerRef::from_dataset(self.data_set, self.schema_set, self.asset_id)
+    }
+
+    pub fn warn<T: Into<String>>(
+        &self,
+        message: T,
+    ) {
+        self.add_issue(None, LogEventLevel::Warning, message.into());
+    }
+
+    pub fn error<T: Into<String>>(
+        &self,
+        message: T,
+    ) {
+        self.add_issue(None, LogEventLevel::Error, message.into());
+    }
+
+    pub fn warn_property<T: Into<String>>(
+        &self,
+        property_path: impl AsRef<str>,
+        message: T,
+    ) {
+        self.add_issue(
+            Some(PropertyPath::default().push(property_path.as_ref())),
+            LogEventLevel::Warning,
+            message.into(),
+        );
+    }
+
+    pub fn error_property<T: Into<String>>(
+        &self,
+        property_path: impl AsRef<str>,
+        message: T,
+    ) {
+        self.add_issue(
+            Some(PropertyPath::default().push(property_path.as_ref())),
+            LogEventLevel::Error,
+            message.into(),
+        );
+    }
+
+    fn add_issue(
+        &self,
+        property_path: Option<PropertyPath>,
+        level: LogEventLevel,
+        message: String,
+    ) {
+        self.issues.borrow_mut().push(AssetValidationIssue {
+            asset_id: self.asset_id,
+            property_path,
+            level,
+            message,
+        });
+    }
+}
+
+// Issues found by validating a set of assets
+#[derive(Default)]
+pub struct AssetValidationReport {
+    // Assets that have a builder, others can't be validated
+    pub validated_asset_count: usize,
+    pub issues: Vec<AssetValidationIssue>,
+}
+
+impl AssetValidationReport {
+    pub fn issues_for_asset(
+        &self,
+        asset_id: AssetId,
+    ) -> impl Iterator<Item = &AssetValidationIssue> {
+        self.issues.iter().filter(move |x| x.asset_id == asset_id)
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|x| matches!(x.level, LogEventLevel::Warning))
+            .count()
+    }
+
+    // Counts Error and FatalError issues
+    pub fn error_count(&self) -> usize {
+        self.issues
+            .iter()
+            .filter(|x| matches!(x.level, LogEventLevel::Error | LogEventLevel::FatalError))
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+}
+
+// Runs a builder's validation for one asset. A validate() that returns an error is reported as a
+// fatal issue.
+pub(crate) fn validate_asset(
+    builder: &dyn Builder,
+    asset_id: AssetId,
+    data_set: &DataSet,
+    schema_set: &SchemaSet,
+    build_profile: &BuildProfile,
+) -> Vec<AssetValidationIssue> {
+    let issues = RefCell::default();
+    let result: PipelineResult<()> = builder.validate(ValidationContext {
+        asset_id,
+        data_set,
+        schema_set,
+        build_profile,
+        issues: &issues,
+    });
+
+    let mut issues = issues.into_inner();
+    if let Err(e) = result {
+        issues.push(AssetValidationIssue {
+            asset_id,
+            property_path: None,
+            level: LogEventLevel::FatalError,
+            message: format!("validate returned error: {}", e.to_string()),
+        });
+    }
+
+    issues
+}
+
+// Checks assets for problems without building them. Nothing is imported or built, so this is fast
+// enough to run over a whole project. Assets without a builder are skipped.
+pub fn validate_assets(
+    builder_registry: &BuilderRegistry,
+    data_set: &DataSet,
+    schema_set: &SchemaSet,
+    build_profile: &BuildProfile,
+    asset_ids: impl IntoIterator<Item = AssetId>,
+) -> AssetValidationReport {
+    profiling::scope!("validate_assets");
+
+    let mut report = AssetValidationReport::default();
+    for asset_id in asset_ids {
+        let Some(asset_schema) = data_set.asset_schema(asset_id) else {
+            continue;
+        };
+
+        let Some(builder) = builder_registry.builder_for_asset(asset_schema.fingerprint()) else {
+            continue;
+        };
+
+        report.validated_asset_count += 1;
+        report.issues.extend(validate_asset(
+            &**builder,
+            asset_id,
+            data_set,
+            schema_set,
+            build_profile,
+        ));
+    }
+
+    // Sort so that issues are reported in a consistent order
+    report.issues.sort_by_key(|x| x.asset_id);
+    report
+}
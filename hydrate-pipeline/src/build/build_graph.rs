@@ -0,0 +1,365 @@
+use crate::build::JobRequestor;
+use crate::{BuildLogData, DynEditorModel, JobId, PipelineResult};
+use hydrate_base::hashing::{HashMap, HashSet};
+use hydrate_base::{ArtifactId, AssetId};
+use std::fmt::Write;
+use std::path::Path;
+
+// An asset whose builder requested jobs
+#[derive(Debug, Clone)]
+pub struct BuildGraphAsset {
+    pub asset_id: AssetId,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildGraphJob {
+    pub job_id: JobId,
+    pub job_type_name: &'static str,
+    pub debug_name: String,
+    // Everything that enqueued the job. A job with the same inputs is only run once, so it may
+    // have been requested many times.
+    pub requested_by: Vec<JobRequestor>,
+    // Jobs that had to finish before this job could run
+    pub upstream_jobs: Vec<JobId>,
+    pub produced_artifacts: Vec<ArtifactId>,
+    pub used_cached_result: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildGraphArtifact {
+    pub artifact_id: ArtifactId,
+    pub asset_id: AssetId,
+    pub artifact_key_debug_name: Option<String>,
+    pub produced_by: JobId,
+    // Artifacts this artifact holds handles to, they are loaded along with it
+    pub dependencies: Vec<ArtifactId>,
+}
+
+// The assets, jobs and artifacts of a completed build and how they are connected. Sorted so that
+// exports are stable between builds.
+#[derive(Debug, Clone, Default)]
+pub struct BuildGraph {
+    pub assets: Vec<BuildGraphAsset>,
+    pub jobs: Vec<BuildGraphJob>,
+    pub artifacts: Vec<BuildGraphArtifact>,
+}
+
+impl BuildGraph {
+    pub fn new(
+        log_data: &BuildLogData,
+        editor_model: &dyn DynEditorModel,
+    ) -> Self {
+        let mut asset_ids = HashSet::<AssetId>::default();
+        let mut jobs = Vec::default();
+        for (&job_id, job_timing) in log_data.job_timings() {
+            let requested_by = log_data.requestors(job_id).to_vec();
+            for requestor in &requested_by {
+                if let JobRequestor::Builder(asset_id) = requestor {
+                    asset_ids.insert(*asset_id);
+                }
+            }
+
+            let mut produced_artifacts: Vec<_> = log_data
+                .written_artifacts()
+                .get(&job_id)
+                .map(|x| x.iter().map(|x| x.artifact_id).collect())
+                .unwrap_or_default();
+            produced_artifacts.sort();
+
+            jobs.push(BuildGraphJob {
+                job_id,
+                job_type_name: job_timing.job_type_name,
+                debug_name: (*job_timing.debug_name).clone(),
+                requested_by,
+                upstream_jobs: job_timing.upstream_jobs.clone(),
+                produced_artifacts,
+                used_cached_result: job_timing.used_cached_result,
+            });
+        }
+        jobs.sort_by_key(|x| x.job_id);
+
+        let mut artifacts = Vec::default();
+        for (&job_id, written_artifacts) in log_data.written_artifacts() {
+            for written_artifact in written_artifacts {
+                artifacts.push(BuildGraphArtifact {
+                    artifact_id: written_artifact.artifact_id,
+                    asset_id: written_artifact.asset_id,
+                    artifact_key_debug_name: written_artifact.artifact_key_debug_name.clone(),
+                    produced_by: job_id,
+                    dependencies: written_artifact.metadata.dependencies.clone(),
+                });
+            }
+        }
+        artifacts.sort_by_key(|x| x.artifact_id);
+
+        let mut assets: Vec<_> = asset_ids
+            .into_iter()
+            .map(|asset_id| BuildGraphAsset {
+                asset_id,
+                name: editor_model.asset_display_name_long(asset_id),
+            })
+            .collect();
+        assets.sort_by_key(|x| x.asset_id);
+
+        BuildGraph {
+            assets,
+            jobs,
+            artifacts,
+        }
+    }
+
+    // The part of the graph that exists because of one asset: the jobs its builder requested, the
+    // jobs those requested in turn, and the artifacts they produced. Upstream jobs are included
+    // even if another asset requested them.
+    pub fn for_asset(
+        &self,
+        asset_id: AssetId,
+    ) -> BuildGraph {
+        let jobs_by_id: HashMap<_, _> = self.jobs.iter().map(|x| (x.job_id, x)).collect();
+
+        // A job is included if the asset or an included job requested it, or an included job
+        // waits on it
+        let mut requested_jobs = HashMap::<JobId, Vec<JobId>>::default();
+        let mut included_jobs = HashSet::<JobId>::default();
+        let mut pending_jobs = Vec::default();
+        for job in &self.jobs {
+            for requestor in &job.requested_by {
+                match requestor {
+                    JobRequestor::Builder(requestor_asset_id) => {
+                        if *requestor_asset_id == asset_id && included_jobs.insert(job.job_id) {
+                            pending_jobs.push(job.job_id);
+                        }
+                    }
+                    JobRequestor::Job(requestor_job_id) => requested_jobs
+                        .entry(*requestor_job_id)
+                        .or_default()
+                        .push(job.job_id),
+                }
+            }
+        }
+
+        while let Some(job_id) = pending_jobs.pop() {
+            let downstream = requested_jobs.get(&job_id).into_iter().flatten();
+            let upstream = jobs_by_id
+                .get(&job_id)
+                .into_iter()
+                .flat_map(|x| x.upstream_jobs.iter());
+            for &next_job_id in downstream.chain(upstream) {
+                if included_jobs.insert(next_job_id) {
+                    pending_jobs.push(next_job_id);
+                }
+            }
+        }
+
+        BuildGraph {
+            assets: self
+                .assets
+                .iter()
+                .filter(|x| x.asset_id == asset_id)
+                .cloned()
+                .collect(),
+            jobs: self
+                .jobs
+                .iter()
+                .filter(|x| included_jobs.contains(&x.job_id))
+                .cloned()
+                .collect(),
+            artifacts: self
+                .artifacts
+                .iter()
+                .filter(|x| included_jobs.contains(&x.produced_by))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let assets: Vec<_> = self
+            .assets
+            .iter()
+            .map(|asset| {
+                serde_json::json!({
+                    "asset_id": asset.asset_id.as_uuid().to_string(),
+                    "name": asset.name,
+                })
+            })
+            .collect();
+
+        let jobs: Vec<_> = self
+            .jobs
+            .iter()
+            .map(|job| {
+                let requested_by: Vec<_> = job
+                    .requested_by
+                    .iter()
+                    .map(|requestor| match requestor {
+                        JobRequestor::Builder(asset_id) => serde_json::json!({
+                            "asset_id": asset_id.as_uuid().to_string()
+                        }),
+                        JobRequestor::Job(job_id) => serde_json::json!({
+                            "job_id": job_id.as_uuid().to_string()
+                        }),
+                    })
+                    .collect();
+
+                serde_json::json!({
+                    "job_id": job.job_id.as_uuid().to_string(),
+                    "job_type": job.job_type_name,
+                    "debug_name": job.debug_name,
+                    "requested_by": requested_by,
+                    "upstream_jobs": job.upstream_jobs
+                        .iter()
+                        .map(|x| x.as_uuid().to_string())
+                        .collect::<Vec<_>>(),
+                    "produced_artifacts": job.produced_artifacts
+                        .iter()
+                        .map(|x| x.as_uuid().to_string())
+                        .collect::<Vec<_>>(),
+                    "cached": job.used_cached_result,
+                })
+            })
+            .collect();
+
+        let artifacts: Vec<_> = self
+            .artifacts
+            .iter()
+            .map(|artifact| {
+                serde_json::json!({
+                    "artifact_id": artifact.artifact_id.as_uuid().to_string(),
+                    "asset_id": artifact.asset_id.as_uuid().to_string(),
+                    "artifact_key": artifact.artifact_key_debug_name,
+                    "produced_by": artifact.produced_by.as_uuid().to_string(),
+                    "dependencies": artifact.dependencies
+                        .iter()
+                        .map(|x| x.as_uuid().to_string())
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "assets": assets,
+            "jobs": jobs,
+            "artifacts": artifacts,
+        })
+    }
+
+    // Graphviz DOT. Assets are boxes, jobs are ellipses (gray if the cached result was used) and
+    // artifacts are notes. Dashed edges are jobs waiting on upstream jobs, dotted edges are
+    // artifacts referencing other artifacts.
+    pub fn to_dot(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        }
+
+        // Writing to a String can't fail
+        let mut text = String::default();
+        writeln!(text, "digraph build {{").unwrap();
+        writeln!(text, "  rankdir=LR;").unwrap();
+
+        for asset in &self.assets {
+            writeln!(
+                text,
+                "  \"{}\" [shape=box, label=\"{}\"];",
+                asset.asset_id.as_uuid(),
+                escape(&asset.name)
+            )
+            .unwrap();
+        }
+
+        for job in &self.jobs {
+            writeln!(
+                text,
+                "  \"{}\" [shape=ellipse, label=\"{}\\n{}\"{}];",
+                job.job_id.as_uuid(),
+                escape(job.job_type_name),
+                escape(&job.debug_name),
+                if job.used_cached_result {
+                    ", style=filled, fillcolor=lightgray"
+                } else {
+                    ""
+                }
+            )
+            .unwrap();
+
+            for requestor in &job.requested_by {
+                let requestor_id = match requestor {
+                    JobRequestor::Builder(asset_id) => asset_id.as_uuid(),
+                    JobRequestor::Job(job_id) => job_id.as_uuid(),
+                };
+                writeln!(
+                    text,
+                    "  \"{}\" -> \"{}\";",
+                    requestor_id,
+                    job.job_id.as_uuid()
+                )
+                .unwrap();
+            }
+
+            for upstream_job in &job.upstream_jobs {
+                writeln!(
+                    text,
+                    "  \"{}\" -> \"{}\" [style=dashed];",
+                    upstream_job.as_uuid(),
+                    job.job_id.as_uuid()
+                )
+                .unwrap();
+            }
+        }
+
+        for artifact in &self.artifacts {
+            let label = match &artifact.artifact_key_debug_name {
+                Some(key) => format!("{}#{}", artifact.artifact_id.as_uuid(), key),
+                None => artifact.artifact_id.as_uuid().to_string(),
+            };
+            writeln!(
+                text,
+                "  \"{}\" [shape=note, label=\"{}\"];",
+                artifact.artifact_id.as_uuid(),
+                escape(&label)
+            )
+            .unwrap();
+            writeln!(
+                text,
+                "  \"{}\" -> \"{}\";",
+                artifact.produced_by.as_uuid(),
+                artifact.artifact_id.as_uuid()
+            )
+            .unwrap();
+
+            for dependency in &artifact.dependencies {
+                writeln!(
+                    text,
+                    "  \"{}\" -> \"{}\" [style=dotted];",
+                    artifact.artifact_id.as_uuid(),
+                    dependency.as_uuid()
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(text, "}}").unwrap();
+        text
+    }
+}
+
+// Writes <name>.build_graph.json and <name>.build_graph.dot into the given directory
+pub(crate) fn write_build_graph(
+    directory: &Path,
+    name: &str,
+    build_graph: &BuildGraph,
+) -> PipelineResult<()> {
+    std::fs::create_dir_all(directory)?;
+
+    std::fs::write(
+        directory.join(format!("{}.build_graph.json", name)),
+        serde_json::to_string(&build_graph.to_json())?,
+    )?;
+    std::fs::write(
+        directory.join(format!("{}.build_graph.dot", name)),
+        build_graph.to_dot(),
+    )?;
+
+    Ok(())
+}
@@ -1,6 +1,10 @@
 use crate::import::ImportJobs;
-use crate::{BuildLogData, BuildLogEvent, DynEditorModel, LogEventLevel, PipelineResult};
+use crate::{
+    BuildLogData, BuildLogEvent, BuildProfile, DynEditorModel, LogEventLevel, PipelineEvents,
+    PipelineResult,
+};
 use hydrate_base::hashing::HashSet;
+use hydrate_base::release_manifest::ReleaseManifestEntry;
 use hydrate_base::{hashing::HashMap, AssetId};
 use hydrate_base::{
     ArtifactId, BuiltArtifactHeaderData, DebugArtifactManifestDataJson, DebugManifestFileJson,
@@ -11,6 +15,7 @@ use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::hash::{Hash, Hasher};
 use std::io::Write;
+use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -21,6 +26,20 @@ struct BuildRequest {
     asset_id: AssetId,
 }
 
+// The assets a build should start from. Anything these assets reference (discovered through the
+// dependencies of the artifacts they produce) is built too, and the manifest only contains what
+// was built.
+pub enum BuildRoots {
+    All,
+    Assets(Vec<AssetId>),
+    // Builds every asset the function returns true for (for example, all levels)
+    Query(Box<dyn Fn(&DataSet, AssetId) -> bool>),
+    // Rebuilds the assets that changed since the last build of all assets, and the assets whose
+    // jobs read them. Everything else is carried over, so the manifest still has every asset. If
+    // there is no previous build to start from, this is the same as All.
+    Changed,
+}
+
 // A known build job, each existing asset will have an associated build job.
 // It could be in a completed state, or there could be a problem with it and we need to re-run it.
 struct BuildJob {
@@ -54,6 +73,54 @@ struct BuiltArtifactInfo {
     metadata: BuiltArtifactHeaderData,
 }
 
+// What a build of all assets produced. An incremental build carries this over for the assets that
+// haven't changed.
+struct CompletedBuild {
+    build_profile: Arc<BuildProfile>,
+    asset_hashes: HashMap<AssetId, u64>,
+    build_hashes: HashMap<ArtifactId, u64>,
+    artifact_asset_lookup: HashMap<ArtifactId, AssetId>,
+    built_artifact_info: HashMap<ArtifactId, BuiltArtifactInfo>,
+    // The assets read by jobs that each asset's builder started, directly or through other jobs.
+    // Builders themselves should only read their own asset, anything else they read isn't tracked.
+    assets_read: HashMap<AssetId, HashSet<AssetId>>,
+    failed_assets: HashSet<AssetId>,
+}
+
+impl CompletedBuild {
+    // Assets that were added or modified since this build, assets that failed in it, and the
+    // assets whose jobs read any of those or an asset that was deleted
+    fn assets_to_rebuild(
+        &self,
+        asset_hashes: &HashMap<AssetId, u64>,
+    ) -> HashSet<AssetId> {
+        let mut changed_assets: HashSet<_> = asset_hashes
+            .iter()
+            .filter(|(asset_id, hash)| self.asset_hashes.get(asset_id) != Some(hash))
+            .map(|(&asset_id, _)| asset_id)
+            .collect();
+        changed_assets.extend(
+            self.asset_hashes
+                .keys()
+                .filter(|x| !asset_hashes.contains_key(x)),
+        );
+
+        // Assets that only hold handles to a changed asset's artifacts don't need to be rebuilt,
+        // the handles stay the same
+        let mut assets_to_rebuild = changed_assets.clone();
+        for (&asset_id, assets_read) in &self.assets_read {
+            if !assets_read.is_disjoint(&changed_assets) {
+                assets_to_rebuild.insert(asset_id);
+            }
+        }
+        assets_to_rebuild.extend(self.failed_assets.iter().copied());
+
+        // Deleted assets can't be built
+        assets_to_rebuild.retain(|x| asset_hashes.contains_key(x));
+        assets_to_rebuild
+    }
+}
+
 struct BuildTask {
     requested_build_ops: VecDeque<BuildRequest>,
     started_build_ops: HashSet<AssetId>,
@@ -62,8 +129,15 @@ struct BuildTask {
     built_artifact_info: HashMap<ArtifactId, BuiltArtifactInfo>,
     data_set: Arc<DataSet>,
     schema_set: SchemaSet,
+    build_profile: Arc<BuildProfile>,
     manifest_build_hash: u64,
     log_data: BuildLogData,
+    cancelled: bool,
+    // Set if the build covers every asset, including results carried over from a previous build
+    is_complete: bool,
+    asset_hashes: HashMap<AssetId, u64>,
+    // For assets whose results were carried over, what their jobs read in the previous build
+    carried_over_assets_read: HashMap<AssetId, HashSet<AssetId>>,
 }
 
 // Cache of all build jobs. This includes builds that are complete, in progress, or not started.
@@ -71,14 +145,29 @@ struct BuildTask {
 // job is complete, or is in a failed or stale state.
 pub struct BuildJobs {
     build_data_root_path: PathBuf,
+    build_profiles: Vec<Arc<BuildProfile>>,
+    // Used for the next build that starts
+    active_build_profile: Arc<BuildProfile>,
+    // If set, the next build that starts checks that jobs are deterministic
+    build_verification: Option<Arc<BuildVerification>>,
     job_executor: JobExecutor,
     build_jobs: HashMap<AssetId, BuildJob>,
     //force_rebuild_operations: Vec<BuildOp>
     current_build_task: Option<BuildTask>,
     previous_manifest_build_hash: Option<u64>,
-    request_build: bool,
+    requested_build: Option<BuildRoots>,
     needs_build: bool,
     force_build_queue: HashSet<AssetId>,
+    // The last build of all assets, used as the starting point for BuildRoots::Changed
+    previous_complete_build: Option<CompletedBuild>,
+    // If set, changed assets are rebuilt once nothing has been changed for this long
+    auto_build_debounce: Option<std::time::Duration>,
+    // The data's hash in the last update and when it changed, so that auto builds wait for edits
+    // and imports to settle
+    observed_manifest_build_hash: Option<u64>,
+    last_change_instant: std::time::Instant,
+    // Auto builds don't retry data that they already started a build for
+    auto_build_manifest_build_hash: Option<u64>,
 }
 
 impl BuildJobs {
@@ -90,12 +179,24 @@ impl BuildJobs {
         self.current_build_task.is_some()
     }
 
+    // Stops the in-flight build. Running jobs are allowed to finish (or stop early if they check
+    // for cancellation), then the build completes without writing a manifest.
+    pub fn cancel(&mut self) {
+        if let Some(build_task) = &mut self.current_build_task {
+            build_task.cancelled = true;
+            build_task.requested_build_ops.clear();
+            self.job_executor.cancel();
+        }
+    }
+
     pub fn new(
         schema_set: &SchemaSet,
         job_processor_registry: &JobProcessorRegistry,
         import_data_root_path: PathBuf,
         job_data_root_path: PathBuf,
         build_data_root_path: PathBuf,
+        build_profiles: Vec<BuildProfile>,
+        pipeline_events: PipelineEvents,
     ) -> Self {
         //TODO: May need to scan disk to see what is cached?
         let job_executor = JobExecutor::new(
@@ -104,19 +205,34 @@ impl BuildJobs {
             import_data_root_path,
             job_data_root_path,
             build_data_root_path.clone(),
+            pipeline_events,
         );
         let build_jobs = Default::default();
 
+        let mut build_profiles: Vec<_> = build_profiles.into_iter().map(Arc::new).collect();
+        if build_profiles.is_empty() {
+            build_profiles.push(Default::default());
+        }
+        let active_build_profile = build_profiles[0].clone();
+
         BuildJobs {
             build_data_root_path,
+            build_profiles,
+            active_build_profile,
+            build_verification: None,
             job_executor,
             build_jobs,
             //force_rebuild_operations: Default::default()
             current_build_task: None,
             previous_manifest_build_hash: None,
-            request_build: false,
+            requested_build: None,
             needs_build: false,
             force_build_queue: Default::default(),
+            previous_complete_build: None,
+            auto_build_debounce: None,
+            observed_manifest_build_hash: None,
+            last_change_instant: std::time::Instant::now(),
+            auto_build_manifest_build_hash: None,
         }
     }
 
@@ -127,14 +243,117 @@ impl BuildJobs {
         self.force_build_queue.insert(asset_id);
     }
 
+    pub fn build_profiles(&self) -> &[Arc<BuildProfile>] {
+        &self.build_profiles
+    }
+
+    pub fn active_build_profile(&self) -> &BuildProfile {
+        &self.active_build_profile
+    }
+
+    // Selects the profile used by builds started after this call. A build that is already running
+    // continues with the profile it started with.
+    pub fn set_active_build_profile(
+        &mut self,
+        name: &str,
+    ) -> PipelineResult<()> {
+        let build_profile = self
+            .build_profiles
+            .iter()
+            .find(|x| x.name == name)
+            .ok_or_else(|| format!("Build profile {:?} is not defined in the project", name))?;
+        self.active_build_profile = build_profile.clone();
+        Ok(())
+    }
+
+    pub fn build_verification(&self) -> Option<&BuildVerification> {
+        self.build_verification.as_deref()
+    }
+
+    // Verification applies to builds started after this call. Verification builds run jobs even if
+    // they have cached results, so they are slower than normal builds.
+    pub fn set_build_verification(
+        &mut self,
+        build_verification: Option<BuildVerification>,
+    ) {
+        self.build_verification = build_verification.map(Arc::new);
+    }
+
+    // Jobs that start after this call run in job worker processes instead of on the editor's
+    // threads. Returns the address that workers connect to.
+    pub fn start_job_workers(
+        &mut self,
+        job_worker_config: JobWorkerConfig,
+    ) -> PipelineResult<SocketAddr> {
+        let job_worker_pool = JobWorkerPool::new(
+            job_worker_config,
+            self.job_executor.job_processor_registry(),
+        )?;
+        let listen_address = job_worker_pool.listen_address();
+        self.job_executor
+            .set_job_worker_pool(Some(Arc::new(job_worker_pool)));
+        Ok(listen_address)
+    }
+
+    // Jobs that start after this call run in-process again. Workers are shut down once jobs
+    // already running on them have finished.
+    pub fn stop_job_workers(&mut self) {
+        self.job_executor.set_job_worker_pool(None);
+    }
+
+    // The address that job workers connect to, if job workers have been started
+    pub fn job_worker_address(&self) -> Option<SocketAddr> {
+        self.job_executor
+            .job_worker_pool()
+            .map(|x| x.listen_address())
+    }
+
+    // Jobs for these assets start ahead of other jobs, including in a build that is already running
+    pub fn set_prioritized_assets(
+        &mut self,
+        asset_ids: &HashSet<AssetId>,
+    ) {
+        self.job_executor.set_prioritized_assets(asset_ids);
+    }
+
+    pub fn set_max_concurrent_jobs(
+        &self,
+        job_type: JobTypeId,
+        max_concurrent_jobs: Option<usize>,
+    ) {
+        self.job_executor
+            .set_max_concurrent_jobs(job_type, max_concurrent_jobs);
+    }
+
     pub fn build(&mut self) {
-        self.request_build = true;
+        self.requested_build = Some(BuildRoots::All);
+    }
+
+    pub fn build_roots(
+        &mut self,
+        build_roots: BuildRoots,
+    ) {
+        self.requested_build = Some(build_roots);
     }
 
     pub fn needs_build(&self) -> bool {
         self.needs_build
     }
 
+    pub fn auto_build_debounce(&self) -> Option<std::time::Duration> {
+        self.auto_build_debounce
+    }
+
+    // While set, a BuildRoots::Changed build starts when the data no longer matches the last build
+    // and nothing has been changed for the debounce duration. Edits that are still in progress
+    // hold the build back until they're committed.
+    pub fn set_auto_build_debounce(
+        &mut self,
+        auto_build_debounce: Option<std::time::Duration>,
+    ) {
+        self.auto_build_debounce = auto_build_debounce;
+    }
+
     #[profiling::function]
     pub fn update(
         &mut self,
@@ -159,10 +378,17 @@ impl BuildJobs {
                     let asset_id = request.asset_id;
                     build_task.started_build_ops.insert(asset_id);
 
-                    // If this unwrap trips, possibly there is a handle to an artifact with an asset
-                    // ID that doesn't exist
+                    // Possibly there is a handle to an artifact with an asset ID that doesn't exist
                     let Some(asset_type) = editor_model.data_set().asset_schema(asset_id) else {
-                        panic!("Asset id {:?} was referenced but does not exist", asset_id);
+                        let log_event = BuildLogEvent {
+                            job_id: None,
+                            asset_id: Some(asset_id),
+                            level: LogEventLevel::Error,
+                            message: "Asset was referenced but does not exist".to_string(),
+                        };
+                        log::error!("Build Error: {:?}", log_event);
+                        build_task.log_data.log_events.push(log_event);
+                        continue;
                     };
 
                     let Some(builder) =
@@ -171,10 +397,41 @@ impl BuildJobs {
                         continue;
                     };
 
+                    // Assets with validation errors are not built
+                    let validation_issues = validate_asset(
+                        &**builder,
+                        asset_id,
+                        &build_task.data_set,
+                        &build_task.schema_set,
+                        &build_task.build_profile,
+                    );
+                    let mut failed_validation = false;
+                    for validation_issue in validation_issues {
+                        let log_event = BuildLogEvent {
+                            job_id: None,
+                            asset_id: Some(asset_id),
+                            level: validation_issue.level,
+                            message: format!("Validation: {}", validation_issue.to_text()),
+                        };
+                        match log_event.level {
+                            LogEventLevel::Warning => log::warn!("Build Warning: {:?}", log_event),
+                            _ => {
+                                log::error!("Build Error: {:?}", log_event);
+                                failed_validation = true;
+                            }
+                        }
+                        build_task.log_data.log_events.push(log_event);
+                    }
+
+                    if failed_validation {
+                        continue;
+                    }
+
                     if let Err(e) = builder.start_jobs(BuilderContext {
                         asset_id,
                         data_set: &build_task.data_set,
                         schema_set: &build_task.schema_set,
+                        build_profile: &build_task.build_profile,
                         job_api: self.job_executor.job_api(),
                         log_events: &Rc::new(RefCell::new(&mut build_task.log_data.log_events)),
                     }) {
@@ -206,19 +463,33 @@ impl BuildJobs {
                 // Jobs will produce artifacts. We will save these to disk and possibly trigger
                 // additional jobs for assets that they reference.
                 //
-                let written_artifacts = self
-                    .job_executor
-                    .take_written_artifacts(&mut build_task.artifact_asset_lookup);
+                let written_artifacts = self.job_executor.take_written_artifacts(
+                    &mut build_task.artifact_asset_lookup,
+                    &mut build_task.log_data.log_events,
+                );
 
                 for written_artifact in written_artifacts {
                     //
                     // Trigger building any dependencies.
                     //
                     for &dependency_artifact_id in &written_artifact.metadata.dependencies {
-                        let dependency_asset_id = *build_task
+                        let Some(&dependency_asset_id) = build_task
                             .artifact_asset_lookup
                             .get(&dependency_artifact_id)
-                            .unwrap();
+                        else {
+                            let log_event = BuildLogEvent {
+                                job_id: None,
+                                asset_id: Some(written_artifact.asset_id),
+                                level: LogEventLevel::Error,
+                                message: format!(
+                                    "Artifact {:?} depends on artifact {:?}, but no asset is known to produce it",
+                                    written_artifact.artifact_id, dependency_artifact_id
+                                ),
+                            };
+                            log::error!("Build Error: {:?}", log_event);
+                            build_task.log_data.log_events.push(log_event);
+                            continue;
+                        };
                         build_task.requested_build_ops.push_back(BuildRequest {
                             asset_id: dependency_asset_id,
                         });
@@ -266,165 +537,110 @@ impl BuildJobs {
         //
         // Finish the current build task
         //
-        if let Some(build_task) = self.current_build_task.take() {
-            //
-            // Write the manifest file
-            //TODO: Only if it doesn't already exist? We could skip the whole building process in that case
-            //
-            let mut manifest_path = self.build_data_root_path.clone();
-            manifest_path.push("manifests");
-            std::fs::create_dir_all(&manifest_path).unwrap();
-
-            // This is a more compact file that is run at release
-            let manifest_path_release = manifest_path.join(format!(
-                "{:0>16x}.manifest_release",
-                build_task.manifest_build_hash
-            ));
-            let manifest_release_file = std::fs::File::create(manifest_path_release).unwrap();
-            let mut manifest_release_file_writer = std::io::BufWriter::new(manifest_release_file);
+        if let Some(mut build_task) = self.current_build_task.take() {
+            build_task.log_data.end_instant = Some(std::time::Instant::now());
 
-            // This is a json file that supplements the release manifest
-            let manifest_path_debug = manifest_path.join(format!(
-                "{:0>16x}.manifest_debug",
-                build_task.manifest_build_hash
-            ));
-
-            let mut manifest_json = DebugManifestFileJson::default();
-
-            let mut all_hashes = HashSet::default();
-            for (&artifact_id, &build_hash) in &build_task.build_hashes {
-                let built_artifact_info = build_task.built_artifact_info.get(&artifact_id).unwrap();
-                let asset_id = built_artifact_info.asset_id;
-
-                fn add_dependencies_recursively(
-                    artifact_id: ArtifactId,
-                    combined_hash: &mut u64,
-                    all_dependencies: &mut HashSet<ArtifactId>,
-                    build_task: &BuildTask,
-                ) {
-                    // Get the hash and combine it with the hash so far
-                    *combined_hash ^= build_task.build_hashes.get(&artifact_id).unwrap();
-
-                    // Visit all of its dependencies
-                    for dependency in &build_task
-                        .built_artifact_info
-                        .get(&artifact_id)
-                        .unwrap()
-                        .metadata
-                        .dependencies
-                    {
-                        // Visit each artifact only once
-                        if !all_dependencies.contains(&dependency) {
-                            add_dependencies_recursively(
-                                *dependency,
-                                combined_hash,
-                                all_dependencies,
-                                build_task,
-                            );
-                        }
-                    }
-                }
+            if build_task.cancelled {
+                let log_event = BuildLogEvent {
+                    job_id: None,
+                    asset_id: None,
+                    level: LogEventLevel::Warning,
+                    message: "Build was cancelled, no manifest was written".to_string(),
+                };
+                log::warn!("Build Warning: {:?}", log_event);
+                build_task.log_data.log_events.push(log_event);
 
-                let mut combined_build_hash = 0;
-                let mut all_dependencies = HashSet::<ArtifactId>::default();
-                add_dependencies_recursively(
-                    artifact_id,
-                    &mut combined_build_hash,
-                    &mut all_dependencies,
-                    &build_task,
-                );
+                // The last complete build is still on disk but may not match the data anymore
+                self.previous_manifest_build_hash = None;
+                return Ok(BuildStatus::Completed(Arc::new(build_task.log_data)));
+            }
 
-                let is_default_artifact = artifact_id.as_uuid() == asset_id.as_uuid();
-                let symbol_name = if is_default_artifact {
-                    // editor_model.path_node_id_to_path(asset_id.get)
-                    // //let location = edit_context.asset_location(asset_id).unwrap();
-                    //TODO: Assert the cached asset path tree is not stale?
-                    let path = editor_model.asset_display_name_long(asset_id);
-                    assert!(!path.is_empty());
-                    Some(path)
-                } else {
-                    None
+            // A failure to write the manifest is reported like any other build error rather than
+            // ending the build
+            let manifest_result =
+                Self::write_manifest(&self.build_data_root_path, &mut build_task, editor_model);
+            if let Err(e) = &manifest_result {
+                let log_event = BuildLogEvent {
+                    job_id: None,
+                    asset_id: None,
+                    level: LogEventLevel::FatalError,
+                    message: format!("Failed to write manifest: {}", e.to_string()),
                 };
+                log::error!("Build Error: {:?}", log_event);
+                build_task.log_data.log_events.push(log_event);
+            }
 
-                let symbol_name_hash =
-                    StringHash::from_runtime_str(&symbol_name.clone().unwrap_or_default()).hash();
-                if symbol_name_hash != 0 {
-                    let newly_inserted = all_hashes.insert(symbol_name_hash);
-                    if !newly_inserted {
-                        // We have a hash collision if this fires
-                        panic!("Two artifacts have been produced with the same symbol name. Check for assets with the same name: {:?}", symbol_name);
-                    }
-                }
-
-                let debug_name = if let Some(artifact_key_debug_name) =
-                    &built_artifact_info.artifact_key_debug_name
-                {
-                    format!(
-                        "{}#{}",
-                        editor_model.asset_display_name_long(asset_id),
-                        artifact_key_debug_name
-                    )
-                } else {
-                    editor_model.asset_display_name_long(asset_id)
+            // The timing report and build graph are written next to the manifest. They are only
+            // diagnostic, so failing to write them is a warning.
+            let manifest_name = format!("{:0>16x}", build_task.manifest_build_hash);
+            let manifest_path = build_task
+                .build_profile
+                .build_data_path(&self.build_data_root_path)
+                .join("manifests");
+            if let Err(e) =
+                write_build_timing_report(&manifest_path, &manifest_name, &build_task.log_data)
+            {
+                let log_event = BuildLogEvent {
+                    job_id: None,
+                    asset_id: None,
+                    level: LogEventLevel::Warning,
+                    message: format!("Failed to write build timing report: {}", e.to_string()),
                 };
+                log::warn!("Build Warning: {:?}", log_event);
+                build_task.log_data.log_events.push(log_event);
+            }
 
-                manifest_json.artifacts.push(DebugArtifactManifestDataJson {
-                    artifact_id,
-                    build_hash: format!("{:0>16x}", build_hash),
-                    combined_build_hash: format!("{:0>16x}", combined_build_hash),
-                    symbol_hash: format!("{:0>32x}", symbol_name_hash),
-                    symbol_name: symbol_name.unwrap_or_default(),
-                    artifact_type: built_artifact_info.metadata.asset_type,
-                    debug_name,
-                    //dependencies: artifact_metadata.dependencies.clone(),
-                });
+            let build_graph = BuildGraph::new(&build_task.log_data, editor_model);
+            if let Err(e) = write_build_graph(&manifest_path, &manifest_name, &build_graph) {
+                let log_event = BuildLogEvent {
+                    job_id: None,
+                    asset_id: None,
+                    level: LogEventLevel::Warning,
+                    message: format!("Failed to write build graph: {}", e.to_string()),
+                };
+                log::warn!("Build Warning: {:?}", log_event);
+                build_task.log_data.log_events.push(log_event);
+            }
 
-                // Write the artifact ID, build hash, asset type, and hash of symbol name in CSV (this could be very compact binary one day
-                write!(
-                    manifest_release_file_writer,
-                    "{:0>32x},{:0>16x},{:0>16x},{:0>32x},{:0>32x}\n",
-                    artifact_id.as_u128(),
-                    build_hash,
-                    combined_build_hash,
-                    built_artifact_info.metadata.asset_type.as_u128(),
-                    symbol_name_hash
-                )
-                .unwrap();
+            let failed_assets = build_task.log_data.failed_assets();
+            if !failed_assets.is_empty() {
+                log::error!(
+                    "{} assets failed to build: {:?}",
+                    failed_assets.len(),
+                    failed_assets
+                );
             }
 
-            drop(manifest_release_file_writer);
+            // Without a manifest the build is not usable, so try again next time
+            self.previous_manifest_build_hash = manifest_result
+                .as_ref()
+                .ok()
+                .map(|_| build_task.manifest_build_hash);
 
-            {
-                profiling::scope!("Write debug manifest data");
-                let json = {
-                    profiling::scope!("serde_json::to_string_pretty");
-                    serde_json::to_string_pretty(&manifest_json).unwrap()
-                };
+            if manifest_result.is_ok() && build_task.is_complete {
+                let mut assets_read = std::mem::take(&mut build_task.carried_over_assets_read);
+                for (&job_id, job_assets_read) in &build_task.log_data.assets_read {
+                    for asset_id in build_task.log_data.assets_relying_on_job(job_id) {
+                        assets_read
+                            .entry(asset_id)
+                            .or_default()
+                            .extend(job_assets_read.iter().copied());
+                    }
+                }
 
-                profiling::scope!("std::fs::write");
-                std::fs::write(manifest_path_debug, json).unwrap();
+                self.previous_complete_build = Some(CompletedBuild {
+                    build_profile: build_task.build_profile,
+                    asset_hashes: build_task.asset_hashes,
+                    build_hashes: build_task.build_hashes,
+                    artifact_asset_lookup: build_task.artifact_asset_lookup,
+                    built_artifact_info: build_task.built_artifact_info,
+                    assets_read,
+                    failed_assets: failed_assets.into_iter().collect(),
+                });
+            } else if manifest_result.is_err() {
+                self.previous_complete_build = None;
             }
 
-            //
-            // Write a new TOC with summary of this build
-            //
-            let mut toc_path = self.build_data_root_path.clone();
-            toc_path.push("toc");
-            std::fs::create_dir_all(&toc_path).unwrap();
-
-            let timestamp = std::time::SystemTime::now()
-                .duration_since(std::time::SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_millis();
-            toc_path.push(format!("{:0>16x}.toc", timestamp));
-
-            std::fs::write(
-                toc_path,
-                format!("{:0>16x}", build_task.manifest_build_hash),
-            )
-            .unwrap();
-
-            self.previous_manifest_build_hash = Some(build_task.manifest_build_hash);
             return Ok(BuildStatus::Completed(Arc::new(build_task.log_data)));
         }
 
@@ -466,6 +682,14 @@ impl BuildJobs {
             manifest_build_hash = manifest_build_hash ^ inner_hasher.finish();
         }
 
+        // Building the same data with a different profile produces a different build
+        let build_profile = self.active_build_profile.clone();
+        if !build_profile.name.is_empty() {
+            let mut inner_hasher = siphasher::sip::SipHasher::default();
+            build_profile.hash(&mut inner_hasher);
+            manifest_build_hash = manifest_build_hash ^ inner_hasher.finish();
+        }
+
         self.needs_build =
             if let Some(previous_manifest_build_hash) = self.previous_manifest_build_hash {
                 previous_manifest_build_hash != manifest_build_hash
@@ -473,36 +697,145 @@ impl BuildJobs {
                 true
             };
 
+        // Auto builds are debounced so that a burst of edits or a series of imports results in
+        // one build
+        if self.observed_manifest_build_hash != Some(manifest_build_hash) {
+            self.observed_manifest_build_hash = Some(manifest_build_hash);
+            self.last_change_instant = std::time::Instant::now();
+        }
+
+        let should_auto_build = if let Some(auto_build_debounce) = self.auto_build_debounce {
+            self.needs_build
+                && self.auto_build_manifest_build_hash != Some(manifest_build_hash)
+                && self.last_change_instant.elapsed() >= auto_build_debounce
+                && !editor_model.has_pending_edits()
+        } else {
+            false
+        };
+
         //
         // Decide what assets we will initially request. This could be everything or just
         // a small set of assets (like a level, or all assets marked as "always export")
         //
-        let mut requested_build_ops = VecDeque::default();
-        if self.request_build {
-            self.request_build = false;
-            // if !needs_rebuild {
-            //     return Ok(BuildStatus::Idle);
-            // } else {
-            for (&asset_id, _) in &asset_hashes {
-                assert!(!editor_model.is_path_node_or_root(
-                    &editor_model.data_set().asset_schema(asset_id).unwrap()
-                ));
-
-                //TODO: Skip assets that aren't explicitly requested, if any were requested
-                //      For now just build everything
-                requested_build_ops.push_back(BuildRequest { asset_id });
-            }
-            //}
+        let build_roots = if let Some(build_roots) = self.requested_build.take() {
+            build_roots
         } else if !self.force_build_queue.is_empty() {
-            for asset_id in self.force_build_queue.drain() {
-                requested_build_ops.push_back(BuildRequest { asset_id });
-            }
+            BuildRoots::Assets(self.force_build_queue.drain().collect())
+        } else if should_auto_build {
+            self.auto_build_manifest_build_hash = Some(manifest_build_hash);
+            BuildRoots::Changed
         } else {
             return Ok(BuildStatus::Idle);
+        };
+
+        // Only a build of all assets with the same profile can be carried over
+        let previous_complete_build = if matches!(build_roots, BuildRoots::Changed) {
+            self.previous_complete_build
+                .take()
+                .filter(|x| Arc::ptr_eq(&x.build_profile, &build_profile))
+        } else {
+            None
+        };
+
+        let mut log_data = BuildLogData::default();
+        let mut requested_build_ops = VecDeque::default();
+        let mut started_build_ops = HashSet::default();
+        let mut build_hashes = HashMap::default();
+        let mut artifact_asset_lookup = HashMap::default();
+        let mut built_artifact_info = HashMap::default();
+        let mut carried_over_assets_read = HashMap::default();
+        match &build_roots {
+            BuildRoots::Changed if previous_complete_build.is_some() => {
+                let previous_build = previous_complete_build.unwrap();
+                let assets_to_rebuild = previous_build.assets_to_rebuild(&asset_hashes);
+                log::info!(
+                    "Building {} assets that changed since the last build",
+                    assets_to_rebuild.len()
+                );
+                for &asset_id in &assets_to_rebuild {
+                    requested_build_ops.push_back(BuildRequest { asset_id });
+                }
+
+                // Everything else keeps the results of the previous build. Marking the assets as
+                // started keeps artifacts that reference them from building them again.
+                let is_carried_over = |asset_id: &AssetId| {
+                    asset_hashes.contains_key(asset_id) && !assets_to_rebuild.contains(asset_id)
+                };
+                started_build_ops.extend(
+                    previous_build
+                        .asset_hashes
+                        .keys()
+                        .copied()
+                        .filter(is_carried_over),
+                );
+                artifact_asset_lookup.extend(
+                    previous_build
+                        .artifact_asset_lookup
+                        .into_iter()
+                        .filter(|(_, asset_id)| is_carried_over(asset_id)),
+                );
+                for (artifact_id, info) in previous_build.built_artifact_info {
+                    if is_carried_over(&info.asset_id) {
+                        build_hashes.insert(artifact_id, previous_build.build_hashes[&artifact_id]);
+                        built_artifact_info.insert(artifact_id, info);
+                    }
+                }
+                carried_over_assets_read.extend(
+                    previous_build
+                        .assets_read
+                        .into_iter()
+                        .filter(|(asset_id, _)| is_carried_over(asset_id)),
+                );
+            }
+            BuildRoots::All | BuildRoots::Changed => {
+                for &asset_id in asset_hashes.keys() {
+                    requested_build_ops.push_back(BuildRequest { asset_id });
+                }
+            }
+            BuildRoots::Assets(asset_ids) => {
+                for &asset_id in asset_ids {
+                    // Path nodes are excluded from asset_hashes, they can't be built
+                    if asset_hashes.contains_key(&asset_id) {
+                        requested_build_ops.push_back(BuildRequest { asset_id });
+                    } else {
+                        let log_event = BuildLogEvent {
+                            job_id: None,
+                            asset_id: Some(asset_id),
+                            level: LogEventLevel::Warning,
+                            message: "Requested asset does not exist or can't be built".to_string(),
+                        };
+                        log::warn!("Build Warning: {:?}", log_event);
+                        log_data.log_events.push(log_event);
+                    }
+                }
+            }
+            BuildRoots::Query(query) => {
+                for &asset_id in asset_hashes.keys() {
+                    if query(editor_model.data_set(), asset_id) {
+                        requested_build_ops.push_back(BuildRequest { asset_id });
+                    }
+                }
+            }
         }
 
-        self.job_executor.reset();
+        // The manifest of a partial build must not be confused with the manifest of a full build of
+        // the same data
+        let is_complete = matches!(build_roots, BuildRoots::All | BuildRoots::Changed);
+        if !is_complete {
+            let mut root_asset_ids: Vec<_> =
+                requested_build_ops.iter().map(|x| x.asset_id).collect();
+            root_asset_ids.sort();
 
+            let mut inner_hasher = siphasher::sip::SipHasher::default();
+            root_asset_ids.hash(&mut inner_hasher);
+            manifest_build_hash = manifest_build_hash ^ inner_hasher.finish();
+        }
+
+        self.job_executor
+            .reset(build_profile.clone(), self.build_verification.clone());
+
+        // Jobs read this snapshot while the user keeps editing. Assets are shared with the
+        // editor's data set until one of them is modified, so only the asset map is copied.
         let data_set = {
             profiling::scope!("Clone Dataset");
             Arc::new(editor_model.data_set().clone())
@@ -513,14 +846,19 @@ impl BuildJobs {
         let total_job_count = requested_build_ops.len();
         self.current_build_task = Some(BuildTask {
             requested_build_ops,
-            started_build_ops: Default::default(),
-            build_hashes: Default::default(),
-            artifact_asset_lookup: Default::default(),
-            built_artifact_info: Default::default(),
+            started_build_ops,
+            build_hashes,
+            artifact_asset_lookup,
+            built_artifact_info,
             data_set,
             schema_set,
+            build_profile,
             manifest_build_hash: manifest_build_hash,
-            log_data: Default::default(),
+            log_data,
+            cancelled: false,
+            is_complete,
+            asset_hashes,
+            carried_over_assets_read,
         });
 
         Ok(BuildStatus::Building(BuildStatusBuilding {
@@ -528,4 +866,246 @@ impl BuildJobs {
             completed_job_count: 0,
         }))
     }
+
+    // Writes the manifest and TOC for everything that was built. Artifacts that failed to build are
+    // left out, so the manifest always describes data that exists on disk.
+    fn write_manifest(
+        build_data_root_path: &PathBuf,
+        build_task: &mut BuildTask,
+        editor_model: &dyn DynEditorModel,
+    ) -> PipelineResult<()> {
+        //TODO: Only if it doesn't already exist? We could skip the whole building process in that case
+        let build_data_path = build_task
+            .build_profile
+            .build_data_path(build_data_root_path);
+        let mut manifest_path = build_data_path.clone();
+        manifest_path.push("manifests");
+        std::fs::create_dir_all(&manifest_path)?;
+
+        // This is a more compact file that is run at release
+        let manifest_path_release = manifest_path.join(format!(
+            "{:0>16x}.manifest_release",
+            build_task.manifest_build_hash
+        ));
+
+        // This is a json file that supplements the release manifest
+        let manifest_path_debug = manifest_path.join(format!(
+            "{:0>16x}.manifest_debug",
+            build_task.manifest_build_hash
+        ));
+
+        let mut manifest_json = DebugManifestFileJson::default();
+        let mut manifest_release_entries = Vec::default();
+        let mut log_events = Vec::default();
+
+        // Sort so that errors are reported in a consistent order
+        let mut build_hashes: Vec<_> = build_task
+            .build_hashes
+            .iter()
+            .map(|(&artifact_id, &build_hash)| (artifact_id, build_hash))
+            .collect();
+        build_hashes.sort_by_key(|(artifact_id, _)| *artifact_id);
+
+        let mut all_hashes = HashMap::<u128, AssetId>::default();
+        for (artifact_id, build_hash) in build_hashes {
+            let built_artifact_info = build_task.built_artifact_info.get(&artifact_id).unwrap();
+            let asset_id = built_artifact_info.asset_id;
+
+            // Dependencies that failed to build are collected in missing_dependencies
+            fn add_dependencies_recursively(
+                artifact_id: ArtifactId,
+                combined_hash: &mut u64,
+                all_dependencies: &mut HashSet<ArtifactId>,
+                missing_dependencies: &mut Vec<ArtifactId>,
+                build_task: &BuildTask,
+            ) {
+                let (Some(build_hash), Some(built_artifact_info)) = (
+                    build_task.build_hashes.get(&artifact_id),
+                    build_task.built_artifact_info.get(&artifact_id),
+                ) else {
+                    missing_dependencies.push(artifact_id);
+                    return;
+                };
+
+                // Get the hash and combine it with the hash so far
+                *combined_hash ^= build_hash;
+
+                // Visit all of its dependencies
+                for dependency in &built_artifact_info.metadata.dependencies {
+                    // Visit each artifact only once
+                    if all_dependencies.insert(*dependency) {
+                        add_dependencies_recursively(
+                            *dependency,
+                            combined_hash,
+                            all_dependencies,
+                            missing_dependencies,
+                            build_task,
+                        );
+                    }
+                }
+            }
+
+            let mut combined_build_hash = 0;
+            let mut all_dependencies = HashSet::<ArtifactId>::default();
+            let mut missing_dependencies = Vec::default();
+            add_dependencies_recursively(
+                artifact_id,
+                &mut combined_build_hash,
+                &mut all_dependencies,
+                &mut missing_dependencies,
+                build_task,
+            );
+
+            // The artifact would have handles that can't be loaded, so it is left out. Anything
+            // that depends on it is missing the same dependencies and is left out too.
+            if !missing_dependencies.is_empty() {
+                log_events.push(BuildLogEvent {
+                    job_id: None,
+                    asset_id: Some(asset_id),
+                    level: LogEventLevel::Error,
+                    message: format!(
+                        "Artifact {:?} is not included in the manifest because it depends on artifacts that were not built: {:?}",
+                        artifact_id, missing_dependencies
+                    ),
+                });
+                continue;
+            }
+
+            let is_default_artifact = artifact_id.as_uuid() == asset_id.as_uuid();
+            let mut symbol_name = if is_default_artifact {
+                // editor_model.path_node_id_to_path(asset_id.get)
+                // //let location = edit_context.asset_location(asset_id).unwrap();
+                //TODO: Assert the cached asset path tree is not stale?
+                let path = editor_model.asset_display_name_long(asset_id);
+                if path.is_empty() {
+                    // The artifact is still included, but it can't be looked up by symbol name
+                    log_events.push(BuildLogEvent {
+                        job_id: None,
+                        asset_id: Some(asset_id),
+                        level: LogEventLevel::Error,
+                        message: format!(
+                            "Artifact {:?} has no symbol name because its asset has no path",
+                            artifact_id
+                        ),
+                    });
+                    None
+                } else {
+                    Some(path)
+                }
+            } else {
+                None
+            };
+
+            let mut symbol_name_hash =
+                StringHash::from_runtime_str(&symbol_name.clone().unwrap_or_default()).hash();
+            if symbol_name_hash != 0 {
+                if let Some(other_asset_id) = all_hashes.get(&symbol_name_hash) {
+                    // We have a hash collision if this fires. The artifact is still included, but
+                    // it can't be looked up by symbol name.
+                    log_events.push(BuildLogEvent {
+                        job_id: None,
+                        asset_id: Some(asset_id),
+                        level: LogEventLevel::Error,
+                        message: format!(
+                            "Two artifacts have been produced with the same symbol name. Check for assets with the same name: {:?} (also produced by asset {:?})",
+                            symbol_name, other_asset_id
+                        ),
+                    });
+                    symbol_name = None;
+                    symbol_name_hash = 0;
+                } else {
+                    all_hashes.insert(symbol_name_hash, asset_id);
+                }
+            }
+
+            let debug_name = if let Some(artifact_key_debug_name) =
+                &built_artifact_info.artifact_key_debug_name
+            {
+                format!(
+                    "{}#{}",
+                    editor_model.asset_display_name_long(asset_id),
+                    artifact_key_debug_name
+                )
+            } else {
+                editor_model.asset_display_name_long(asset_id)
+            };
+
+            manifest_json.artifacts.push(DebugArtifactManifestDataJson {
+                artifact_id,
+                build_hash: format!("{:0>16x}", build_hash),
+                combined_build_hash: format!("{:0>16x}", combined_build_hash),
+                symbol_hash: format!("{:0>32x}", symbol_name_hash),
+                symbol_name: symbol_name.unwrap_or_default(),
+                artifact_type: built_artifact_info.metadata.asset_type,
+                debug_name,
+                //dependencies: artifact_metadata.dependencies.clone(),
+            });
+
+            manifest_release_entries.push(ReleaseManifestEntry {
+                artifact_id,
+                build_hash,
+                combined_build_hash,
+                artifact_type: built_artifact_info.metadata.asset_type,
+                symbol_hash: symbol_name_hash,
+            });
+        }
+
+        for log_event in log_events {
+            match log_event.level {
+                LogEventLevel::Warning => log::warn!("Build Warning: {:?}", log_event),
+                _ => log::error!("Build Error: {:?}", log_event),
+            }
+            build_task.log_data.log_events.push(log_event);
+        }
+
+        {
+            profiling::scope!("Write release manifest data");
+            // Rebuilding the same data produces a manifest with the same name, and a running game
+            // may have the old one mapped into memory. Truncating it in place would crash the game,
+            // so write a new file and replace the old one.
+            let temp_path = manifest_path_release.with_extension("manifest_release.tmp");
+            let manifest_release_file = std::fs::File::create(&temp_path)?;
+            let mut manifest_release_file_writer = std::io::BufWriter::new(manifest_release_file);
+            hydrate_base::release_manifest::write_release_manifest(
+                &mut manifest_release_file_writer,
+                &mut manifest_release_entries,
+            )?;
+            manifest_release_file_writer.flush()?;
+            drop(manifest_release_file_writer);
+            std::fs::rename(&temp_path, &manifest_path_release)?;
+        }
+
+        {
+            profiling::scope!("Write debug manifest data");
+            let json = {
+                profiling::scope!("serde_json::to_string_pretty");
+                serde_json::to_string_pretty(&manifest_json)?
+            };
+
+            profiling::scope!("std::fs::write");
+            let temp_path = manifest_path_debug.with_extension("manifest_debug.tmp");
+            std::fs::write(&temp_path, json)?;
+            std::fs::rename(&temp_path, &manifest_path_debug)?;
+        }
+
+        //
+        // Write a new TOC with summary of this build
+        //
+        let mut toc_path = build_data_path;
+        toc_path.push("toc");
+        std::fs::create_dir_all(&toc_path)?;
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        toc_path.push(format!("{:0>16x}.toc", timestamp));
+
+        std::fs::write(
+            toc_path,
+            format!("{:0>16x}", build_task.manifest_build_hash),
+        )?;
+
+        Ok(())
+    }
 }
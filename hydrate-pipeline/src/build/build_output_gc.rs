@@ -0,0 +1,280 @@
+use crate::{HydrateProjectConfiguration, PipelineResult};
+use hydrate_base::build_pin;
+use hydrate_base::hashing::HashSet;
+use hydrate_base::release_manifest::ReleaseManifest;
+use hydrate_base::uuid_path::uuid_and_hash_to_path;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+// Build output that is no longer needed. Every build writes new artifacts, a new manifest and a
+// new TOC, and nothing deletes the old ones.
+#[derive(Debug, Default)]
+pub struct BuildOutputGarbageReport {
+    // TOCs older than the ones being kept
+    pub tocs: Vec<PathBuf>,
+    // Manifests, debug manifests, timing reports and build graphs that no kept TOC or pin refers to
+    pub manifests: Vec<PathBuf>,
+    // Artifacts that are not in any kept manifest
+    pub artifacts: Vec<PathBuf>,
+    // Pins left behind by games that did not exit cleanly
+    pub stale_pins: Vec<PathBuf>,
+    pub garbage_size_in_bytes: u64,
+    // Size of the TOCs, manifests and artifacts that are kept
+    pub kept_size_in_bytes: u64,
+}
+
+impl BuildOutputGarbageReport {
+    pub fn is_empty(&self) -> bool {
+        self.tocs.is_empty()
+            && self.manifests.is_empty()
+            && self.artifacts.is_empty()
+            && self.stale_pins.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::default();
+
+        // Writing to a String can't fail
+        writeln!(
+            text,
+            "{} TOCs, {} manifest files, {} artifacts and {} stale pins are garbage",
+            self.tocs.len(),
+            self.manifests.len(),
+            self.artifacts.len(),
+            self.stale_pins.len()
+        )
+        .unwrap();
+        writeln!(
+            text,
+            "{:.1} MB can be deleted, {:.1} MB is kept",
+            self.garbage_size_in_bytes as f64 / (1024.0 * 1024.0),
+            self.kept_size_in_bytes as f64 / (1024.0 * 1024.0)
+        )
+        .unwrap();
+
+        text
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    path.metadata().map(|x| x.len()).unwrap_or_default()
+}
+
+// Returns the files directly in the directory, or nothing if the directory doesn't exist
+fn files_in_dir(dir: &Path) -> PipelineResult<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::default());
+    }
+
+    let mut files = Vec::default();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push(entry.path());
+        }
+    }
+
+    Ok(files)
+}
+
+// Files in a manifests directory are named [manifest build hash].[kind]
+fn manifest_build_hash_of_file(path: &Path) -> Option<u64> {
+    let file_name = path.file_name()?.to_str()?;
+    let (manifest_build_hash, _) = file_name.split_once('.')?;
+    u64::from_str_radix(manifest_build_hash, 16).ok()
+}
+
+// Artifacts are stored at [build data path]/[x]/[y]/[artifact ID]-[build hash].bf. Other
+// directories (like the build data of other profiles) are skipped.
+fn find_artifact_files(build_data_path: &Path) -> PipelineResult<Vec<PathBuf>> {
+    let is_artifact_dir = |path: &Path| {
+        path.is_dir()
+            && path
+                .file_name()
+                .and_then(|x| x.to_str())
+                .map(|x| x.len() == 1 && x.chars().all(|c| c.is_ascii_hexdigit()))
+                .unwrap_or(false)
+    };
+
+    let mut artifact_files = Vec::default();
+    if !build_data_path.exists() {
+        return Ok(artifact_files);
+    }
+
+    for first_entry in std::fs::read_dir(build_data_path)? {
+        let first_dir = first_entry?.path();
+        if !is_artifact_dir(&first_dir) {
+            continue;
+        }
+
+        for second_entry in std::fs::read_dir(&first_dir)? {
+            let second_dir = second_entry?.path();
+            if !is_artifact_dir(&second_dir) {
+                continue;
+            }
+
+            for file in files_in_dir(&second_dir)? {
+                if file.extension().map(|x| x == "bf").unwrap_or(false) {
+                    artifact_files.push(file);
+                }
+            }
+        }
+    }
+
+    Ok(artifact_files)
+}
+
+// Finds garbage in the build output of a single build profile. The newest keep_toc_count TOCs
+// are kept along with anything a running game has pinned.
+fn find_build_output_garbage_in_path(
+    build_data_path: &Path,
+    keep_toc_count: usize,
+    report: &mut BuildOutputGarbageReport,
+) -> PipelineResult<()> {
+    profiling::scope!("find_build_output_garbage_in_path");
+
+    //
+    // Pinned manifests are kept regardless of their age
+    //
+    let mut kept_manifest_build_hashes = HashSet::default();
+    for pin_path in files_in_dir(&build_pin::build_pin_dir_path(build_data_path))? {
+        let Some(manifest_build_hash) = build_pin::parse_build_pin_path(&pin_path) else {
+            continue;
+        };
+
+        if build_pin::is_build_pin_live(&pin_path) {
+            kept_manifest_build_hashes.insert(manifest_build_hash);
+        } else {
+            report.garbage_size_in_bytes += file_size(&pin_path);
+            report.stale_pins.push(pin_path);
+        }
+    }
+
+    //
+    // TOC file names are the time they were written, keep the newest ones and any that point at a
+    // pinned manifest
+    //
+    let mut tocs = Vec::default();
+    for toc_path in files_in_dir(&build_data_path.join("toc"))? {
+        let timestamp = toc_path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .and_then(|x| x.strip_suffix(".toc"))
+            .and_then(|x| u64::from_str_radix(x, 16).ok());
+        if let Some(timestamp) = timestamp {
+            let manifest_build_hash =
+                u64::from_str_radix(std::fs::read_to_string(&toc_path)?.trim(), 16).map_err(
+                    |_| format!("TOC {:?} does not contain a manifest build hash", toc_path),
+                )?;
+            tocs.push((timestamp, toc_path, manifest_build_hash));
+        }
+    }
+
+    tocs.sort_by(|a, b| b.0.cmp(&a.0));
+    for (i, (_, toc_path, manifest_build_hash)) in tocs.into_iter().enumerate() {
+        if i < keep_toc_count || kept_manifest_build_hashes.contains(&manifest_build_hash) {
+            kept_manifest_build_hashes.insert(manifest_build_hash);
+            report.kept_size_in_bytes += file_size(&toc_path);
+        } else {
+            report.garbage_size_in_bytes += file_size(&toc_path);
+            report.tocs.push(toc_path);
+        }
+    }
+
+    //
+    // Artifacts in a kept manifest are kept. A kept manifest that can't be read would cause its
+    // artifacts to be deleted, so that is an error rather than garbage.
+    //
+    let manifest_dir_path = build_data_path.join("manifests");
+    let mut kept_artifacts = HashSet::default();
+    for &manifest_build_hash in &kept_manifest_build_hashes {
+        let manifest_path =
+            manifest_dir_path.join(format!("{:0>16x}.manifest_release", manifest_build_hash));
+        if !manifest_path.exists() {
+            // The manifest of a pinned build may already have been deleted by hand
+            continue;
+        }
+
+        let release_manifest = ReleaseManifest::new(std::fs::read(&manifest_path)?)
+            .map_err(|e| format!("Could not read manifest {:?}: {}", manifest_path, e))?;
+        for entry in release_manifest.iter() {
+            kept_artifacts.insert(uuid_and_hash_to_path(
+                build_data_path,
+                entry.artifact_id.as_uuid(),
+                entry.build_hash,
+                "bf",
+            ));
+        }
+    }
+
+    for manifest_file in files_in_dir(&manifest_dir_path)? {
+        match manifest_build_hash_of_file(&manifest_file) {
+            Some(manifest_build_hash)
+                if !kept_manifest_build_hashes.contains(&manifest_build_hash) =>
+            {
+                report.garbage_size_in_bytes += file_size(&manifest_file);
+                report.manifests.push(manifest_file);
+            }
+            _ => report.kept_size_in_bytes += file_size(&manifest_file),
+        }
+    }
+
+    for artifact_file in find_artifact_files(build_data_path)? {
+        if kept_artifacts.contains(&artifact_file) {
+            report.kept_size_in_bytes += file_size(&artifact_file);
+        } else {
+            report.garbage_size_in_bytes += file_size(&artifact_file);
+            report.artifacts.push(artifact_file);
+        }
+    }
+
+    Ok(())
+}
+
+// Finds garbage in the build output of every build profile in the project. Nothing is changed,
+// pass the result to delete_build_output_garbage() to remove it. Must not be called while a build
+// is running, as artifacts are written before the manifest that refers to them.
+pub fn find_build_output_garbage(
+    project_configuration: &HydrateProjectConfiguration,
+    keep_toc_count: usize,
+) -> PipelineResult<BuildOutputGarbageReport> {
+    // The newest build is always kept, it's the one the game loads at startup
+    let keep_toc_count = keep_toc_count.max(1);
+
+    let mut report = BuildOutputGarbageReport::default();
+    let build_data_root_path = &project_configuration.build_data_path;
+    let mut build_data_paths: Vec<_> = project_configuration
+        .build_profiles
+        .iter()
+        .map(|x| x.build_data_path(build_data_root_path))
+        .collect();
+    if build_data_paths.is_empty() {
+        build_data_paths.push(build_data_root_path.clone());
+    }
+
+    for build_data_path in build_data_paths {
+        find_build_output_garbage_in_path(&build_data_path, keep_toc_count, &mut report)?;
+    }
+
+    Ok(report)
+}
+
+// Deletes everything in a report produced by find_build_output_garbage(). Manifests are deleted
+// before the artifacts they refer to so that an interrupted delete never leaves a manifest with
+// missing artifacts.
+pub fn delete_build_output_garbage(report: &BuildOutputGarbageReport) -> PipelineResult<()> {
+    profiling::scope!("delete_build_output_garbage");
+    for path in report
+        .tocs
+        .iter()
+        .chain(&report.manifests)
+        .chain(&report.artifacts)
+        .chain(&report.stale_pins)
+    {
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::{ManifestContents, ManifestDiff, PipelineResult};
+use hydrate_base::build_patch::{self, BuildPatchJson};
+use hydrate_base::uuid_path::uuid_and_hash_to_path;
+use std::path::Path;
+
+// Summary of a patch written by write_build_patch()
+#[derive(Debug)]
+pub struct BuildPatchReport {
+    pub diff: ManifestDiff,
+    pub patch_size_in_bytes: u64,
+}
+
+impl BuildPatchReport {
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}\nPatch contains {} artifacts, {:.1} MB",
+            self.diff.to_text(),
+            self.diff.added.len() + self.diff.content_changed.len(),
+            self.patch_size_in_bytes as f64 / (1024.0 * 1024.0)
+        )
+    }
+}
+
+fn copy_file(
+    src: &Path,
+    dst: &Path,
+) -> PipelineResult<u64> {
+    if let Some(parent) = dst.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    Ok(std::fs::copy(src, dst)
+        .map_err(|e| format!("Could not copy {:?} to {:?}: {}", src, dst, e))?)
+}
+
+// Writes a patch that turns the base build into the new build when layered over it by the
+// loader. Both builds must be in build_data_path, they are identified by the manifest build hash
+// in their TOC. The patch contains the artifacts that were added or changed along with the new
+// build's manifest and a TOC pointing at it. See hydrate_base::build_patch.
+pub fn write_build_patch(
+    build_data_path: &Path,
+    base_manifest_build_hash: u64,
+    manifest_build_hash: u64,
+    patch_path: &Path,
+) -> PipelineResult<BuildPatchReport> {
+    profiling::scope!("write_build_patch");
+
+    // Don't mix the output of two patches
+    if patch_path.exists() && std::fs::read_dir(patch_path)?.next().is_some() {
+        Err(format!(
+            "Patch output path {:?} must be empty or not exist",
+            patch_path
+        ))?;
+    }
+
+    let base_manifest = ManifestContents::load_build(build_data_path, base_manifest_build_hash)?;
+    let manifest = ManifestContents::load_build(build_data_path, manifest_build_hash)?;
+    let diff = ManifestDiff::new(&base_manifest, &manifest);
+
+    //
+    // Artifacts whose build hash is unchanged are the same file in the base build. The rest are
+    // copied into the patch.
+    //
+    let mut patch_size_in_bytes = 0;
+    let changed_artifacts = diff
+        .added
+        .iter()
+        .chain(diff.content_changed.iter().map(|(_, new)| new));
+    for artifact in changed_artifacts {
+        let artifact_uuid = artifact.artifact_id.as_uuid();
+        patch_size_in_bytes += copy_file(
+            &uuid_and_hash_to_path(build_data_path, artifact_uuid, artifact.build_hash, "bf"),
+            &uuid_and_hash_to_path(patch_path, artifact_uuid, artifact.build_hash, "bf"),
+        )?;
+    }
+
+    //
+    // The new build's manifest, the debug manifest is optional
+    //
+    for extension in ["manifest_release", "manifest_debug"] {
+        let file_name = format!("{:0>16x}.{}", manifest_build_hash, extension);
+        let src = build_data_path.join("manifests").join(&file_name);
+        if extension == "manifest_debug" && !src.exists() {
+            continue;
+        }
+
+        patch_size_in_bytes += copy_file(&src, &patch_path.join("manifests").join(&file_name))?;
+    }
+
+    //
+    // A TOC so that the loader finds the new manifest
+    //
+    let toc_dir_path = patch_path.join("toc");
+    std::fs::create_dir_all(&toc_dir_path)?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    std::fs::write(
+        toc_dir_path.join(format!("{:0>16x}.toc", timestamp)),
+        format!("{:0>16x}", manifest_build_hash),
+    )?;
+
+    //
+    // The patch file is written last, the loader won't use a patch without it
+    //
+    let patch_json = BuildPatchJson {
+        base_manifest_build_hash: format!("{:0>16x}", base_manifest_build_hash),
+        manifest_build_hash: format!("{:0>16x}", manifest_build_hash),
+        artifact_count: diff.added.len() + diff.content_changed.len(),
+    };
+    std::fs::write(
+        build_patch::build_patch_file_path(patch_path),
+        serde_json::to_string_pretty(&patch_json)?,
+    )?;
+
+    Ok(BuildPatchReport {
+        diff,
+        patch_size_in_bytes,
+    })
+}
@@ -0,0 +1,393 @@
+use crate::build::JobRequestor;
+use crate::{BuildLogData, JobId, PipelineResult};
+use hydrate_base::hashing::{HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+// How many jobs to list in the slowest jobs section of the report
+const SLOWEST_JOB_COUNT: usize = 25;
+
+// When a job was enqueued, started, and finished during a build. Times are relative to the start
+// of the build. Jobs that never ran (i.e. they were cancelled) have no start or finish time.
+#[derive(Debug, Clone)]
+pub struct JobTiming {
+    pub job_id: JobId,
+    pub job_type_name: &'static str,
+    pub debug_name: Arc<String>,
+    pub upstream_jobs: Vec<JobId>,
+    pub enqueued: Duration,
+    pub started: Option<Duration>,
+    pub finished: Option<Duration>,
+    pub thread_index: Option<usize>,
+    pub used_cached_result: bool,
+}
+
+impl JobTiming {
+    pub fn run_duration(&self) -> Duration {
+        match (self.started, self.finished) {
+            (Some(started), Some(finished)) => finished.saturating_sub(started),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+pub struct JobTypeTiming {
+    pub job_type_name: &'static str,
+    pub job_count: usize,
+    pub cached_job_count: usize,
+    pub total_duration: Duration,
+}
+
+// Summary of where the time went in a build
+pub struct BuildTimingReport {
+    pub total_duration: Duration,
+    pub slowest_jobs: Vec<JobTiming>,
+    // Sorted by total duration, longest first
+    pub job_type_timings: Vec<JobTypeTiming>,
+    // The chain of dependent jobs with the longest combined run time, in the order they ran. Even
+    // with unlimited threads, the build can't take less time than this.
+    pub critical_path: Vec<JobTiming>,
+    pub critical_path_duration: Duration,
+}
+
+impl BuildTimingReport {
+    pub fn new(log_data: &BuildLogData) -> Self {
+        let job_timings = log_data.job_timings();
+
+        let total_duration = job_timings
+            .values()
+            .filter_map(|x| x.finished)
+            .max()
+            .unwrap_or_default();
+
+        let mut slowest_jobs: Vec<_> = job_timings.values().cloned().collect();
+        slowest_jobs.sort_by(|a, b| {
+            b.run_duration()
+                .cmp(&a.run_duration())
+                .then(a.job_id.cmp(&b.job_id))
+        });
+        slowest_jobs.truncate(SLOWEST_JOB_COUNT);
+
+        let mut job_type_timings = HashMap::<&'static str, JobTypeTiming>::default();
+        for job_timing in job_timings.values() {
+            let job_type_timing = job_type_timings
+                .entry(job_timing.job_type_name)
+                .or_insert_with(|| JobTypeTiming {
+                    job_type_name: job_timing.job_type_name,
+                    job_count: 0,
+                    cached_job_count: 0,
+                    total_duration: Duration::ZERO,
+                });
+            job_type_timing.job_count += 1;
+            if job_timing.used_cached_result {
+                job_type_timing.cached_job_count += 1;
+            }
+            job_type_timing.total_duration += job_timing.run_duration();
+        }
+
+        let mut job_type_timings: Vec<_> = job_type_timings.into_values().collect();
+        job_type_timings.sort_by(|a, b| {
+            b.total_duration
+                .cmp(&a.total_duration)
+                .then(a.job_type_name.cmp(b.job_type_name))
+        });
+
+        let (critical_path, critical_path_duration) = Self::find_critical_path(log_data);
+
+        BuildTimingReport {
+            total_duration,
+            slowest_jobs,
+            job_type_timings,
+            critical_path,
+            critical_path_duration,
+        }
+    }
+
+    // A job can't start until its upstream jobs have finished, and it can't be enqueued until the
+    // job that requested it has run. Both are treated as edges in the graph.
+    fn find_critical_path(log_data: &BuildLogData) -> (Vec<JobTiming>, Duration) {
+        let job_timings = log_data.job_timings();
+
+        let predecessors = |job_timing: &JobTiming| -> Vec<JobId> {
+            let mut predecessors = job_timing.upstream_jobs.clone();
+            for requestor in log_data.requestors(job_timing.job_id) {
+                if let JobRequestor::Job(job_id) = requestor {
+                    predecessors.push(*job_id);
+                }
+            }
+            predecessors.retain(|x| job_timings.contains_key(x));
+            predecessors
+        };
+
+        // The longest path ending at each job, and the predecessor on that path. Dependency chains
+        // in large builds can be long enough to overflow the stack, so this walks them depth first
+        // with a stack of its own. A job is finished once all its predecessors are.
+        fn longest_path_to(
+            job_id: JobId,
+            job_timings: &HashMap<JobId, JobTiming>,
+            predecessors: &dyn Fn(&JobTiming) -> Vec<JobId>,
+            longest_paths: &mut HashMap<JobId, (Duration, Option<JobId>)>,
+        ) -> Duration {
+            // Predecessors are None until the job has been visited and its predecessors pushed
+            let mut stack: Vec<(JobId, Option<Vec<JobId>>)> = vec![(job_id, None)];
+            let mut visiting = HashSet::default();
+            while let Some((next_job_id, job_predecessors)) = stack.pop() {
+                if longest_paths.contains_key(&next_job_id) {
+                    continue;
+                }
+
+                let Some(job_predecessors) = job_predecessors else {
+                    // Guard against cycles, they shouldn't exist but the report shouldn't hang if
+                    // they do. A job that is already being visited is only reached again by a cycle.
+                    if !visiting.insert(next_job_id) {
+                        continue;
+                    }
+
+                    let job_predecessors = predecessors(&job_timings[&next_job_id]);
+                    let unfinished: Vec<_> = job_predecessors
+                        .iter()
+                        .filter(|x| !longest_paths.contains_key(x) && !visiting.contains(x))
+                        .copied()
+                        .collect();
+                    stack.push((next_job_id, Some(job_predecessors)));
+                    stack.extend(unfinished.into_iter().map(|x| (x, None)));
+                    continue;
+                };
+
+                // Predecessors that are part of a cycle were not finished and count as zero
+                let mut longest_predecessor = None;
+                let mut longest_predecessor_duration = Duration::ZERO;
+                for predecessor in job_predecessors {
+                    let duration = longest_paths
+                        .get(&predecessor)
+                        .map(|(duration, _)| *duration)
+                        .unwrap_or(Duration::ZERO);
+                    if longest_predecessor.is_none() || duration > longest_predecessor_duration {
+                        longest_predecessor = Some(predecessor);
+                        longest_predecessor_duration = duration;
+                    }
+                }
+
+                visiting.remove(&next_job_id);
+                let duration =
+                    longest_predecessor_duration + job_timings[&next_job_id].run_duration();
+                longest_paths.insert(next_job_id, (duration, longest_predecessor));
+            }
+
+            longest_paths[&job_id].0
+        }
+
+        let mut longest_paths = HashMap::default();
+        let mut job_ids: Vec<_> = job_timings.keys().copied().collect();
+        job_ids.sort();
+
+        let mut critical_path_end = None;
+        let mut critical_path_duration = Duration::ZERO;
+        for job_id in job_ids {
+            let duration = longest_path_to(job_id, job_timings, &predecessors, &mut longest_paths);
+            if critical_path_end.is_none() || duration > critical_path_duration {
+                critical_path_end = Some(job_id);
+                critical_path_duration = duration;
+            }
+        }
+
+        let mut critical_path = Vec::default();
+        let mut visited = HashSet::default();
+        let mut next = critical_path_end;
+        while let Some(job_id) = next {
+            if !visited.insert(job_id) {
+                break;
+            }
+
+            critical_path.push(job_timings[&job_id].clone());
+            next = longest_paths.get(&job_id).and_then(|x| x.1);
+        }
+        critical_path.reverse();
+
+        (critical_path, critical_path_duration)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::default();
+
+        // Writing to a String can't fail
+        writeln!(text, "Build took {:.3}s", self.total_duration.as_secs_f64()).unwrap();
+
+        writeln!(text, "\nSlowest jobs:").unwrap();
+        for job_timing in &self.slowest_jobs {
+            writeln!(
+                text,
+                "  {:>10.3}s  {}{}",
+                job_timing.run_duration().as_secs_f64(),
+                job_timing.debug_name,
+                if job_timing.used_cached_result {
+                    " (cached)"
+                } else {
+                    ""
+                }
+            )
+            .unwrap();
+        }
+
+        writeln!(text, "\nTime per job type:").unwrap();
+        for job_type_timing in &self.job_type_timings {
+            writeln!(
+                text,
+                "  {:>10.3}s  {} ({} jobs, {} cached)",
+                job_type_timing.total_duration.as_secs_f64(),
+                job_type_timing.job_type_name,
+                job_type_timing.job_count,
+                job_type_timing.cached_job_count
+            )
+            .unwrap();
+        }
+
+        writeln!(
+            text,
+            "\nCritical path ({:.3}s):",
+            self.critical_path_duration.as_secs_f64()
+        )
+        .unwrap();
+        for job_timing in &self.critical_path {
+            writeln!(
+                text,
+                "  {:>10.3}s  {}",
+                job_timing.run_duration().as_secs_f64(),
+                job_timing.debug_name
+            )
+            .unwrap();
+        }
+
+        text
+    }
+}
+
+// Produces a trace that can be opened in chrome://tracing or Perfetto. Each job is a slice on the
+// thread that ran it.
+pub fn build_chrome_trace_json(log_data: &BuildLogData) -> serde_json::Value {
+    let mut job_timings: Vec<_> = log_data
+        .job_timings()
+        .values()
+        .filter(|x| x.started.is_some())
+        .collect();
+    job_timings.sort_by_key(|x| (x.started, x.job_id));
+
+    let trace_events: Vec<_> = job_timings
+        .into_iter()
+        .map(|job_timing| {
+            serde_json::json!({
+                "name": *job_timing.debug_name,
+                "cat": job_timing.job_type_name,
+                "ph": "X",
+                "ts": job_timing.started.unwrap_or_default().as_micros() as u64,
+                "dur": job_timing.run_duration().as_micros() as u64,
+                "pid": 0,
+                "tid": job_timing.thread_index.unwrap_or_default(),
+                "args": {
+                    "job_id": job_timing.job_id.as_uuid().to_string(),
+                    "enqueued_us": job_timing.enqueued.as_micros() as u64,
+                    "cached": job_timing.used_cached_result,
+                    "upstream_jobs": job_timing
+                        .upstream_jobs
+                        .iter()
+                        .map(|x| x.as_uuid().to_string())
+                        .collect::<Vec<_>>(),
+                }
+            })
+        })
+        .collect();
+
+    serde_json::json!({ "traceEvents": trace_events })
+}
+
+// Writes <name>.build_report (plain text) and <name>.build_trace.json into the given directory
+pub(crate) fn write_build_timing_report(
+    directory: &Path,
+    name: &str,
+    log_data: &BuildLogData,
+) -> PipelineResult<()> {
+    std::fs::create_dir_all(directory)?;
+
+    let report = BuildTimingReport::new(log_data);
+    std::fs::write(
+        directory.join(format!("{}.build_report", name)),
+        report.to_text(),
+    )?;
+
+    let trace_json = serde_json::to_string(&build_chrome_trace_json(log_data))?;
+    std::fs::write(
+        directory.join(format!("{}.build_trace.json", name)),
+        trace_json,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn job_timing(
+        index: u128,
+        upstream_jobs: Vec<JobId>,
+    ) -> JobTiming {
+        JobTiming {
+            job_id: JobId::from_u128(index),
+            job_type_name: "TestJob",
+            debug_name: Arc::new(index.to_string()),
+            upstream_jobs,
+            enqueued: Duration::ZERO,
+            started: Some(Duration::from_millis(index as u64)),
+            finished: Some(Duration::from_millis(index as u64 + 1)),
+            thread_index: Some(0),
+            used_cached_result: false,
+        }
+    }
+
+    // Far longer than could be walked with recursion. Each job depends on the next one, so the
+    // first job looked at is at the end of the whole chain.
+    #[test]
+    fn critical_path_of_long_chain() {
+        const CHAIN_LENGTH: u128 = 200_000;
+        let mut log_data = BuildLogData::default();
+        for i in 1..=CHAIN_LENGTH {
+            let upstream_jobs = if i < CHAIN_LENGTH {
+                vec![JobId::from_u128(i + 1)]
+            } else {
+                vec![]
+            };
+            log_data
+                .job_timings
+                .insert(JobId::from_u128(i), job_timing(i, upstream_jobs));
+        }
+
+        let report = BuildTimingReport::new(&log_data);
+        assert_eq!(report.critical_path.len(), CHAIN_LENGTH as usize);
+        assert_eq!(
+            report.critical_path[0].job_id,
+            JobId::from_u128(CHAIN_LENGTH)
+        );
+        assert_eq!(
+            report.critical_path_duration,
+            Duration::from_millis(CHAIN_LENGTH as u64)
+        );
+    }
+
+    // Cycles shouldn't happen, but the report still finishes if they do
+    #[test]
+    fn critical_path_with_cycle() {
+        let mut log_data = BuildLogData::default();
+        for (i, upstream_job) in [(1, 3), (2, 1), (3, 2)] {
+            log_data.job_timings.insert(
+                JobId::from_u128(i),
+                job_timing(i, vec![JobId::from_u128(upstream_job)]),
+            );
+        }
+
+        let report = BuildTimingReport::new(&log_data);
+        assert_eq!(report.critical_path.len(), 3);
+        assert_eq!(report.critical_path_duration, Duration::from_millis(3));
+    }
+}
@@ -1,7 +1,8 @@
-use super::{JobApi, JobId, JobProcessor, JobRequestor};
-use crate::{BuildLogEvent, LogEventLevel, PipelineResult};
+use super::{JobApi, JobId, JobProcessor, JobRequestor, ValidationContext};
+use crate::{BuildLogEvent, BuildProfile, LogEventLevel, PipelineResult};
 use hydrate_base::{ArtifactId, BuiltArtifactHeaderData};
 use hydrate_data::{AssetId, DataSet, SchemaSet};
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -13,6 +14,7 @@ pub struct BuiltArtifact {
     pub artifact_key_debug_name: Option<String>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct WrittenArtifact {
     pub asset_id: AssetId,
     pub artifact_id: ArtifactId,
@@ -25,6 +27,7 @@ pub struct BuilderContext<'a> {
     pub asset_id: AssetId,
     pub data_set: &'a DataSet,
     pub schema_set: &'a SchemaSet,
+    pub build_profile: &'a BuildProfile,
     pub job_api: &'a dyn JobApi,
     pub(crate) log_events: &'a Rc<RefCell<&'a mut Vec<BuildLogEvent>>>,
 }
@@ -87,4 +90,14 @@ pub trait Builder {
         &self,
         context: BuilderContext,
     ) -> PipelineResult<()>;
+
+    // Checks the asset for problems without building it. This runs before start_jobs() and can
+    // also run on its own over many assets, so it should be quick. If any errors are reported
+    // the asset is not built.
+    fn validate(
+        &self,
+        _context: ValidationContext,
+    ) -> PipelineResult<()> {
+        Ok(())
+    }
 }
@@ -1,19 +1,24 @@
-use crate::build::{BuiltArtifact, WrittenArtifact};
+use crate::build::{BuiltArtifact, JobTiming, WrittenArtifact};
 use crate::import::ImportData;
-use crate::{BuildLogData, BuildLogEvent, LogEventLevel, PipelineResult};
+use crate::{
+    BuildLogData, BuildLogEvent, BuildProfile, LogEventLevel, PipelineError, PipelineEvent,
+    PipelineEvents, PipelineResult,
+};
 use crossbeam_channel::{Receiver, Sender};
-use hydrate_base::hashing::HashMap;
+use hydrate_base::hashing::{HashMap, HashSet};
 use hydrate_base::uuid_path::uuid_and_hash_to_path;
 use hydrate_base::{ArtifactId, AssetId};
 use hydrate_data::{DataSet, SchemaSet};
 use serde::{Deserialize, Serialize};
+use siphasher::sip128::Hasher128;
 use std::cell::RefCell;
-use std::hash::Hasher;
+use std::hash::{Hash, Hasher};
 use std::io::{BufWriter, Write};
 use std::panic::RefUnwindSafe;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 
 use super::*;
 
@@ -28,6 +33,10 @@ where
         self.0.version()
     }
 
+    fn type_name_inner(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
     fn enumerate_dependencies_inner(
         &self,
         job_id: JobId,
@@ -54,8 +63,9 @@ where
         input: &Vec<u8>,
         data_set: &DataSet,
         schema_set: &SchemaSet,
+        build_profile: &BuildProfile,
         job_api: &dyn JobApi,
-        fetched_asset_data: &mut HashMap<AssetId, FetchedAssetData>,
+        fetched_asset_data: &mut HashMap<AssetDataRead, u64>,
         fetched_import_data: &mut HashMap<AssetId, FetchedImportData>,
         log_events: &mut Vec<BuildLogEvent>,
     ) -> PipelineResult<Arc<Vec<u8>>> {
@@ -67,6 +77,7 @@ where
                 input: &data,
                 data_set,
                 schema_set,
+                build_profile,
                 fetched_asset_data: &Rc::new(RefCell::new(fetched_asset_data)),
                 fetched_import_data: &Rc::new(RefCell::new(fetched_import_data)),
                 job_api,
@@ -77,41 +88,30 @@ where
     }
 }
 
-// struct JobHistory {
-//     // version() returned from the processor, if it bumps we invalidate the job
-//     job_version: u32,
-//
-//     // The dependencies that existed when we ran this job last time (may not need this?)
-//     dependencies: JobEnumeratedDependencies,
-//     // Hash of import data used to run the job. If our import data changed, the job results can't be
-//     // reused
-//     import_data_hashes: HashMap<AssetId, u128>,
-//     // All the jobs this job produced. Even if we can reuse the results of this job, we will have
-//     // to check downstream jobs do not detect an input data change.
-//     downstream_jobs: Vec<QueuedJob>,
-// }
-
 struct JobState {
     job_type: JobTypeId,
     dependencies: Arc<JobEnumeratedDependencies>,
     input_data: Arc<Vec<u8>>,
     debug_name: Arc<String>,
 
+    // Upstream jobs that haven't finished. The job is sent to the thread pool when this reaches zero.
+    remaining_upstream_job_count: usize,
+    // Set if a prioritized asset needs this job, it starts ahead of other jobs
+    prioritized: bool,
+
     // When we send the job to the thread pool, this is set to true
     has_been_scheduled: bool,
-    // This would eventually be stored on file system
+    // Set when the job completes. Results are also persisted in the job cache.
     output_data: Option<JobStateOutput>,
 }
 
 struct JobStateOutput {
     _output_data: PipelineResult<Arc<Vec<u8>>>,
-    _fetched_asset_data: HashMap<AssetId, FetchedAssetData>,
-    _fetched_import_data: HashMap<AssetId, FetchedImportData>,
+    _fetched_asset_data: HashMap<AssetDataRead, u64>,
+    _fetched_import_data: HashMap<AssetId, FetchedImportDataInfo>,
 }
 
-//TODO: Future optimization, we clone this and it could be big, especially when we re-run jobs. We
-// could just enqueue the ID of the job if we have the job history
-#[derive(Clone)]
+// Moved through the create queue, the input data is shared with the job state rather than copied
 struct QueuedJob {
     job_id: JobId,
     job_requestor: JobRequestor,
@@ -119,6 +119,7 @@ struct QueuedJob {
     input_data: Arc<Vec<u8>>,
     dependencies: PipelineResult<JobEnumeratedDependencies>,
     debug_name: Arc<String>,
+    enqueued_instant: std::time::Instant,
 }
 
 #[derive(Default)]
@@ -203,12 +204,32 @@ impl JobProcessorRegistry {
     ) -> Option<Arc<dyn JobProcessorAbstract>> {
         self.inner.job_processors.get(&job_type).cloned()
     }
+
+    // Workers must have the same processors at the same versions as the build they connect to
+    pub(crate) fn job_processor_versions(&self) -> Vec<(JobTypeId, u32)> {
+        self.inner
+            .job_processors
+            .iter()
+            .map(|(job_type, job_processor)| (*job_type, job_processor.version_inner()))
+            .collect()
+    }
 }
 
 struct JobApiImplInner {
     schema_set: SchemaSet,
     import_data_root_path: PathBuf,
     build_data_root_path: PathBuf,
+    // Set when a build starts, all jobs in the build use the same profile
+    build_profile: RwLock<Arc<BuildProfile>>,
+    // Set when a build starts, if the build should check that jobs are deterministic
+    build_verification: RwLock<Option<Arc<BuildVerification>>>,
+    // Set when the current build is cancelled, cleared when the next build starts
+    cancelled: AtomicBool,
+    // Incremented when a build starts, job workers use it to tell if their data set is stale
+    build_id: AtomicU64,
+    // If set, jobs run in worker processes instead of on the thread pool
+    job_worker_pool: RwLock<Option<Arc<JobWorkerPool>>>,
+    pipeline_events: PipelineEvents,
     job_processor_registry: JobProcessorRegistry,
     job_create_queue_tx: Sender<QueuedJob>,
     artifact_handle_created_tx: Sender<AssetArtifactIdPair>,
@@ -237,7 +258,9 @@ impl JobApi for JobApiImpl {
         // - Intermediate data (we need the job's input hash, which takes into account the parameters of the job including
         //   hashes of above stuff
         // - Build Data (we need the build hash, which takes into account the asset/import data
-        let job_id = JobId::from_u128(new_job.input_hash);
+        //
+        // Jobs with the same input must run separately for each build profile
+        let job_id = self.job_id_for_input_hash(new_job.input_hash);
         let processor = self
             .inner
             .job_processor_registry
@@ -262,6 +285,7 @@ impl JobApi for JobApiImpl {
                 input_data: Arc::new(new_job.input_data),
                 dependencies,
                 debug_name: Arc::new(debug_name),
+                enqueued_instant: std::time::Instant::now(),
             })
             .unwrap();
 
@@ -286,64 +310,167 @@ impl JobApi for JobApiImpl {
     fn produce_artifact(
         &self,
         artifact: BuiltArtifact,
+    ) -> PipelineResult<()> {
+        let written_artifact = self.write_artifact(artifact)?;
+        self.send_written_artifact(written_artifact);
+        Ok(())
+    }
+
+    fn fetch_import_data(
+        &self,
+        asset_id: AssetId,
+    ) -> PipelineResult<ImportData> {
+        crate::import::load_import_data(
+            &self.inner.import_data_root_path,
+            &self.inner.schema_set,
+            asset_id,
+        )
+    }
+
+    fn build_profile(&self) -> Arc<BuildProfile> {
+        self.inner.build_profile.read().unwrap().clone()
+    }
+
+    fn report_progress(
+        &self,
+        job_id: JobId,
+        progress: f32,
     ) {
+        self.inner.pipeline_events.send(PipelineEvent::JobProgress {
+            job_id,
+            progress: progress.clamp(0.0, 1.0),
+        });
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl JobApiImpl {
+    pub(super) fn pipeline_events(&self) -> &PipelineEvents {
+        &self.inner.pipeline_events
+    }
+
+    pub(super) fn import_data_root_path(&self) -> &Path {
+        &self.inner.import_data_root_path
+    }
+
+    pub(super) fn build_id(&self) -> u64 {
+        self.inner.build_id.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn job_worker_pool(&self) -> Option<Arc<JobWorkerPool>> {
+        self.inner.job_worker_pool.read().unwrap().clone()
+    }
+
+    // Returns the verification settings if the job should be verified in the current build
+    pub(super) fn build_verification(
+        &self,
+        job_type_name: &str,
+    ) -> Option<Arc<BuildVerification>> {
+        self.inner
+            .build_verification
+            .read()
+            .unwrap()
+            .clone()
+            .filter(|x| x.should_verify(job_type_name))
+    }
+
+    // Jobs with the same input must run separately for each build profile
+    pub(super) fn job_id_for_input_hash(
+        &self,
+        input_hash: u128,
+    ) -> JobId {
+        let build_profile = self.build_profile();
+        if build_profile.name.is_empty() {
+            JobId::from_u128(input_hash)
+        } else {
+            let mut hasher = siphasher::sip128::SipHasher::default();
+            input_hash.hash(&mut hasher);
+            build_profile.hash(&mut hasher);
+            JobId::from_u128(hasher.finish128().as_u128())
+        }
+    }
+
+    // Covers everything written to the artifact's file, so artifacts with the same hash are
+    // byte-identical
+    pub(super) fn artifact_build_hash(
+        &self,
+        artifact: &BuiltArtifact,
+    ) -> u64 {
+        let build_profile = self.build_profile();
+        let mut hasher = siphasher::sip::SipHasher::default();
+        artifact.data.hash(&mut hasher);
+        artifact.metadata.hash(&mut hasher);
+        if !build_profile.name.is_empty() {
+            build_profile.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    pub(super) fn write_artifact(
+        &self,
+        artifact: BuiltArtifact,
+    ) -> PipelineResult<WrittenArtifact> {
         profiling::scope!("Write Asset to Disk");
         //
         // Hash the artifact
         //
-        let mut hasher = siphasher::sip::SipHasher::default();
-        artifact.data.hash(&mut hasher);
-        artifact.metadata.hash(&mut hasher);
-        let build_hash = hasher.finish();
+        let build_profile = self.build_profile();
+        let build_hash = self.artifact_build_hash(&artifact);
 
         //
         // Determine where we will store the asset and ensure the directory exists
         //
         let path = uuid_and_hash_to_path(
-            &self.inner.build_data_root_path,
+            &build_profile.build_data_path(&self.inner.build_data_root_path),
             artifact.artifact_id.as_uuid(),
             build_hash,
             "bf",
         );
 
         if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent).unwrap();
+            std::fs::create_dir_all(parent)?;
         }
 
         //
         // Serialize the artifacts to disk
         //
-        let file = std::fs::File::create(&path).unwrap();
-        let mut buf_writer = BufWriter::new(file);
-        artifact.metadata.write_header(&mut buf_writer).unwrap();
-        buf_writer.write(&artifact.data).unwrap();
+        let write_file = || -> std::io::Result<()> {
+            let file = std::fs::File::create(&path)?;
+            let mut buf_writer = BufWriter::new(file);
+            artifact.metadata.write_header(&mut buf_writer)?;
+            buf_writer.write_all(&artifact.data)?;
+            buf_writer.flush()
+        };
 
-        //
-        // Send info about the written asset back to main thread for inclusion in the manifest
-        //
+        if let Err(e) = write_file() {
+            // Don't leave a partially written artifact behind
+            let _ = std::fs::remove_file(&path);
+            Err(format!("Failed to write artifact {:?}: {}", path, e))?;
+        }
+
+        Ok(WrittenArtifact {
+            asset_id: artifact.asset_id,
+            artifact_id: artifact.artifact_id,
+            metadata: artifact.metadata,
+            build_hash,
+            artifact_key_debug_name: artifact.artifact_key_debug_name,
+        })
+    }
+
+    // Send info about the written asset back to main thread for inclusion in the manifest
+    pub(super) fn send_written_artifact(
+        &self,
+        written_artifact: WrittenArtifact,
+    ) {
         //TODO: Is this necessary, can we handle it when the job result is returned?
         self.inner
             .written_artifact_queue_tx
-            .send(WrittenArtifact {
-                asset_id: artifact.asset_id,
-                artifact_id: artifact.artifact_id,
-                metadata: artifact.metadata,
-                build_hash,
-                artifact_key_debug_name: artifact.artifact_key_debug_name,
-            })
+            .send(written_artifact)
             .unwrap();
     }
-
-    fn fetch_import_data(
-        &self,
-        asset_id: AssetId,
-    ) -> PipelineResult<ImportData> {
-        crate::import::load_import_data(
-            &self.inner.import_data_root_path,
-            &self.inner.schema_set,
-            asset_id,
-        )
-    }
 }
 
 #[derive(Clone, Debug)]
@@ -353,8 +480,6 @@ pub struct AssetArtifactIdPair {
 }
 
 pub struct JobExecutor {
-    // Will be needed when we start doing job caching
-    _root_path: PathBuf,
     job_api_impl: JobApiImpl,
 
     job_processor_registry: JobProcessorRegistry,
@@ -365,7 +490,16 @@ pub struct JobExecutor {
     // Queue for jobs to request additional jobs to run
     job_create_queue_rx: Receiver<QueuedJob>,
 
-    //TODO: We will have additional deques for jobs that are in a ready state to avoid O(n) iteration
+    // Jobs waiting on each unfinished job
+    downstream_jobs: HashMap<JobId, Vec<JobId>>,
+    // The jobs each builder or job requested, used to find everything a prioritized asset needs
+    jobs_by_requestor: HashMap<JobRequestor, Vec<JobId>>,
+    // Jobs that are no longer waiting on anything, sent to the thread pool at the end of the update
+    ready_jobs: Vec<JobId>,
+    // Jobs that haven't produced output yet
+    unfinished_job_count: usize,
+    prioritized_assets: HashSet<AssetId>,
+
     artifact_handle_created_rx: Receiver<AssetArtifactIdPair>,
 
     written_artifact_queue_rx: Receiver<WrittenArtifact>,
@@ -374,6 +508,8 @@ pub struct JobExecutor {
     thread_pool: Option<JobExecutorThreadPool>,
 
     completed_job_count: usize,
+    // Jobs that were skipped because a result was found in the job cache
+    cached_job_count: usize,
     last_job_print_time: Option<std::time::Instant>,
 }
 
@@ -385,10 +521,29 @@ impl Drop for JobExecutor {
 }
 
 impl JobExecutor {
-    pub fn reset(&mut self) {
+    pub fn reset(
+        &mut self,
+        build_profile: Arc<BuildProfile>,
+        build_verification: Option<Arc<BuildVerification>>,
+    ) {
         assert!(self.is_idle());
+        *self.job_api_impl.inner.build_profile.write().unwrap() = build_profile;
+        *self.job_api_impl.inner.build_verification.write().unwrap() = build_verification;
+        self.job_api_impl
+            .inner
+            .cancelled
+            .store(false, Ordering::Relaxed);
+        self.job_api_impl
+            .inner
+            .build_id
+            .fetch_add(1, Ordering::Relaxed);
         self.current_jobs.clear();
+        self.downstream_jobs.clear();
+        self.jobs_by_requestor.clear();
+        self.ready_jobs.clear();
+        self.unfinished_job_count = 0;
         self.completed_job_count = 0;
+        self.cached_job_count = 0;
     }
 
     pub fn new(
@@ -397,6 +552,7 @@ impl JobExecutor {
         import_data_root_path: PathBuf,
         job_data_root_path: PathBuf,
         build_data_root_path: PathBuf,
+        pipeline_events: PipelineEvents,
     ) -> Self {
         let (job_create_queue_tx, job_create_queue_rx) = crossbeam_channel::unbounded();
         //let (job_completed_queue_tx, job_completed_queue_rx) = crossbeam_channel::unbounded();
@@ -410,7 +566,13 @@ impl JobExecutor {
             inner: Arc::new(JobApiImplInner {
                 schema_set: schema_set.clone(),
                 import_data_root_path: import_data_root_path.clone(),
-                build_data_root_path,
+                build_data_root_path: build_data_root_path.clone(),
+                build_profile: Default::default(),
+                build_verification: Default::default(),
+                cancelled: AtomicBool::new(false),
+                build_id: AtomicU64::new(0),
+                job_worker_pool: Default::default(),
+                pipeline_events,
                 job_processor_registry: job_processor_registry.clone(),
                 job_create_queue_tx,
                 artifact_handle_created_tx,
@@ -421,24 +583,34 @@ impl JobExecutor {
         let thread_count = num_cpus::get();
         //let thread_count = 1;
 
+        let job_cache = Arc::new(JobCache::new(
+            job_data_root_path,
+            import_data_root_path,
+            build_data_root_path,
+        ));
+
         let (thread_pool_result_tx, thread_pool_result_rx) = crossbeam_channel::unbounded();
         let thread_pool = JobExecutorThreadPool::new(
             job_processor_registry.clone(),
             schema_set.clone(),
-            &job_data_root_path,
+            job_cache,
             job_api_impl.clone(),
             thread_count,
             thread_pool_result_tx,
         );
 
         JobExecutor {
-            _root_path: job_data_root_path,
             job_api_impl,
             job_processor_registry: job_processor_registry.clone(),
             //job_history: Default::default(),
             current_jobs: Default::default(),
             //job_create_queue_tx,
             job_create_queue_rx,
+            downstream_jobs: Default::default(),
+            jobs_by_requestor: Default::default(),
+            ready_jobs: Default::default(),
+            unfinished_job_count: 0,
+            prioritized_assets: Default::default(),
             //job_completed_queue_tx,
             //job_completed_queue_rx,
             // built_asset_queue_tx,
@@ -450,14 +622,84 @@ impl JobExecutor {
             thread_pool_result_rx,
             thread_pool: Some(thread_pool),
             completed_job_count: 0,
+            cached_job_count: 0,
             last_job_print_time: None,
         }
     }
 
+    // Jobs that start after this is called run in worker processes. Pass None to run jobs on the
+    // thread pool again.
+    pub fn set_job_worker_pool(
+        &self,
+        job_worker_pool: Option<Arc<JobWorkerPool>>,
+    ) {
+        *self.job_api_impl.inner.job_worker_pool.write().unwrap() = job_worker_pool;
+    }
+
+    pub fn job_worker_pool(&self) -> Option<Arc<JobWorkerPool>> {
+        self.job_api_impl.job_worker_pool()
+    }
+
+    pub fn job_processor_registry(&self) -> &JobProcessorRegistry {
+        &self.job_processor_registry
+    }
+
     pub fn job_api(&self) -> &dyn JobApi {
         &self.job_api_impl
     }
 
+    // Jobs that haven't started are dropped and running jobs may stop early. The executor becomes
+    // idle once running jobs have returned.
+    pub fn cancel(&self) {
+        self.job_api_impl
+            .inner
+            .cancelled
+            .store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.job_api_impl.is_cancelled()
+    }
+
+    // Jobs for these assets, and the jobs they need, start ahead of other jobs. Jobs that were
+    // already prioritized stay that way until the next build.
+    pub fn set_prioritized_assets(
+        &mut self,
+        asset_ids: &HashSet<AssetId>,
+    ) {
+        if self.prioritized_assets == *asset_ids {
+            return;
+        }
+
+        let mut prioritized_jobs = Vec::default();
+        for &asset_id in asset_ids {
+            if !self.prioritized_assets.contains(&asset_id) {
+                if let Some(job_ids) = self.jobs_by_requestor.get(&JobRequestor::Builder(asset_id))
+                {
+                    prioritized_jobs.extend(job_ids.iter().copied());
+                }
+            }
+        }
+
+        self.prioritized_assets = asset_ids.clone();
+        for job_id in prioritized_jobs {
+            self.prioritize_job(job_id);
+        }
+    }
+
+    // Limits how many jobs of the given type run at the same time, for example so that
+    // memory-hungry jobs don't run on every thread at once. None removes the limit.
+    pub fn set_max_concurrent_jobs(
+        &self,
+        job_type: JobTypeId,
+        max_concurrent_jobs: Option<usize>,
+    ) {
+        self.thread_pool
+            .as_ref()
+            .unwrap()
+            .set_max_concurrent_jobs(job_type, max_concurrent_jobs);
+    }
+
     // pub fn take_built_assets(&self) -> Vec<BuiltAsset> {
     //     let mut built_assets = Vec::default();
     //     while let Ok(built_asset) = self.built_asset_queue_rx.try_recv() {
@@ -470,131 +712,458 @@ impl JobExecutor {
     pub fn take_written_artifacts(
         &self,
         artifact_asset_lookup: &mut HashMap<ArtifactId, AssetId>,
+        log_events: &mut Vec<BuildLogEvent>,
     ) -> Vec<WrittenArtifact> {
-        let mut written_artifacts = Vec::default();
-        while let Ok(written_artifact) = self.written_artifact_queue_rx.try_recv() {
-            let old = artifact_asset_lookup
-                .insert(written_artifact.artifact_id, written_artifact.asset_id);
-            //assert!(old.is_none());
-
-            if old.is_some() {
-                // Is it possible a job has already created a handle to this asset, even if the asset hasn't been built yet
-                assert_eq!(old, Some(written_artifact.asset_id));
+        // An artifact ID is derived from the asset ID, so it can only belong to a different asset
+        // if a job made up its own artifact IDs. The first asset is kept.
+        fn add_to_lookup(
+            artifact_asset_lookup: &mut HashMap<ArtifactId, AssetId>,
+            artifact_id: ArtifactId,
+            asset_id: AssetId,
+            log_events: &mut Vec<BuildLogEvent>,
+        ) {
+            // It's possible a job has already created a handle to this artifact, even if the
+            // asset hasn't been built yet
+            let old = *artifact_asset_lookup.entry(artifact_id).or_insert(asset_id);
+            if old != asset_id {
+                let log_event = BuildLogEvent {
+                    job_id: None,
+                    asset_id: Some(asset_id),
+                    level: LogEventLevel::Error,
+                    message: format!(
+                        "Artifact {:?} is associated with asset {:?}, but asset {:?} also claims it",
+                        artifact_id, old, asset_id
+                    ),
+                };
+                log::error!("Build Error: {:?}", log_event);
+                log_events.push(log_event);
             }
+        }
 
+        let mut written_artifacts = Vec::default();
+        while let Ok(written_artifact) = self.written_artifact_queue_rx.try_recv() {
+            add_to_lookup(
+                artifact_asset_lookup,
+                written_artifact.artifact_id,
+                written_artifact.asset_id,
+                log_events,
+            );
             written_artifacts.push(written_artifact);
         }
 
+        // This happens after taking built artifacts because the built artifacts might have handles
+        // to artifacts and we need to know the asset ID associated with them.
         while let Ok(asset_artifact_pair) = self.artifact_handle_created_rx.try_recv() {
-            let old = artifact_asset_lookup.insert(
+            add_to_lookup(
+                artifact_asset_lookup,
                 asset_artifact_pair.artifact_id,
                 asset_artifact_pair.asset_id,
+                log_events,
             );
-            if old.is_some() {
-                // This happens after taking built artifacts because the built artifacts might have handles
-                // to artifacts and we need to know the asset ID associated with them.
-                assert_eq!(old, Some(asset_artifact_pair.asset_id));
-            }
         }
 
         written_artifacts
     }
 
+    fn is_prioritized_requestor(
+        &self,
+        job_requestor: JobRequestor,
+    ) -> bool {
+        match job_requestor {
+            JobRequestor::Builder(asset_id) => self.prioritized_assets.contains(&asset_id),
+            JobRequestor::Job(job_id) => self
+                .current_jobs
+                .get(&job_id)
+                .map(|x| x.prioritized)
+                .unwrap_or(false),
+        }
+    }
+
+    fn job_priority(
+        &self,
+        job_id: JobId,
+    ) -> JobPriority {
+        JobPriority {
+            prioritized: self.current_jobs[&job_id].prioritized,
+            waiting_job_count: self
+                .downstream_jobs
+                .get(&job_id)
+                .map(|x| x.len())
+                .unwrap_or(0),
+        }
+    }
+
+    // Prioritizes the job, the jobs it waits on and the jobs it requested
+    fn prioritize_job(
+        &mut self,
+        job_id: JobId,
+    ) {
+        let mut pending_jobs = vec![job_id];
+        while let Some(job_id) = pending_jobs.pop() {
+            let Some(job_state) = self.current_jobs.get_mut(&job_id) else {
+                continue;
+            };
+
+            if job_state.prioritized {
+                continue;
+            }
+
+            job_state.prioritized = true;
+            pending_jobs.extend(job_state.dependencies.upstream_jobs.iter().copied());
+            if let Some(requested_jobs) = self.jobs_by_requestor.get(&JobRequestor::Job(job_id)) {
+                pending_jobs.extend(requested_jobs.iter().copied());
+            }
+
+            // Jobs already sent to the thread pool move ahead if they haven't started
+            if job_state.has_been_scheduled && job_state.output_data.is_none() {
+                let priority = self.job_priority(job_id);
+                self.thread_pool
+                    .as_ref()
+                    .unwrap()
+                    .set_request_priority(job_id, priority);
+            }
+        }
+    }
+
+    // Records the job's output and releases the jobs waiting on it. If the job failed, the jobs
+    // waiting on it can't produce anything meaningful and are cancelled. Cancelled jobs count as
+    // failed, so anything downstream of them is cancelled too.
+    fn finish_job(
+        &mut self,
+        job_id: JobId,
+        output: JobStateOutput,
+        log_events: &mut Vec<BuildLogEvent>,
+    ) {
+        let mut finished_jobs = vec![(job_id, output)];
+        while let Some((job_id, output)) = finished_jobs.pop() {
+            let failed = output._output_data.is_err();
+            let job_state = self.current_jobs.get_mut(&job_id).unwrap();
+            assert!(job_state.output_data.is_none());
+            job_state.output_data = Some(output);
+            self.unfinished_job_count -= 1;
+            let debug_name = job_state.debug_name.clone();
+
+            for downstream_job_id in self.downstream_jobs.remove(&job_id).unwrap_or_default() {
+                let downstream_job_state = self.current_jobs.get_mut(&downstream_job_id).unwrap();
+                // Already cancelled
+                if downstream_job_state.has_been_scheduled {
+                    continue;
+                }
+
+                if failed {
+                    let cancel_reason = format!(
+                        "Job was cancelled because upstream job {:?} ({}) failed",
+                        job_id, debug_name
+                    );
+                    let log_event = BuildLogEvent {
+                        job_id: Some(downstream_job_id),
+                        asset_id: None,
+                        level: LogEventLevel::Error,
+                        message: cancel_reason.clone(),
+                    };
+                    log::error!("Build Error: {:?}", log_event);
+                    log_events.push(log_event);
+
+                    downstream_job_state.has_been_scheduled = true;
+                    self.completed_job_count += 1;
+                    finished_jobs.push((
+                        downstream_job_id,
+                        JobStateOutput {
+                            _output_data: Err(cancel_reason.into()),
+                            _fetched_asset_data: Default::default(),
+                            _fetched_import_data: Default::default(),
+                        },
+                    ));
+                } else {
+                    downstream_job_state.remaining_upstream_job_count -= 1;
+                    if downstream_job_state.remaining_upstream_job_count == 0 {
+                        self.ready_jobs.push(downstream_job_id);
+                    }
+                }
+            }
+        }
+    }
+
     fn handle_create_queue(
         &mut self,
         log_data: &mut BuildLogData,
     ) {
+        let mut created_jobs = Vec::default();
         while let Ok(queued_job) = self.job_create_queue_rx.try_recv() {
             log_data
                 .requestors
                 .entry(queued_job.job_id)
                 .or_default()
                 .push(queued_job.job_requestor);
+            let prioritized = self.is_prioritized_requestor(queued_job.job_requestor);
+            self.jobs_by_requestor
+                .entry(queued_job.job_requestor)
+                .or_default()
+                .push(queued_job.job_id);
+
             // If key exists, we already queued a job with these exact inputs and we can reuse the outputs
-            if !self.current_jobs.contains_key(&queued_job.job_id) {
-                assert!(self
-                    .job_processor_registry
-                    .contains_key(queued_job.job_type));
+            if self.current_jobs.contains_key(&queued_job.job_id) {
+                if prioritized {
+                    self.prioritize_job(queued_job.job_id);
+                }
+                continue;
+            }
 
-                let job_state = match queued_job.dependencies {
-                    Ok(dependencies) => JobState {
+            let dependencies = if self
+                .job_processor_registry
+                .contains_key(queued_job.job_type)
+            {
+                queued_job.dependencies
+            } else {
+                Err(format!(
+                    "No job processor is registered for job type {:?}",
+                    queued_job.job_type
+                )
+                .into())
+            };
+
+            let job_state = match dependencies {
+                Ok(dependencies) => {
+                    self.unfinished_job_count += 1;
+                    created_jobs.push(queued_job.job_id);
+                    JobState {
                         job_type: queued_job.job_type,
                         dependencies: Arc::new(dependencies),
                         input_data: queued_job.input_data,
                         debug_name: queued_job.debug_name,
+                        remaining_upstream_job_count: 0,
+                        prioritized: false,
                         has_been_scheduled: false,
                         output_data: None,
-                    },
-                    Err(e) => {
-                        let log_error = BuildLogEvent {
-                            job_id: Some(queued_job.job_id),
-                            asset_id: None,
-                            level: LogEventLevel::FatalError,
-                            message: format!(
-                                "enumerate_dependencies returned error: {}",
-                                e.to_string()
-                            ),
-                        };
-                        log::error!("Build Error: {:?}", log_error);
-                        log_data.log_events.push(log_error);
-
-                        JobState {
-                            job_type: queued_job.job_type,
-                            dependencies: Arc::new(JobEnumeratedDependencies::default()),
-                            input_data: queued_job.input_data,
-                            debug_name: queued_job.debug_name,
-                            has_been_scheduled: true,
-                            output_data: Some(JobStateOutput {
-                                _output_data: Err(e),
-                                _fetched_asset_data: Default::default(),
-                                _fetched_import_data: Default::default(),
-                            }),
+                    }
+                }
+                Err(e) => {
+                    let log_error = BuildLogEvent {
+                        job_id: Some(queued_job.job_id),
+                        asset_id: None,
+                        level: LogEventLevel::FatalError,
+                        message: format!(
+                            "enumerate_dependencies returned error: {}",
+                            e.to_string()
+                        ),
+                    };
+                    log::error!("Build Error: {:?}", log_error);
+                    log_data.log_events.push(log_error);
+
+                    JobState {
+                        job_type: queued_job.job_type,
+                        dependencies: Arc::new(JobEnumeratedDependencies::default()),
+                        input_data: queued_job.input_data,
+                        debug_name: queued_job.debug_name,
+                        remaining_upstream_job_count: 0,
+                        prioritized: false,
+                        has_been_scheduled: true,
+                        output_data: Some(JobStateOutput {
+                            _output_data: Err(e),
+                            _fetched_asset_data: Default::default(),
+                            _fetched_import_data: Default::default(),
+                        }),
+                    }
+                }
+            };
+
+            log_data.job_timings.insert(
+                queued_job.job_id,
+                JobTiming {
+                    job_id: queued_job.job_id,
+                    job_type_name: self
+                        .job_processor_registry
+                        .get(queued_job.job_type)
+                        .map(|x| x.type_name_inner())
+                        .unwrap_or("Unknown"),
+                    debug_name: job_state.debug_name.clone(),
+                    upstream_jobs: job_state.dependencies.upstream_jobs.clone(),
+                    enqueued: queued_job
+                        .enqueued_instant
+                        .saturating_duration_since(log_data.start_instant),
+                    started: None,
+                    finished: None,
+                    thread_index: None,
+                    used_cached_result: false,
+                },
+            );
+
+            self.current_jobs.insert(queued_job.job_id, job_state);
+            if prioritized {
+                self.prioritize_job(queued_job.job_id);
+            }
+        }
+
+        //
+        // Count the upstream jobs each new job waits on. This happens after the whole queue is
+        // drained so that a job can depend on a job that was created right after it.
+        //
+        for job_id in created_jobs {
+            let job_state = &self.current_jobs[&job_id];
+            // May have been cancelled by an upstream job that was created in this batch
+            if job_state.has_been_scheduled {
+                continue;
+            }
+
+            let mut remaining_upstream_job_count = 0;
+            let mut cancel_reason = None;
+            for upstream_job in &job_state.dependencies.upstream_jobs {
+                let Some(upstream_job_state) = self.current_jobs.get(upstream_job) else {
+                    cancel_reason = Some(format!(
+                        "Job has a dependency on job {:?} which has not been created",
+                        upstream_job
+                    ));
+                    break;
+                };
+
+                match &upstream_job_state.output_data {
+                    None => remaining_upstream_job_count += 1,
+                    Some(output_data) => {
+                        if output_data._output_data.is_err() {
+                            cancel_reason = Some(format!(
+                                "Job was cancelled because upstream job {:?} ({}) failed",
+                                upstream_job, upstream_job_state.debug_name
+                            ));
+                            break;
                         }
                     }
+                }
+            }
+
+            if let Some(cancel_reason) = cancel_reason {
+                let log_event = BuildLogEvent {
+                    job_id: Some(job_id),
+                    asset_id: None,
+                    level: LogEventLevel::Error,
+                    message: cancel_reason.clone(),
                 };
+                log::error!("Build Error: {:?}", log_event);
+                log_data.log_events.push(log_event);
+
+                self.current_jobs
+                    .get_mut(&job_id)
+                    .unwrap()
+                    .has_been_scheduled = true;
+                self.completed_job_count += 1;
+                self.finish_job(
+                    job_id,
+                    JobStateOutput {
+                        _output_data: Err(cancel_reason.into()),
+                        _fetched_asset_data: Default::default(),
+                        _fetched_import_data: Default::default(),
+                    },
+                    &mut log_data.log_events,
+                );
+                continue;
+            }
 
-                self.current_jobs.insert(queued_job.job_id, job_state);
+            let upstream_jobs = job_state.dependencies.clone();
+            for upstream_job in &upstream_jobs.upstream_jobs {
+                if self.current_jobs[upstream_job].output_data.is_none() {
+                    self.downstream_jobs
+                        .entry(*upstream_job)
+                        .or_default()
+                        .push(job_id);
+                }
+            }
+
+            let job_state = self.current_jobs.get_mut(&job_id).unwrap();
+            job_state.remaining_upstream_job_count = remaining_upstream_job_count;
+            if remaining_upstream_job_count == 0 {
+                self.ready_jobs.push(job_id);
             }
         }
     }
 
     fn handle_completed_queue(
         &mut self,
-        log_events: &mut Vec<BuildLogEvent>,
+        log_data: &mut BuildLogData,
     ) {
+        let log_events = &mut log_data.log_events;
         while let Ok(result) = self.thread_pool_result_rx.try_recv() {
             match result {
                 JobExecutorThreadPoolOutcome::RunJobComplete(msg) => {
-                    let job = self.current_jobs.get_mut(&msg.request.job_id).unwrap();
-                    match msg.result {
+                    if let Some(job_timing) = log_data.job_timings.get_mut(&msg.request.job_id) {
+                        job_timing.started = Some(
+                            msg.start_instant
+                                .saturating_duration_since(log_data.start_instant),
+                        );
+                        job_timing.finished = Some(
+                            msg.end_instant
+                                .saturating_duration_since(log_data.start_instant),
+                        );
+                        job_timing.thread_index = Some(msg.thread_index);
+                        job_timing.used_cached_result =
+                            matches!(&msg.result, Ok(data) if data.used_cached_result);
+                    }
+
+                    let output = match msg.result {
                         Ok(data) => {
-                            job.output_data = Some(JobStateOutput {
-                                _output_data: Ok(data.output_data),
-                                _fetched_asset_data: data.fetched_asset_data,
-                                _fetched_import_data: data.fetched_import_data,
-                            });
+                            if data.used_cached_result {
+                                self.cached_job_count += 1;
+                            }
+
+                            log_data
+                                .written_artifacts
+                                .insert(msg.request.job_id, data.written_artifacts);
+
+                            let mut assets_read: Vec<_> = data
+                                .fetched_asset_data
+                                .keys()
+                                .map(|x| x.asset_id())
+                                .chain(data.fetched_import_data.keys().copied())
+                                .collect();
+                            assets_read.sort();
+                            assets_read.dedup();
+                            log_data.assets_read.insert(msg.request.job_id, assets_read);
 
                             for log_event in data.log_events {
                                 log_events.push(log_event);
                             }
+
+                            for determinism_mismatch in data.determinism_mismatches {
+                                let log_event = BuildLogEvent {
+                                    job_id: Some(msg.request.job_id),
+                                    asset_id: determinism_mismatch.asset_id,
+                                    level: LogEventLevel::Error,
+                                    message: format!(
+                                        "Job processor {} is not deterministic: {}",
+                                        determinism_mismatch.job_type_name,
+                                        determinism_mismatch.message
+                                    ),
+                                };
+                                log::error!("Build Error: {:?}", log_event);
+                                log_events.push(log_event);
+                                log_data.determinism_mismatches.push(determinism_mismatch);
+                            }
+
+                            JobStateOutput {
+                                _output_data: Ok(data.output_data),
+                                _fetched_asset_data: data.fetched_asset_data,
+                                _fetched_import_data: data.fetched_import_data,
+                            }
                         }
                         Err(e) => {
-                            let log_event = BuildLogEvent {
-                                job_id: Some(msg.request.job_id),
-                                asset_id: None,
-                                level: LogEventLevel::FatalError,
-                                message: format!("Build job returned error: {}", e.to_string()),
-                            };
-                            log::error!("Build Error: {:?}", log_event);
-                            log_events.push(log_event);
-
-                            job.output_data = Some(JobStateOutput {
+                            // Jobs cancelled along with the build are not errors
+                            if !matches!(e.error, PipelineError::Cancelled) {
+                                let log_event = BuildLogEvent {
+                                    job_id: Some(msg.request.job_id),
+                                    asset_id: None,
+                                    level: LogEventLevel::FatalError,
+                                    message: format!("Build job returned error: {}", e.to_string()),
+                                };
+                                log::error!("Build Error: {:?}", log_event);
+                                log_events.push(log_event);
+                            }
+
+                            JobStateOutput {
                                 _output_data: Err(e),
                                 _fetched_asset_data: Default::default(),
                                 _fetched_import_data: Default::default(),
-                            });
+                            }
                         }
-                    }
+                    };
+                    self.finish_job(msg.request.job_id, output, log_events);
                     self.completed_job_count += 1;
 
                     // When we a
@@ -615,99 +1184,53 @@ impl JobExecutor {
         //
         self.handle_create_queue(log_data);
 
-        let mut started_jobs = Vec::default();
-
-        //TODO: Don't iterate every job in existence
-        for (&job_id, job_state) in &self.current_jobs {
-            //
-            // See if we already did this job during the current execution cycle
-            //
-            if job_state.has_been_scheduled {
-                continue;
-            }
-
-            assert!(job_state.output_data.is_none());
-
-            //
-            // See if the job we need to wait for has completed
-            //
-            let mut waiting_on_upstream_job = false;
-            for upstream_job in &job_state.dependencies.upstream_jobs {
-                let dependency = self.current_jobs.get(upstream_job);
-                let Some(dependency_job_state) = dependency else {
-                    panic!("Job has a dependency on another job that has not been created");
-                    //TODO: We would not terminate if we remove the panic
-                    //break;
-                };
-
-                if dependency_job_state.output_data.is_none() {
-                    waiting_on_upstream_job = true;
-                    break;
+        //
+        // If the build was cancelled, drop every job that hasn't been sent to the thread pool
+        //
+        if self.is_cancelled() {
+            for job_state in self.current_jobs.values_mut() {
+                if !job_state.has_been_scheduled {
+                    job_state.has_been_scheduled = true;
+                    job_state.output_data = Some(JobStateOutput {
+                        _output_data: Err(PipelineError::Cancelled.into()),
+                        _fetched_asset_data: Default::default(),
+                        _fetched_import_data: Default::default(),
+                    });
+                    self.unfinished_job_count -= 1;
                 }
             }
+            self.ready_jobs.clear();
 
-            if waiting_on_upstream_job {
-                continue;
-            }
-
-            //
-            // If we've run this job in the past and have a cached result, we can reuse the result.
-            // But we still need to schedule downstream jobs in case their dependencies changed and
-            // they need to be reprocessed
-            //
-            //let mut _has_run_job_before = false;
-            //let mut _can_reuse_result = true;
-            // let job_history = self.job_history.get(&job_id);
-            // if let Some(job_history) = job_history {
-            //     _has_run_job_before = true;
-            //     _can_reuse_result = true;
-            //     //TODO: Check if input data not represented in the job hash changed
-            //     // job_history.import_data_hashed
-            //     // job_history.dependencies
-            //
-            //     // can_reuse_result may be set to false here
-            //
-            //
-            //     if _has_run_job_before && _can_reuse_result {
-            //         // Kick off child jobs
-            //         for downstream_job in &job_history.downstream_jobs {
-            //             self.enqueue_job_internal(downstream_job.clone());
-            //         }
-            //
-            //         // Bail, we will reuse the output from the previous run
-            //         break;
-            //     }
-            // }
-
-            //
-            // At this point we have either never run the job before, or we know the job inputs have changed
-            // Go ahead and run it.
-            //
-            self.thread_pool
-                .as_ref()
-                .unwrap()
-                .add_request(JobExecutorThreadPoolRequest::RunJob(
-                    JobExecutorThreadPoolRequestRunJob {
-                        job_id,
-                        job_type: job_state.job_type,
-                        data_set: data_set.clone(),
-                        _debug_name: job_state.debug_name.clone(),
-                        _dependencies: job_state.dependencies.clone(),
-                        input_data: job_state.input_data.clone(),
-                    },
-                ));
-
-            started_jobs.push(job_id);
+            self.handle_completed_queue(log_data);
+            return;
         }
 
-        for job_id in started_jobs {
-            self.current_jobs
-                .get_mut(&job_id)
-                .unwrap()
-                .has_been_scheduled = true;
-        }
+        self.handle_completed_queue(log_data);
 
-        self.handle_completed_queue(&mut log_data.log_events);
+        //
+        // Send jobs that are no longer waiting on anything to the thread pool. If we've run this
+        // job in the past and nothing it read has changed, the thread will reuse the cached result
+        // instead of running it. Everything the job did is replayed, so downstream jobs are still
+        // enqueued and will check the cache themselves.
+        //
+        for job_id in std::mem::take(&mut self.ready_jobs) {
+            let priority = self.job_priority(job_id);
+            let job_state = self.current_jobs.get_mut(&job_id).unwrap();
+            assert!(!job_state.has_been_scheduled);
+            job_state.has_been_scheduled = true;
+
+            self.thread_pool.as_ref().unwrap().add_request(
+                JobExecutorThreadPoolRequestRunJob {
+                    job_id,
+                    job_type: job_state.job_type,
+                    data_set: data_set.clone(),
+                    _debug_name: job_state.debug_name.clone(),
+                    _dependencies: job_state.dependencies.clone(),
+                    input_data: job_state.input_data.clone(),
+                },
+                priority,
+            );
+        }
 
         let now = std::time::Instant::now();
         let mut print_progress = true;
@@ -719,9 +1242,10 @@ impl JobExecutor {
 
         if print_progress {
             log::info!(
-                "Jobs: {}/{}",
+                "Jobs: {}/{} ({} reused from cache)",
                 self.completed_job_count,
-                self.current_jobs.len()
+                self.current_jobs.len(),
+                self.cached_job_count
             );
             self.last_job_print_time = Some(now);
         }
@@ -756,13 +1280,257 @@ impl JobExecutor {
             return false;
         }
 
-        //TODO: Don't iterate, keep a count
-        for (_id, job) in &self.current_jobs {
-            if job.output_data.is_none() {
-                return false;
+        self.unfinished_job_count == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+    use type_uuid::TypeUuid;
+
+    // (test ID, job ID, true when the job starts and false when it ends) for every job that ran
+    static JOB_EVENTS: Mutex<Vec<(u64, u32, bool)>> = Mutex::new(Vec::new());
+
+    #[derive(Hash, Serialize, Deserialize)]
+    struct RecordingJobInput {
+        test_id: u64,
+        id: u32,
+        upstream_jobs: Vec<JobId>,
+        fail: bool,
+    }
+    impl JobInput for RecordingJobInput {}
+
+    #[derive(Serialize, Deserialize)]
+    struct RecordingJobOutput {}
+    impl JobOutput for RecordingJobOutput {}
+
+    #[derive(Default, TypeUuid)]
+    #[uuid = "8d3b4b58-0f0e-4f6d-9a55-0d8a1e3b1c11"]
+    struct RecordingJob;
+
+    impl JobProcessor for RecordingJob {
+        type InputT = RecordingJobInput;
+        type OutputT = RecordingJobOutput;
+
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn enumerate_dependencies(
+            &self,
+            context: EnumerateDependenciesContext<Self::InputT>,
+        ) -> PipelineResult<JobEnumeratedDependencies> {
+            Ok(JobEnumeratedDependencies {
+                upstream_jobs: context.input.upstream_jobs.clone(),
+            })
+        }
+
+        fn run<'a>(
+            &'a self,
+            context: &'a RunContext<'a, Self::InputT>,
+        ) -> PipelineResult<Self::OutputT> {
+            let input = context.input;
+            JOB_EVENTS
+                .lock()
+                .unwrap()
+                .push((input.test_id, input.id, true));
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            JOB_EVENTS
+                .lock()
+                .unwrap()
+                .push((input.test_id, input.id, false));
+            if input.fail {
+                Err("Job failed")?;
             }
+            Ok(RecordingJobOutput {})
+        }
+    }
+
+    struct TestExecutor {
+        test_id: u64,
+        root_path: PathBuf,
+        executor: JobExecutor,
+        data_set: DataSet,
+        schema_set: SchemaSet,
+        log_data: BuildLogData,
+    }
+
+    impl TestExecutor {
+        fn new() -> Self {
+            let test_id = uuid::Uuid::new_v4().as_u128() as u64;
+            let root_path = std::env::temp_dir().join(format!("hydrate-test-{:x}", test_id));
+            let mut job_processor_registry = JobProcessorRegistryBuilder::default();
+            job_processor_registry.register_job_processor::<RecordingJob>();
+            let schema_set = hydrate_data::SchemaSetBuilder::default().build();
+            let mut executor = JobExecutor::new(
+                &schema_set,
+                &job_processor_registry.build(),
+                root_path.join("import_data"),
+                root_path.join("job_data"),
+                root_path.join("build_data"),
+                PipelineEvents::default(),
+            );
+            executor.reset(Arc::new(BuildProfile::default()), None);
+
+            TestExecutor {
+                test_id,
+                root_path,
+                executor,
+                data_set: DataSet::default(),
+                schema_set,
+                log_data: BuildLogData::default(),
+            }
+        }
+
+        fn enqueue(
+            &mut self,
+            asset_id: u128,
+            id: u32,
+            upstream_jobs: Vec<JobId>,
+            fail: bool,
+        ) -> JobId {
+            enqueue_job::<RecordingJob>(
+                JobRequestor::Builder(AssetId::from_u128(asset_id)),
+                &self.data_set,
+                &self.schema_set,
+                self.executor.job_api(),
+                RecordingJobInput {
+                    test_id: self.test_id,
+                    id,
+                    upstream_jobs,
+                    fail,
+                },
+                &mut self.log_data.log_events,
+            )
+            .unwrap()
+        }
+
+        fn limit_to_one_job_at_a_time(&self) {
+            self.executor
+                .set_max_concurrent_jobs(JobTypeId::from_bytes(RecordingJob::UUID), Some(1));
+        }
+
+        fn update(&mut self) {
+            // Jobs only read the data set through the API, the executor needs one to send them
+            let data_set = Arc::new(DataSet::default());
+            self.executor.update(&data_set, &mut self.log_data);
+        }
+
+        fn run_until_idle(&mut self) {
+            self.update();
+            while !self.executor.is_idle() {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                self.update();
+            }
+        }
+
+        fn job_events(&self) -> Vec<(u32, bool)> {
+            JOB_EVENTS
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|x| x.0 == self.test_id)
+                .map(|x| (x.1, x.2))
+                .collect()
+        }
+
+        // The order jobs started in
+        fn started_jobs(&self) -> Vec<u32> {
+            self.job_events()
+                .into_iter()
+                .filter(|x| x.1)
+                .map(|x| x.0)
+                .collect()
+        }
+    }
+
+    impl Drop for TestExecutor {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.root_path);
+        }
+    }
+
+    // Jobs start once all of their upstream jobs have finished, and never if one of them failed
+    #[test]
+    fn jobs_wait_for_upstream_jobs() {
+        let mut test = TestExecutor::new();
+        let a = test.enqueue(1, 1, vec![], false);
+        let b = test.enqueue(1, 2, vec![a], false);
+        let c = test.enqueue(1, 3, vec![a], false);
+        test.enqueue(1, 4, vec![c, b, a], false);
+        let e = test.enqueue(1, 5, vec![], true);
+        let f = test.enqueue(1, 6, vec![e], false);
+        test.enqueue(1, 7, vec![a, f], false);
+        test.run_until_idle();
+
+        let job_events = test.job_events();
+        let position = |event: (u32, bool)| job_events.iter().position(|x| *x == event).unwrap();
+        for (downstream, upstream) in [(2, 1), (3, 1), (4, 1), (4, 2), (4, 3)] {
+            assert!(position((upstream, false)) < position((downstream, true)));
         }
 
-        true
+        let mut started_jobs = test.started_jobs();
+        started_jobs.sort();
+        assert_eq!(started_jobs, vec![1, 2, 3, 4, 5]);
+        assert_eq!(test.executor.completed_job_count(), 7);
+    }
+
+    // Running one job at a time, starting a job means nothing else was running
+    #[test]
+    fn max_concurrent_jobs() {
+        let mut test = TestExecutor::new();
+        test.limit_to_one_job_at_a_time();
+        for id in 0..20 {
+            test.enqueue(1, id, vec![], false);
+        }
+        test.run_until_idle();
+
+        let job_events = test.job_events();
+        assert_eq!(job_events.len(), 40);
+        for pair in job_events.chunks(2) {
+            assert_eq!(pair, [(pair[0].0, true), (pair[0].0, false)]);
+        }
+    }
+
+    // Jobs for prioritized assets, and the jobs they wait on, start ahead of jobs that were
+    // enqueued earlier, including jobs that are already waiting in the thread pool
+    #[test]
+    fn prioritized_assets_start_first() {
+        let mut test = TestExecutor::new();
+        test.limit_to_one_job_at_a_time();
+        let mut prioritized_assets = HashSet::default();
+        prioritized_assets.insert(AssetId::from_u128(2));
+        test.executor.set_prioritized_assets(&prioritized_assets);
+        for id in 100..120 {
+            test.enqueue(1, id, vec![], false);
+        }
+        let upstream_job = test.enqueue(3, 1, vec![], false);
+        test.enqueue(2, 2, vec![upstream_job], false);
+        test.run_until_idle();
+
+        // The first job starts as soon as it is sent to the thread pool. The prioritized job waits
+        // for its upstream job to be reported as finished, which may take a job or two.
+        let started_jobs = test.started_jobs();
+        assert_eq!(started_jobs.len(), 22);
+        let position = |id: u32| started_jobs.iter().position(|x| *x == id).unwrap();
+        assert!(position(1) <= 1);
+        assert!(position(2) < 10);
+
+        // The requests are already queued when the asset is prioritized
+        let mut test = TestExecutor::new();
+        test.limit_to_one_job_at_a_time();
+        for id in 100..120 {
+            test.enqueue(1, id, vec![], false);
+        }
+        test.enqueue(2, 1, vec![], false);
+        test.update();
+        test.executor.set_prioritized_assets(&prioritized_assets);
+        test.run_until_idle();
+
+        let started_jobs = test.started_jobs();
+        assert_eq!(started_jobs.len(), 21);
+        assert!(started_jobs.iter().position(|x| *x == 1).unwrap() <= 2);
     }
 }
@@ -0,0 +1,352 @@
+use super::job_system_traits::{AssetDataRead, FetchedImportDataInfo};
+use super::{JobApi, JobApiImpl, JobId, JobRequestor, JobRunSummary, JobTypeId, NewJob};
+use crate::build::{BuiltArtifact, WrittenArtifact};
+use crate::import::ImportData;
+use crate::{BuildLogEvent, BuildProfile, LogEventLevel, PipelineResult};
+use hydrate_base::hashing::HashMap;
+use hydrate_base::uuid_path::{uuid_and_hash_to_path, uuid_to_path};
+use hydrate_base::{ArtifactId, AssetId};
+use hydrate_data::{DataSet, SchemaSet};
+use serde::{Deserialize, Serialize};
+use siphasher::sip128::Hasher128;
+use std::hash::Hash;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+// Bump this if the format of cache entries changes. Entries written with a different version are
+// ignored.
+const JOB_CACHE_VERSION: u32 = 2;
+
+// A job that was enqueued by a cached job while it ran
+#[derive(Serialize, Deserialize)]
+struct CachedNewJob {
+    job_type: JobTypeId,
+    input_hash: u128,
+    input_data: Vec<u8>,
+    debug_name: String,
+}
+
+// Everything we need to skip running a job again. The job's output is not enough on its own. Jobs
+// enqueue other jobs and write artifacts, and all of that has to be replayed when the entry is used.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct JobCacheEntry {
+    // Hashes of the asset data and import data the job read when it ran. If any of these have
+    // changed the entry can't be used.
+    asset_data_hashes: Vec<(AssetDataRead, u64)>,
+    import_data_hashes: Vec<(AssetId, u64)>,
+
+    enqueued_jobs: Vec<CachedNewJob>,
+    artifact_handles_created: Vec<(AssetId, ArtifactId)>,
+    written_artifacts: Vec<WrittenArtifact>,
+    log_events: Vec<BuildLogEvent>,
+    output_data: Vec<u8>,
+}
+
+impl JobCacheEntry {
+    // Re-sends everything the job did when it originally ran. Returns the job's output and log
+    // events.
+    pub(crate) fn replay(
+        self,
+        job_api: &JobApiImpl,
+        job_id: JobId,
+        data_set: &DataSet,
+        schema_set: &SchemaSet,
+    ) -> PipelineResult<(Arc<Vec<u8>>, Vec<BuildLogEvent>)> {
+        let mut log_events = self.log_events;
+
+        for (asset_id, artifact_id) in self.artifact_handles_created {
+            job_api.artifact_handle_created(asset_id, artifact_id);
+        }
+
+        for written_artifact in self.written_artifacts {
+            job_api.send_written_artifact(written_artifact);
+        }
+
+        for enqueued_job in self.enqueued_jobs {
+            job_api.enqueue_job(
+                JobRequestor::Job(job_id),
+                data_set,
+                schema_set,
+                NewJob {
+                    job_type: enqueued_job.job_type,
+                    input_hash: enqueued_job.input_hash,
+                    input_data: enqueued_job.input_data,
+                },
+                enqueued_job.debug_name,
+                &mut log_events,
+            )?;
+        }
+
+        Ok((Arc::new(self.output_data), log_events))
+    }
+
+    pub(crate) fn written_artifacts(&self) -> &[WrittenArtifact] {
+        &self.written_artifacts
+    }
+
+    pub(crate) fn asset_data_hashes(&self) -> &[(AssetDataRead, u64)] {
+        &self.asset_data_hashes
+    }
+
+    pub(crate) fn import_data_hashes(&self) -> &[(AssetId, u64)] {
+        &self.import_data_hashes
+    }
+
+    pub(crate) fn run_summary(&self) -> JobRunSummary {
+        JobRunSummary::new(
+            self.written_artifacts.iter(),
+            self.enqueued_jobs.iter().map(|x| x.input_hash),
+            &self.output_data,
+        )
+    }
+}
+
+// Job results persisted in the job data path, so that jobs can be skipped if nothing they read has
+// changed, even across editor restarts. Reads are tracked by RunContext.
+pub(crate) struct JobCache {
+    root_path: PathBuf,
+    import_data_root_path: PathBuf,
+    build_data_root_path: PathBuf,
+}
+
+impl JobCache {
+    pub(crate) fn new(
+        root_path: PathBuf,
+        import_data_root_path: PathBuf,
+        build_data_root_path: PathBuf,
+    ) -> Self {
+        JobCache {
+            root_path,
+            import_data_root_path,
+            build_data_root_path,
+        }
+    }
+
+    // The job ID is a hash of the job's input, so the key covers the job type, its version, and its
+    // input
+    fn entry_path(
+        &self,
+        job_type: JobTypeId,
+        job_version: u32,
+        job_id: JobId,
+    ) -> PathBuf {
+        let mut hasher = siphasher::sip128::SipHasher::default();
+        JOB_CACHE_VERSION.hash(&mut hasher);
+        job_type.hash(&mut hasher);
+        job_version.hash(&mut hasher);
+        job_id.hash(&mut hasher);
+        let key = hasher.finish128().as_u128();
+        uuid_to_path(&self.root_path, uuid::Uuid::from_u128(key), "job")
+    }
+
+    // Returns the cached result of the job if everything it read last time is unchanged and all
+    // artifacts it wrote still exist
+    #[profiling::function]
+    pub(crate) fn load(
+        &self,
+        job_type: JobTypeId,
+        job_version: u32,
+        job_id: JobId,
+        data_set: &DataSet,
+        build_profile: &BuildProfile,
+    ) -> Option<JobCacheEntry> {
+        let path = self.entry_path(job_type, job_version, job_id);
+        let bytes = std::fs::read(&path).ok()?;
+        let entry: JobCacheEntry = bincode::deserialize(&bytes).ok()?;
+
+        for &(asset_data_read, contents_hash) in &entry.asset_data_hashes {
+            let current_hash = asset_data_read.hash_current_value(data_set).ok();
+            if current_hash != Some(contents_hash) {
+                return None;
+            }
+        }
+
+        for &(asset_id, contents_hash) in &entry.import_data_hashes {
+            let current_hash = crate::import::load_import_data_contents_hash(
+                &self.import_data_root_path,
+                asset_id,
+            )
+            .ok();
+            if current_hash != Some(contents_hash) {
+                return None;
+            }
+        }
+
+        for written_artifact in &entry.written_artifacts {
+            let artifact_path = uuid_and_hash_to_path(
+                &build_profile.build_data_path(&self.build_data_root_path),
+                written_artifact.artifact_id.as_uuid(),
+                written_artifact.build_hash,
+                "bf",
+            );
+            if !artifact_path.exists() {
+                return None;
+            }
+        }
+
+        Some(entry)
+    }
+
+    pub(crate) fn store(
+        &self,
+        job_type: JobTypeId,
+        job_version: u32,
+        job_id: JobId,
+        entry: &JobCacheEntry,
+    ) -> PipelineResult<()> {
+        let path = self.entry_path(job_type, job_version, job_id);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first so that a partially written entry is never read
+        let temp_path = path.with_extension("job.tmp");
+        std::fs::write(&temp_path, bincode::serialize(entry)?)?;
+        std::fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+}
+
+// Passed to a job instead of the JobApiImpl so that everything the job does can be saved in the
+// job cache
+pub(crate) struct RecordingJobApi<'a> {
+    job_api: &'a JobApiImpl,
+    entry: Mutex<JobCacheEntry>,
+}
+
+impl<'a> RecordingJobApi<'a> {
+    pub(crate) fn new(job_api: &'a JobApiImpl) -> Self {
+        RecordingJobApi {
+            job_api,
+            entry: Default::default(),
+        }
+    }
+
+    pub(crate) fn written_artifacts(&self) -> Vec<WrittenArtifact> {
+        self.entry.lock().unwrap().written_artifacts.clone()
+    }
+
+    pub(crate) fn run_summary(
+        &self,
+        output_data: &[u8],
+    ) -> JobRunSummary {
+        let entry = self.entry.lock().unwrap();
+        JobRunSummary::new(
+            entry.written_artifacts.iter(),
+            entry.enqueued_jobs.iter().map(|x| x.input_hash),
+            output_data,
+        )
+    }
+
+    // Returns None if the job's results shouldn't be cached. Jobs that logged errors are re-run
+    // next time so that the errors are not hidden behind a stale result.
+    pub(crate) fn finish(
+        self,
+        output_data: &Vec<u8>,
+        fetched_asset_data: &HashMap<AssetDataRead, u64>,
+        fetched_import_data: &HashMap<AssetId, FetchedImportDataInfo>,
+        log_events: &[BuildLogEvent],
+    ) -> Option<JobCacheEntry> {
+        for log_event in log_events {
+            if matches!(
+                log_event.level,
+                LogEventLevel::Error | LogEventLevel::FatalError
+            ) {
+                return None;
+            }
+        }
+
+        let mut entry = self.entry.into_inner().unwrap();
+        entry.asset_data_hashes = fetched_asset_data
+            .iter()
+            .map(|(&asset_data_read, &hash)| (asset_data_read, hash))
+            .collect();
+        entry.import_data_hashes = fetched_import_data
+            .iter()
+            .map(|(&asset_id, fetched)| (asset_id, fetched.contents_hash))
+            .collect();
+        entry.log_events = log_events.to_vec();
+        entry.output_data = output_data.clone();
+        Some(entry)
+    }
+}
+
+impl<'a> JobApi for RecordingJobApi<'a> {
+    fn enqueue_job(
+        &self,
+        job_requestor: JobRequestor,
+        data_set: &DataSet,
+        schema_set: &SchemaSet,
+        new_job: NewJob,
+        debug_name: String,
+        log_events: &mut Vec<BuildLogEvent>,
+    ) -> PipelineResult<JobId> {
+        self.entry.lock().unwrap().enqueued_jobs.push(CachedNewJob {
+            job_type: new_job.job_type,
+            input_hash: new_job.input_hash,
+            input_data: new_job.input_data.clone(),
+            debug_name: debug_name.clone(),
+        });
+
+        self.job_api.enqueue_job(
+            job_requestor,
+            data_set,
+            schema_set,
+            new_job,
+            debug_name,
+            log_events,
+        )
+    }
+
+    fn artifact_handle_created(
+        &self,
+        asset_id: AssetId,
+        artifact_id: ArtifactId,
+    ) {
+        self.entry
+            .lock()
+            .unwrap()
+            .artifact_handles_created
+            .push((asset_id, artifact_id));
+
+        self.job_api.artifact_handle_created(asset_id, artifact_id);
+    }
+
+    fn produce_artifact(
+        &self,
+        artifact: BuiltArtifact,
+    ) -> PipelineResult<()> {
+        let written_artifact = self.job_api.write_artifact(artifact)?;
+        self.entry
+            .lock()
+            .unwrap()
+            .written_artifacts
+            .push(written_artifact.clone());
+
+        self.job_api.send_written_artifact(written_artifact);
+        Ok(())
+    }
+
+    fn fetch_import_data(
+        &self,
+        asset_id: AssetId,
+    ) -> PipelineResult<ImportData> {
+        self.job_api.fetch_import_data(asset_id)
+    }
+
+    fn build_profile(&self) -> Arc<BuildProfile> {
+        self.job_api.build_profile()
+    }
+
+    fn report_progress(
+        &self,
+        job_id: JobId,
+        progress: f32,
+    ) {
+        self.job_api.report_progress(job_id, progress);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.job_api.is_cancelled()
+    }
+}
@@ -1,15 +1,21 @@
-use super::job_system_traits::{FetchedAssetData, FetchedImportData};
+use super::job_system_traits::{
+    AssetDataRead, FetchedImportData, FetchedImportDataInfo, JobProcessorAbstract,
+};
 use super::{
-    JobApi, JobApiImpl, JobEnumeratedDependencies, JobId, JobProcessorRegistry, JobTypeId,
+    BuildVerificationMode, DeterminismMismatch, JobApi, JobApiImpl, JobCache,
+    JobEnumeratedDependencies, JobId, JobProcessorRegistry, JobRunSummary, JobTypeId,
+    RecordingJobApi, VerifyingJobApi,
 };
-use crate::{BuildLogEvent, PipelineResult};
+use crate::build::WrittenArtifact;
+use crate::{BuildLogEvent, BuildProfile, PipelineError, PipelineEvent, PipelineResult};
 use crossbeam_channel::{Receiver, Sender};
 use hydrate_base::hashing::HashMap;
 use hydrate_base::AssetId;
 use hydrate_data::{DataSet, SchemaSet};
-use std::path::{Path, PathBuf};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 
 // Ask the thread to gather build data from the asset
@@ -22,21 +28,162 @@ pub(crate) struct JobExecutorThreadPoolRequestRunJob {
     pub data_set: Arc<DataSet>,
 }
 
-pub(crate) enum JobExecutorThreadPoolRequest {
-    RunJob(JobExecutorThreadPoolRequestRunJob),
+// Ready jobs start in this order when there are more of them than threads
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct JobPriority {
+    // Jobs for prioritized assets and everything those jobs need
+    pub prioritized: bool,
+    // Jobs that other jobs are waiting on are more likely to be on the critical path
+    pub waiting_job_count: usize,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct QueuedRequestEntry {
+    priority: JobPriority,
+    // Requests with the same priority start in the order they were added
+    sequence: Reverse<u64>,
+    job_id: JobId,
+}
+
+struct QueuedRequest {
+    priority: JobPriority,
+    request: JobExecutorThreadPoolRequestRunJob,
+}
+
+// Requests that have not started yet. Threads take the highest priority request whose job type is
+// below its concurrency limit.
+#[derive(Default)]
+struct JobExecutorRequestQueue {
+    // May contain stale entries for requests that already started or were given a new priority
+    ready: BinaryHeap<QueuedRequestEntry>,
+    // Requests waiting for a running job of the same type to finish
+    throttled: HashMap<JobTypeId, BinaryHeap<QueuedRequestEntry>>,
+    requests: HashMap<JobId, QueuedRequest>,
+    running_job_counts: HashMap<JobTypeId, usize>,
+    max_concurrent_jobs: HashMap<JobTypeId, usize>,
+    next_sequence: u64,
+}
+
+impl JobExecutorRequestQueue {
+    fn push_entry(
+        &mut self,
+        job_id: JobId,
+        priority: JobPriority,
+    ) {
+        self.ready.push(QueuedRequestEntry {
+            priority,
+            sequence: Reverse(self.next_sequence),
+            job_id,
+        });
+        self.next_sequence += 1;
+    }
+
+    fn add(
+        &mut self,
+        request: JobExecutorThreadPoolRequestRunJob,
+        priority: JobPriority,
+    ) {
+        let job_id = request.job_id;
+        self.requests
+            .insert(job_id, QueuedRequest { priority, request });
+        self.push_entry(job_id, priority);
+    }
+
+    // Does nothing if the request already started
+    fn set_priority(
+        &mut self,
+        job_id: JobId,
+        priority: JobPriority,
+    ) {
+        if let Some(queued_request) = self.requests.get_mut(&job_id) {
+            if queued_request.priority != priority {
+                queued_request.priority = priority;
+                self.push_entry(job_id, priority);
+            }
+        }
+    }
+
+    fn take_next(&mut self) -> Option<JobExecutorThreadPoolRequestRunJob> {
+        while let Some(entry) = self.ready.pop() {
+            let Some(queued_request) = self.requests.get(&entry.job_id) else {
+                continue;
+            };
+            if queued_request.priority != entry.priority {
+                continue;
+            }
+
+            let job_type = queued_request.request.job_type;
+            let running_job_count = self.running_job_counts.entry(job_type).or_default();
+            if let Some(&max_concurrent_jobs) = self.max_concurrent_jobs.get(&job_type) {
+                if *running_job_count >= max_concurrent_jobs {
+                    self.throttled.entry(job_type).or_default().push(entry);
+                    continue;
+                }
+            }
+
+            *running_job_count += 1;
+            return self.requests.remove(&entry.job_id).map(|x| x.request);
+        }
+
+        None
+    }
+
+    fn job_finished(
+        &mut self,
+        job_type: JobTypeId,
+    ) {
+        *self.running_job_counts.get_mut(&job_type).unwrap() -= 1;
+        self.release_throttled(job_type, 1);
+    }
+
+    // Moves requests that were held back by the concurrency limit back to the ready queue. Returns
+    // how many were moved.
+    fn release_throttled(
+        &mut self,
+        job_type: JobTypeId,
+        count: usize,
+    ) -> usize {
+        let Some(throttled) = self.throttled.get_mut(&job_type) else {
+            return 0;
+        };
+
+        let mut released_count = 0;
+        while released_count < count {
+            let Some(entry) = throttled.pop() else {
+                break;
+            };
+
+            let priority = self.requests.get(&entry.job_id).map(|x| x.priority);
+            if priority == Some(entry.priority) {
+                self.ready.push(entry);
+                released_count += 1;
+            }
+        }
+
+        released_count
+    }
 }
 
 pub struct JobExecutorThreadPoolOutcomeRunJobCompleteData {
     pub output_data: Arc<Vec<u8>>,
-    pub fetched_asset_data: HashMap<AssetId, FetchedAssetData>,
-    pub fetched_import_data: HashMap<AssetId, FetchedImportData>,
+    pub fetched_asset_data: HashMap<AssetDataRead, u64>,
+    pub fetched_import_data: HashMap<AssetId, FetchedImportDataInfo>,
     pub log_events: Vec<BuildLogEvent>,
+    // Artifacts the job produced, including ones replayed from the job cache
+    pub written_artifacts: Vec<WrittenArtifact>,
+    // True if the job didn't run because a result was found in the job cache
+    pub used_cached_result: bool,
+    // Only populated in verification builds
+    pub determinism_mismatches: Vec<DeterminismMismatch>,
 }
 
 // Results from successful build
 pub(crate) struct JobExecutorThreadPoolOutcomeRunJobComplete {
     pub request: JobExecutorThreadPoolRequestRunJob,
     pub result: PipelineResult<JobExecutorThreadPoolOutcomeRunJobCompleteData>,
+    pub start_instant: std::time::Instant,
+    pub end_instant: std::time::Instant,
+    pub thread_index: usize,
     //asset: SingleObject,
     //import_data: SingleObject,
 }
@@ -45,60 +192,248 @@ pub(crate) enum JobExecutorThreadPoolOutcome {
     RunJobComplete(JobExecutorThreadPoolOutcomeRunJobComplete),
 }
 
-// Thread that tries to take jobs out of the request channel and ends when the finish channel is signalled
+// Thread that takes jobs out of the request queue when woken and ends when the finish channel is signalled
 struct JobExecutorWorkerThread {
     finish_tx: Sender<()>,
     join_handle: JoinHandle<()>,
 }
 
+// Runs the job in a job worker process if the build has any, otherwise on the calling thread
+fn run_job(
+    job_processor: &dyn JobProcessorAbstract,
+    schema_set: &SchemaSet,
+    job_api_impl: &JobApiImpl,
+    job_api: &dyn JobApi,
+    request: &JobExecutorThreadPoolRequestRunJob,
+    build_profile: &BuildProfile,
+    fetched_asset_data: &mut HashMap<AssetDataRead, u64>,
+    fetched_import_data: &mut HashMap<AssetId, FetchedImportDataInfo>,
+    log_events: &mut Vec<BuildLogEvent>,
+) -> PipelineResult<Arc<Vec<u8>>> {
+    if let Some(job_worker_pool) = job_api_impl.job_worker_pool() {
+        return job_worker_pool.run_job(
+            job_api_impl,
+            job_api,
+            request.job_id,
+            request.job_type,
+            &request.input_data,
+            &request._debug_name,
+            &*request.data_set,
+            schema_set,
+            build_profile,
+            fetched_asset_data,
+            fetched_import_data,
+            log_events,
+        );
+    }
+
+    let mut local_fetched_import_data = HashMap::<AssetId, FetchedImportData>::default();
+    let result = job_processor.run_inner(
+        request.job_id,
+        &request.input_data,
+        &*request.data_set,
+        schema_set,
+        build_profile,
+        job_api,
+        fetched_asset_data,
+        &mut local_fetched_import_data,
+        log_events,
+    );
+
+    fetched_import_data.extend(
+        local_fetched_import_data
+            .into_iter()
+            .map(|(asset_id, x)| (asset_id, x.info)),
+    );
+    result
+}
+
 fn do_build(
     job_processor_registry: &JobProcessorRegistry,
     schema_set: &SchemaSet,
-    job_api: &dyn JobApi,
+    job_api: &JobApiImpl,
+    job_cache: &JobCache,
     request: &JobExecutorThreadPoolRequestRunJob,
 ) -> PipelineResult<JobExecutorThreadPoolOutcomeRunJobCompleteData> {
     profiling::scope!(&format!("Handle Job {}", request._debug_name));
 
-    let mut fetched_asset_data = HashMap::<AssetId, FetchedAssetData>::default();
-    let mut fetched_import_data = HashMap::<AssetId, FetchedImportData>::default();
-    let mut log_events = Vec::default();
+    // The request may have been queued before the build was cancelled
+    if job_api.is_cancelled() {
+        return Err(PipelineError::Cancelled.into());
+    }
 
-    // Execute the job
     let job_processor = job_processor_registry
         .get_processor(request.job_type)
         .unwrap();
+    let job_version = job_processor.version_inner();
+    let build_profile = job_api.build_profile();
+
+    let build_verification = job_api.build_verification(job_processor.type_name_inner());
+
+    // Reuse the result from a previous run if nothing the job read has changed. Jobs being verified
+    // always run, but in some modes the cached result is what the new result is compared against.
+    let cache_entry = job_cache.load(
+        request.job_type,
+        job_version,
+        request.job_id,
+        &*request.data_set,
+        &build_profile,
+    );
+    let previous_run = match (&build_verification, cache_entry) {
+        (None, Some(cache_entry)) => {
+            let written_artifacts = cache_entry.written_artifacts().to_vec();
+
+            // Report what the job read when it ran so the build knows which assets it depends on.
+            // Metadata hashes aren't kept in the cache, nothing reads them once the job finishes.
+            let fetched_asset_data = cache_entry.asset_data_hashes().iter().copied().collect();
+            let fetched_import_data = cache_entry
+                .import_data_hashes()
+                .iter()
+                .map(|&(asset_id, contents_hash)| {
+                    (
+                        asset_id,
+                        FetchedImportDataInfo {
+                            contents_hash,
+                            _metadata_hash: 0,
+                        },
+                    )
+                })
+                .collect();
+
+            let (output_data, log_events) =
+                cache_entry.replay(job_api, request.job_id, &*request.data_set, schema_set)?;
+
+            return Ok(JobExecutorThreadPoolOutcomeRunJobCompleteData {
+                output_data,
+                fetched_asset_data,
+                fetched_import_data,
+                log_events,
+                written_artifacts,
+                used_cached_result: true,
+                determinism_mismatches: Default::default(),
+            });
+        }
+        (Some(build_verification), Some(cache_entry))
+            if build_verification.mode == BuildVerificationMode::CompareWithPreviousBuild =>
+        {
+            Some(cache_entry.run_summary())
+        }
+        _ => None,
+    };
+
+    let mut fetched_asset_data = HashMap::<AssetDataRead, u64>::default();
+    let mut fetched_import_data = HashMap::<AssetId, FetchedImportDataInfo>::default();
+    let mut log_events = Vec::default();
+
+    // Execute the job
+    let recording_job_api = RecordingJobApi::new(job_api);
     let output_data = {
         profiling::scope!(&format!("JobProcessor::run_inner"));
-        job_processor.run_inner(
-            request.job_id,
-            &request.input_data,
-            &*request.data_set,
+        run_job(
+            &*job_processor,
             schema_set,
             job_api,
+            &recording_job_api,
+            request,
+            &build_profile,
             &mut fetched_asset_data,
             &mut fetched_import_data,
             &mut log_events,
         )
     }?;
 
+    let mut determinism_mismatches = Vec::default();
+    if let Some(build_verification) = &build_verification {
+        let expected_run = match build_verification.mode {
+            BuildVerificationMode::RunTwice => {
+                profiling::scope!(&format!("JobProcessor::run_inner (verification)"));
+                let verifying_job_api = VerifyingJobApi::new(job_api);
+                let result = run_job(
+                    &*job_processor,
+                    schema_set,
+                    job_api,
+                    &verifying_job_api,
+                    request,
+                    &build_profile,
+                    &mut Default::default(),
+                    &mut Default::default(),
+                    &mut Vec::default(),
+                )
+                .map(|output_data| verifying_job_api.finish(&output_data))
+                .map_err(|e| e.to_string());
+                Some(result)
+            }
+            BuildVerificationMode::CompareWithPreviousBuild => previous_run.map(Ok),
+        };
+
+        let differences = match expected_run {
+            Some(Ok(expected_run)) => {
+                JobRunSummary::compare(&expected_run, &recording_job_api.run_summary(&output_data))
+            }
+            Some(Err(e)) => vec![(
+                None,
+                None,
+                format!("The job failed when it ran a second time: {}", e),
+            )],
+            // There's nothing to compare against if the job has no cached result for the same
+            // inputs
+            None => Vec::default(),
+        };
+
+        for (asset_id, artifact_id, message) in differences {
+            determinism_mismatches.push(DeterminismMismatch {
+                job_id: request.job_id,
+                job_type_name: job_processor.type_name_inner(),
+                debug_name: request._debug_name.clone(),
+                asset_id,
+                artifact_id,
+                message,
+            });
+        }
+    }
+
+    let written_artifacts = recording_job_api.written_artifacts();
+
+    // Failing to write the cache only means the job will run again next time. Jobs that aren't
+    // deterministic aren't cached so that they are verified again in the next verification build.
+    if determinism_mismatches.is_empty() {
+        if let Some(cache_entry) = recording_job_api.finish(
+            &output_data,
+            &fetched_asset_data,
+            &fetched_import_data,
+            &log_events,
+        ) {
+            if let Err(e) =
+                job_cache.store(request.job_type, job_version, request.job_id, &cache_entry)
+            {
+                log::warn!(
+                    "Failed to write job cache entry for job {:?}: {}",
+                    request.job_id,
+                    e.to_string()
+                );
+            }
+        }
+    }
+
     Ok(JobExecutorThreadPoolOutcomeRunJobCompleteData {
         output_data,
         fetched_asset_data,
         fetched_import_data,
         log_events,
+        written_artifacts,
+        used_cached_result: false,
+        determinism_mismatches,
     })
-
-    //TODO: Write to file
-    //hydrate_base::uuid_path::uuid_to_path()
 }
 
 impl JobExecutorWorkerThread {
     fn new(
         job_processor_registry: JobProcessorRegistry,
         schema_set: SchemaSet,
-        _job_data_root_path: Arc<PathBuf>,
+        job_cache: Arc<JobCache>,
         job_api: JobApiImpl,
-        request_rx: Receiver<JobExecutorThreadPoolRequest>,
+        request_queue: Arc<Mutex<JobExecutorRequestQueue>>,
+        wake_rx: Receiver<()>,
         outcome_tx: Sender<JobExecutorThreadPoolOutcome>,
         active_request_count: Arc<AtomicUsize>,
         _thread_index: usize,
@@ -110,36 +445,66 @@ impl JobExecutorWorkerThread {
                 profiling::register_thread!(&format!("JobExecutorWorkerThread {}", _thread_index));
                 loop {
                     crossbeam_channel::select! {
-                        recv(request_rx) -> msg => {
-                            match msg.unwrap() {
-                                JobExecutorThreadPoolRequest::RunJob(msg) => {
-                                    let result = std::panic::catch_unwind(|| {
-                                        profiling::scope!("JobExecutorThreadPoolRequest::RequestBuild");
-                                        do_build(
-                                            &job_processor_registry,
-                                            &schema_set,
-                                            &job_api,
-                                            &msg
-                                        )
-                                    });
-
-                                    match result {
-                                        Ok(result) => {
-                                            outcome_tx.send(JobExecutorThreadPoolOutcome::RunJobComplete(JobExecutorThreadPoolOutcomeRunJobComplete {
-                                                request: msg,
-                                                result,
-                                            })).unwrap();
-                                        },
-                                        Err(_) => {
-                                            outcome_tx.send(JobExecutorThreadPoolOutcome::RunJobComplete(JobExecutorThreadPoolOutcomeRunJobComplete {
-                                                request: msg,
-                                                result: Err("Panic detected in build job.".into())
-                                            })).unwrap();
-                                        }
+                        recv(wake_rx) -> msg => {
+                            msg.unwrap();
+
+                            // Keep going until nothing can start, finishing a job may have released
+                            // a request that was held back by its job type's concurrency limit
+                            loop {
+                                let Some(msg) = request_queue.lock().unwrap().take_next() else {
+                                    break;
+                                };
+                                let job_type = msg.job_type;
+
+                                let start_instant = std::time::Instant::now();
+                                job_api.pipeline_events().send(PipelineEvent::JobStarted {
+                                    job_id: msg.job_id,
+                                    debug_name: msg._debug_name.clone(),
+                                });
+
+                                let result = std::panic::catch_unwind(|| {
+                                    profiling::scope!("JobExecutorThreadPoolRequest::RequestBuild");
+                                    do_build(
+                                        &job_processor_registry,
+                                        &schema_set,
+                                        &job_api,
+                                        &job_cache,
+                                        &msg
+                                    )
+                                });
+
+                                let end_instant = std::time::Instant::now();
+                                job_api.pipeline_events().send(PipelineEvent::JobFinished {
+                                    job_id: msg.job_id,
+                                    debug_name: msg._debug_name.clone(),
+                                    duration: end_instant - start_instant,
+                                    used_cached_result: matches!(&result, Ok(Ok(data)) if data.used_cached_result),
+                                    succeeded: matches!(&result, Ok(Ok(_))),
+                                });
+
+                                match result {
+                                    Ok(result) => {
+                                        outcome_tx.send(JobExecutorThreadPoolOutcome::RunJobComplete(JobExecutorThreadPoolOutcomeRunJobComplete {
+                                            request: msg,
+                                            result,
+                                            start_instant,
+                                            end_instant,
+                                            thread_index: _thread_index,
+                                        })).unwrap();
+                                    },
+                                    Err(_) => {
+                                        outcome_tx.send(JobExecutorThreadPoolOutcome::RunJobComplete(JobExecutorThreadPoolOutcomeRunJobComplete {
+                                            request: msg,
+                                            result: Err("Panic detected in build job.".into()),
+                                            start_instant,
+                                            end_instant,
+                                            thread_index: _thread_index,
+                                        })).unwrap();
                                     }
+                                }
 
-                                    active_request_count.fetch_sub(1, Ordering::Release);
-                                },
+                                request_queue.lock().unwrap().job_finished(job_type);
+                                active_request_count.fetch_sub(1, Ordering::Release);
                             }
                         },
                         recv(finish_rx) -> _msg => {
@@ -160,7 +525,9 @@ impl JobExecutorWorkerThread {
 // Spans N threads, proxies messages to/from them, and kills the threads when the pool is dropped
 pub struct JobExecutorThreadPool {
     worker_threads: Vec<JobExecutorWorkerThread>,
-    request_tx: Sender<JobExecutorThreadPoolRequest>,
+    request_queue: Arc<Mutex<JobExecutorRequestQueue>>,
+    // Each request sends a message to wake a thread
+    wake_tx: Sender<()>,
     active_request_count: Arc<AtomicUsize>,
 }
 
@@ -168,14 +535,13 @@ impl JobExecutorThreadPool {
     pub(crate) fn new(
         job_processor_registry: JobProcessorRegistry,
         schema_set: SchemaSet,
-        job_data_root_path: &Path,
+        job_cache: Arc<JobCache>,
         job_api: JobApiImpl,
         max_requests_in_flight: usize,
         result_tx: Sender<JobExecutorThreadPoolOutcome>,
     ) -> Self {
-        let job_data_root_path = Arc::new(job_data_root_path.to_path_buf());
-        let (request_tx, request_rx) =
-            crossbeam_channel::unbounded::<JobExecutorThreadPoolRequest>();
+        let request_queue = Arc::new(Mutex::new(JobExecutorRequestQueue::default()));
+        let (wake_tx, wake_rx) = crossbeam_channel::unbounded();
         let active_request_count = Arc::new(AtomicUsize::new(0));
 
         let mut worker_threads = Vec::with_capacity(max_requests_in_flight);
@@ -183,9 +549,10 @@ impl JobExecutorThreadPool {
             let worker = JobExecutorWorkerThread::new(
                 job_processor_registry.clone(),
                 schema_set.clone(),
-                job_data_root_path.clone(),
+                job_cache.clone(),
                 job_api.clone(),
-                request_rx.clone(),
+                request_queue.clone(),
+                wake_rx.clone(),
                 result_tx.clone(),
                 active_request_count.clone(),
                 thread_index,
@@ -194,7 +561,8 @@ impl JobExecutorThreadPool {
         }
 
         JobExecutorThreadPool {
-            request_tx,
+            request_queue,
+            wake_tx,
             worker_threads,
             active_request_count,
         }
@@ -210,10 +578,47 @@ impl JobExecutorThreadPool {
 
     pub(crate) fn add_request(
         &self,
-        request: JobExecutorThreadPoolRequest,
+        request: JobExecutorThreadPoolRequestRunJob,
+        priority: JobPriority,
     ) {
         self.active_request_count.fetch_add(1, Ordering::Release);
-        self.request_tx.send(request).unwrap();
+        self.request_queue.lock().unwrap().add(request, priority);
+        self.wake_tx.send(()).unwrap();
+    }
+
+    // Moves a request that hasn't started yet ahead of (or behind) other requests
+    pub(crate) fn set_request_priority(
+        &self,
+        job_id: JobId,
+        priority: JobPriority,
+    ) {
+        self.request_queue
+            .lock()
+            .unwrap()
+            .set_priority(job_id, priority);
+    }
+
+    // None removes the limit. A limit of zero is treated as one so that requests can't get stuck.
+    pub(crate) fn set_max_concurrent_jobs(
+        &self,
+        job_type: JobTypeId,
+        max_concurrent_jobs: Option<usize>,
+    ) {
+        let mut request_queue = self.request_queue.lock().unwrap();
+        match max_concurrent_jobs {
+            Some(max_concurrent_jobs) => request_queue
+                .max_concurrent_jobs
+                .insert(job_type, max_concurrent_jobs.max(1)),
+            None => request_queue.max_concurrent_jobs.remove(&job_type),
+        };
+
+        // If the limit went up, held back requests may be able to start now. Any that still can't
+        // will be held back again.
+        let released_count = request_queue.release_throttled(job_type, usize::MAX);
+        drop(request_queue);
+        for _ in 0..released_count {
+            self.wake_tx.send(()).unwrap();
+        }
     }
 
     pub(crate) fn finish(self) {
@@ -226,3 +631,113 @@ impl JobExecutorThreadPool {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn job_type_a() -> JobTypeId {
+        JobTypeId::from_u128(1)
+    }
+
+    fn job_type_b() -> JobTypeId {
+        JobTypeId::from_u128(2)
+    }
+
+    fn request(
+        job_id: u128,
+        job_type: JobTypeId,
+    ) -> JobExecutorThreadPoolRequestRunJob {
+        JobExecutorThreadPoolRequestRunJob {
+            job_id: JobId::from_u128(job_id),
+            job_type,
+            _debug_name: Arc::new(format!("job {}", job_id)),
+            _dependencies: Default::default(),
+            input_data: Default::default(),
+            data_set: Default::default(),
+        }
+    }
+
+    fn take_next_id(request_queue: &mut JobExecutorRequestQueue) -> Option<u128> {
+        request_queue.take_next().map(|x| x.job_id.as_u128())
+    }
+
+    #[test]
+    fn request_queue_order() {
+        let mut request_queue = JobExecutorRequestQueue::default();
+        let waited_on = JobPriority {
+            prioritized: false,
+            waiting_job_count: 2,
+        };
+        let prioritized = JobPriority {
+            prioritized: true,
+            waiting_job_count: 0,
+        };
+        request_queue.add(request(1, job_type_a()), JobPriority::default());
+        request_queue.add(request(2, job_type_a()), waited_on);
+        request_queue.add(request(3, job_type_b()), prioritized);
+        request_queue.add(request(4, job_type_a()), JobPriority::default());
+        request_queue.add(request(5, job_type_a()), JobPriority::default());
+        request_queue.set_priority(JobId::from_u128(5), prioritized);
+
+        let mut order = Vec::default();
+        while let Some(job_id) = take_next_id(&mut request_queue) {
+            order.push(job_id);
+        }
+        assert_eq!(order, vec![3, 5, 2, 1, 4]);
+    }
+
+    #[test]
+    fn request_queue_concurrency_limit() {
+        let mut request_queue = JobExecutorRequestQueue::default();
+        request_queue.max_concurrent_jobs.insert(job_type_a(), 1);
+        request_queue.add(request(1, job_type_a()), JobPriority::default());
+        request_queue.add(request(2, job_type_a()), JobPriority::default());
+        request_queue.add(request(3, job_type_b()), JobPriority::default());
+
+        // The second request of type A waits, but requests of other types don't
+        assert_eq!(take_next_id(&mut request_queue), Some(1));
+        assert_eq!(take_next_id(&mut request_queue), Some(3));
+        assert_eq!(take_next_id(&mut request_queue), None);
+
+        request_queue.job_finished(job_type_b());
+        assert_eq!(take_next_id(&mut request_queue), None);
+        request_queue.job_finished(job_type_a());
+        assert_eq!(take_next_id(&mut request_queue), Some(2));
+        request_queue.job_finished(job_type_a());
+        assert_eq!(take_next_id(&mut request_queue), None);
+        assert!(request_queue.requests.is_empty());
+    }
+
+    // A throttled request that gets a new priority has two entries in the queue, it must start
+    // exactly once
+    #[test]
+    fn request_queue_reprioritize_throttled_request() {
+        let mut request_queue = JobExecutorRequestQueue::default();
+        request_queue.max_concurrent_jobs.insert(job_type_a(), 1);
+        for job_id in 1..=3 {
+            request_queue.add(request(job_id, job_type_a()), JobPriority::default());
+        }
+
+        let mut order = vec![take_next_id(&mut request_queue).unwrap()];
+        assert_eq!(take_next_id(&mut request_queue), None);
+        request_queue.set_priority(
+            JobId::from_u128(3),
+            JobPriority {
+                prioritized: true,
+                waiting_job_count: 0,
+            },
+        );
+        assert_eq!(take_next_id(&mut request_queue), None);
+
+        for _ in 0..3 {
+            request_queue.job_finished(job_type_a());
+            if let Some(job_id) = take_next_id(&mut request_queue) {
+                order.push(job_id);
+            }
+            assert_eq!(take_next_id(&mut request_queue), None);
+        }
+        assert_eq!(order, vec![1, 3, 2]);
+        assert!(request_queue.requests.is_empty());
+    }
+}
@@ -1,18 +1,18 @@
 use super::{JobId, JobTypeId};
 use crate::build::{AssetArtifactIdPair, BuiltArtifact};
 use crate::import::{ImportData, ImportJobs};
-use crate::{BuildLogEvent, LogEventLevel, PipelineResult};
+use crate::{BuildLogEvent, BuildProfile, LogEventLevel, PipelineResult};
 use hydrate_base::handle::DummySerdeContextHandle;
 use hydrate_base::hashing::HashMap;
 use hydrate_base::{ArtifactId, AssetId, BuiltArtifactHeaderData, Handle};
 use hydrate_data::{
-    DataContainerRef, DataSet, DataSetError, FieldRef, HashObjectMode, PropertyPath, Record,
-    SchemaSet, SingleObject,
+    CanonicalPathReference, DataContainerRef, DataSet, DataSetError, FieldRef, HashObjectMode,
+    ImportInfo, PathReferenceHash, PropertyPath, Record, SchemaSet, SingleObject,
 };
 use serde::{Deserialize, Serialize};
 use siphasher::sip128::Hasher128;
 use std::cell::RefCell;
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
 use std::panic::RefUnwindSafe;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -48,14 +48,19 @@ pub struct NewJob {
     pub input_data: Vec<u8>,
 }
 
+// The default artifact always has the same ID as the asset, so it can be found from the asset ID
 fn create_artifact_id<T: Hash>(
     asset_id: AssetId,
     artifact_key: Option<T>,
+    build_profile: &BuildProfile,
 ) -> ArtifactId {
     if let Some(artifact_key) = artifact_key {
         let mut hasher = siphasher::sip128::SipHasher::default();
         asset_id.hash(&mut hasher);
         artifact_key.hash(&mut hasher);
+        if !build_profile.name.is_empty() {
+            build_profile.name.hash(&mut hasher);
+        }
         let input_hash = hasher.finish128().as_u128();
         ArtifactId::from_u128(input_hash)
     } else {
@@ -63,7 +68,7 @@ fn create_artifact_id<T: Hash>(
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum JobRequestor {
     Builder(AssetId),
     Job(JobId),
@@ -92,12 +97,24 @@ pub trait JobApi: Send + Sync {
     fn produce_artifact(
         &self,
         artifact: BuiltArtifact,
-    );
+    ) -> PipelineResult<()>;
 
     fn fetch_import_data(
         &self,
         asset_id: AssetId,
     ) -> PipelineResult<ImportData>;
+
+    // The profile of the build that is currently running
+    fn build_profile(&self) -> Arc<BuildProfile>;
+
+    fn report_progress(
+        &self,
+        job_id: JobId,
+        progress: f32,
+    );
+
+    // True if the build that is currently running has been cancelled
+    fn is_cancelled(&self) -> bool;
 }
 
 //
@@ -109,27 +126,17 @@ pub trait JobOutput: Serialize + for<'a> Deserialize<'a> {}
 
 #[derive(Default, Clone)]
 pub struct JobEnumeratedDependencies {
-    // The contents of assets can affect the output so we need to include a hash of the contents of
-    // the asset. But assets can ref other assets, task needs to list all assets that are touched
-    // (including prototypes of those assets).
-    //
-    // We could do it at asset type granularity? (i.e. if you change an asset of type X all jobs that
-    // read an asset of type X have to rerun.
-    //
-    // What if we provide a data_set reader that keeps track of what was read? When we run the task
-    // the first time we don't know what we will touch or how to hash it but we can store it. Second
-    // build we can check if anything that was read last time was modified.
-    //
-    // Alternatively, jobs that read assets must always copy data out of the data set into a hashable
-    // form and pass it as input to a job.
-    //pub import_data: Vec<AssetId>,
-    //pub built_data: Vec<ArtifactId>,
+    // Asset data and import data don't need to be listed here. RunContext records everything a job
+    // reads while it runs, and a later build checks if anything that was read has been modified.
     pub upstream_jobs: Vec<JobId>,
 }
 
 pub(crate) trait JobProcessorAbstract: Send + Sync + RefUnwindSafe {
     fn version_inner(&self) -> u32;
 
+    // Used to group jobs in timing reports
+    fn type_name_inner(&self) -> &'static str;
+
     fn enumerate_dependencies_inner(
         &self,
         job_id: JobId,
@@ -146,8 +153,9 @@ pub(crate) trait JobProcessorAbstract: Send + Sync + RefUnwindSafe {
         input: &Vec<u8>,
         data_set: &DataSet,
         schema_set: &SchemaSet,
+        build_profile: &BuildProfile,
         job_api: &dyn JobApi,
-        fetched_asset_data: &mut HashMap<AssetId, FetchedAssetData>,
+        fetched_asset_data: &mut HashMap<AssetDataRead, u64>,
         fetched_import_data: &mut HashMap<AssetId, FetchedImportData>,
         log_events: &mut Vec<BuildLogEvent>,
     ) -> PipelineResult<Arc<Vec<u8>>>;
@@ -212,27 +220,85 @@ impl<'a, InputT> EnumerateDependenciesContext<'a, InputT> {
     }
 }
 
-pub(crate) struct FetchedAssetData {
-    pub(crate) _contents_hash: u64,
+// Something a job read from the data set while it ran. Jobs record a hash of everything they read
+// so that a later build can tell if a job's cached result is still valid.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) enum AssetDataRead {
+    // The asset's properties, including those inherited from prototypes
+    Properties(AssetId),
+    ImportInfo(AssetId),
+    // Path references of the asset and its prototypes, resolved to canonical paths
+    HashedPathReferences(AssetId),
+    // Canonical paths referenced by the asset and its prototypes, resolved to asset IDs
+    PathReferenceOverrides(AssetId),
+}
+
+impl AssetDataRead {
+    pub(crate) fn asset_id(&self) -> AssetId {
+        match *self {
+            AssetDataRead::Properties(asset_id)
+            | AssetDataRead::ImportInfo(asset_id)
+            | AssetDataRead::HashedPathReferences(asset_id)
+            | AssetDataRead::PathReferenceOverrides(asset_id) => asset_id,
+        }
+    }
+
+    pub(crate) fn hash_current_value(
+        &self,
+        data_set: &DataSet,
+    ) -> PipelineResult<u64> {
+        match *self {
+            AssetDataRead::Properties(asset_id) => {
+                Ok(data_set.hash_object(asset_id, HashObjectMode::PropertiesOnly)?)
+            }
+            AssetDataRead::ImportInfo(asset_id) => {
+                let mut hasher = siphasher::sip::SipHasher::default();
+                data_set.import_info(asset_id).hash(&mut hasher);
+                Ok(hasher.finish())
+            }
+            AssetDataRead::HashedPathReferences(asset_id) => Ok(hash_map_unordered(
+                &data_set.resolve_all_hashed_path_references(asset_id)?,
+            )),
+            AssetDataRead::PathReferenceOverrides(asset_id) => Ok(hash_map_unordered(
+                &data_set.resolve_all_path_reference_overrides(asset_id)?,
+            )),
+        }
+    }
+}
+
+// Combines the hashes of all entries with xor so that the result does not depend on iteration order
+fn hash_map_unordered<K: Hash, V: Hash>(map: &HashMap<K, V>) -> u64 {
+    let mut combined_hash = 0;
+    for (k, v) in map {
+        let mut hasher = siphasher::sip::SipHasher::default();
+        k.hash(&mut hasher);
+        v.hash(&mut hasher);
+        combined_hash ^= hasher.finish();
+    }
+    combined_hash
 }
 
+#[derive(Copy, Clone)]
 pub(crate) struct FetchedImportDataInfo {
-    pub(crate) _contents_hash: u64,
+    pub(crate) contents_hash: u64,
     pub(crate) _metadata_hash: u64,
 }
 
 pub(crate) struct FetchedImportData {
-    pub(crate) _info: FetchedImportDataInfo,
+    pub(crate) info: FetchedImportDataInfo,
     pub(crate) import_data: Arc<SingleObject>,
 }
 
+// Jobs must read asset data through the functions on this context rather than the data set
+// directly, so that everything the job read is recorded
 #[derive(Copy, Clone)]
 pub struct RunContext<'a, InputT> {
     pub job_id: JobId,
     pub input: &'a InputT,
-    pub data_set: &'a DataSet,
+    pub(crate) data_set: &'a DataSet,
     pub schema_set: &'a SchemaSet,
-    pub(crate) fetched_asset_data: &'a Rc<RefCell<&'a mut HashMap<AssetId, FetchedAssetData>>>,
+    pub build_profile: &'a BuildProfile,
+    pub(crate) fetched_asset_data: &'a Rc<RefCell<&'a mut HashMap<AssetDataRead, u64>>>,
     pub(crate) fetched_import_data: &'a Rc<RefCell<&'a mut HashMap<AssetId, FetchedImportData>>>,
     pub(crate) job_api: &'a dyn JobApi,
     pub(crate) log_events: &'a Rc<RefCell<&'a mut Vec<BuildLogEvent>>>,
@@ -269,6 +335,33 @@ impl<'a, InputT> RunContext<'a, InputT> {
         log_events.push(log_event);
     }
 
+    // Long-running jobs can call this to report how far along they are, in the range 0..=1
+    pub fn report_progress(
+        &self,
+        progress: f32,
+    ) {
+        self.job_api.report_progress(self.job_id, progress);
+    }
+
+    // Long-running jobs can check this and return PipelineError::Cancelled early. A cancelled
+    // job's result is thrown away.
+    pub fn is_cancelled(&self) -> bool {
+        self.job_api.is_cancelled()
+    }
+
+    fn record_asset_data_read(
+        &self,
+        asset_data_read: AssetDataRead,
+    ) -> PipelineResult<()> {
+        let mut fetched_asset_data = self.fetched_asset_data.borrow_mut();
+        if !fetched_asset_data.contains_key(&asset_data_read) {
+            let hash = asset_data_read.hash_current_value(self.data_set)?;
+            fetched_asset_data.insert(asset_data_read, hash);
+        }
+
+        Ok(())
+    }
+
     pub fn asset<T: Record>(
         &'a self,
         asset_id: AssetId,
@@ -283,22 +376,51 @@ impl<'a, InputT> RunContext<'a, InputT> {
             Err(DataSetError::InvalidSchema)?;
         }
 
-        let mut fetched_asset_data = self.fetched_asset_data.borrow_mut();
-        fetched_asset_data
-            .entry(asset_id)
-            .or_insert_with(|| FetchedAssetData {
-                _contents_hash: self
-                    .data_set
-                    .hash_object(asset_id, HashObjectMode::PropertiesOnly)
-                    .unwrap(),
-            });
-
         Ok(<T as Record>::Reader::new(
             PropertyPath::default(),
-            DataContainerRef::from_dataset(self.data_set, self.schema_set, asset_id),
+            self.asset_data_container(asset_id)?,
+        ))
+    }
+
+    // Untyped access to an asset's properties
+    pub fn asset_data_container(
+        &self,
+        asset_id: AssetId,
+    ) -> PipelineResult<DataContainerRef<'a>> {
+        self.record_asset_data_read(AssetDataRead::Properties(asset_id))?;
+        Ok(DataContainerRef::from_dataset(
+            self.data_set,
+            self.schema_set,
+            asset_id,
         ))
     }
 
+    pub fn import_info(
+        &self,
+        asset_id: AssetId,
+    ) -> PipelineResult<Option<&'a ImportInfo>> {
+        self.record_asset_data_read(AssetDataRead::ImportInfo(asset_id))?;
+        Ok(self.data_set.import_info(asset_id))
+    }
+
+    pub fn resolve_all_hashed_path_references(
+        &self,
+        asset_id: AssetId,
+    ) -> PipelineResult<HashMap<PathReferenceHash, CanonicalPathReference>> {
+        self.record_asset_data_read(AssetDataRead::HashedPathReferences(asset_id))?;
+        Ok(self.data_set.resolve_all_hashed_path_references(asset_id)?)
+    }
+
+    pub fn resolve_all_path_reference_overrides(
+        &self,
+        asset_id: AssetId,
+    ) -> PipelineResult<HashMap<CanonicalPathReference, AssetId>> {
+        self.record_asset_data_read(AssetDataRead::PathReferenceOverrides(asset_id))?;
+        Ok(self
+            .data_set
+            .resolve_all_path_reference_overrides(asset_id)?)
+    }
+
     pub fn imported_data<T: Record>(
         &'a self,
         asset_id: AssetId,
@@ -314,8 +436,8 @@ impl<'a, InputT> RunContext<'a, InputT> {
                 asset_id,
                 FetchedImportData {
                     import_data: import_data.clone(),
-                    _info: FetchedImportDataInfo {
-                        _contents_hash: newly_fetched_import_data.contents_hash,
+                    info: FetchedImportDataInfo {
+                        contents_hash: newly_fetched_import_data.contents_hash,
                         _metadata_hash: newly_fetched_import_data.metadata_hash,
                     },
                 },
@@ -486,7 +608,7 @@ fn produce_artifact_with_handles<
     asset_fn: F,
 ) -> PipelineResult<ArtifactId> {
     let artifact_key_debug_name = artifact_key.as_ref().map(|x| format!("{}", x));
-    let artifact_id = create_artifact_id(asset_id, artifact_key);
+    let artifact_id = create_artifact_id(asset_id, artifact_key, &job_api.build_profile());
 
     let mut ctx = DummySerdeContextHandle::default();
     ctx.begin_serialize_artifact(artifact_id);
@@ -516,7 +638,7 @@ fn produce_artifact_with_handles<
         },
         data: built_data?,
         artifact_key_debug_name,
-    });
+    })?;
 
     Ok(artifact_id)
 }
@@ -561,7 +683,7 @@ impl<'a> HandleFactory<'a> {
         asset_id: AssetId,
         artifact_key: Option<K>,
     ) -> Handle<T> {
-        let artifact_id = create_artifact_id(asset_id, artifact_key);
+        let artifact_id = create_artifact_id(asset_id, artifact_key, &self.job_api.build_profile());
         self.job_api.artifact_handle_created(asset_id, artifact_id);
         hydrate_base::handle::make_handle_within_serde_context::<T>(artifact_id)
     }
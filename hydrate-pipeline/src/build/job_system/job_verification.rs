@@ -0,0 +1,247 @@
+use super::{JobApi, JobApiImpl, JobId, JobRequestor, NewJob};
+use crate::build::{BuiltArtifact, WrittenArtifact};
+use crate::import::ImportData;
+use crate::{BuildLogEvent, BuildProfile, PipelineResult};
+use hydrate_base::hashing::HashMap;
+use hydrate_base::{ArtifactId, AssetId};
+use hydrate_data::{DataSet, SchemaSet};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+// How a verification build checks that jobs are deterministic
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BuildVerificationMode {
+    // Every verified job runs a second time and the results of both runs are compared. The second
+    // run doesn't write artifacts or enqueue jobs.
+    RunTwice,
+    // Verified jobs run even if they have a cached result, and the new result is compared against
+    // the cached one. Only jobs whose inputs are unchanged since the previous build are checked.
+    CompareWithPreviousBuild,
+}
+
+// Job processors that don't produce the same bytes for the same input (for example, because they
+// iterate a HashMap) get a new build hash every time they run, which silently defeats caching.
+// A verification build finds them.
+#[derive(Debug, Clone)]
+pub struct BuildVerification {
+    pub mode: BuildVerificationMode,
+    // Only jobs whose processor type name contains one of these strings are verified. All jobs
+    // are verified if this is empty.
+    pub job_type_filter: Vec<String>,
+}
+
+impl BuildVerification {
+    pub fn new(mode: BuildVerificationMode) -> Self {
+        BuildVerification {
+            mode,
+            job_type_filter: Vec::default(),
+        }
+    }
+
+    pub fn should_verify(
+        &self,
+        job_type_name: &str,
+    ) -> bool {
+        self.job_type_filter.is_empty()
+            || self
+                .job_type_filter
+                .iter()
+                .any(|x| job_type_name.contains(x.as_str()))
+    }
+}
+
+// A difference between two runs of a job with identical inputs
+#[derive(Debug, Clone)]
+pub struct DeterminismMismatch {
+    pub job_id: JobId,
+    pub job_type_name: &'static str,
+    pub debug_name: Arc<String>,
+    pub asset_id: Option<AssetId>,
+    pub artifact_id: Option<ArtifactId>,
+    pub message: String,
+}
+
+// The parts of a job run that must be the same every time the job runs with the same input
+#[derive(Default)]
+pub(crate) struct JobRunSummary {
+    // Build hashes cover the full contents of the written .bf file
+    artifacts: HashMap<ArtifactId, (AssetId, u64)>,
+    enqueued_job_input_hashes: Vec<u128>,
+    output_hash: u64,
+}
+
+impl JobRunSummary {
+    pub(crate) fn new<'a>(
+        written_artifacts: impl Iterator<Item = &'a WrittenArtifact>,
+        enqueued_job_input_hashes: impl Iterator<Item = u128>,
+        output_data: &[u8],
+    ) -> Self {
+        let mut enqueued_job_input_hashes: Vec<_> = enqueued_job_input_hashes.collect();
+        enqueued_job_input_hashes.sort();
+
+        let mut hasher = siphasher::sip::SipHasher::default();
+        output_data.hash(&mut hasher);
+
+        JobRunSummary {
+            artifacts: written_artifacts
+                .map(|x| (x.artifact_id, (x.asset_id, x.build_hash)))
+                .collect(),
+            enqueued_job_input_hashes,
+            output_hash: hasher.finish(),
+        }
+    }
+
+    // Returns a description of every difference between the runs
+    pub(crate) fn compare(
+        expected: &JobRunSummary,
+        actual: &JobRunSummary,
+    ) -> Vec<(Option<AssetId>, Option<ArtifactId>, String)> {
+        let mut differences = Vec::default();
+
+        let mut artifact_ids: Vec<_> = expected
+            .artifacts
+            .keys()
+            .chain(actual.artifacts.keys())
+            .copied()
+            .collect();
+        artifact_ids.sort();
+        artifact_ids.dedup();
+
+        for artifact_id in artifact_ids {
+            match (
+                expected.artifacts.get(&artifact_id),
+                actual.artifacts.get(&artifact_id),
+            ) {
+                (Some((asset_id, expected_hash)), Some((_, actual_hash))) => {
+                    if expected_hash != actual_hash {
+                        differences.push((
+                            Some(*asset_id),
+                            Some(artifact_id),
+                            format!(
+                                "Artifact {:?} has different contents ({:0>16x} and {:0>16x})",
+                                artifact_id, expected_hash, actual_hash
+                            ),
+                        ));
+                    }
+                }
+                (Some((asset_id, _)), None) | (None, Some((asset_id, _))) => {
+                    differences.push((
+                        Some(*asset_id),
+                        Some(artifact_id),
+                        format!(
+                            "Artifact {:?} was only produced by one of the runs",
+                            artifact_id
+                        ),
+                    ));
+                }
+                (None, None) => unreachable!(),
+            }
+        }
+
+        if expected.enqueued_job_input_hashes != actual.enqueued_job_input_hashes {
+            differences.push((None, None, "The job enqueued different jobs".to_string()));
+        }
+
+        if expected.output_hash != actual.output_hash {
+            differences.push((None, None, "The job returned different output".to_string()));
+        }
+
+        differences
+    }
+}
+
+// Passed to a job when it runs a second time to verify it's deterministic. Records what the job
+// does without writing anything or enqueueing more jobs.
+pub(crate) struct VerifyingJobApi<'a> {
+    job_api: &'a JobApiImpl,
+    written_artifacts: Mutex<Vec<WrittenArtifact>>,
+    enqueued_job_input_hashes: Mutex<Vec<u128>>,
+}
+
+impl<'a> VerifyingJobApi<'a> {
+    pub(crate) fn new(job_api: &'a JobApiImpl) -> Self {
+        VerifyingJobApi {
+            job_api,
+            written_artifacts: Default::default(),
+            enqueued_job_input_hashes: Default::default(),
+        }
+    }
+
+    pub(crate) fn finish(
+        self,
+        output_data: &[u8],
+    ) -> JobRunSummary {
+        let written_artifacts = self.written_artifacts.into_inner().unwrap();
+        let enqueued_job_input_hashes = self.enqueued_job_input_hashes.into_inner().unwrap();
+        JobRunSummary::new(
+            written_artifacts.iter(),
+            enqueued_job_input_hashes.into_iter(),
+            output_data,
+        )
+    }
+}
+
+impl<'a> JobApi for VerifyingJobApi<'a> {
+    fn enqueue_job(
+        &self,
+        _job_requestor: JobRequestor,
+        _data_set: &DataSet,
+        _schema_set: &SchemaSet,
+        new_job: NewJob,
+        _debug_name: String,
+        _log_events: &mut Vec<BuildLogEvent>,
+    ) -> PipelineResult<JobId> {
+        self.enqueued_job_input_hashes
+            .lock()
+            .unwrap()
+            .push(new_job.input_hash);
+        Ok(self.job_api.job_id_for_input_hash(new_job.input_hash))
+    }
+
+    fn artifact_handle_created(
+        &self,
+        _asset_id: AssetId,
+        _artifact_id: ArtifactId,
+    ) {
+    }
+
+    fn produce_artifact(
+        &self,
+        artifact: BuiltArtifact,
+    ) -> PipelineResult<()> {
+        let build_hash = self.job_api.artifact_build_hash(&artifact);
+        self.written_artifacts
+            .lock()
+            .unwrap()
+            .push(WrittenArtifact {
+                asset_id: artifact.asset_id,
+                artifact_id: artifact.artifact_id,
+                metadata: artifact.metadata,
+                build_hash,
+                artifact_key_debug_name: artifact.artifact_key_debug_name,
+            });
+        Ok(())
+    }
+
+    fn fetch_import_data(
+        &self,
+        asset_id: AssetId,
+    ) -> PipelineResult<ImportData> {
+        self.job_api.fetch_import_data(asset_id)
+    }
+
+    fn build_profile(&self) -> Arc<BuildProfile> {
+        self.job_api.build_profile()
+    }
+
+    fn report_progress(
+        &self,
+        _job_id: JobId,
+        _progress: f32,
+    ) {
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.job_api.is_cancelled()
+    }
+}
@@ -0,0 +1,240 @@
+use super::job_system_traits::{AssetDataRead, FetchedImportData};
+use super::job_worker_protocol::*;
+use super::{JobApi, JobId, JobProcessorRegistry, JobRequestor, NewJob};
+use crate::build::BuiltArtifact;
+use crate::import::ImportData;
+use crate::{BuildLogEvent, BuildProfile, PipelineResult};
+use hydrate_base::hashing::HashMap;
+use hydrate_base::{ArtifactId, AssetId};
+use hydrate_data::json_storage::DataSetJson;
+use hydrate_data::{DataSet, PathReferenceNamespaceResolver, SchemaSet};
+use std::net::TcpStream;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+
+// Passed to jobs running in a worker process. Calls are forwarded to the build over the
+// connection, and calls that return a value wait for the build to reply.
+struct RemoteJobApi {
+    stream: Mutex<TcpStream>,
+    schema_set: SchemaSet,
+    build_profile: Arc<BuildProfile>,
+}
+
+impl RemoteJobApi {
+    fn send(
+        &self,
+        message: JobWorkerResponse,
+    ) {
+        // If the connection is lost, the job fails when it completes
+        if let Err(e) = write_message(&mut *self.stream.lock().unwrap(), &message) {
+            log::error!("Failed to send message to build: {}", e.to_string());
+        }
+    }
+
+    fn request(
+        &self,
+        message: JobWorkerResponse,
+    ) -> PipelineResult<JobWorkerRequest> {
+        let mut stream = self.stream.lock().unwrap();
+        write_message(&mut *stream, &message)?;
+        read_message(&mut *stream)
+    }
+}
+
+impl JobApi for RemoteJobApi {
+    fn enqueue_job(
+        &self,
+        _job_requestor: JobRequestor,
+        _data_set: &DataSet,
+        _schema_set: &SchemaSet,
+        new_job: NewJob,
+        debug_name: String,
+        _log_events: &mut Vec<BuildLogEvent>,
+    ) -> PipelineResult<JobId> {
+        // The build enumerates the job's dependencies, and logs any problems with them
+        match self.request(JobWorkerResponse::EnqueueJob {
+            job_type: new_job.job_type,
+            input_hash: new_job.input_hash,
+            input_data: new_job.input_data,
+            debug_name,
+        })? {
+            JobWorkerRequest::EnqueueJobResult(result) => Ok(result?),
+            _ => Err("Unexpected reply from build when enqueueing a job")?,
+        }
+    }
+
+    fn artifact_handle_created(
+        &self,
+        asset_id: AssetId,
+        artifact_id: ArtifactId,
+    ) {
+        self.send(JobWorkerResponse::ArtifactHandleCreated {
+            asset_id,
+            artifact_id,
+        });
+    }
+
+    fn produce_artifact(
+        &self,
+        artifact: BuiltArtifact,
+    ) -> PipelineResult<()> {
+        self.send(JobWorkerResponse::ProduceArtifact {
+            asset_id: artifact.asset_id,
+            artifact_id: artifact.artifact_id,
+            metadata: artifact.metadata,
+            data: artifact.data,
+            artifact_key_debug_name: artifact.artifact_key_debug_name,
+        });
+        Ok(())
+    }
+
+    fn fetch_import_data(
+        &self,
+        asset_id: AssetId,
+    ) -> PipelineResult<ImportData> {
+        match self.request(JobWorkerResponse::FetchImportData { asset_id })? {
+            JobWorkerRequest::ImportData(result) => {
+                let (bytes, metadata_hash) = result?;
+                crate::import::load_import_data_from_bytes(&self.schema_set, &bytes, metadata_hash)
+            }
+            _ => Err("Unexpected reply from build when fetching import data")?,
+        }
+    }
+
+    fn build_profile(&self) -> Arc<BuildProfile> {
+        self.build_profile.clone()
+    }
+
+    fn report_progress(
+        &self,
+        _job_id: JobId,
+        progress: f32,
+    ) {
+        self.send(JobWorkerResponse::ReportProgress { progress });
+    }
+
+    fn is_cancelled(&self) -> bool {
+        // Stop early if the build can't be reached, the result would be discarded anyways
+        match self.request(JobWorkerResponse::IsCancelled) {
+            Ok(JobWorkerRequest::IsCancelled(is_cancelled)) => is_cancelled,
+            _ => true,
+        }
+    }
+}
+
+// Connects to a build started with a JobWorkerConfig and runs jobs for it until the connection
+// closes. The process must register the same job processors as the editor, and the token must
+// match the config's token.
+pub fn run_job_worker(
+    address: &str,
+    token: &str,
+    schema_set: &SchemaSet,
+    job_processor_registry: &JobProcessorRegistry,
+    namespace_resolver: &dyn PathReferenceNamespaceResolver,
+) -> PipelineResult<()> {
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+
+    write_message(
+        &mut stream,
+        &JobWorkerResponse::Hello {
+            protocol_version: JOB_WORKER_PROTOCOL_VERSION,
+            token: token.to_string(),
+            job_processors: job_processor_registry.job_processor_versions(),
+        },
+    )?;
+
+    log::info!("Connected to build at {}", address);
+
+    let mut data_set = None;
+    loop {
+        let request = match read_message::<JobWorkerRequest, _>(&mut stream) {
+            Ok(request) => request,
+            Err(e) => {
+                log::info!("Connection to build closed: {}", e.to_string());
+                return Ok(());
+            }
+        };
+
+        match request {
+            JobWorkerRequest::SyncDataSet {
+                build_id,
+                data_set_json,
+            } => {
+                profiling::scope!("Load data set from build");
+                let data_set_json: DataSetJson = serde_json::from_str(&data_set_json)?;
+                data_set = Some(data_set_json.to_data_set(schema_set, namespace_resolver)?);
+                log::info!("Loaded data set for build {}", build_id);
+            }
+            JobWorkerRequest::RunJob {
+                job_id,
+                job_type,
+                input_data,
+                debug_name,
+                build_profile,
+            } => {
+                profiling::scope!(&format!("Handle Job {}", debug_name));
+                write_message(&mut stream, &JobWorkerResponse::JobStarted)?;
+                let job_api = RemoteJobApi {
+                    stream: Mutex::new(stream.try_clone()?),
+                    schema_set: schema_set.clone(),
+                    build_profile: Arc::new(build_profile),
+                };
+
+                let mut fetched_asset_data = HashMap::<AssetDataRead, u64>::default();
+                let mut fetched_import_data = HashMap::<AssetId, FetchedImportData>::default();
+                let mut log_events = Vec::default();
+
+                let output_data = match (&data_set, job_processor_registry.get_processor(job_type))
+                {
+                    (Some(data_set), Some(job_processor)) => {
+                        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                            job_processor.run_inner(
+                                job_id,
+                                &input_data,
+                                data_set,
+                                schema_set,
+                                &job_api.build_profile,
+                                &job_api,
+                                &mut fetched_asset_data,
+                                &mut fetched_import_data,
+                                &mut log_events,
+                            )
+                        }));
+
+                        match result {
+                            Ok(Ok(output_data)) => Ok((*output_data).clone()),
+                            Ok(Err(e)) => Err(e.to_string()),
+                            Err(_) => Err("Panic detected in build job.".to_string()),
+                        }
+                    }
+                    (None, _) => Err("Job worker has no data set".to_string()),
+                    (_, None) => Err(format!(
+                        "Job worker has no processor for job {}",
+                        debug_name
+                    )),
+                };
+
+                write_message(
+                    &mut stream,
+                    &JobWorkerResponse::JobComplete {
+                        output_data,
+                        fetched_asset_data: fetched_asset_data.into_iter().collect(),
+                        fetched_import_data: fetched_import_data
+                            .into_iter()
+                            .map(|(asset_id, x)| {
+                                (asset_id, x.info.contents_hash, x.info._metadata_hash)
+                            })
+                            .collect(),
+                        log_events,
+                    },
+                )?;
+            }
+            JobWorkerRequest::Rejected(reason) => Err(format!(
+                "Build at {} rejected this worker: {}",
+                address, reason
+            ))?,
+            _ => Err("Unexpected message from build")?,
+        }
+    }
+}
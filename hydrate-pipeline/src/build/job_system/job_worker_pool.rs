@@ -0,0 +1,561 @@
+use super::job_system_traits::{AssetDataRead, FetchedImportDataInfo};
+use super::job_worker_protocol::*;
+use super::{JobApi, JobApiImpl, JobId, JobProcessorRegistry, JobRequestor, JobTypeId, NewJob};
+use crate::build::BuiltArtifact;
+use crate::{BuildLogEvent, BuildProfile, PipelineError, PipelineResult};
+use crossbeam_channel::{Receiver, Sender};
+use hydrate_base::hashing::HashMap;
+use hydrate_base::AssetId;
+use hydrate_data::json_storage::DataSetJson;
+use hydrate_data::{DataSet, SchemaSet};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// How often a job waiting for a worker checks if the build was cancelled
+const WORKER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// A job is sent to another worker if the connection fails before the worker starts it. A job that
+// keeps failing to start probably takes down whatever worker gets it.
+const MAX_JOB_START_ATTEMPTS: usize = 3;
+
+// Local worker processes get the token in this environment variable rather than on the command
+// line, where other users on the machine could see it
+pub const JOB_WORKER_TOKEN_ENV_VAR: &str = "HYDRATE_JOB_WORKER_TOKEN";
+
+// How a worker process is started. The address of the build is appended to the arguments and the
+// token is in JOB_WORKER_TOKEN_ENV_VAR. The process is expected to call run_job_worker with them.
+#[derive(Debug, Clone)]
+pub struct JobWorkerCommand {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl JobWorkerCommand {
+    // Runs the current executable with the given arguments
+    pub fn current_exe(args: Vec<String>) -> PipelineResult<Self> {
+        Ok(JobWorkerCommand {
+            program: std::env::current_exe()?,
+            args,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JobWorkerConfig {
+    // The build listens here for workers. Port 0 picks a free port. Workers on other machines can
+    // connect if this is not a loopback address.
+    pub listen_address: SocketAddr,
+    // Worker processes that the build starts, and restarts if they exit
+    pub local_worker_command: Option<JobWorkerCommand>,
+    pub local_worker_count: usize,
+    // Jobs wait for a worker as long as at least one is connected. If none are connected for this
+    // long, the job fails.
+    pub worker_wait_timeout: Duration,
+    // Workers must send this when they connect. Anyone who can reach the listen address could
+    // otherwise run jobs and see the project's data.
+    pub token: String,
+}
+
+impl JobWorkerConfig {
+    // Starts the given number of worker processes on this machine
+    pub fn local(
+        local_worker_command: JobWorkerCommand,
+        local_worker_count: usize,
+    ) -> Self {
+        JobWorkerConfig {
+            listen_address: SocketAddr::from(([127, 0, 0, 1], 0)),
+            local_worker_command: Some(local_worker_command),
+            local_worker_count,
+            worker_wait_timeout: Duration::from_secs(60),
+            token: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+}
+
+// A connected worker that is not running a job
+struct JobWorkerConnection {
+    stream: TcpStream,
+    peer_address: SocketAddr,
+    // The build that the worker has a copy of the data set for
+    synced_build_id: Option<u64>,
+}
+
+// Accepts connections from worker processes and hands out idle workers to run jobs. Jobs run in a
+// worker process can't take down the editor if they crash.
+pub struct JobWorkerPool {
+    config: JobWorkerConfig,
+    listen_address: SocketAddr,
+    idle_worker_tx: Sender<JobWorkerConnection>,
+    idle_worker_rx: Receiver<JobWorkerConnection>,
+    local_workers: Mutex<Vec<Child>>,
+    // Includes workers that are running a job
+    connected_worker_count: Arc<AtomicUsize>,
+    // The SyncDataSet message for the current build, serialized once and sent to every worker
+    data_set_snapshot: Mutex<Option<(u64, Arc<Vec<u8>>)>>,
+    finish: Arc<AtomicBool>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for JobWorkerPool {
+    fn drop(&mut self) {
+        self.finish.store(true, Ordering::Relaxed);
+        if let Some(accept_thread) = self.accept_thread.take() {
+            accept_thread.join().unwrap();
+        }
+
+        // Workers exit when their connection closes, but don't leave them running if they're stuck
+        for mut local_worker in self.local_workers.lock().unwrap().drain(..) {
+            let _ = local_worker.kill();
+            let _ = local_worker.wait();
+        }
+    }
+}
+
+impl JobWorkerPool {
+    pub fn new(
+        config: JobWorkerConfig,
+        job_processor_registry: &JobProcessorRegistry,
+    ) -> PipelineResult<Self> {
+        let listener = TcpListener::bind(config.listen_address)?;
+        let listen_address = listener.local_addr()?;
+        // Polled so that the thread can notice when the pool is dropped
+        listener.set_nonblocking(true)?;
+
+        let mut expected_job_processors = job_processor_registry.job_processor_versions();
+        expected_job_processors.sort();
+        let expected_job_processors = Arc::new(expected_job_processors);
+
+        let (idle_worker_tx, idle_worker_rx) = crossbeam_channel::unbounded();
+        let finish = Arc::new(AtomicBool::new(false));
+        let connected_worker_count = Arc::new(AtomicUsize::new(0));
+
+        let accept_thread = {
+            let token = Arc::new(config.token.clone());
+            let idle_worker_tx = idle_worker_tx.clone();
+            let finish = finish.clone();
+            let connected_worker_count = connected_worker_count.clone();
+            std::thread::Builder::new()
+                .name("Job Worker Accept Thread".into())
+                .spawn(move || {
+                    while !finish.load(Ordering::Relaxed) {
+                        match listener.accept() {
+                            Ok((stream, peer_address)) => {
+                                // The handshake waits on the worker, so it runs on its own thread
+                                // to keep a slow or silent connection from holding up other workers
+                                let token = token.clone();
+                                let expected_job_processors = expected_job_processors.clone();
+                                let idle_worker_tx = idle_worker_tx.clone();
+                                let connected_worker_count = connected_worker_count.clone();
+                                let result = std::thread::Builder::new()
+                                    .name("Job Worker Handshake Thread".into())
+                                    .spawn(move || {
+                                        match Self::accept_worker(
+                                            stream,
+                                            &token,
+                                            &expected_job_processors,
+                                        ) {
+                                            Ok(stream) => {
+                                                log::info!("Job worker {} connected", peer_address);
+                                                connected_worker_count
+                                                    .fetch_add(1, Ordering::Relaxed);
+                                                // Fails if the pool was dropped during the handshake
+                                                let _ = idle_worker_tx.send(JobWorkerConnection {
+                                                    stream,
+                                                    peer_address,
+                                                    synced_build_id: None,
+                                                });
+                                            }
+                                            Err(e) => log::warn!(
+                                                "Rejected job worker {}: {}",
+                                                peer_address,
+                                                e.to_string()
+                                            ),
+                                        }
+                                    });
+                                if let Err(e) = result {
+                                    log::warn!(
+                                        "Failed to start handshake with job worker {}: {}",
+                                        peer_address,
+                                        e
+                                    );
+                                }
+                            }
+                            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                                std::thread::sleep(Duration::from_millis(50));
+                            }
+                            Err(e) => {
+                                log::warn!("Failed to accept job worker connection: {}", e);
+                                std::thread::sleep(Duration::from_millis(50));
+                            }
+                        }
+                    }
+                })?
+        };
+
+        log::info!("Listening for job workers on {}", listen_address);
+
+        let pool = JobWorkerPool {
+            config,
+            listen_address,
+            idle_worker_tx,
+            idle_worker_rx,
+            local_workers: Default::default(),
+            connected_worker_count,
+            data_set_snapshot: Default::default(),
+            finish,
+            accept_thread: Some(accept_thread),
+        };
+
+        pool.start_missing_local_workers()?;
+        Ok(pool)
+    }
+
+    // Workers on other machines connect to this address
+    pub fn listen_address(&self) -> SocketAddr {
+        self.listen_address
+    }
+
+    fn accept_worker(
+        mut stream: TcpStream,
+        token: &str,
+        expected_job_processors: &[(JobTypeId, u32)],
+    ) -> PipelineResult<TcpStream> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let hello: JobWorkerResponse = read_message(&mut stream)?;
+        stream.set_read_timeout(None)?;
+
+        // The worker is told why it was turned away, so that it doesn't look like the build closed
+        if let Err(e) = Self::check_worker_hello(hello, token, expected_job_processors) {
+            let _ = write_message(&mut stream, &JobWorkerRequest::Rejected(e.to_string()));
+            return Err(e);
+        }
+
+        Ok(stream)
+    }
+
+    fn check_worker_hello(
+        hello: JobWorkerResponse,
+        token: &str,
+        expected_job_processors: &[(JobTypeId, u32)],
+    ) -> PipelineResult<()> {
+        let JobWorkerResponse::Hello {
+            protocol_version,
+            token: worker_token,
+            mut job_processors,
+        } = hello
+        else {
+            Err("Worker did not send a hello message")?
+        };
+
+        if worker_token != token {
+            Err("Worker did not send the correct token")?;
+        }
+
+        if protocol_version != JOB_WORKER_PROTOCOL_VERSION {
+            Err(format!(
+                "Worker uses protocol version {} but version {} is required",
+                protocol_version, JOB_WORKER_PROTOCOL_VERSION
+            ))?;
+        }
+
+        job_processors.sort();
+        if job_processors != expected_job_processors {
+            Err("Worker was not built with the same job processors as the editor")?;
+        }
+
+        Ok(())
+    }
+
+    // Replaces local worker processes that have exited
+    fn start_missing_local_workers(&self) -> PipelineResult<()> {
+        let Some(command) = &self.config.local_worker_command else {
+            return Ok(());
+        };
+
+        let mut local_workers = self.local_workers.lock().unwrap();
+        local_workers.retain_mut(|x| !matches!(x.try_wait(), Ok(Some(_))));
+        while local_workers.len() < self.config.local_worker_count {
+            let child = Command::new(&command.program)
+                .args(&command.args)
+                .arg(self.listen_address.to_string())
+                .env(JOB_WORKER_TOKEN_ENV_VAR, &self.config.token)
+                .spawn()?;
+            log::info!("Started job worker process {}", child.id());
+            local_workers.push(child);
+        }
+
+        Ok(())
+    }
+
+    fn data_set_snapshot(
+        &self,
+        build_id: u64,
+        data_set: &DataSet,
+    ) -> PipelineResult<Arc<Vec<u8>>> {
+        let mut data_set_snapshot = self.data_set_snapshot.lock().unwrap();
+        if let Some((snapshot_build_id, snapshot)) = &*data_set_snapshot {
+            if *snapshot_build_id == build_id {
+                return Ok(snapshot.clone());
+            }
+        }
+
+        profiling::scope!("Serialize data set for job workers");
+        let data_set_json = serde_json::to_string(&DataSetJson::new(data_set))?;
+        let snapshot = Arc::new(bincode::serialize(&JobWorkerRequest::SyncDataSet {
+            build_id,
+            data_set_json,
+        })?);
+        *data_set_snapshot = Some((build_id, snapshot.clone()));
+        Ok(snapshot)
+    }
+
+    // Runs the job on the next idle worker. Calls the worker makes back to the build are forwarded
+    // to job_api. If the worker crashes or disconnects while running the job, the job fails and the
+    // worker is replaced. A worker that disconnected before it started the job (for example, while
+    // it was idle) is dropped and the job is sent to another worker.
+    pub(crate) fn run_job(
+        &self,
+        job_api_impl: &JobApiImpl,
+        job_api: &dyn JobApi,
+        job_id: JobId,
+        job_type: JobTypeId,
+        input_data: &Vec<u8>,
+        debug_name: &str,
+        data_set: &DataSet,
+        schema_set: &SchemaSet,
+        build_profile: &BuildProfile,
+        fetched_asset_data: &mut HashMap<AssetDataRead, u64>,
+        fetched_import_data: &mut HashMap<AssetId, FetchedImportDataInfo>,
+        log_events: &mut Vec<BuildLogEvent>,
+    ) -> PipelineResult<Arc<Vec<u8>>> {
+        let mut start_attempt_count = 0;
+        loop {
+            let mut connection = self.wait_for_idle_worker(job_api)?;
+            start_attempt_count += 1;
+            let mut job_started = false;
+            let result = self.run_job_on_connection(
+                &mut connection,
+                &mut job_started,
+                job_api_impl,
+                job_api,
+                job_id,
+                job_type,
+                input_data,
+                debug_name,
+                data_set,
+                schema_set,
+                build_profile,
+                fetched_asset_data,
+                fetched_import_data,
+                log_events,
+            );
+
+            let e = match result {
+                Ok(output) => {
+                    // The job may have failed, but the worker is still usable
+                    self.idle_worker_tx.send(connection).unwrap();
+                    return output;
+                }
+                Err(e) => e,
+            };
+
+            self.connected_worker_count.fetch_sub(1, Ordering::Relaxed);
+            self.start_missing_local_workers()?;
+            if !job_started && start_attempt_count < MAX_JOB_START_ATTEMPTS {
+                log::warn!(
+                    "Lost connection to job worker {} before it started job {}, trying another worker: {}",
+                    connection.peer_address,
+                    debug_name,
+                    e.to_string()
+                );
+                continue;
+            }
+
+            log::error!(
+                "Lost connection to job worker {} while running job {}: {}",
+                connection.peer_address,
+                debug_name,
+                e.to_string()
+            );
+            Err(format!(
+                "Job worker {} disconnected while running the job: {}",
+                connection.peer_address,
+                e.to_string()
+            ))?;
+        }
+    }
+
+    fn wait_for_idle_worker(
+        &self,
+        job_api: &dyn JobApi,
+    ) -> PipelineResult<JobWorkerConnection> {
+        let mut time_without_workers = Duration::ZERO;
+        loop {
+            if let Ok(connection) = self.idle_worker_rx.recv_timeout(WORKER_POLL_INTERVAL) {
+                return Ok(connection);
+            }
+
+            if job_api.is_cancelled() {
+                Err(PipelineError::Cancelled)?;
+            }
+
+            // Workers may be busy with long jobs, only give up if there are none at all
+            if self.connected_worker_count.load(Ordering::Relaxed) == 0 {
+                time_without_workers += WORKER_POLL_INTERVAL;
+                if time_without_workers >= self.config.worker_wait_timeout {
+                    self.start_missing_local_workers()?;
+                    Err(format!(
+                        "No job worker connected within {} seconds",
+                        self.config.worker_wait_timeout.as_secs()
+                    ))?;
+                }
+            } else {
+                time_without_workers = Duration::ZERO;
+            }
+        }
+    }
+
+    // The outer error means the connection failed, the inner result is the job's result
+    fn run_job_on_connection(
+        &self,
+        connection: &mut JobWorkerConnection,
+        job_started: &mut bool,
+        job_api_impl: &JobApiImpl,
+        job_api: &dyn JobApi,
+        job_id: JobId,
+        job_type: JobTypeId,
+        input_data: &Vec<u8>,
+        debug_name: &str,
+        data_set: &DataSet,
+        schema_set: &SchemaSet,
+        build_profile: &BuildProfile,
+        fetched_asset_data: &mut HashMap<AssetDataRead, u64>,
+        fetched_import_data: &mut HashMap<AssetId, FetchedImportDataInfo>,
+        log_events: &mut Vec<BuildLogEvent>,
+    ) -> PipelineResult<PipelineResult<Arc<Vec<u8>>>> {
+        let build_id = job_api_impl.build_id();
+        let stream = &mut connection.stream;
+        if connection.synced_build_id != Some(build_id) {
+            let sync_data_set_message = self.data_set_snapshot(build_id, data_set)?;
+            write_message_bytes(stream, &sync_data_set_message)?;
+            connection.synced_build_id = Some(build_id);
+        }
+
+        write_message(
+            stream,
+            &JobWorkerRequest::RunJob {
+                job_id,
+                job_type,
+                input_data: input_data.clone(),
+                debug_name: debug_name.to_string(),
+                build_profile: build_profile.clone(),
+            },
+        )?;
+
+        // The worker doesn't wait for artifacts to be written, so a failed write fails the job when
+        // it completes
+        let mut produce_artifact_error = None;
+        loop {
+            match read_message::<JobWorkerResponse, _>(stream)? {
+                JobWorkerResponse::Hello { .. } => Err("Unexpected hello message from worker")?,
+                JobWorkerResponse::JobStarted => *job_started = true,
+                JobWorkerResponse::EnqueueJob {
+                    job_type,
+                    input_hash,
+                    input_data,
+                    debug_name,
+                } => {
+                    let result = job_api
+                        .enqueue_job(
+                            JobRequestor::Job(job_id),
+                            data_set,
+                            schema_set,
+                            NewJob {
+                                job_type,
+                                input_hash,
+                                input_data,
+                            },
+                            debug_name,
+                            log_events,
+                        )
+                        .map_err(|e| e.to_string());
+                    write_message(stream, &JobWorkerRequest::EnqueueJobResult(result))?;
+                }
+                JobWorkerResponse::ArtifactHandleCreated {
+                    asset_id,
+                    artifact_id,
+                } => {
+                    job_api.artifact_handle_created(asset_id, artifact_id);
+                }
+                JobWorkerResponse::ProduceArtifact {
+                    asset_id,
+                    artifact_id,
+                    metadata,
+                    data,
+                    artifact_key_debug_name,
+                } => {
+                    let result = job_api.produce_artifact(BuiltArtifact {
+                        asset_id,
+                        artifact_id,
+                        metadata,
+                        data,
+                        artifact_key_debug_name,
+                    });
+                    if let Err(e) = result {
+                        produce_artifact_error.get_or_insert(e);
+                    }
+                }
+                JobWorkerResponse::FetchImportData { asset_id } => {
+                    let result = crate::import::load_import_data_bytes(
+                        job_api_impl.import_data_root_path(),
+                        asset_id,
+                    )
+                    .map_err(|e| e.to_string());
+                    write_message(stream, &JobWorkerRequest::ImportData(result))?;
+                }
+                JobWorkerResponse::ReportProgress { progress } => {
+                    job_api.report_progress(job_id, progress);
+                }
+                JobWorkerResponse::IsCancelled => {
+                    write_message(
+                        stream,
+                        &JobWorkerRequest::IsCancelled(job_api.is_cancelled()),
+                    )?;
+                }
+                JobWorkerResponse::JobComplete {
+                    output_data,
+                    fetched_asset_data: worker_fetched_asset_data,
+                    fetched_import_data: worker_fetched_import_data,
+                    log_events: worker_log_events,
+                } => {
+                    fetched_asset_data.extend(worker_fetched_asset_data);
+                    for (asset_id, contents_hash, metadata_hash) in worker_fetched_import_data {
+                        fetched_import_data.insert(
+                            asset_id,
+                            FetchedImportDataInfo {
+                                contents_hash,
+                                _metadata_hash: metadata_hash,
+                            },
+                        );
+                    }
+                    log_events.extend(worker_log_events);
+
+                    if let Some(e) = produce_artifact_error {
+                        return Ok(Err(e));
+                    }
+
+                    return Ok(output_data
+                        .map(Arc::new)
+                        .map_err(|e| PipelineError::StringError(e).into()));
+                }
+            }
+        }
+    }
+}
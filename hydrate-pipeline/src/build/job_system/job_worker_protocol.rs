@@ -0,0 +1,127 @@
+use super::job_system_traits::AssetDataRead;
+use super::{JobId, JobTypeId};
+use crate::{BuildLogEvent, BuildProfile, PipelineResult};
+use hydrate_base::{ArtifactId, AssetId, BuiltArtifactHeaderData};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+// Bump this if any message changes. Workers with a different version are turned away.
+pub(crate) const JOB_WORKER_PROTOCOL_VERSION: u32 = 2;
+
+// Messages are bincode, prefixed by their length. Anything larger than this is assumed to be a
+// corrupt stream rather than a real message. The largest messages are artifacts and the data set.
+const MAX_MESSAGE_SIZE_IN_BYTES: u64 = 1024 * 1024 * 1024;
+
+// Sent from the build to a worker
+#[derive(Serialize, Deserialize)]
+pub(crate) enum JobWorkerRequest {
+    // Sent before the first job of each build. The data set is a DataSetJson.
+    SyncDataSet {
+        build_id: u64,
+        data_set_json: String,
+    },
+    RunJob {
+        job_id: JobId,
+        job_type: JobTypeId,
+        input_data: Vec<u8>,
+        debug_name: String,
+        build_profile: BuildProfile,
+    },
+    // Replies to the worker's requests while a job runs
+    EnqueueJobResult(Result<JobId, String>),
+    // File contents and metadata hash of the import data
+    ImportData(Result<(Vec<u8>, u64), String>),
+    IsCancelled(bool),
+    // Sent instead of any other message if the worker's hello is not accepted, before the build
+    // closes the connection
+    Rejected(String),
+}
+
+// Sent from a worker to the build
+#[derive(Serialize, Deserialize)]
+pub(crate) enum JobWorkerResponse {
+    // First message after connecting. The build checks the worker has the same job processors.
+    // The token must match JobWorkerConfig::token, anything else that connects is turned away.
+    Hello {
+        protocol_version: u32,
+        token: String,
+        job_processors: Vec<(JobTypeId, u32)>,
+    },
+    // Sent as soon as a RunJob is received. If the connection fails before this arrives, the
+    // worker never saw the job and it can be sent to another worker.
+    JobStarted,
+    // Sent while a job runs, these are the same as the calls on JobApi
+    EnqueueJob {
+        job_type: JobTypeId,
+        input_hash: u128,
+        input_data: Vec<u8>,
+        debug_name: String,
+    },
+    ArtifactHandleCreated {
+        asset_id: AssetId,
+        artifact_id: ArtifactId,
+    },
+    ProduceArtifact {
+        asset_id: AssetId,
+        artifact_id: ArtifactId,
+        metadata: BuiltArtifactHeaderData,
+        data: Vec<u8>,
+        artifact_key_debug_name: Option<String>,
+    },
+    FetchImportData {
+        asset_id: AssetId,
+    },
+    ReportProgress {
+        progress: f32,
+    },
+    IsCancelled,
+    JobComplete {
+        output_data: Result<Vec<u8>, String>,
+        fetched_asset_data: Vec<(AssetDataRead, u64)>,
+        // Asset ID, contents hash and metadata hash of each import data the job read
+        fetched_import_data: Vec<(AssetId, u64, u64)>,
+        log_events: Vec<BuildLogEvent>,
+    },
+}
+
+pub(crate) fn write_message<T: Serialize, W: Write>(
+    writer: &mut W,
+    message: &T,
+) -> PipelineResult<()> {
+    let bytes = bincode::serialize(message)?;
+    write_message_bytes(writer, &bytes)
+}
+
+// Writes a message that was already serialized with bincode, for messages sent to many workers
+pub(crate) fn write_message_bytes<W: Write>(
+    writer: &mut W,
+    bytes: &[u8],
+) -> PipelineResult<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub(crate) fn read_message<T: DeserializeOwned, R: Read>(reader: &mut R) -> PipelineResult<T> {
+    let mut length_bytes = [0u8; 8];
+    reader.read_exact(&mut length_bytes)?;
+    let length = u64::from_le_bytes(length_bytes);
+    if length > MAX_MESSAGE_SIZE_IN_BYTES {
+        Err(format!(
+            "Job worker message is too large ({} bytes)",
+            length
+        ))?;
+    }
+
+    // Read rather than allocate the whole length up front, a bad length shouldn't allocate a huge
+    // buffer before failing
+    let mut bytes = Vec::default();
+    reader.take(length).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != length {
+        Err("Job worker connection closed in the middle of a message")?;
+    }
+
+    Ok(bincode::deserialize(&bytes)?)
+}
@@ -7,6 +7,20 @@ pub use executor::*;
 mod job_executor_thread_pool;
 use job_executor_thread_pool::*;
 
+mod job_cache;
+use job_cache::*;
+
+mod job_verification;
+pub use job_verification::*;
+
+mod job_worker_protocol;
+
+mod job_worker_pool;
+pub use job_worker_pool::*;
+
+mod job_worker;
+pub use job_worker::*;
+
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::hash::Hash;
@@ -0,0 +1,287 @@
+use crate::PipelineResult;
+use hydrate_base::hashing::HashMap;
+use hydrate_base::release_manifest::ReleaseManifest;
+use hydrate_base::{ArtifactId, DebugManifestFileJson};
+use std::fmt::Write;
+use std::path::Path;
+use uuid::Uuid;
+
+// An artifact listed in a manifest. Debug and symbol names are only available if the build wrote
+// a debug manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestArtifactInfo {
+    pub artifact_id: ArtifactId,
+    pub build_hash: u64,
+    pub combined_build_hash: u64,
+    pub artifact_type: Uuid,
+    pub symbol_hash: u128,
+    pub symbol_name: Option<String>,
+    pub debug_name: Option<String>,
+}
+
+impl ManifestArtifactInfo {
+    pub fn display_name(&self) -> String {
+        match &self.debug_name {
+            Some(debug_name) => debug_name.clone(),
+            None => format!("{:?}", self.artifact_id),
+        }
+    }
+}
+
+// Every artifact in a build's manifest
+#[derive(Debug, Default)]
+pub struct ManifestContents {
+    pub artifacts: HashMap<ArtifactId, ManifestArtifactInfo>,
+}
+
+impl ManifestContents {
+    pub fn from_debug_manifest(debug_manifest: &DebugManifestFileJson) -> PipelineResult<Self> {
+        let invalid_hash =
+            |hash: &str| format!("Debug manifest contains an invalid hash {:?}", hash);
+        let parse_hash = |hash: &str| u64::from_str_radix(hash, 16).map_err(|_| invalid_hash(hash));
+
+        let mut artifacts = HashMap::default();
+        for artifact in &debug_manifest.artifacts {
+            artifacts.insert(
+                artifact.artifact_id,
+                ManifestArtifactInfo {
+                    artifact_id: artifact.artifact_id,
+                    build_hash: parse_hash(&artifact.build_hash)?,
+                    combined_build_hash: parse_hash(&artifact.combined_build_hash)?,
+                    artifact_type: artifact.artifact_type,
+                    symbol_hash: u128::from_str_radix(&artifact.symbol_hash, 16)
+                        .map_err(|_| invalid_hash(&artifact.symbol_hash))?,
+                    symbol_name: Some(artifact.symbol_name.clone()).filter(|x| !x.is_empty()),
+                    debug_name: Some(artifact.debug_name.clone()),
+                },
+            );
+        }
+
+        Ok(ManifestContents { artifacts })
+    }
+
+    pub fn from_release_manifest<T: AsRef<[u8]>>(release_manifest: &ReleaseManifest<T>) -> Self {
+        let artifacts = release_manifest
+            .iter()
+            .map(|entry| {
+                (
+                    entry.artifact_id,
+                    ManifestArtifactInfo {
+                        artifact_id: entry.artifact_id,
+                        build_hash: entry.build_hash,
+                        combined_build_hash: entry.combined_build_hash,
+                        artifact_type: entry.artifact_type,
+                        symbol_hash: entry.symbol_hash,
+                        symbol_name: None,
+                        debug_name: None,
+                    },
+                )
+            })
+            .collect();
+
+        ManifestContents { artifacts }
+    }
+
+    // Loads a .manifest_release or .manifest_debug file. The debug manifest next to a release
+    // manifest is used for names if it exists.
+    pub fn load_file(path: &Path) -> PipelineResult<Self> {
+        let extension = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default();
+        match extension {
+            "manifest_debug" => {
+                let debug_manifest: DebugManifestFileJson =
+                    serde_json::from_str(&std::fs::read_to_string(path)?)?;
+                Self::from_debug_manifest(&debug_manifest)
+            }
+            "manifest_release" => {
+                let release_manifest = ReleaseManifest::new(std::fs::read(path)?)
+                    .map_err(|e| format!("Could not read manifest {:?}: {}", path, e))?;
+                let mut contents = Self::from_release_manifest(&release_manifest);
+
+                let debug_manifest_path = path.with_extension("manifest_debug");
+                if debug_manifest_path.exists() {
+                    let debug_contents = Self::load_file(&debug_manifest_path)?;
+                    for (artifact_id, artifact) in &mut contents.artifacts {
+                        if let Some(debug_artifact) = debug_contents.artifacts.get(artifact_id) {
+                            artifact.symbol_name = debug_artifact.symbol_name.clone();
+                            artifact.debug_name = debug_artifact.debug_name.clone();
+                        }
+                    }
+                }
+
+                Ok(contents)
+            }
+            _ => Err(format!("{:?} is not a manifest file", path))?,
+        }
+    }
+
+    // Returns the manifest build hash in the newest TOC, which is the build the game loads
+    pub fn find_latest_manifest_build_hash(build_data_path: &Path) -> PipelineResult<u64> {
+        let toc_dir_path = build_data_path.join("toc");
+        let mut latest_toc = None;
+        if toc_dir_path.exists() {
+            for entry in std::fs::read_dir(&toc_dir_path)? {
+                let toc_path = entry?.path();
+                let timestamp = toc_path
+                    .file_name()
+                    .and_then(|x| x.to_str())
+                    .and_then(|x| x.strip_suffix(".toc"))
+                    .and_then(|x| u64::from_str_radix(x, 16).ok());
+                if let Some(timestamp) = timestamp {
+                    if latest_toc
+                        .as_ref()
+                        .map(|(x, _)| timestamp > *x)
+                        .unwrap_or(true)
+                    {
+                        latest_toc = Some((timestamp, toc_path));
+                    }
+                }
+            }
+        }
+
+        let (_, toc_path) =
+            latest_toc.ok_or_else(|| format!("No TOC files found in {:?}", toc_dir_path))?;
+        Ok(
+            u64::from_str_radix(std::fs::read_to_string(&toc_path)?.trim(), 16).map_err(|_| {
+                format!("TOC {:?} does not contain a manifest build hash", toc_path)
+            })?,
+        )
+    }
+
+    // Loads the manifest of a build. The manifest build hash is the one in the build's TOC file.
+    pub fn load_build(
+        build_data_path: &Path,
+        manifest_build_hash: u64,
+    ) -> PipelineResult<Self> {
+        Self::load_file(
+            &build_data_path
+                .join("manifests")
+                .join(format!("{:0>16x}.manifest_release", manifest_build_hash)),
+        )
+    }
+}
+
+// The differences between the manifests of two builds
+#[derive(Debug, Default)]
+pub struct ManifestDiff {
+    pub added: Vec<ManifestArtifactInfo>,
+    pub removed: Vec<ManifestArtifactInfo>,
+    // The artifact's own data changed (the build hash is different). Pairs are (old, new).
+    pub content_changed: Vec<(ManifestArtifactInfo, ManifestArtifactInfo)>,
+    // The artifact is the same but something it depends on changed (only the combined build hash
+    // is different). Pairs are (old, new).
+    pub dependency_changed: Vec<(ManifestArtifactInfo, ManifestArtifactInfo)>,
+}
+
+impl ManifestDiff {
+    pub fn new(
+        old: &ManifestContents,
+        new: &ManifestContents,
+    ) -> Self {
+        let mut diff = ManifestDiff::default();
+        for (artifact_id, old_artifact) in &old.artifacts {
+            match new.artifacts.get(artifact_id) {
+                None => diff.removed.push(old_artifact.clone()),
+                Some(new_artifact) => {
+                    if old_artifact.build_hash != new_artifact.build_hash {
+                        diff.content_changed
+                            .push((old_artifact.clone(), new_artifact.clone()));
+                    } else if old_artifact.combined_build_hash != new_artifact.combined_build_hash {
+                        diff.dependency_changed
+                            .push((old_artifact.clone(), new_artifact.clone()));
+                    }
+                }
+            }
+        }
+
+        for (artifact_id, new_artifact) in &new.artifacts {
+            if !old.artifacts.contains_key(artifact_id) {
+                diff.added.push(new_artifact.clone());
+            }
+        }
+
+        // Sorted so that the output is stable and easy to read
+        let sort_key = |x: &ManifestArtifactInfo| (x.display_name(), x.artifact_id.as_u128());
+        diff.added.sort_by_key(sort_key);
+        diff.removed.sort_by_key(sort_key);
+        diff.content_changed.sort_by_key(|x| sort_key(&x.1));
+        diff.dependency_changed.sort_by_key(|x| sort_key(&x.1));
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.content_changed.is_empty()
+            && self.dependency_changed.is_empty()
+    }
+
+    pub fn to_text(&self) -> String {
+        fn write_artifact(
+            text: &mut String,
+            artifact: &ManifestArtifactInfo,
+        ) {
+            // Writing to a String can't fail
+            write!(
+                text,
+                "  {} [{:?}] type: {}",
+                artifact.display_name(),
+                artifact.artifact_id,
+                artifact.artifact_type
+            )
+            .unwrap();
+            if let Some(symbol_name) = &artifact.symbol_name {
+                write!(text, " symbol: {}", symbol_name).unwrap();
+            }
+        }
+
+        let mut text = String::default();
+        writeln!(
+            text,
+            "{} added, {} removed, {} changed, {} with changed dependencies",
+            self.added.len(),
+            self.removed.len(),
+            self.content_changed.len(),
+            self.dependency_changed.len()
+        )
+        .unwrap();
+
+        writeln!(text, "\nAdded:").unwrap();
+        for artifact in &self.added {
+            write_artifact(&mut text, artifact);
+            writeln!(text, " build hash: {:0>16x}", artifact.build_hash).unwrap();
+        }
+
+        writeln!(text, "\nRemoved:").unwrap();
+        for artifact in &self.removed {
+            write_artifact(&mut text, artifact);
+            writeln!(text, " build hash: {:0>16x}", artifact.build_hash).unwrap();
+        }
+
+        writeln!(text, "\nChanged:").unwrap();
+        for (old, new) in &self.content_changed {
+            write_artifact(&mut text, new);
+            writeln!(
+                text,
+                " build hash: {:0>16x} -> {:0>16x}",
+                old.build_hash, new.build_hash
+            )
+            .unwrap();
+        }
+
+        writeln!(text, "\nDependencies changed:").unwrap();
+        for (old, new) in &self.dependency_changed {
+            write_artifact(&mut text, new);
+            writeln!(
+                text,
+                " combined build hash: {:0>16x} -> {:0>16x}",
+                old.combined_build_hash, new.combined_build_hash
+            )
+            .unwrap();
+        }
+
+        text
+    }
+}
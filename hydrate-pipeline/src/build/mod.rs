@@ -7,5 +7,26 @@ pub use build_jobs::*;
 mod build_types;
 pub use build_types::*;
 
+mod build_timing;
+pub use build_timing::*;
+
+mod build_graph;
+pub use build_graph::*;
+
+mod build_output_gc;
+pub use build_output_gc::*;
+
+mod manifest_diff;
+pub use manifest_diff::*;
+
+mod build_patch;
+pub use build_patch::*;
+
+mod artifact_inspector;
+pub use artifact_inspector::*;
+
 mod builder_registry;
 pub use builder_registry::*;
+
+mod asset_validation;
+pub use asset_validation::*;
@@ -14,7 +14,7 @@ use crate::import::import_thread_pool::{
 use crate::import::import_util::RequestedImportable;
 use crate::{
     DynEditorModel, HydrateProjectConfiguration, ImportJobToQueue, ImportLogData, ImportLogEvent,
-    LogEventLevel, PipelineResult,
+    LogEventLevel, PipelineError, PipelineEvents, PipelineResult,
 };
 use hydrate_base::uuid_path::{path_to_uuid, uuid_to_path};
 use hydrate_data::ImportableName;
@@ -47,6 +47,48 @@ pub fn load_import_data(
     })
 }
 
+// Reads the import data file without parsing it, so that it can be sent to a job worker process.
+// Returns the file contents and the hash of the file's metadata.
+pub(crate) fn load_import_data_bytes(
+    import_data_root_path: &Path,
+    asset_id: AssetId,
+) -> PipelineResult<(Vec<u8>, u64)> {
+    let path = uuid_to_path(import_data_root_path, asset_id.as_uuid(), "if");
+    let bytes = std::fs::read(&path)?;
+    let metadata_hash = hash_file_metadata(&path.metadata()?);
+    Ok((bytes, metadata_hash))
+}
+
+// Parses import data returned by load_import_data_bytes
+pub(crate) fn load_import_data_from_bytes(
+    schema_set: &SchemaSet,
+    bytes: &[u8],
+    metadata_hash: u64,
+) -> PipelineResult<ImportData> {
+    let import_data = super::import_storage::load_import_data_from_b3f(
+        schema_set,
+        &mut std::io::Cursor::new(bytes),
+    )?;
+
+    Ok(ImportData {
+        import_data: import_data.single_object,
+        contents_hash: import_data.metadata.import_data_contents_hash,
+        metadata_hash,
+    })
+}
+
+// Reads only the header of the import data file, this is much cheaper than loading the import data
+pub(crate) fn load_import_data_contents_hash(
+    import_data_root_path: &Path,
+    asset_id: AssetId,
+) -> PipelineResult<u64> {
+    let path = uuid_to_path(import_data_root_path, asset_id.as_uuid(), "if");
+    let file = std::fs::File::open(&path)?;
+    let mut buf_reader = BufReader::new(file);
+    let metadata = super::import_storage::load_import_metadata_from_b3f(&mut buf_reader)?;
+    Ok(metadata.import_data_contents_hash)
+}
+
 pub(super) fn hash_file_metadata(metadata: &std::fs::Metadata) -> u64 {
     let mut hasher = siphasher::sip::SipHasher::default();
     metadata.modified().unwrap().hash(&mut hasher);
@@ -128,6 +170,7 @@ pub struct ImportJobs {
     import_jobs: HashMap<AssetId, ImportJob>,
     import_operations: VecDeque<ImportJobToQueue>,
     current_import_task: Option<ImportTask>,
+    pipeline_events: PipelineEvents,
 }
 
 impl ImportJobs {
@@ -151,6 +194,20 @@ impl ImportJobs {
         self.current_import_task.is_some()
     }
 
+    // True if an import is running or queued. Import data may be written before the assets it
+    // belongs to exist.
+    pub fn has_pending_imports(&self) -> bool {
+        self.current_import_task.is_some() || !self.import_operations.is_empty()
+    }
+
+    // Source files that haven't started importing are skipped. Files that are being imported are
+    // allowed to finish and are committed as usual. Queued import operations are not affected.
+    pub fn cancel(&mut self) {
+        if let Some(current_import_task) = &self.current_import_task {
+            current_import_task.thread_pool.cancel();
+        }
+    }
+
     pub fn import_data_root_path(&self) -> &Path {
         &self.import_data_root_path
     }
@@ -160,6 +217,7 @@ impl ImportJobs {
         importer_registry: &ImporterRegistry,
         editor_model: &dyn DynEditorModel,
         import_data_root_path: &Path,
+        pipeline_events: PipelineEvents,
     ) -> Self {
         let import_jobs =
             ImportJobs::find_all_jobs(importer_registry, editor_model, import_data_root_path);
@@ -170,6 +228,7 @@ impl ImportJobs {
             import_jobs,
             import_operations: Default::default(),
             current_import_task: None,
+            pipeline_events,
         }
     }
 
@@ -262,6 +321,7 @@ impl ImportJobs {
             &self.import_data_root_path,
             thread_count,
             result_tx,
+            self.pipeline_events.clone(),
         );
 
         //
@@ -346,6 +406,7 @@ impl ImportJobs {
             //
             // Commit the imports
             //
+            let mut cancelled_import_count = 0;
             for outcome in finished_import_task.result_rx.try_iter() {
                 match outcome {
                     ImportThreadOutcome::Complete(msg) => match msg.result {
@@ -367,6 +428,19 @@ impl ImportJobs {
                                 }
                             }
                         }
+                        Err(e) if matches!(e.error, PipelineError::Cancelled) => {
+                            cancelled_import_count += 1;
+                            finished_import_task
+                                .log_data
+                                .log_events
+                                .push(ImportLogEvent {
+                                    path: msg.request.import_op.path.clone(),
+                                    asset_id: None,
+                                    level: LogEventLevel::Warning,
+                                    message: "Import was cancelled before this file was imported"
+                                        .to_string(),
+                                })
+                        }
                         Err(e) => finished_import_task
                             .log_data
                             .log_events
@@ -380,6 +454,13 @@ impl ImportJobs {
                 }
             }
 
+            if cancelled_import_count > 0 {
+                log::warn!(
+                    "Import was cancelled, {} source files were not imported",
+                    cancelled_import_count
+                );
+            }
+
             return Ok(ImportStatus::Completed(Arc::new(
                 finished_import_task.log_data,
             )));
@@ -181,7 +181,7 @@ pub fn save_single_object_to_b3f<W: std::io::Write>(
     metadata: &ImportDataMetadata,
     schema_set: &SchemaSet,
     default_asset: &SingleObject,
-) {
+) -> std::io::Result<()> {
     let mut b3f_writer = b3f::B3FWriter::new_from_u8_tag(*b"HYIF", 1);
 
     //
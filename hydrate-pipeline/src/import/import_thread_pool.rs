@@ -1,6 +1,9 @@
 use crate::import::import_storage::ImportDataMetadata;
 use crate::import::{ImportContext, ImportOp, ImportType, ImportableAsset};
-use crate::{HydrateProjectConfiguration, ImporterRegistry, PipelineResult};
+use crate::{
+    HydrateProjectConfiguration, ImporterRegistry, PipelineError, PipelineEvent, PipelineEvents,
+    PipelineResult,
+};
 use crossbeam_channel::{Receiver, Sender};
 use hydrate_base::hashing::HashMap;
 use hydrate_base::uuid_path::uuid_to_path;
@@ -9,7 +12,7 @@ use hydrate_data::{ImportInfo, ImportableName, PathReference, SchemaSet, SingleO
 use std::hash::{Hash, Hasher};
 use std::io::BufWriter;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread::JoinHandle;
 use std::time::SystemTime;
@@ -240,7 +243,7 @@ fn do_import(
                     &import_data_metadata,
                     schema_set,
                     &imported_asset.default_asset,
-                );
+                )?;
 
                 let data_to_write = buf_writer
                     .into_inner()
@@ -335,6 +338,8 @@ impl ImportWorkerThread {
         request_rx: Receiver<ImportThreadRequest>,
         outcome_tx: Sender<ImportThreadOutcome>,
         active_request_count: Arc<AtomicUsize>,
+        cancelled: Arc<AtomicBool>,
+        pipeline_events: PipelineEvents,
         _thread_index: usize,
     ) -> Self {
         let (finish_tx, finish_rx) = crossbeam_channel::bounded(1);
@@ -347,6 +352,22 @@ impl ImportWorkerThread {
                         recv(request_rx) -> msg => {
                             match msg.unwrap() {
                                 ImportThreadRequest::RequestImport(msg) => {
+                                    // Requests that were queued before the import was cancelled
+                                    // are returned without doing any work
+                                    if cancelled.load(Ordering::Relaxed) {
+                                        outcome_tx.send(ImportThreadOutcome::Complete(ImportThreadOutcomeComplete {
+                                            request: msg,
+                                            result: Err(PipelineError::Cancelled.into()),
+                                        })).unwrap();
+                                        active_request_count.fetch_sub(1, Ordering::Release);
+                                        continue;
+                                    }
+
+                                    let start_instant = std::time::Instant::now();
+                                    pipeline_events.send(PipelineEvent::ImportStarted {
+                                        path: msg.import_op.path.clone(),
+                                    });
+
                                     let result = std::panic::catch_unwind(|| {
                                         profiling::scope!("ImportThreadRequest::RequestImport");
                                         do_import(
@@ -359,6 +380,12 @@ impl ImportWorkerThread {
                                         )
                                     });
 
+                                    pipeline_events.send(PipelineEvent::ImportFinished {
+                                        path: msg.import_op.path.clone(),
+                                        duration: start_instant.elapsed(),
+                                        succeeded: matches!(&result, Ok(Ok(_))),
+                                    });
+
                                     match result {
                                         Ok(result) => {
                                             outcome_tx.send(ImportThreadOutcome::Complete(ImportThreadOutcomeComplete {
@@ -398,6 +425,7 @@ pub struct ImportWorkerThreadPool {
     worker_threads: Vec<ImportWorkerThread>,
     request_tx: Sender<ImportThreadRequest>,
     active_request_count: Arc<AtomicUsize>,
+    cancelled: Arc<AtomicBool>,
 }
 
 impl ImportWorkerThreadPool {
@@ -409,10 +437,12 @@ impl ImportWorkerThreadPool {
         import_data_root_path: &Path,
         max_requests_in_flight: usize,
         result_tx: Sender<ImportThreadOutcome>,
+        pipeline_events: PipelineEvents,
     ) -> Self {
         let import_data_root_path = Arc::new(import_data_root_path.to_path_buf());
         let (request_tx, request_rx) = crossbeam_channel::unbounded::<ImportThreadRequest>();
         let active_request_count = Arc::new(AtomicUsize::new(0));
+        let cancelled = Arc::new(AtomicBool::new(false));
 
         let mut worker_threads = Vec::with_capacity(max_requests_in_flight);
         for thread_index in 0..max_requests_in_flight {
@@ -425,6 +455,8 @@ impl ImportWorkerThreadPool {
                 request_rx.clone(),
                 result_tx.clone(),
                 active_request_count.clone(),
+                cancelled.clone(),
+                pipeline_events.clone(),
                 thread_index,
             );
             worker_threads.push(worker);
@@ -434,9 +466,16 @@ impl ImportWorkerThreadPool {
             request_tx,
             worker_threads,
             active_request_count,
+            cancelled,
         }
     }
 
+    // Requests that haven't been picked up by a thread complete immediately with
+    // PipelineError::Cancelled
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
     pub fn is_idle(&self) -> bool {
         self.active_request_count() == 0
     }
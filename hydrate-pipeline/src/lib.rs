@@ -1,3 +1,5 @@
+use crossbeam_channel::Receiver;
+use std::net::SocketAddr;
 use std::sync::Arc;
 
 pub use hydrate_schema::*;
@@ -20,13 +22,21 @@ pub use import::{
     ScanContext, ScannedImportable,
 };
 
-pub use project::{HydrateProjectConfiguration, NamePathPair};
+pub use project::{BuildProfile, HydrateProjectConfiguration, NamePathPair};
 
 pub use crate::build::{
-    AssetArtifactIdPair, BuildJobs, BuildStatus, BuildStatusBuilding, Builder, BuilderContext,
-    BuilderRegistry, BuilderRegistryBuilder, EnumerateDependenciesContext, HandleFactory,
-    JobEnumeratedDependencies, JobId, JobInput, JobOutput, JobProcessor, JobProcessorRegistry,
-    JobProcessorRegistryBuilder, RunContext,
+    build_chrome_trace_json, delete_build_output_garbage, find_build_output_garbage,
+    inspect_built_artifact, run_job_worker, validate_assets, write_build_patch,
+    ArtifactDecoderRegistry, AssetArtifactIdPair, AssetValidationIssue, AssetValidationReport,
+    BuildGraph, BuildGraphArtifact, BuildGraphAsset, BuildGraphJob, BuildJobs,
+    BuildOutputGarbageReport, BuildPatchReport, BuildRoots, BuildStatus, BuildStatusBuilding,
+    BuildTimingReport, BuildVerification, BuildVerificationMode, Builder, BuilderContext,
+    BuilderRegistry, BuilderRegistryBuilder, BuiltArtifactFile, DeterminismMismatch,
+    EnumerateDependenciesContext, HandleFactory, JobEnumeratedDependencies, JobId, JobInput,
+    JobOutput, JobProcessor, JobProcessorRegistry, JobProcessorRegistryBuilder, JobRequestor,
+    JobTiming, JobTypeId, JobTypeTiming, JobWorkerCommand, JobWorkerConfig, ManifestArtifactInfo,
+    JOB_WORKER_TOKEN_ENV_VAR,
+    ManifestContents, ManifestDiff, RunContext, ValidationContext,
 };
 pub use pipeline_error::*;
 
@@ -34,6 +44,9 @@ mod log_events;
 mod uuid_newtype;
 pub use log_events::*;
 
+mod pipeline_events;
+pub use pipeline_events::*;
+
 pub struct AssetPluginRegistries {
     pub importer_registry: ImporterRegistry,
     pub builder_registry: BuilderRegistry,
@@ -123,6 +136,10 @@ pub trait DynEditorModel {
         &self,
         asset_id: AssetId,
     ) -> String;
+
+    // True while an edit is still in progress (like a slider being dragged) and hasn't been
+    // committed to the undo history yet
+    fn has_pending_edits(&self) -> bool;
 }
 
 pub trait DynEditContext {
@@ -145,6 +162,7 @@ pub struct AssetEngine {
     builder_registry: BuilderRegistry,
     build_jobs: BuildJobs,
     thumbnail_system: ThumbnailSystem,
+    pipeline_events: PipelineEvents,
 }
 
 impl AssetEngine {
@@ -154,11 +172,14 @@ impl AssetEngine {
         editor_model: &dyn DynEditorModel,
         project_configuration: &HydrateProjectConfiguration,
     ) -> Self {
+        let pipeline_events = PipelineEvents::default();
+
         let import_jobs = ImportJobs::new(
             project_configuration,
             &registries.importer_registry,
             editor_model,
             &project_configuration.import_data_path,
+            pipeline_events.clone(),
         );
 
         let build_jobs = BuildJobs::new(
@@ -167,6 +188,8 @@ impl AssetEngine {
             project_configuration.import_data_path.clone(),
             project_configuration.job_data_path.clone(),
             project_configuration.build_data_path.clone(),
+            project_configuration.build_profiles.clone(),
+            pipeline_events.clone(),
         );
 
         let thumbnail_system = ThumbnailSystem::new(
@@ -183,6 +206,7 @@ impl AssetEngine {
             builder_registry: registries.builder_registry,
             build_jobs,
             thumbnail_system,
+            pipeline_events,
         }
     }
 
@@ -263,6 +287,22 @@ impl AssetEngine {
         }
     }
 
+    // Returns a stream of job and import start/finish events. Events are only sent while the
+    // receiver exists, so drop it when it's no longer needed.
+    pub fn subscribe_pipeline_events(&self) -> Receiver<PipelineEvent> {
+        self.pipeline_events.subscribe()
+    }
+
+    // Stops the in-flight import. Files that were already imported are kept.
+    pub fn cancel_import(&mut self) {
+        self.import_jobs.cancel();
+    }
+
+    // Stops the in-flight build. No manifest is written for a cancelled build.
+    pub fn cancel_build(&mut self) {
+        self.build_jobs.cancel();
+    }
+
     pub fn thumbnail_system_state(&self) -> &ThumbnailSystemState {
         self.thumbnail_system.system_state()
     }
@@ -296,6 +336,10 @@ impl AssetEngine {
         self.import_jobs.queue_import_operation(import_job_to_queue);
     }
 
+    pub fn has_pending_imports(&self) -> bool {
+        self.import_jobs.has_pending_imports()
+    }
+
     pub fn queue_build_asset(
         &mut self,
         asset_id: AssetId,
@@ -311,6 +355,108 @@ impl AssetEngine {
         self.build_jobs.build();
     }
 
+    pub fn auto_build_debounce(&self) -> Option<std::time::Duration> {
+        self.build_jobs.auto_build_debounce()
+    }
+
+    // While set, edits and finished imports are built automatically once nothing has changed for
+    // the debounce duration. Only the assets that changed and the assets that read them are
+    // rebuilt. None turns auto builds off.
+    pub fn set_auto_build_debounce(
+        &mut self,
+        auto_build_debounce: Option<std::time::Duration>,
+    ) {
+        self.build_jobs.set_auto_build_debounce(auto_build_debounce);
+    }
+
+    pub fn build_profiles(&self) -> &[Arc<BuildProfile>] {
+        self.build_jobs.build_profiles()
+    }
+
+    pub fn active_build_profile(&self) -> &BuildProfile {
+        self.build_jobs.active_build_profile()
+    }
+
+    // Selects the build profile for builds started after this call
+    pub fn set_active_build_profile(
+        &mut self,
+        name: &str,
+    ) -> PipelineResult<()> {
+        self.build_jobs.set_active_build_profile(name)
+    }
+
+    pub fn build_verification(&self) -> Option<&BuildVerification> {
+        self.build_jobs.build_verification()
+    }
+
+    // Makes builds started after this call check that jobs are deterministic
+    pub fn set_build_verification(
+        &mut self,
+        build_verification: Option<BuildVerification>,
+    ) {
+        self.build_jobs.set_build_verification(build_verification);
+    }
+
+    // Runs jobs in separate processes so that a crashing job processor can't take down the editor
+    pub fn start_job_workers(
+        &mut self,
+        job_worker_config: JobWorkerConfig,
+    ) -> PipelineResult<SocketAddr> {
+        self.build_jobs.start_job_workers(job_worker_config)
+    }
+
+    pub fn stop_job_workers(&mut self) {
+        self.build_jobs.stop_job_workers();
+    }
+
+    pub fn job_worker_address(&self) -> Option<SocketAddr> {
+        self.build_jobs.job_worker_address()
+    }
+
+    // Jobs for these assets start ahead of other jobs, for example so that the assets the user
+    // selected are built first
+    pub fn set_prioritized_assets(
+        &mut self,
+        asset_ids: &HashSet<AssetId>,
+    ) {
+        self.build_jobs.set_prioritized_assets(asset_ids);
+    }
+
+    // Limits how many jobs of type T run at the same time, for example to keep memory-hungry jobs
+    // from running on every thread at once. None removes the limit.
+    pub fn set_max_concurrent_jobs<T: JobProcessor>(
+        &self,
+        max_concurrent_jobs: Option<usize>,
+    ) {
+        self.build_jobs
+            .set_max_concurrent_jobs(JobTypeId::from_bytes(T::UUID), max_concurrent_jobs);
+    }
+
+    // Runs builders' validation over the given assets with the active build profile. Nothing is
+    // built.
+    pub fn validate_assets(
+        &self,
+        data_set: &DataSet,
+        schema_set: &SchemaSet,
+        asset_ids: impl IntoIterator<Item = AssetId>,
+    ) -> AssetValidationReport {
+        validate_assets(
+            &self.builder_registry,
+            data_set,
+            schema_set,
+            self.build_jobs.active_build_profile(),
+            asset_ids,
+        )
+    }
+
+    // Builds only the given assets and anything they depend on
+    pub fn queue_build_roots(
+        &mut self,
+        build_roots: BuildRoots,
+    ) {
+        self.build_jobs.build_roots(build_roots);
+    }
+
     pub fn duplicate_import_data(
         &self,
         old_asset_id: AssetId,
@@ -1,12 +1,13 @@
-use crate::build::JobRequestor;
+use crate::build::{DeterminismMismatch, JobRequestor, JobTiming, WrittenArtifact};
 use crate::JobId;
 use hydrate_base::hashing::{HashMap, HashSet};
 use hydrate_base::AssetId;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::sync::Arc;
 use uuid::Uuid;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum LogEventLevel {
     Warning,
     Error,
@@ -98,7 +99,7 @@ impl Default for ImportLogData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildLogEvent {
     pub asset_id: Option<AssetId>,
     pub job_id: Option<JobId>,
@@ -113,6 +114,12 @@ pub struct BuildLogData {
     pub(crate) start_time: std::time::SystemTime,
     pub(crate) log_events: Vec<BuildLogEvent>,
     pub(crate) requestors: HashMap<JobId, Vec<JobRequestor>>,
+    pub(crate) job_timings: HashMap<JobId, JobTiming>,
+    pub(crate) written_artifacts: HashMap<JobId, Vec<WrittenArtifact>>,
+    // Assets whose data or import data each job read, including jobs that used a cached result
+    pub(crate) assets_read: HashMap<JobId, Vec<AssetId>>,
+    // Only populated in verification builds
+    pub(crate) determinism_mismatches: Vec<DeterminismMismatch>,
 }
 
 impl Default for BuildLogData {
@@ -124,6 +131,10 @@ impl Default for BuildLogData {
             start_time: std::time::SystemTime::now(),
             log_events: vec![],
             requestors: Default::default(),
+            job_timings: Default::default(),
+            written_artifacts: Default::default(),
+            assets_read: Default::default(),
+            determinism_mismatches: Default::default(),
         }
     }
 }
@@ -133,20 +144,57 @@ impl BuildLogData {
         &self.log_events
     }
 
+    pub fn determinism_mismatches(&self) -> &[DeterminismMismatch] {
+        &self.determinism_mismatches
+    }
+
+    pub fn job_timings(&self) -> &HashMap<JobId, JobTiming> {
+        &self.job_timings
+    }
+
+    // Artifacts produced by each job, including jobs that reused a cached result
+    pub fn written_artifacts(&self) -> &HashMap<JobId, Vec<WrittenArtifact>> {
+        &self.written_artifacts
+    }
+
+    pub fn requestors(
+        &self,
+        job_id: JobId,
+    ) -> &[JobRequestor] {
+        self.requestors
+            .get(&job_id)
+            .map(|x| x.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn assets_read(
+        &self,
+        job_id: JobId,
+    ) -> &[AssetId] {
+        self.assets_read
+            .get(&job_id)
+            .map(|x| x.as_slice())
+            .unwrap_or_default()
+    }
+
     pub fn assets_relying_on_job(
         &self,
         job_id: JobId,
     ) -> Vec<AssetId> {
         let mut assets = vec![];
-        let checked_requestors = HashSet::<JobId>::default();
+        let mut checked_requestors = HashSet::<JobId>::default();
         let mut requestor_check_queue = vec![job_id];
 
         while let Some(requestor) = requestor_check_queue.pop() {
-            for requestor in self.requestors.get(&requestor).unwrap() {
+            let Some(requestors) = self.requestors.get(&requestor) else {
+                continue;
+            };
+
+            for requestor in requestors {
                 match requestor {
                     JobRequestor::Builder(asset_id) => assets.push(*asset_id),
                     JobRequestor::Job(job_id) => {
-                        if !checked_requestors.contains(job_id) {
+                        if checked_requestors.insert(*job_id) {
                             requestor_check_queue.push(*job_id)
                         }
                     }
@@ -156,4 +204,30 @@ impl BuildLogData {
 
         assets
     }
+
+    // Assets that had an error or fatal error, either directly or in a job that was run on their
+    // behalf. Artifacts from these assets may be missing from the manifest.
+    pub fn failed_assets(&self) -> Vec<AssetId> {
+        let mut failed_assets = HashSet::<AssetId>::default();
+        for log_event in &self.log_events {
+            if !matches!(
+                log_event.level,
+                LogEventLevel::Error | LogEventLevel::FatalError
+            ) {
+                continue;
+            }
+
+            if let Some(asset_id) = log_event.asset_id {
+                failed_assets.insert(asset_id);
+            }
+
+            if let Some(job_id) = log_event.job_id {
+                failed_assets.extend(self.assets_relying_on_job(job_id));
+            }
+        }
+
+        let mut failed_assets: Vec<_> = failed_assets.into_iter().collect();
+        failed_assets.sort();
+        failed_assets
+    }
 }
@@ -12,6 +12,8 @@ pub enum PipelineError {
     JsonError(Arc<serde_json::Error>),
     UuidError(uuid::Error),
     ThumbnailUnavailable,
+    // The import or build was cancelled before the work was done
+    Cancelled,
 }
 
 impl std::error::Error for PipelineError {
@@ -25,6 +27,7 @@ impl std::error::Error for PipelineError {
             PipelineError::JsonError(ref e) => Some(&**e),
             PipelineError::UuidError(ref e) => Some(e),
             PipelineError::ThumbnailUnavailable => None,
+            PipelineError::Cancelled => None,
         }
     }
 }
@@ -49,6 +52,7 @@ impl core::fmt::Display for PipelineError {
             PipelineError::JsonError(ref e) => e.fmt(fmt),
             PipelineError::UuidError(ref e) => e.fmt(fmt),
             PipelineError::ThumbnailUnavailable => "ThumbnailUnavailable".fmt(fmt),
+            PipelineError::Cancelled => "Cancelled".fmt(fmt),
         }
     }
 }
@@ -0,0 +1,60 @@
+use crate::JobId;
+use crossbeam_channel::{Receiver, Sender};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Fine-grained progress of imports and builds. Unlike log events, these are not kept after the
+// task completes. They are intended for progress UI and timing reports.
+#[derive(Debug, Clone)]
+pub enum PipelineEvent {
+    ImportStarted {
+        path: PathBuf,
+    },
+    ImportFinished {
+        path: PathBuf,
+        duration: Duration,
+        succeeded: bool,
+    },
+    JobStarted {
+        job_id: JobId,
+        debug_name: Arc<String>,
+    },
+    // Sent by long-running jobs through RunContext::report_progress. Progress is in the range 0..=1
+    JobProgress {
+        job_id: JobId,
+        progress: f32,
+    },
+    JobFinished {
+        job_id: JobId,
+        debug_name: Arc<String>,
+        duration: Duration,
+        used_cached_result: bool,
+        succeeded: bool,
+    },
+}
+
+// Sends pipeline events to everything that subscribed. Cloned into worker threads. If there are no
+// subscribers, events are dropped.
+#[derive(Default, Clone)]
+pub struct PipelineEvents {
+    subscribers: Arc<Mutex<Vec<Sender<PipelineEvent>>>>,
+}
+
+impl PipelineEvents {
+    pub fn subscribe(&self) -> Receiver<PipelineEvent> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    pub(crate) fn send(
+        &self,
+        event: PipelineEvent,
+    ) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        // Subscribers that dropped their receiver are removed
+        subscribers.retain(|x| x.send(event.clone()).is_ok());
+    }
+}
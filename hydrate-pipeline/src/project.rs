@@ -1,5 +1,6 @@
 use hydrate_data::PathReferenceNamespaceResolver;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::path::{Path, PathBuf};
 
@@ -17,16 +18,27 @@ pub struct SchemaCodegenJobsJson {
     outfile: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct BuildProfileJson {
+    pub name: String,
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct HydrateProjectConfigurationJson {
     pub schema_def_paths: Vec<String>,
     pub import_data_path: String,
     pub build_data_path: String,
     pub job_data_path: String,
+    #[serde(default)]
+    pub undo_history_path: Option<String>,
     pub id_based_asset_sources: Vec<NamePathPairJson>,
     pub path_based_asset_sources: Vec<NamePathPairJson>,
     pub source_file_locations: Vec<NamePathPairJson>,
     pub schema_codegen_jobs: Vec<SchemaCodegenJobsJson>,
+    #[serde(default)]
+    pub build_profiles: Vec<BuildProfileJson>,
 }
 
 #[derive(Debug, Clone)]
@@ -43,6 +55,39 @@ pub struct SchemaCodegenJobs {
     pub outfile: PathBuf,
 }
 
+// A named way of building the same assets, for example for a particular platform or with debug
+// data included. Builders and jobs can read the settings to decide how to build.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BuildProfile {
+    // Empty for the default profile that is used when the project doesn't define any
+    pub name: String,
+    pub settings: BTreeMap<String, String>,
+}
+
+impl BuildProfile {
+    pub fn setting(
+        &self,
+        key: &str,
+    ) -> Option<&str> {
+        self.settings.get(key).map(|x| x.as_str())
+    }
+
+    // Each profile has its own artifacts, manifests and TOC files in profiles/<name> under the
+    // build data path. The default profile writes directly to the build data path. Profiles get
+    // their own parent directory so that a name like "toc" or "a" can't collide with the default
+    // profile's output.
+    pub fn build_data_path(
+        &self,
+        build_data_root_path: &Path,
+    ) -> PathBuf {
+        if self.name.is_empty() {
+            build_data_root_path.to_path_buf()
+        } else {
+            build_data_root_path.join("profiles").join(&self.name)
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct HydrateProjectConfiguration {
     // Directories to all schema files that should be used
@@ -54,9 +99,13 @@ pub struct HydrateProjectConfiguration {
     // Path to where all built data will be stored (this is what the game consumes)
     pub build_data_path: PathBuf,
 
-    // Unused for now, but it will be a cache for intermediate build data later
+    // Cache of job results, used to skip jobs whose inputs have not changed since they last ran
     pub job_data_path: PathBuf,
 
+    // File the editor's undo history is saved to, so it survives restarting the editor. Defaults
+    // to a file in the job data path
+    pub undo_history_path: PathBuf,
+
     // Asset storage location that uses file system paths for names/asset references
     pub id_based_asset_sources: Vec<NamePathPair>,
     // Asset storage location that uses IDs for file names/asset references
@@ -66,6 +115,9 @@ pub struct HydrateProjectConfiguration {
     pub source_file_locations: Vec<NamePathPair>,
 
     pub schema_codegen_jobs: Vec<SchemaCodegenJobs>,
+
+    // If none are defined in the project file, this contains a single default profile
+    pub build_profiles: Vec<BuildProfile>,
 }
 
 impl PathReferenceNamespaceResolver for HydrateProjectConfiguration {
@@ -161,6 +213,11 @@ impl HydrateProjectConfiguration {
         let import_data_path = Self::parse_dir_path(&root_path, &project_file.import_data_path)?;
         let build_data_path = Self::parse_dir_path(&root_path, &project_file.build_data_path)?;
         let job_data_path = Self::parse_dir_path(&root_path, &project_file.job_data_path)?;
+        let undo_history_path = if let Some(undo_history_path) = &project_file.undo_history_path {
+            Self::unverified_absolute_path(&root_path, undo_history_path)
+        } else {
+            job_data_path.join("undo_history.json")
+        };
 
         let mut schema_def_paths = Vec::default();
         for path in &project_file.schema_def_paths {
@@ -213,15 +270,52 @@ impl HydrateProjectConfiguration {
             })
         }
 
+        let mut build_profiles = Vec::default();
+        for build_profile in project_file.build_profiles {
+            // The name is used as a directory name
+            let is_valid_name = !build_profile.name.is_empty()
+                && build_profile
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if !is_valid_name {
+                Err(format!(
+                    "Build profile name {:?} must be non-empty and only contain letters, numbers, '-' and '_'",
+                    build_profile.name
+                ))?;
+            }
+
+            if build_profiles
+                .iter()
+                .any(|x: &BuildProfile| x.name == build_profile.name)
+            {
+                Err(format!(
+                    "Build profile {:?} is defined more than once",
+                    build_profile.name
+                ))?;
+            }
+
+            build_profiles.push(BuildProfile {
+                name: build_profile.name,
+                settings: build_profile.settings,
+            });
+        }
+
+        if build_profiles.is_empty() {
+            build_profiles.push(BuildProfile::default());
+        }
+
         Ok(HydrateProjectConfiguration {
             schema_def_paths,
             import_data_path,
             build_data_path,
             job_data_path,
+            undo_history_path,
             id_based_asset_sources,
             path_based_asset_sources,
             source_file_locations,
             schema_codegen_jobs,
+            build_profiles,
         })
     }
 
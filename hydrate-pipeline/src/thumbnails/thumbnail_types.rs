@@ -89,8 +89,8 @@ impl<'a> ThumbnailProviderRenderContext<'a> {
                 asset_id,
                 FetchedImportData {
                     import_data: import_data.clone(),
-                    _info: FetchedImportDataInfo {
-                        _contents_hash: newly_fetched_import_data.contents_hash,
+                    info: FetchedImportDataInfo {
+                        contents_hash: newly_fetched_import_data.contents_hash,
                         _metadata_hash: newly_fetched_import_data.metadata_hash,
                     },
                 },
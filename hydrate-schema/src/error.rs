@@ -22,6 +22,12 @@ pub enum DataSetError {
     NewLocationIsChildOfCurrentAsset,
     UnknownPathNamespace,
     InvalidPath,
+    UndoStepNotFound,
+    AssetNameCollision,
+    FilePathCollision,
+    LocationNotInDataSource,
+    GeneratedAssetCannotBeRenamed,
+    FileNotWritable,
 
     // the data was in a container, but moved out of it (i.e. Option::take())
     DataTaken,